@@ -0,0 +1,240 @@
+//! Derive macro of `tinybufr::record::BufrRecord`
+//!
+//! This crate is used through the `derive` feature of tinybufr. See the documentation
+//! of `tinybufr::record` for the attributes and the supported field types.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::ParseStream;
+use syn::spanned::Spanned;
+use syn::{
+    Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, PathArguments, Token, Type,
+    parse_macro_input,
+};
+
+#[proc_macro_derive(BufrRecord, attributes(bufr))]
+pub fn derive_bufr_record(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Where the value of a field comes from.
+#[derive(Debug, PartialEq)]
+enum Source {
+    /// An element or sequence, as (F, X, Y)
+    Descriptor(u8, u8, u8),
+    /// The items of the first replication
+    Replication,
+}
+
+struct FieldAttrs {
+    source: Source,
+    default: bool,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "BufrRecord can only be derived for structs with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "BufrRecord can only be derived for structs",
+            ));
+        }
+    };
+
+    let mut inits = vec![];
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let attrs = parse_attrs(field)?;
+        let value = field_value(ident, &field.ty, &attrs)?;
+        inits.push(quote! { #ident: #value });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::tinybufr::record::BufrRecord for #name #ty_generics #where_clause {
+            fn from_values(
+                values: &[::tinybufr::SubsetValue],
+            ) -> ::std::result::Result<Self, ::tinybufr::Error> {
+                ::std::result::Result::Ok(Self { #(#inits,)* })
+            }
+        }
+
+        impl #impl_generics ::tinybufr::record::FromSubsetValue for #name #ty_generics #where_clause {
+            fn from_value(
+                value: &::tinybufr::SubsetValue,
+            ) -> ::std::result::Result<::std::option::Option<Self>, ::tinybufr::Error> {
+                ::tinybufr::record::from_sequence(value)
+            }
+        }
+    })
+}
+
+/// Generates the expression giving the value of a field.
+fn field_value(ident: &Ident, ty: &Type, attrs: &FieldAttrs) -> syn::Result<TokenStream2> {
+    let field = ident.to_string();
+    let missing = quote! { ::tinybufr::record::missing(#field) };
+    let value = match attrs.source {
+        Source::Descriptor(f, x, y) => {
+            let found = quote! {
+                &::tinybufr::record::find(values, ::tinybufr::Descriptor { f: #f, x: #x, y: #y })
+            };
+            match wrapper(ty) {
+                Some(("Option", inner)) => quote! { ::tinybufr::record::first::<#inner>(#found)? },
+                Some(("Vec", inner)) => {
+                    quote! { ::tinybufr::record::all::<#inner>(#found, #field)? }
+                }
+                _ if attrs.default => {
+                    quote! { ::tinybufr::record::first::<#ty>(#found)?.unwrap_or_default() }
+                }
+                _ => quote! {
+                    ::tinybufr::record::first::<#ty>(#found)?.ok_or_else(|| #missing)?
+                },
+            }
+        }
+        Source::Replication => {
+            let items = |inner| quote! { ::tinybufr::record::items::<#inner>(values)? };
+            match wrapper(ty) {
+                Some(("Option", inner)) => match wrapper(inner) {
+                    Some(("Vec", inner)) => items(inner),
+                    _ => return Err(replication_type_error(ty)),
+                },
+                Some(("Vec", inner)) if attrs.default => {
+                    let items = items(inner);
+                    quote! { #items.unwrap_or_default() }
+                }
+                Some(("Vec", inner)) => {
+                    let items = items(inner);
+                    quote! { #items.ok_or_else(|| #missing)? }
+                }
+                _ => return Err(replication_type_error(ty)),
+            }
+        }
+    };
+    Ok(value)
+}
+
+fn replication_type_error(ty: &Type) -> syn::Error {
+    syn::Error::new(
+        ty.span(),
+        "replication fields must be of type `Vec<T>` or `Option<Vec<T>>`",
+    )
+}
+
+/// Returns the name and the type argument of `Option<T>` or `Vec<T>`.
+fn wrapper(ty: &Type) -> Option<(&'static str, &Type)> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    let name = match segment.ident.to_string().as_str() {
+        "Option" => "Option",
+        "Vec" => "Vec",
+        _ => return None,
+    };
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) if args.args.len() == 1 => Some((name, inner)),
+        _ => None,
+    }
+}
+
+fn parse_attrs(field: &syn::Field) -> syn::Result<FieldAttrs> {
+    let mut source = None;
+    let mut default = false;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("bufr")) {
+        attr.parse_args_with(|input: ParseStream| {
+            loop {
+                if input.peek(LitStr) {
+                    let lit: LitStr = input.parse()?;
+                    source = Some(parse_descriptor(&lit)?);
+                } else {
+                    let ident: Ident = input.parse()?;
+                    match ident.to_string().as_str() {
+                        "replication" => source = Some(Source::Replication),
+                        "default" => default = true,
+                        _ => {
+                            return Err(syn::Error::new(
+                                ident.span(),
+                                "expected a descriptor, `replication` or `default`",
+                            ));
+                        }
+                    }
+                }
+                if input.is_empty() {
+                    return Ok(());
+                }
+                input.parse::<Token![,]>()?;
+            }
+        })?;
+    }
+    let source = source.ok_or_else(|| {
+        syn::Error::new(
+            field.span(),
+            "missing #[bufr(\"FXXYYY\")] or #[bufr(replication)] attribute",
+        )
+    })?;
+    Ok(FieldAttrs { source, default })
+}
+
+/// Parses a descriptor of an element or sequence written as "FXXYYY" or "F XX YYY".
+fn parse_descriptor(lit: &LitStr) -> syn::Result<Source> {
+    let error = || {
+        syn::Error::new(
+            lit.span(),
+            "expected the descriptor of an element or sequence, like \"012101\" or \"3 01 011\"",
+        )
+    };
+    let digits: String = lit.value().chars().filter(|c| *c != ' ').collect();
+    if digits.len() != 6 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(error());
+    }
+    let parse = |range: std::ops::Range<usize>| digits[range].parse::<u8>().map_err(|_| error());
+    let (f, x, y) = (parse(0..1)?, parse(1..3)?, parse(3..6)?);
+    if f != 0 && f != 3 || x > 63 {
+        return Err(error());
+    }
+    Ok(Source::Descriptor(f, x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_descriptor() {
+        let parse = |s| parse_descriptor(&LitStr::new(s, proc_macro2::Span::call_site())).ok();
+        assert_eq!(parse("012101"), Some(Source::Descriptor(0, 12, 101)));
+        assert_eq!(parse("3 01 011"), Some(Source::Descriptor(3, 1, 11)));
+        assert_eq!(parse("101000"), None);
+        assert_eq!(parse("012"), None);
+        assert_eq!(parse("0x2101"), None);
+        assert_eq!(parse("064001"), None);
+        assert_eq!(parse("012300"), None);
+    }
+
+    #[test]
+    fn test_wrapper() {
+        let ty: Type = syn::parse_quote!(Option<Vec<f64>>);
+        let (name, inner) = wrapper(&ty).unwrap();
+        assert_eq!(name, "Option");
+        assert_eq!(wrapper(inner).unwrap().0, "Vec");
+        assert!(wrapper(&syn::parse_quote!(f64)).is_none());
+        assert!(wrapper(&syn::parse_quote!(std::collections::HashMap<String, f64>)).is_none());
+    }
+}
@@ -0,0 +1,17 @@
+//! Command-line options shared by the examples
+
+use tinybufr::recovery::RecoveryPolicy;
+
+#[derive(clap::Args)]
+pub struct RecoveryArgs {
+    /// How to handle errors in the input and messages ("fail-fast", "skip-message",
+    /// "skip-subset" or "best-effort")
+    #[arg(long, value_name = "POLICY")]
+    pub recovery: Option<RecoveryPolicy>,
+}
+
+impl RecoveryArgs {
+    pub fn policy(&self) -> RecoveryPolicy {
+        self.recovery.unwrap_or_default()
+    }
+}
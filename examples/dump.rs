@@ -1,7 +1,5 @@
-use std::fs;
-use std::io::{BufRead, BufReader};
-
 use clap::Parser;
+use tinybufr::input::open_any;
 use tinybufr::*;
 
 #[derive(clap::Parser)]
@@ -14,6 +12,10 @@ struct Args {
     /// Skip first line of input
     #[arg(short, long)]
     skip_first_line: bool,
+
+    /// Print the bit offset of each value in the data section
+    #[arg(short, long)]
+    offsets: bool,
 }
 
 fn main() -> Result<(), Error> {
@@ -24,32 +26,11 @@ fn main() -> Result<(), Error> {
     #[cfg(feature = "jma")]
     tinybufr::tables::local::jma::install_jma_descriptors(&mut tables);
 
-    let file = fs::File::open(args.filename).unwrap();
-    let mut reader = BufReader::new(file);
-
-    // Check if the file starts with "BUFR", if not skip the first line (up to 1024 bytes)
-    {
-        let buf = reader.fill_buf()?;
-        if buf.len() >= 4 && &buf[..4] != b"BUFR" {
-            // File doesn't start with BUFR, skip to the next line
-            let max_skip = buf.len().min(1024);
-            let consumed =
-                if let Some(newline_pos) = buf[..max_skip].iter().position(|&b| b == b'\n') {
-                    // Found newline within limit, skip past it
-                    newline_pos + 1
-                } else if buf.len() < 1024 {
-                    // Reached EOF without finding newline
-                    return Err(Error::Fatal("No BUFR data found in file".to_string()));
-                } else {
-                    // No newline found within 1024 bytes
-                    return Err(Error::Fatal(
-                        "First line too long (>1024 bytes) and doesn't start with BUFR".to_string(),
-                    ));
-                };
-
-            reader.consume(consumed);
-        }
-    }
+    // Read the first message, skipping any header line before it
+    let Some(message) = open_any(&args.filename)?.next() else {
+        return Err(Error::Fatal("No BUFR data found in file".to_string()));
+    };
+    let mut reader = message.as_slice();
 
     // Parse header sections
     let header = HeaderSections::read(&mut reader).unwrap();
@@ -61,26 +42,87 @@ fn main() -> Result<(), Error> {
     let mut data_reader = DataReader::new(&mut reader, &data_spec).unwrap();
 
     loop {
-        match data_reader.read_event() {
-            Ok(DataEvent::Data { idx: _, xy, value }) => {
-                if let Some(b) = tables.table_b.get(&xy) {
-                    println!("Data {} = {:?} [{}]", b.element_name, value, b.unit);
-                } else {
-                    println!("Data: {value:?}");
-                };
+        let event = data_reader.read_event();
+        let at = match args.offsets {
+            true => format!("{:>8} ", data_reader.event_offset()),
+            false => String::new(),
+        };
+        match event {
+            Ok(DataEvent::Data { entry, value, .. }) => {
+                println!(
+                    "{at}Data {} = {:?} [{}]",
+                    entry.element_name, value, entry.unit
+                );
             }
-            Ok(DataEvent::CompressedData { idx: _, xy, values }) => {
+            Ok(DataEvent::CompressedData { entry, values, .. }) => {
+                println!(
+                    "{at}Data {} = {:?} {}",
+                    entry.element_name, values, entry.unit
+                );
+            }
+            Ok(DataEvent::Quality {
+                x,
+                xy,
+                significance,
+                value,
+                ..
+            }) => {
+                let mut label = match x {
+                    23 => "Substituted value",
+                    24 => "First-order statistic",
+                    25 => "Difference statistic",
+                    _ => "Replaced/retained value",
+                }
+                .to_string();
+                if let Some(significance) = significance {
+                    label.push_str(&format!(" ({significance})"));
+                }
                 if let Some(b) = tables.table_b.get(&xy) {
-                    println!("Data {} = {:?} {}", b.element_name, values, b.unit);
+                    println!(
+                        "{at}{label} of {} = {:?} [{}]",
+                        b.element_name, value, b.unit
+                    );
                 } else {
-                    println!("Data: {values:?}");
+                    println!("{at}{label}: {value:?}");
                 };
             }
+            Ok(DataEvent::SignifiedCharacters { value, .. }) => {
+                println!("{at}Signified characters = {value:?}");
+            }
+            Ok(DataEvent::CompressedSignifiedCharacters { values, .. }) => {
+                println!("{at}Signified characters = {values:?}");
+            }
+            Ok(DataEvent::UnknownLocalElement {
+                xy, width, values, ..
+            }) => {
+                println!(
+                    "{at}Unknown local element 0 {:02} {:03} ({width} bits): {values:?}",
+                    xy.x, xy.y
+                );
+            }
+            Ok(DataEvent::OperatorHandled {
+                x,
+                value,
+                name,
+                operator,
+                ..
+            }) => {
+                println!(
+                    "{at}Operator 2 {x:02} {value:03} ({}): {operator:?}",
+                    name.unwrap_or("unknown operator")
+                );
+            }
             Ok(DataEvent::Eof) => {
                 break;
             }
             Ok(_) => {}
-            Err(e) => return Err(e),
+            Err(e) => {
+                eprintln!(
+                    "Failed to read the data section at bit {}",
+                    data_reader.event_offset()
+                );
+                return Err(e);
+            }
         }
     }
 
@@ -0,0 +1,76 @@
+mod common;
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+use clap::Parser;
+use tinybufr::postgres::{CopyFormat, PgCopyWriter, copy_sql, create_table_sql};
+use tinybufr::*;
+
+/// Convert BUFR into PostgreSQL COPY data (e.g. `dump_pgcopy input.bufr | psql -c "COPY bufr FROM STDIN"`)
+#[derive(clap::Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Input BUFR file path
+    #[arg(index = 1)]
+    filename: String,
+
+    /// Output path (stdout if omitted)
+    #[arg(index = 2)]
+    output: Option<String>,
+
+    /// Use the binary COPY format
+    #[arg(long)]
+    binary: bool,
+
+    /// Add a PostGIS point column built from latitude and longitude
+    #[arg(long)]
+    geometry: bool,
+
+    /// Print the CREATE TABLE and COPY statements for the given table name and exit
+    #[arg(long)]
+    print_sql: Option<String>,
+
+    #[command(flatten)]
+    recovery: common::RecoveryArgs,
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let format = if args.binary {
+        CopyFormat::Binary
+    } else {
+        CopyFormat::Text
+    };
+
+    if let Some(table_name) = &args.print_sql {
+        println!("{}", create_table_sql(table_name, args.geometry));
+        println!("{}", copy_sql(table_name, format));
+        return Ok(());
+    }
+
+    #[allow(unused_mut)]
+    let mut tables = Tables::default();
+    #[cfg(feature = "jma")]
+    tinybufr::tables::local::jma::install_jma_descriptors(&mut tables);
+
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(fs::File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let copy = PgCopyWriter::new(BufWriter::new(output), format, args.geometry)?;
+    let recovery = args.recovery.policy();
+    let messages = input::open_any_with_recovery(&args.filename, recovery)?;
+    for warning in messages.warnings() {
+        eprintln!("{warning}");
+    }
+    let options = sink::ExportOptions {
+        recovery,
+        ..Default::default()
+    };
+    let summary = sink::export_with_options(copy, messages, &tables, &options)?;
+    for note in summary.notes() {
+        eprintln!("{note}");
+    }
+    Ok(())
+}
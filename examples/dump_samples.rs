@@ -0,0 +1,58 @@
+mod common;
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+use clap::Parser;
+use tinybufr::tsdb::{SampleFormat, SampleWriter};
+use tinybufr::*;
+
+/// Convert BUFR into time-series samples (e.g. `dump_samples input.bufr | influx write`)
+#[derive(clap::Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Input BUFR file path
+    #[arg(index = 1)]
+    filename: String,
+
+    /// Output path (stdout if omitted)
+    #[arg(index = 2)]
+    output: Option<String>,
+
+    /// Sample format ("influx" for the InfluxDB line protocol or "prometheus" for the
+    /// Prometheus text exposition format)
+    #[arg(long, default_value = "influx")]
+    format: SampleFormat,
+
+    #[command(flatten)]
+    recovery: common::RecoveryArgs,
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    #[allow(unused_mut)]
+    let mut tables = Tables::default();
+    #[cfg(feature = "jma")]
+    tinybufr::tables::local::jma::install_jma_descriptors(&mut tables);
+
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(fs::File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let samples = SampleWriter::new(BufWriter::new(output), args.format);
+    let recovery = args.recovery.policy();
+    let messages = input::open_any_with_recovery(&args.filename, recovery)?;
+    for warning in messages.warnings() {
+        eprintln!("{warning}");
+    }
+    let options = sink::ExportOptions {
+        recovery,
+        ..Default::default()
+    };
+    let summary = sink::export_with_options(samples, messages, &tables, &options)?;
+    for note in summary.notes() {
+        eprintln!("{note}");
+    }
+    Ok(())
+}
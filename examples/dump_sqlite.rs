@@ -0,0 +1,57 @@
+mod common;
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+use clap::Parser;
+use tinybufr::sqlite::SqliteScriptWriter;
+use tinybufr::*;
+
+/// Convert BUFR into a SQL script for SQLite (e.g. `dump_sqlite input.bufr | sqlite3 out.db`)
+#[derive(clap::Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Input BUFR file path
+    #[arg(index = 1)]
+    filename: String,
+
+    /// Output SQL script path (stdout if omitted)
+    #[arg(index = 2)]
+    output: Option<String>,
+
+    /// Name of the table to insert into
+    #[arg(long, default_value = "bufr")]
+    table: String,
+
+    #[command(flatten)]
+    recovery: common::RecoveryArgs,
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    #[allow(unused_mut)]
+    let mut tables = Tables::default();
+    #[cfg(feature = "jma")]
+    tinybufr::tables::local::jma::install_jma_descriptors(&mut tables);
+
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(fs::File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let script = SqliteScriptWriter::new(BufWriter::new(output), &args.table)?;
+    let recovery = args.recovery.policy();
+    let messages = input::open_any_with_recovery(&args.filename, recovery)?;
+    for warning in messages.warnings() {
+        eprintln!("{warning}");
+    }
+    let options = sink::ExportOptions {
+        recovery,
+        ..Default::default()
+    };
+    let summary = sink::export_with_options(script, messages, &tables, &options)?;
+    for note in summary.notes() {
+        eprintln!("{note}");
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use tinybufr::grep::Grep;
+use tinybufr::*;
+
+#[derive(clap::Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Predicate on the values of an element (e.g. "012101>313.15")
+    #[arg(index = 1)]
+    predicate: String,
+
+    /// Input BUFR files or directories
+    #[arg(index = 2, required = true)]
+    paths: Vec<PathBuf>,
+
+    /// Decode messages leniently
+    #[arg(long)]
+    lenient: bool,
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    #[allow(unused_mut)]
+    let mut tables = Tables::default();
+    #[cfg(feature = "jma")]
+    tinybufr::tables::local::jma::install_jma_descriptors(&mut tables);
+
+    let mut grep = Grep::new(args.predicate.parse()?, &tables);
+    if args.lenient {
+        grep = grep.with_options(DecodeOptions::lenient());
+    }
+    let mut found = false;
+    for path in &args.paths {
+        let hits = match path.is_dir() {
+            true => grep.search_dir(path)?,
+            false => grep.search_files([path.clone()]),
+        };
+        for hit in hits {
+            match hit {
+                Ok(hit) => {
+                    println!("{hit}");
+                    found = true;
+                }
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+    }
+    if !found {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::fs;
+use std::io::{self, Write};
+
+use clap::Parser;
+use tinybufr::Error;
+use tinybufr::merge::merge_files;
+
+/// Merge the messages of several BUFR files in chronological order (by the typical
+/// time in Section 1)
+#[derive(clap::Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Input BUFR files, each in chronological order
+    #[arg(index = 1, required = true)]
+    filenames: Vec<String>,
+
+    /// Output path (stdout if omitted)
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// List the messages with their times and inputs instead of writing them
+    #[arg(long)]
+    list: bool,
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    let mut output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    for message in merge_files(&args.filenames)? {
+        if args.list {
+            let time = message.time.map_or("-".to_string(), |t| t.to_string());
+            writeln!(
+                output,
+                "{time}\t{}\t{} bytes",
+                args.filenames[message.input],
+                message.message.len()
+            )?;
+        } else {
+            output.write_all(&message.message)?;
+        }
+    }
+    output.flush()?;
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use clap::Parser;
+use tinybufr::scan::{scan, scan_with_tables};
+use tinybufr::*;
+
+#[derive(clap::Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Input BUFR files
+    #[arg(index = 1, required = true)]
+    filenames: Vec<String>,
+
+    /// Also read through the data sections
+    #[arg(long)]
+    data: bool,
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    #[allow(unused_mut)]
+    let mut tables = Tables::default();
+    #[cfg(feature = "jma")]
+    tinybufr::tables::local::jma::install_jma_descriptors(&mut tables);
+
+    let mut failed = 0;
+    for filename in &args.filenames {
+        let results = match args.data {
+            true => scan_with_tables(filename, &tables)?,
+            false => scan(filename)?,
+        };
+        if results.is_empty() {
+            println!("{filename}: FAIL no BUFR message found");
+            failed += 1;
+        }
+        for (i, result) in results.iter().enumerate() {
+            match &result.error {
+                None => println!(
+                    "{filename} #{i} @{}: ok ({} bytes, edition {})",
+                    result.offset, result.total_length, result.edition_number
+                ),
+                Some(e) => {
+                    println!("{filename} #{i} @{}: FAIL {e}", result.offset);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,372 @@
+//! Key names of element descriptors, compatible with ecCodes
+//!
+//! ecCodes names BUFR elements by keys (e.g. `airTemperature` for 0 12 101), taken from
+//! the abbreviations of its `element.table` files, and centres add names for their local
+//! descriptors in concept files (`shortName.def` and `units.def` of `localConcepts`).
+//! [`Aliases`] loads both, so elements can be addressed by the same keys whether they
+//! are master or local entries:
+//!
+//! ```
+//! use tinybufr::{XY, aliases::Aliases};
+//!
+//! let mut aliases = Aliases::new();
+//! aliases
+//!     .load_element_table("012101|airTemperature|double|TEMPERATURE/AIR TEMPERATURE|K|2|0|16|K|2|5")
+//!     .unwrap();
+//! aliases
+//!     .load_concepts(
+//!         "## Local road surface temperature
+//!         'roadSurfaceTemperature' = { code = 012192 ; }",
+//!         Some("'K' = { code = 012192 ; }"),
+//!     )
+//!     .unwrap();
+//! assert_eq!(aliases.element("airTemperature").unwrap(), XY { x: 12, y: 101 });
+//! assert_eq!(aliases.element("012192").unwrap(), XY { x: 12, y: 192 });
+//! assert_eq!(aliases.unit(XY { x: 12, y: 192 }), Some("K"));
+//! ```
+
+use hashbrown::HashMap;
+
+use crate::{Error, XY, overrides::parse_element_descriptor};
+
+/// The key name of an element descriptor, and its unit as given by ecCodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias {
+    pub key: String,
+    pub unit: Option<String>,
+}
+
+/// Key names of element descriptors, looked up both ways.
+#[derive(Debug, Clone, Default)]
+pub struct Aliases {
+    by_xy: HashMap<XY, Alias>,
+    by_key: HashMap<String, XY>,
+}
+
+impl Aliases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names an element descriptor, replacing its previous name.
+    ///
+    /// Fails if the key already names another element.
+    pub fn insert(&mut self, xy: XY, key: &str) -> Result<(), Error> {
+        match self.by_key.get(key) {
+            Some(&other) if other != xy => {
+                return Err(Error::Invalid(format!(
+                    "Key {key:?} of {xy:?} already names {other:?}"
+                )));
+            }
+            _ => {}
+        }
+        let unit = match self.by_xy.remove(&xy) {
+            Some(previous) => {
+                self.by_key.remove(&previous.key);
+                previous.unit
+            }
+            None => None,
+        };
+        self.by_key.insert(key.to_string(), xy);
+        self.by_xy.insert(
+            xy,
+            Alias {
+                key: key.to_string(),
+                unit,
+            },
+        );
+        Ok(())
+    }
+
+    /// Returns the key name of an element descriptor.
+    pub fn key(&self, xy: XY) -> Option<&str> {
+        self.by_xy.get(&xy).map(|alias| alias.key.as_str())
+    }
+
+    /// Returns the unit of an element descriptor, if one was loaded.
+    pub fn unit(&self, xy: XY) -> Option<&str> {
+        self.by_xy.get(&xy)?.unit.as_deref()
+    }
+
+    /// Returns the element descriptor named by a key.
+    pub fn xy(&self, key: &str) -> Option<XY> {
+        self.by_key.get(key).copied()
+    }
+
+    /// Returns the element descriptor given by a key or as `0XXYYY` (e.g. `012101`).
+    pub fn element(&self, key_or_descriptor: &str) -> Result<XY, Error> {
+        match self.xy(key_or_descriptor) {
+            Some(xy) => Ok(xy),
+            None => parse_element_descriptor(key_or_descriptor)
+                .map_err(|_| Error::Invalid(format!("Unknown element key: {key_or_descriptor:?}"))),
+        }
+    }
+
+    /// Iterates over the named elements in the order of their descriptors.
+    pub fn iter(&self) -> impl Iterator<Item = (XY, &Alias)> {
+        let mut aliases: Vec<_> = self.by_xy.iter().map(|(&xy, alias)| (xy, alias)).collect();
+        aliases.sort_unstable_by_key(|&(xy, _)| xy);
+        aliases.into_iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_xy.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_xy.is_empty()
+    }
+
+    /// Loads the keys and units of an ecCodes `element.table` file, whose lines are
+    /// `code|abbreviation|type|name|unit|scale|reference|width|...`, and returns the
+    /// number of elements named.
+    ///
+    /// Blank lines and lines starting with `#` (the header) are ignored.
+    pub fn load_element_table(&mut self, table: &str) -> Result<usize, Error> {
+        let mut count = 0;
+        for (i, line) in table.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || Error::Invalid(format!("Invalid element table line {}", i + 1));
+            let mut columns = line.split('|').map(str::trim);
+            let (Some(code), Some(key), Some(_), Some(_), Some(unit)) = (
+                columns.next(),
+                columns.next(),
+                columns.next(),
+                columns.next(),
+                columns.next(),
+            ) else {
+                return Err(invalid());
+            };
+            let xy = parse_element_descriptor(code).map_err(|_| invalid())?;
+            self.insert(xy, key)?;
+            self.set_unit(xy, unit);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Loads the keys of an ecCodes `shortName.def` concept file, and the units of a
+    /// `units.def` one, and returns the number of elements named.
+    ///
+    /// Each concept names the element given by its `code` condition (e.g.
+    /// `'roadSurfaceTemperature' = { code = 012192 ; }`). Concepts without one, such as
+    /// those of GRIB parameters, are ignored, as are units of elements without a key.
+    pub fn load_concepts(
+        &mut self,
+        short_names: &str,
+        units: Option<&str>,
+    ) -> Result<usize, Error> {
+        let mut count = 0;
+        for (key, xy) in parse_concepts(short_names)? {
+            self.insert(xy, &key)?;
+            count += 1;
+        }
+        if let Some(units) = units {
+            for (unit, xy) in parse_concepts(units)? {
+                self.set_unit(xy, &unit);
+            }
+        }
+        Ok(count)
+    }
+
+    fn set_unit(&mut self, xy: XY, unit: &str) {
+        if let Some(alias) = self.by_xy.get_mut(&xy) {
+            alias.unit = Some(unit.to_string());
+        }
+    }
+}
+
+/// Parses the concepts of an ecCodes concept file (`'value' = { key = value ; ... }`),
+/// keeping those identifying an element by a `code` condition.
+fn parse_concepts(s: &str) -> Result<Vec<(String, XY)>, Error> {
+    let mut tokens = ConceptTokens { rest: s, line: 1 };
+    let mut concepts = vec![];
+    while let Some(value) = tokens.next()? {
+        tokens.expect("=")?;
+        tokens.expect("{")?;
+        let mut code = None;
+        loop {
+            let key = tokens.required()?;
+            if key == "}" {
+                break;
+            }
+            tokens.expect("=")?;
+            let condition = tokens.required()?;
+            tokens.expect(";")?;
+            if key == "code" {
+                code = Some(parse_element_descriptor(&condition).map_err(|_| {
+                    Error::Invalid(format!(
+                        "Invalid code {condition:?} of concept {value:?} at line {}",
+                        tokens.line
+                    ))
+                })?);
+            }
+        }
+        if let Some(xy) = code {
+            concepts.push((value, xy));
+        }
+    }
+    Ok(concepts)
+}
+
+/// Tokens of a concept file: quoted or bare words and the punctuation `={};`, skipping
+/// comments from `#` to the end of lines.
+struct ConceptTokens<'a> {
+    rest: &'a str,
+    line: usize,
+}
+
+impl ConceptTokens<'_> {
+    fn next(&mut self) -> Result<Option<String>, Error> {
+        loop {
+            let trimmed = self.rest.trim_start();
+            self.line += self.rest[..self.rest.len() - trimmed.len()]
+                .matches('\n')
+                .count();
+            self.rest = trimmed;
+            match self.rest.strip_prefix('#') {
+                Some(comment) => self.rest = comment.find('\n').map_or("", |i| &comment[i..]),
+                None => break,
+            }
+        }
+        let mut chars = self.rest.chars();
+        let token = match chars.next() {
+            None => return Ok(None),
+            Some(c @ ('=' | '{' | '}' | ';')) => {
+                self.rest = chars.as_str();
+                c.to_string()
+            }
+            Some(quote @ ('\'' | '"')) => {
+                let quoted = chars.as_str();
+                let Some(end) = quoted.find(quote) else {
+                    return Err(Error::Invalid(format!(
+                        "Unterminated string at line {} of concepts",
+                        self.line
+                    )));
+                };
+                self.rest = &quoted[end + 1..];
+                quoted[..end].to_string()
+            }
+            Some(_) => {
+                let end = self
+                    .rest
+                    .find(|c: char| c.is_whitespace() || "={};#".contains(c))
+                    .unwrap_or(self.rest.len());
+                let (word, rest) = self.rest.split_at(end);
+                self.rest = rest;
+                word.to_string()
+            }
+        };
+        Ok(Some(token))
+    }
+
+    fn required(&mut self) -> Result<String, Error> {
+        self.next()?.ok_or_else(|| {
+            Error::Invalid(format!("Unexpected end of concepts at line {}", self.line))
+        })
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), Error> {
+        match self.required()? {
+            token if token == expected => Ok(()),
+            token => Err(Error::Invalid(format!(
+                "Expected {expected:?} but found {token:?} at line {} of concepts",
+                self.line
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ELEMENT_TABLE: &str = "\
+#code|abbreviation|type|name|unit|scale|reference|width|crex_unit|crex_scale|crex_width
+001001|blockNumber|long|WMO BLOCK NUMBER|Numeric|0|0|7|Numeric|0|2
+012101|airTemperature|double|TEMPERATURE/AIR TEMPERATURE|K|2|0|16|C|2|4
+";
+
+    #[test]
+    fn test_load_element_table() {
+        let mut aliases = Aliases::new();
+        assert_eq!(aliases.load_element_table(ELEMENT_TABLE).unwrap(), 2);
+        let temperature = XY { x: 12, y: 101 };
+        assert_eq!(aliases.key(temperature), Some("airTemperature"));
+        assert_eq!(aliases.unit(temperature), Some("K"));
+        assert_eq!(aliases.xy("blockNumber"), Some(XY { x: 1, y: 1 }));
+        assert_eq!(aliases.element("012101").unwrap(), temperature);
+        assert!(aliases.element("dewpointTemperature").is_err());
+
+        assert!(aliases.load_element_table("012101|airTemperature").is_err());
+        assert!(aliases.load_element_table("x|a|double|A|K").is_err());
+    }
+
+    #[test]
+    fn test_load_concepts() {
+        let short_names = "
+            # Road surface temperature
+            'roadSurfaceTemperature' = {
+                code = 012192 ;
+            }
+            # GRIB concepts are ignored
+            'tp' = { discipline = 0 ; parameterCategory = 1 ; parameterNumber = 8 ; }
+            \"roadState\" = { code = \"020192\" ; }
+        ";
+        let units = "'K' = { code = 012192 ; } 'm' = { code = 007192 ; }";
+        let mut aliases = Aliases::new();
+        aliases.load_element_table(ELEMENT_TABLE).unwrap();
+        assert_eq!(aliases.load_concepts(short_names, Some(units)).unwrap(), 2);
+        assert_eq!(aliases.len(), 4);
+        assert_eq!(
+            aliases
+                .iter()
+                .map(|(_, alias)| &alias.key[..])
+                .collect::<Vec<_>>(),
+            [
+                "blockNumber",
+                "airTemperature",
+                "roadSurfaceTemperature",
+                "roadState"
+            ]
+        );
+        let road_temperature = XY { x: 12, y: 192 };
+        assert_eq!(
+            aliases.key(road_temperature),
+            Some("roadSurfaceTemperature")
+        );
+        assert_eq!(aliases.unit(road_temperature), Some("K"));
+        assert_eq!(aliases.xy("roadState"), Some(XY { x: 20, y: 192 }));
+        assert_eq!(aliases.unit(XY { x: 20, y: 192 }), None);
+        // Units of unnamed elements are ignored
+        assert_eq!(aliases.unit(XY { x: 7, y: 192 }), None);
+
+        // Local names replace master ones, but keys cannot name two elements
+        aliases
+            .load_concepts("'surfaceAirTemperature' = { code = 012101 ; }", None)
+            .unwrap();
+        assert_eq!(aliases.xy("airTemperature"), None);
+        assert_eq!(aliases.unit(XY { x: 12, y: 101 }), Some("K"));
+        assert!(
+            aliases
+                .load_concepts("'roadState' = { code = 020193 ; }", None)
+                .is_err()
+        );
+
+        for invalid in [
+            "'a' = { code = 012192 }",
+            "'a' { code = 012192 ; }",
+            "'a' = { code = 12 ; }",
+            "'a = { code = 012192 ; }",
+            "'a' = { code = 012192 ;",
+        ] {
+            assert!(
+                Aliases::new().load_concepts(invalid, None).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+}
@@ -0,0 +1,1405 @@
+//! Conversion of BUFR data into Arrow record batches
+//!
+//! Sequences become struct columns and replications become list columns.
+//!
+//! The order of the columns, and of the fields of struct columns, is that of the
+//! expanded descriptors, and their names only depend on the tables and the descriptors.
+//! Neither depends on hash seeds, so conversions of the same data give identical schemas
+//! across runs and platforms, including those of the exports built on them.
+
+use std::io::Read;
+use std::sync::Arc;
+
+use ::arrow::{
+    array::{
+        Array, ArrayRef, AsArray, Float64Builder, Int32Builder, Int64Builder, StringArray,
+        StringBuilder, StructArray, TimestampMicrosecondArray, UInt32Array,
+    },
+    buffer::OffsetBuffer,
+    compute::cast,
+    datatypes::{DataType, Field, Float64Type, Schema, TimeUnit},
+    record_batch::RecordBatch,
+    util::display::{ArrayFormatter, FormatOptions},
+};
+use indexmap::IndexMap;
+
+use crate::{
+    DataEvent, DataReader, DataSpec, Error, SubsetValue, Tables, Value,
+    overrides::{OutputType, ValueOverrides},
+    recovery::RecoveryPolicy,
+    sink::{MessageInput, OutputSink},
+    tables::{ElementKind, TableBEntry},
+    time::unix_micros,
+};
+
+mod columnar;
+mod long;
+
+#[cfg(feature = "parquet")]
+pub use columnar::{FastConvertOptions, ParquetSink, RecoveredErrorCallback, convert_file_fast};
+pub use columnar::{convert_to_arrow_columnar, convert_to_arrow_columnar_with_options};
+pub use long::convert_to_arrow_long;
+
+/// Options for [`convert_to_arrow_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    /// Prepend the `_message_index` and `_subset_index` columns
+    /// (and `_source_file` if `source_file` is set)
+    pub provenance_columns: bool,
+    /// Value of the `_source_file` column
+    pub source_file: Option<String>,
+    /// Value of the `_message_index` column (index of the message in the input)
+    pub message_index: u32,
+    /// Per-descriptor overrides of output types and values
+    pub overrides: ValueOverrides,
+    /// Recovery from errors by [`convert_to_arrow_with_recovery`]
+    pub recovery: RecoveryPolicy,
+}
+
+/// Unified column-oriented data structure
+#[derive(Debug, Clone)]
+pub enum ColumnData {
+    Scalar {
+        values: Vec<Value>,
+        ty: DataType,
+        kind: ElementKind,
+    },
+    Struct {
+        fields: IndexMap<String, ColumnData>,
+    },
+    List {
+        offsets: Vec<i32>,
+        items: Box<ColumnData>,
+    },
+}
+
+/// Convert BUFR data to Arrow RecordBatch
+///
+/// Reads BUFR data from a DataReader and converts it directly to an Arrow RecordBatch.
+pub fn convert_to_arrow<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    data_spec: &DataSpec,
+) -> Result<RecordBatch, Error> {
+    convert_to_arrow_with_options(data_reader, tables, data_spec, &ConvertOptions::default())
+}
+
+/// Convert BUFR data to Arrow RecordBatch with the given options
+///
+/// Each row corresponds to a subset. With `provenance_columns` enabled, the rows
+/// remain traceable to their message and subset after batches are concatenated.
+pub fn convert_to_arrow_with_options<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    data_spec: &DataSpec,
+    options: &ConvertOptions,
+) -> Result<RecordBatch, Error> {
+    // Parse data into column-oriented structure
+    let column_data = parse_data_as_columns(data_reader, tables, &options.overrides, data_spec)?;
+
+    // Convert to Arrow RecordBatch
+    let record_batch = convert_column_data_to_arrow(column_data)?;
+    if options.provenance_columns {
+        add_provenance_columns(record_batch, data_spec.number_of_subsets as usize, options)
+    } else {
+        Ok(record_batch)
+    }
+}
+
+/// Result of [`convert_to_arrow_partial`]
+#[derive(Debug)]
+pub struct PartialConversion {
+    /// Data converted before the error, or `None` if nothing could be converted
+    ///
+    /// For non-compressed data, rows are the subsets read completely. For compressed
+    /// data, all rows are present but only the columns read completely.
+    pub record_batch: Option<RecordBatch>,
+    /// Error that stopped the conversion, if any
+    pub error: Option<Error>,
+}
+
+/// Convert BUFR data to Arrow RecordBatch, keeping what was converted before an error
+///
+/// Unlike [`convert_to_arrow_with_options`], a failure halfway through a message
+/// (e.g. a corrupted subset) does not discard the data read so far.
+pub fn convert_to_arrow_partial<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    data_spec: &DataSpec,
+    options: &ConvertOptions,
+) -> PartialConversion {
+    let mut column_data = IndexMap::new();
+    let error = parse_data_into_columns(
+        data_reader,
+        tables,
+        &options.overrides,
+        data_spec,
+        &mut column_data,
+    )
+    .err();
+    if column_data.is_empty() {
+        return PartialConversion {
+            record_batch: None,
+            error,
+        };
+    }
+    let record_batch = convert_column_data_to_arrow(column_data).and_then(|record_batch| {
+        if options.provenance_columns {
+            let num_rows = record_batch.num_rows();
+            add_provenance_columns(record_batch, num_rows, options)
+        } else {
+            Ok(record_batch)
+        }
+    });
+    match record_batch {
+        Ok(record_batch) => PartialConversion {
+            record_batch: Some(record_batch),
+            error,
+        },
+        Err(e) => PartialConversion {
+            record_batch: None,
+            error: Some(error.unwrap_or(e)),
+        },
+    }
+}
+
+/// Convert BUFR data to Arrow RecordBatch, recovering from errors as set by `options.recovery`
+///
+/// With [`RecoveryPolicy::FailFast`], an error is returned as by
+/// [`convert_to_arrow_with_options`]. Otherwise the error is returned in
+/// [`PartialConversion::error`] along with the data kept by the policy: nothing for
+/// `SkipMessage`, the subsets read completely for `SkipSubset` (nothing for compressed
+/// data) and everything converted, as by [`convert_to_arrow_partial`], for `BestEffort`.
+pub fn convert_to_arrow_with_recovery<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    data_spec: &DataSpec,
+    options: &ConvertOptions,
+) -> Result<PartialConversion, Error> {
+    let partial = convert_to_arrow_partial(data_reader, tables, data_spec, options);
+    let Some(error) = partial.error else {
+        return Ok(partial);
+    };
+    if !options.recovery.recovers() {
+        return Err(error);
+    }
+    Ok(PartialConversion {
+        record_batch: partial
+            .record_batch
+            .filter(|_| options.recovery.keeps_partial(data_spec.is_compressed)),
+        error: Some(error),
+    })
+}
+
+/// Collects the record batches of messages, converted by
+/// [`convert_to_arrow_with_recovery`] with `message_index` set to the index of each
+/// message and `recovery` to the policy of the export.
+///
+/// Messages without subsets are left out, as by
+/// [`ParquetSink`](columnar::ParquetSink).
+#[derive(Debug, Default)]
+pub struct RecordBatchSink {
+    options: ConvertOptions,
+    batches: Vec<RecordBatch>,
+}
+
+impl RecordBatchSink {
+    pub fn new(options: ConvertOptions) -> Self {
+        Self {
+            options,
+            batches: vec![],
+        }
+    }
+}
+
+impl OutputSink for RecordBatchSink {
+    type Output = Vec<RecordBatch>;
+
+    fn write_message(&mut self, input: &mut MessageInput<'_, '_>) -> Result<usize, Error> {
+        if input.data_spec.number_of_subsets == 0 {
+            return Ok(0);
+        }
+        self.options.message_index = input.index;
+        self.options.recovery = input.recovery;
+        let partial = convert_to_arrow_with_recovery(
+            &mut input.data_reader,
+            input.tables,
+            input.data_spec,
+            &self.options,
+        )?;
+        if let Some(error) = partial.error {
+            input.recover(error)?;
+        }
+        let Some(record_batch) = partial.record_batch else {
+            return Ok(0);
+        };
+        let rows = record_batch.num_rows();
+        self.batches.push(record_batch);
+        Ok(rows)
+    }
+
+    fn finish(self) -> Result<Vec<RecordBatch>, Error> {
+        Ok(self.batches)
+    }
+}
+
+/// Writes messages into a CSV file, converted as by [`RecordBatchSink`] and flattened
+/// by [`flatten_struct_columns`].
+///
+/// The header is that of the first message with subsets, which all the others must
+/// share. Messages without subsets are left out, and the output is left empty if no
+/// message has any.
+pub struct CsvSink<W: std::io::Write> {
+    converter: RecordBatchSink,
+    coordinates_first: bool,
+    writer: ::arrow::csv::Writer<W>,
+    schema: Option<::arrow::datatypes::SchemaRef>,
+}
+
+impl<W: std::io::Write> CsvSink<W> {
+    pub fn new(output: W, options: ConvertOptions) -> Self {
+        Self {
+            converter: RecordBatchSink::new(options),
+            coordinates_first: false,
+            writer: ::arrow::csv::Writer::new(output),
+            schema: None,
+        }
+    }
+
+    /// Puts coordinate and qualifier columns before measurements (see
+    /// [`coordinates_first`]).
+    pub fn coordinates_first(mut self, coordinates_first: bool) -> Self {
+        self.coordinates_first = coordinates_first;
+        self
+    }
+}
+
+impl<W: std::io::Write> OutputSink for CsvSink<W> {
+    type Output = W;
+
+    fn write_message(&mut self, input: &mut MessageInput<'_, '_>) -> Result<usize, Error> {
+        let rows = self.converter.write_message(input)?;
+        let Some(record_batch) = self.converter.batches.pop() else {
+            return Ok(rows);
+        };
+        let mut record_batch = flatten_struct_columns(&record_batch)?;
+        if self.coordinates_first {
+            record_batch = coordinates_first(&record_batch)?;
+        }
+        let schema = self.schema.get_or_insert_with(|| record_batch.schema());
+        if *schema != record_batch.schema() {
+            return Err(Error::NotSupported(format!(
+                "Message {} has a different schema from the first message",
+                input.index
+            )));
+        }
+        self.writer
+            .write(&record_batch)
+            .map_err(|e| Error::Fatal(format!("Failed to write CSV file: {e}")))?;
+        Ok(rows)
+    }
+
+    fn finish(self) -> Result<W, Error> {
+        Ok(self.writer.into_inner())
+    }
+}
+
+/// Prepend `_message_index`, `_subset_index` and `_source_file` columns
+fn add_provenance_columns(
+    record_batch: RecordBatch,
+    num_rows: usize,
+    options: &ConvertOptions,
+) -> Result<RecordBatch, Error> {
+    let mut fields = vec![
+        Field::new("_message_index", DataType::UInt32, false),
+        Field::new("_subset_index", DataType::UInt32, false),
+    ];
+    let mut arrays: Vec<ArrayRef> = vec![
+        Arc::new(UInt32Array::from(vec![options.message_index; num_rows])),
+        Arc::new(UInt32Array::from_iter_values(0..num_rows as u32)),
+    ];
+    if let Some(source_file) = &options.source_file {
+        fields.push(Field::new("_source_file", DataType::Utf8, false));
+        arrays.push(Arc::new(StringArray::from(vec![
+            source_file.as_str();
+            num_rows
+        ])));
+    }
+    let schema = record_batch.schema();
+    fields.extend(schema.fields().iter().map(|f| f.as_ref().clone()));
+    arrays.extend(record_batch.columns().iter().cloned());
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+        .map_err(|e| Error::Fatal(format!("Failed to create RecordBatch: {e}")))
+}
+
+/// Parse data into column-oriented structure
+fn parse_data_as_columns<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    overrides: &ValueOverrides,
+    data_spec: &DataSpec,
+) -> Result<IndexMap<String, ColumnData>, Error> {
+    let mut columns = IndexMap::new();
+    parse_data_into_columns(data_reader, tables, overrides, data_spec, &mut columns)?;
+    Ok(columns)
+}
+
+/// Parse data into column-oriented structure, leaving the complete columns on error
+fn parse_data_into_columns<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    overrides: &ValueOverrides,
+    data_spec: &DataSpec,
+    columns: &mut IndexMap<String, ColumnData>,
+) -> Result<(), Error> {
+    if data_spec.is_compressed {
+        parse_compressed_as_columns(
+            data_reader,
+            tables,
+            overrides,
+            data_spec.number_of_subsets,
+            columns,
+        )
+    } else {
+        parse_non_compressed_as_columns(data_reader, tables, overrides, columns)
+    }
+}
+
+/// Parse compressed data (already column-oriented)
+fn parse_compressed_as_columns<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    overrides: &ValueOverrides,
+    num_subsets: u16,
+    columns: &mut IndexMap<String, ColumnData>,
+) -> Result<(), Error> {
+    loop {
+        match data_reader.read_event()? {
+            DataEvent::CompressedStart => {
+                parse_compressed_structure(data_reader, tables, overrides, columns, num_subsets)?;
+            }
+            DataEvent::Eof => break,
+            ev => {
+                return Err(Error::Fatal(format!("Unexpected event: {ev:?}")));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Context for tracking field name occurrences
+#[derive(Default)]
+struct FieldNameContext {
+    element_name_counts: std::collections::HashMap<String, usize>,
+    sequence_title_counts: std::collections::HashMap<String, usize>,
+    replication_count: usize,
+}
+
+impl FieldNameContext {
+    fn track_element(&mut self, element_name: &str) -> usize {
+        let count = self
+            .element_name_counts
+            .entry(element_name.to_string())
+            .or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    fn track_sequence(&mut self, title: &str) -> usize {
+        let count = self
+            .sequence_title_counts
+            .entry(title.to_string())
+            .or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    fn track_replication(&mut self) -> usize {
+        self.replication_count += 1;
+        self.replication_count
+    }
+}
+
+/// Parse compressed structure recursively
+fn parse_compressed_structure<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    overrides: &ValueOverrides,
+    columns: &mut IndexMap<String, ColumnData>,
+    num_subsets: u16,
+) -> Result<(), Error> {
+    let mut ctx = FieldNameContext::default();
+
+    loop {
+        match data_reader.read_event()? {
+            DataEvent::CompressedData { xy, values, .. } => {
+                let Some(b) = tables.table_b.get(&xy) else {
+                    return Err(Error::Fatal(format!("Unknown data descriptor: {xy:#?}")));
+                };
+                let count = ctx.track_element(b.element_name);
+                let field_name = create_field_name(b, count);
+                let ty = column_type(b, overrides);
+                let values = match overrides.get(&xy) {
+                    Some(o) => values.into_iter().map(|v| o.apply(v)).collect(),
+                    None => values,
+                };
+
+                columns.insert(
+                    field_name,
+                    ColumnData::Scalar {
+                        values,
+                        ty,
+                        kind: b.kind(),
+                    },
+                );
+            }
+            DataEvent::SequenceStart { xy, .. } => {
+                let Some(d) = tables.table_d.get(&xy) else {
+                    return Err(Error::Fatal(format!(
+                        "Unknown sequence descriptor: {xy:#?}"
+                    )));
+                };
+
+                let count = ctx.track_sequence(d.title);
+                let label = match count {
+                    0 | 1 => d.title.to_string(),
+                    _ => format!("{} ({})", d.title, count),
+                };
+
+                let mut sequence_fields = IndexMap::new();
+                parse_compressed_structure(
+                    data_reader,
+                    tables,
+                    overrides,
+                    &mut sequence_fields,
+                    num_subsets,
+                )?;
+                columns.insert(
+                    label,
+                    ColumnData::Struct {
+                        fields: sequence_fields,
+                    },
+                );
+            }
+            DataEvent::ReplicationStart {
+                count,
+                subset_counts,
+                ..
+            } => {
+                let rep_num = ctx.track_replication();
+                let label = format!("replication:{rep_num}");
+                let counts = subset_counts.unwrap_or_else(|| vec![count; num_subsets as usize]);
+                let replication_data =
+                    parse_compressed_replication(data_reader, tables, overrides, &counts)?;
+                columns.insert(label, replication_data);
+            }
+            DataEvent::SequenceEnd => break,
+            DataEvent::OperatorHandled { .. }
+            | DataEvent::ReferenceValueChanged { .. }
+            | DataEvent::CompressedAssociatedField { .. }
+            | DataEvent::BitmapTarget { .. }
+            | DataEvent::Quality { .. }
+            | DataEvent::CompressedQuality { .. }
+            | DataEvent::CompressedSignifiedCharacters { .. }
+            | DataEvent::UnknownLocalElement { .. } => {}
+            DataEvent::Eof => break,
+            ev => {
+                return Err(Error::Fatal(format!(
+                    "Unexpected event in compressed structure: {ev:?}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse compressed replication with offset tracking
+///
+/// `counts` are the numbers of items of each subset. The items beyond the count of a
+/// subset are left out of its list.
+fn parse_compressed_replication<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    overrides: &ValueOverrides,
+    counts: &[u16],
+) -> Result<ColumnData, Error> {
+    let num_subsets = counts.len() as u16;
+    let mut all_item_data = Vec::new();
+
+    // Read all replication items
+    loop {
+        match data_reader.read_event()? {
+            DataEvent::ReplicationItemStart => {
+                let mut item_fields = IndexMap::new();
+                parse_compressed_replication_item(
+                    data_reader,
+                    tables,
+                    overrides,
+                    &mut item_fields,
+                    num_subsets,
+                )?;
+                all_item_data.push(item_fields);
+            }
+            DataEvent::ReplicationEnd => break,
+            ev => {
+                return Err(Error::Fatal(format!(
+                    "Unexpected event in compressed replication: {ev:?}"
+                )));
+            }
+        }
+    }
+
+    // Each item holds the values of all subsets, while the list of a subset holds its
+    // own items
+    let counts: Vec<usize> = counts
+        .iter()
+        .map(|&count| (count as usize).min(all_item_data.len()))
+        .collect();
+    let mut offsets = vec![0i32];
+    offsets.extend(counts.iter().scan(0i32, |offset, &count| {
+        *offset += count as i32;
+        Some(*offset)
+    }));
+    let merged_items = merge_replication_items(all_item_data, &counts)?;
+
+    Ok(ColumnData::List {
+        offsets,
+        items: Box::new(ColumnData::Struct {
+            fields: merged_items,
+        }),
+    })
+}
+
+/// Parse compressed replication item (handles ReplicationItemEnd)
+fn parse_compressed_replication_item<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    overrides: &ValueOverrides,
+    columns: &mut IndexMap<String, ColumnData>,
+    num_subsets: u16,
+) -> Result<(), Error> {
+    let mut ctx = FieldNameContext::default();
+
+    loop {
+        match data_reader.read_event()? {
+            DataEvent::CompressedData { xy, values, .. } => {
+                let Some(b) = tables.table_b.get(&xy) else {
+                    return Err(Error::Fatal(format!("Unknown data descriptor: {xy:#?}")));
+                };
+
+                let count = ctx.track_element(b.element_name);
+                let field_name = create_field_name(b, count);
+                let ty = column_type(b, overrides);
+                let values = match overrides.get(&xy) {
+                    Some(o) => values.into_iter().map(|v| o.apply(v)).collect(),
+                    None => values,
+                };
+
+                columns.insert(
+                    field_name,
+                    ColumnData::Scalar {
+                        values,
+                        ty,
+                        kind: b.kind(),
+                    },
+                );
+            }
+            DataEvent::SequenceStart { xy, .. } => {
+                let Some(d) = tables.table_d.get(&xy) else {
+                    return Err(Error::Fatal(format!(
+                        "Unknown sequence descriptor: {xy:#?}"
+                    )));
+                };
+
+                let count = ctx.track_sequence(d.title);
+                let label = match count {
+                    0 | 1 => d.title.to_string(),
+                    _ => format!("{} ({})", d.title, count),
+                };
+
+                let mut sequence_fields = IndexMap::new();
+                parse_compressed_structure(
+                    data_reader,
+                    tables,
+                    overrides,
+                    &mut sequence_fields,
+                    num_subsets,
+                )?;
+                columns.insert(
+                    label,
+                    ColumnData::Struct {
+                        fields: sequence_fields,
+                    },
+                );
+            }
+            DataEvent::ReplicationStart {
+                count,
+                subset_counts,
+                ..
+            } => {
+                let rep_num = ctx.track_replication();
+                let label = format!("replication:{rep_num}");
+                let counts = subset_counts.unwrap_or_else(|| vec![count; num_subsets as usize]);
+                let replication_data =
+                    parse_compressed_replication(data_reader, tables, overrides, &counts)?;
+                columns.insert(label, replication_data);
+            }
+            DataEvent::ReplicationItemEnd => break,
+            DataEvent::OperatorHandled { .. }
+            | DataEvent::ReferenceValueChanged { .. }
+            | DataEvent::CompressedAssociatedField { .. }
+            | DataEvent::BitmapTarget { .. }
+            | DataEvent::Quality { .. }
+            | DataEvent::CompressedQuality { .. }
+            | DataEvent::CompressedSignifiedCharacters { .. }
+            | DataEvent::UnknownLocalElement { .. } => {}
+            ev => {
+                return Err(Error::Fatal(format!(
+                    "Unexpected event in compressed replication item: {ev:?}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse non-compressed data and convert to column-oriented structure
+///
+/// On error, `columns` holds the subsets read completely.
+fn parse_non_compressed_as_columns<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    overrides: &ValueOverrides,
+    columns: &mut IndexMap<String, ColumnData>,
+) -> Result<(), Error> {
+    // First pass: collect one subset to determine structure
+    let first_subset = match data_reader.read_subset()? {
+        Some(values) => to_row(values, tables, overrides)?,
+        None => return Ok(()),
+    };
+
+    // Initialize columns based on first subset structure with proper types from tables
+    let mut builders = initialize_columns_from_subset(&first_subset)?;
+
+    // Add first subset data to columns
+    add_subset_to_columns(&first_subset, &mut builders)?;
+
+    let result = parse_remaining_subsets(data_reader, tables, overrides, &mut builders);
+
+    // Convert builders to final column data
+    *columns = builders
+        .into_iter()
+        .map(|(k, v)| (k, v.into_column_data()))
+        .collect();
+    result
+}
+
+/// Process the subsets after the first one
+fn parse_remaining_subsets<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    overrides: &ValueOverrides,
+    columns: &mut IndexMap<String, ColumnDataBuilder>,
+) -> Result<(), Error> {
+    while let Some(values) = data_reader.read_subset()? {
+        let subset = to_row(values, tables, overrides)?;
+        add_subset_to_columns(&subset, columns)?;
+    }
+    Ok(())
+}
+
+/// Mutable column data for building
+enum ColumnDataBuilder {
+    Scalar {
+        values: Vec<Value>,
+        ty: DataType,
+        kind: ElementKind,
+    },
+    Struct {
+        fields: IndexMap<String, ColumnDataBuilder>,
+    },
+    List {
+        offsets: Vec<i32>,
+        items: Box<ColumnDataBuilder>,
+    },
+}
+
+impl ColumnDataBuilder {
+    fn into_column_data(self) -> ColumnData {
+        match self {
+            ColumnDataBuilder::Scalar {
+                values,
+                ty: data_type,
+                kind,
+            } => ColumnData::Scalar {
+                values,
+                ty: data_type,
+                kind,
+            },
+            ColumnDataBuilder::Struct { fields } => ColumnData::Struct {
+                fields: fields
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into_column_data()))
+                    .collect(),
+            },
+            ColumnDataBuilder::List { offsets, items } => ColumnData::List {
+                offsets,
+                items: Box::new(items.into_column_data()),
+            },
+        }
+    }
+}
+
+/// Initialize column builders from first subset
+fn initialize_columns_from_subset(
+    subset: &IndexMap<String, RowValue>,
+) -> Result<IndexMap<String, ColumnDataBuilder>, Error> {
+    subset
+        .iter()
+        .map(|(name, value)| {
+            let builder = match value {
+                RowValue::Scalar(_, data_type, kind) => ColumnDataBuilder::Scalar {
+                    values: Vec::new(),
+                    ty: data_type.clone(),
+                    kind: *kind,
+                },
+                RowValue::Struct(fields) => ColumnDataBuilder::Struct {
+                    fields: initialize_columns_from_subset(fields)?,
+                },
+                RowValue::List(items) => {
+                    let item_builder = if items.is_empty() {
+                        ColumnDataBuilder::Struct {
+                            fields: IndexMap::new(),
+                        }
+                    } else {
+                        ColumnDataBuilder::Struct {
+                            fields: initialize_columns_from_subset(&items[0])?,
+                        }
+                    };
+                    ColumnDataBuilder::List {
+                        offsets: vec![0],
+                        items: Box::new(item_builder),
+                    }
+                }
+            };
+            Ok((name.clone(), builder))
+        })
+        .collect()
+}
+
+/// Add subset data to column builders
+fn add_subset_to_columns(
+    subset: &IndexMap<String, RowValue>,
+    columns: &mut IndexMap<String, ColumnDataBuilder>,
+) -> Result<(), Error> {
+    for (name, value) in subset {
+        if let Some(column) = columns.get_mut(name) {
+            add_value_to_column(value, column)?;
+        }
+    }
+    Ok(())
+}
+
+/// Add a single value to a column builder
+fn add_value_to_column(value: &RowValue, column: &mut ColumnDataBuilder) -> Result<(), Error> {
+    match (value, column) {
+        (RowValue::Scalar(v, ..), ColumnDataBuilder::Scalar { values, .. }) => {
+            values.push(v.clone());
+        }
+        (RowValue::Struct(fields), ColumnDataBuilder::Struct { fields: col_fields }) => {
+            for (name, val) in fields {
+                if let Some(col) = col_fields.get_mut(name) {
+                    add_value_to_column(val, col)?;
+                }
+            }
+        }
+        (
+            RowValue::List(items),
+            ColumnDataBuilder::List {
+                offsets,
+                items: col_items,
+            },
+        ) => {
+            let last_offset = *offsets.last().unwrap();
+            offsets.push(last_offset + items.len() as i32);
+
+            // Add each item to the items column
+            for item in items {
+                if let ColumnDataBuilder::Struct { fields } = &mut **col_items {
+                    for (name, val) in item {
+                        if let Some(col) = fields.get_mut(name) {
+                            add_value_to_column(val, col)?;
+                        }
+                    }
+                }
+            }
+        }
+        _ => {
+            return Err(Error::Fatal(
+                "Type mismatch when adding to column".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Intermediate row-oriented data structure for non-compressed parsing
+#[derive(Debug, Clone)]
+enum RowValue {
+    Scalar(Value, DataType, ElementKind),
+    Struct(IndexMap<String, RowValue>),
+    List(Vec<IndexMap<String, RowValue>>),
+}
+
+/// Converts the values of a subset, sequence or replication item into row format
+fn to_row(
+    values: Vec<SubsetValue>,
+    tables: &Tables,
+    overrides: &ValueOverrides,
+) -> Result<IndexMap<String, RowValue>, Error> {
+    let mut row = IndexMap::new();
+    let mut ctx = FieldNameContext::default();
+    for value in values {
+        match value {
+            SubsetValue::Scalar { xy, value } => {
+                let Some(b) = tables.table_b.get(&xy) else {
+                    return Err(Error::Fatal(format!("Unknown data descriptor: {xy:#?}")));
+                };
+                let count = ctx.track_element(b.element_name);
+                let label = create_field_name(b, count);
+                row.insert(
+                    label,
+                    RowValue::Scalar(
+                        overrides.apply(&xy, value),
+                        column_type(b, overrides),
+                        b.kind(),
+                    ),
+                );
+            }
+            SubsetValue::Sequence { xy, values } => {
+                let Some(d) = tables.table_d.get(&xy) else {
+                    return Err(Error::Fatal(format!(
+                        "Unknown sequence descriptor: {xy:#?}"
+                    )));
+                };
+
+                let count = ctx.track_sequence(d.title);
+                let label = match count {
+                    0 | 1 => d.title.to_string(),
+                    _ => format!("{} ({})", d.title, count),
+                };
+                row.insert(label, RowValue::Struct(to_row(values, tables, overrides)?));
+            }
+            SubsetValue::Replication { items } => {
+                let rep_num = ctx.track_replication();
+                let label = format!("replication:{rep_num}");
+                let items = items
+                    .into_iter()
+                    .map(|item| to_row(item, tables, overrides))
+                    .collect::<Result<_, _>>()?;
+                row.insert(label, RowValue::List(items));
+            }
+        }
+    }
+    Ok(row)
+}
+
+/// Convert column data to Arrow RecordBatch
+fn convert_column_data_to_arrow(
+    columns: IndexMap<String, ColumnData>,
+) -> Result<RecordBatch, Error> {
+    let (fields, arrays): (Vec<_>, Vec<_>) = columns
+        .into_iter()
+        .filter_map(|(name, column)| {
+            // Skip empty structs as Parquet doesn't support them
+            if is_empty_struct(&column) {
+                None
+            } else {
+                Some(build_arrow_array(&name, column))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .unzip();
+    let schema = Arc::new(Schema::new(fields));
+    if arrays.is_empty() {
+        // No subsets (or only empty structs), which gives no rows
+        return Ok(RecordBatch::new_empty(schema));
+    }
+    RecordBatch::try_new(schema, arrays)
+        .map_err(|e| Error::Fatal(format!("Failed to create RecordBatch: {e}")))
+}
+
+/// Check if a column is an empty struct or contains empty structs
+fn is_empty_struct(column: &ColumnData) -> bool {
+    match column {
+        ColumnData::Struct { fields } => fields.is_empty(),
+        ColumnData::List { items, .. } => is_empty_struct(items),
+        _ => false,
+    }
+}
+
+/// Build Arrow array from column data
+fn build_arrow_array(field_name: &str, column: ColumnData) -> Result<(Field, ArrayRef), Error> {
+    match column {
+        ColumnData::Scalar {
+            values,
+            ty: data_type,
+            kind,
+        } => {
+            let (field, array) = build_scalar_array(field_name, values, data_type)?;
+            Ok((field.with_metadata(kind_metadata(kind)), array))
+        }
+        ColumnData::Struct { fields } => {
+            if fields.is_empty() {
+                // Handle empty struct case
+                let struct_array = StructArray::new_empty_fields(0, None);
+                Ok((
+                    Field::new(
+                        field_name,
+                        DataType::Struct(::arrow::datatypes::Fields::empty()),
+                        true,
+                    ),
+                    Arc::new(struct_array),
+                ))
+            } else {
+                let (sub_fields, sub_arrays): (Vec<_>, Vec<_>) = fields
+                    .into_iter()
+                    .map(|(name, col)| build_arrow_array(&name, col))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .unzip();
+
+                let struct_array = StructArray::new(sub_fields.clone().into(), sub_arrays, None);
+                Ok((
+                    Field::new(field_name, DataType::Struct(sub_fields.into()), true),
+                    Arc::new(struct_array),
+                ))
+            }
+        }
+        ColumnData::List { offsets, items } => {
+            let (item_field, item_array) = match *items {
+                ColumnData::Struct { fields } => {
+                    if fields.is_empty() {
+                        // Calculate the length from offsets
+                        let len = offsets.last().copied().unwrap_or(0) as usize;
+                        let struct_array = StructArray::new_empty_fields(len, None);
+                        (
+                            Field::new(
+                                "item",
+                                DataType::Struct(::arrow::datatypes::Fields::empty()),
+                                true,
+                            ),
+                            Arc::new(struct_array) as ArrayRef,
+                        )
+                    } else {
+                        let (sub_fields, sub_arrays): (Vec<_>, Vec<_>) = fields
+                            .into_iter()
+                            .map(|(name, col)| build_arrow_array(&name, col))
+                            .collect::<Result<Vec<_>, _>>()?
+                            .into_iter()
+                            .unzip();
+
+                        let struct_array =
+                            StructArray::new(sub_fields.clone().into(), sub_arrays, None);
+                        (
+                            Field::new("item", DataType::Struct(sub_fields.into()), true),
+                            Arc::new(struct_array) as ArrayRef,
+                        )
+                    }
+                }
+                _ => {
+                    return Err(Error::Fatal("List items must be struct type".to_string()));
+                }
+            };
+
+            let item_field_arc = Arc::new(item_field);
+            let list_array = ::arrow::array::ListArray::try_new(
+                item_field_arc.clone(),
+                OffsetBuffer::new(offsets.into()),
+                item_array,
+                None,
+            )
+            .map_err(|e| Error::Fatal(format!("Failed to create list array: {e}")))?;
+
+            Ok((
+                Field::new(field_name, DataType::List(item_field_arc), true),
+                Arc::new(list_array),
+            ))
+        }
+    }
+}
+
+/// Build scalar Arrow array
+fn build_scalar_array(
+    field_name: &str,
+    values: Vec<Value>,
+    data_type: DataType,
+) -> Result<(Field, ArrayRef), Error> {
+    match data_type {
+        DataType::Utf8 => {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                match value {
+                    crate::Value::String(s) => builder.append_value(s),
+                    // Kept bytes are not valid UTF-8
+                    crate::Value::Bytes(b) => builder.append_value(String::from_utf8_lossy(&b)),
+                    crate::Value::Missing => builder.append_null(),
+                    _ => return Err(Error::Fatal("Type mismatch: expected string".to_string())),
+                }
+            }
+            Ok((
+                Field::new(field_name, DataType::Utf8, true),
+                Arc::new(builder.finish()),
+            ))
+        }
+        DataType::Int32 => {
+            let mut builder = Int32Builder::new();
+            for value in values {
+                match value {
+                    crate::Value::Integer(v) => builder.append_value(v),
+                    crate::Value::Decimal(v, scale) => {
+                        builder.append_value((v as f64 * 10f64.powi(scale as i32)) as i32)
+                    }
+                    crate::Value::Decimal64(v, scale) => {
+                        builder.append_value((v as f64 * 10f64.powi(scale as i32)) as i32)
+                    }
+                    crate::Value::Missing => builder.append_null(),
+                    _ => return Err(Error::Fatal("Type mismatch: expected integer".to_string())),
+                }
+            }
+            Ok((
+                Field::new(field_name, DataType::Int32, true),
+                Arc::new(builder.finish()),
+            ))
+        }
+        DataType::Int64 => {
+            let mut builder = Int64Builder::new();
+            for value in values {
+                match value {
+                    crate::Value::Integer(v) => builder.append_value(v as i64),
+                    crate::Value::Decimal64(v, 0) => builder.append_value(v),
+                    crate::Value::Decimal(v, scale) => {
+                        builder.append_value((v as f64 * 10f64.powi(scale as i32)) as i64)
+                    }
+                    crate::Value::Decimal64(v, scale) => {
+                        builder.append_value((v as f64 * 10f64.powi(scale as i32)) as i64)
+                    }
+                    crate::Value::Missing => builder.append_null(),
+                    _ => return Err(Error::Fatal("Type mismatch: expected integer".to_string())),
+                }
+            }
+            Ok((
+                Field::new(field_name, DataType::Int64, true),
+                Arc::new(builder.finish()),
+            ))
+        }
+        DataType::Float64 => {
+            let mut builder = Float64Builder::new();
+            for value in values {
+                match value {
+                    crate::Value::Integer(v) => builder.append_value(v as f64),
+                    crate::Value::Decimal(v, scale) => {
+                        builder.append_value(v as f64 * 10f64.powi(scale as i32))
+                    }
+                    crate::Value::Decimal64(v, scale) => {
+                        builder.append_value(v as f64 * 10f64.powi(scale as i32))
+                    }
+                    crate::Value::Missing => builder.append_null(),
+                    _ => return Err(Error::Fatal("Type mismatch: expected numeric".to_string())),
+                }
+            }
+            Ok((
+                Field::new(field_name, DataType::Float64, true),
+                Arc::new(builder.finish()),
+            ))
+        }
+        DataType::Null => Ok((
+            Field::new(field_name, DataType::Null, true),
+            Arc::new(::arrow::array::NullArray::new(values.len())),
+        )),
+        _ => Err(Error::Fatal(format!(
+            "Unsupported data type: {data_type:?}"
+        ))),
+    }
+}
+
+/// Helper functions
+fn create_field_name(b: &TableBEntry, count: usize) -> String {
+    match b.unit {
+        "Numeric" => match count {
+            0 | 1 => b.element_name.to_string(),
+            _ => format!("{} ({})", b.element_name, count),
+        },
+        _ => match count {
+            0 | 1 => format!("{} [{}]", b.element_name, b.unit),
+            _ => format!("{} [{}] ({})", b.element_name, b.unit, count),
+        },
+    }
+}
+
+/// Determines the Arrow type of an element, honoring a forced output type
+fn column_type(b: &TableBEntry, overrides: &ValueOverrides) -> DataType {
+    match overrides.output_type(&b.xy) {
+        Some(OutputType::Integer) => DataType::Int32,
+        Some(OutputType::Float) => DataType::Float64,
+        Some(OutputType::String) => DataType::Utf8,
+        None => determine_arrow_type_from_table_b(b),
+    }
+}
+
+fn determine_arrow_type_from_table_b(entry: &TableBEntry) -> DataType {
+    match entry.unit {
+        "CCITT IA5" => DataType::Utf8,
+        _ if entry.bits > 32 && entry.scale >= 0 => DataType::Int64,
+        "Code table" | "Flag table" => DataType::Int32,
+        _ if entry.scale == 0 => DataType::Int32,
+        _ if entry.scale < 0 => DataType::Float64,
+        // Keep fractions of seconds (e.g. 0 04 007) for timestamps
+        _ if entry.xy.x == 4 => DataType::Float64,
+        _ => DataType::Int32,
+    }
+}
+
+/// Merge the items of a compressed replication into subset-major values, taking the
+/// first `counts[i]` items of subset `i`
+fn merge_replication_items(
+    items: Vec<IndexMap<String, ColumnData>>,
+    counts: &[usize],
+) -> Result<IndexMap<String, ColumnData>, Error> {
+    if items.is_empty() {
+        return Ok(IndexMap::new());
+    }
+
+    // Get field names from first item
+    let field_names: Vec<String> = items[0].keys().cloned().collect();
+
+    field_names
+        .into_iter()
+        .map(|field_name| {
+            // Collect values for this field from all items
+            let mut item_values = Vec::with_capacity(items.len());
+            let mut data_type = DataType::Null;
+            let mut element_kind = ElementKind::Data;
+
+            for item in items.iter() {
+                match item.get(&field_name) {
+                    Some(ColumnData::Scalar {
+                        values,
+                        ty: dt,
+                        kind,
+                    }) => {
+                        item_values.push(values);
+                        if matches!(data_type, DataType::Null) {
+                            data_type = dt.clone();
+                        }
+                        element_kind = *kind;
+                    }
+                    Some(_) => {
+                        return Err(Error::Fatal(
+                            "Nested structures in replication not yet supported".to_string(),
+                        ));
+                    }
+                    None => {
+                        return Err(Error::Fatal(format!(
+                            "Field {field_name} missing in a replication item"
+                        )));
+                    }
+                }
+            }
+            let all_values = counts
+                .iter()
+                .enumerate()
+                .flat_map(|(subset, &count)| {
+                    item_values[..count]
+                        .iter()
+                        .map(move |values| values[subset].clone())
+                })
+                .collect();
+
+            Ok((
+                field_name,
+                ColumnData::Scalar {
+                    values: all_values,
+                    ty: data_type,
+                    kind: element_kind,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Flatten struct columns into top-level columns named `parent.child`
+///
+/// This is useful for writers that only accept flat schemas, such as CSV.
+/// List columns cannot be flattened and are rendered as text instead.
+pub fn flatten_struct_columns(record_batch: &RecordBatch) -> Result<RecordBatch, Error> {
+    let mut fields = Vec::new();
+    let mut arrays = Vec::new();
+    for (field, array) in record_batch
+        .schema()
+        .fields()
+        .iter()
+        .zip(record_batch.columns())
+    {
+        flatten_column(field.name(), field, array, &mut fields, &mut arrays)?;
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+        .map_err(|e| Error::Fatal(format!("Failed to create RecordBatch: {e}")))
+}
+
+fn flatten_column(
+    name: &str,
+    field: &Field,
+    array: &ArrayRef,
+    fields: &mut Vec<Field>,
+    arrays: &mut Vec<ArrayRef>,
+) -> Result<(), Error> {
+    match field.data_type() {
+        DataType::Struct(sub_fields) => {
+            let struct_array = array
+                .as_any()
+                .downcast_ref::<StructArray>()
+                .ok_or_else(|| Error::Fatal(format!("Column '{name}' is not a struct")))?;
+            for (sub_field, sub_array) in sub_fields.iter().zip(struct_array.columns()) {
+                let sub_name = format!("{name}.{}", sub_field.name());
+                flatten_column(&sub_name, sub_field, sub_array, fields, arrays)?;
+            }
+        }
+        DataType::List(_) => {
+            let formatter = ArrayFormatter::try_new(array.as_ref(), &FormatOptions::default())
+                .map_err(|e| Error::Fatal(format!("Failed to format column '{name}': {e}")))?;
+            let mut builder = StringBuilder::new();
+            for i in 0..array.len() {
+                if array.is_null(i) {
+                    builder.append_null();
+                } else {
+                    builder.append_value(formatter.value(i).to_string());
+                }
+            }
+            fields.push(Field::new(name, DataType::Utf8, true));
+            arrays.push(Arc::new(builder.finish()));
+        }
+        data_type => {
+            fields.push(
+                Field::new(name, data_type.clone(), true).with_metadata(field.metadata().clone()),
+            );
+            arrays.push(array.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Key of the field metadata holding the [`ElementKind`] of an element column
+pub const ELEMENT_KIND_METADATA_KEY: &str = "bufr:kind";
+
+fn kind_metadata(kind: ElementKind) -> std::collections::HashMap<String, String> {
+    [(
+        ELEMENT_KIND_METADATA_KEY.to_string(),
+        kind.as_str().to_string(),
+    )]
+    .into()
+}
+
+/// Returns the [`ElementKind`] of an element column, or `None` for other columns.
+pub fn element_kind(field: &Field) -> Option<ElementKind> {
+    field
+        .metadata()
+        .get(ELEMENT_KIND_METADATA_KEY)?
+        .parse()
+        .ok()
+}
+
+/// Reorders the columns of a flat record batch so that coordinates and qualifiers
+/// precede measurements, keeping the order within each group.
+///
+/// Columns without a kind (e.g. provenance columns) stay in front.
+pub fn coordinates_first(record_batch: &RecordBatch) -> Result<RecordBatch, Error> {
+    let schema = record_batch.schema();
+    let (mut indices, data): (Vec<usize>, Vec<usize>) = (0..schema.fields().len())
+        .partition(|&i| !matches!(element_kind(schema.field(i)), Some(ElementKind::Data)));
+    indices.extend(data);
+    record_batch
+        .project(&indices)
+        .map_err(|e| Error::Fatal(format!("Failed to reorder columns: {e}")))
+}
+
+/// Name of the column added by [`add_timestamp_column`]
+pub const TIMESTAMP_COLUMN: &str = "_timestamp";
+
+/// Columns of the date and time elements (0 04 001 to 0 04 007)
+const YEAR_COLUMN: &str = "Year [a]";
+const MONTH_COLUMN: &str = "Month [mon]";
+const DAY_COLUMN: &str = "Day [d]";
+const HOUR_COLUMN: &str = "Hour [h]";
+const MINUTE_COLUMN: &str = "Minute [min]";
+const SECOND_COLUMN: &str = "Second [s]";
+const MICROSECOND_SECOND_COLUMN: &str = "Seconds within a minute (microsecond accuracy) [s]";
+
+/// Appends a `_timestamp` column (microseconds, UTC) synthesized from the first
+/// date and time elements of each row.
+///
+/// Seconds are taken from 0 04 007 (seconds with microsecond accuracy) when present,
+/// falling back to 0 04 006. Elements in sequences are found too, but not those in
+/// replications. Rows with a missing date or time element get a null timestamp.
+/// If there is no year, month and day, the batch is returned unchanged.
+pub fn add_timestamp_column(record_batch: &RecordBatch) -> Result<RecordBatch, Error> {
+    let flat = flatten_struct_columns(record_batch)?;
+    let (Some(year), Some(month), Some(day)) = (
+        time_column(&flat, YEAR_COLUMN)?,
+        time_column(&flat, MONTH_COLUMN)?,
+        time_column(&flat, DAY_COLUMN)?,
+    ) else {
+        return Ok(record_batch.clone());
+    };
+    let hour = time_column(&flat, HOUR_COLUMN)?;
+    let minute = time_column(&flat, MINUTE_COLUMN)?;
+    let second = match time_column(&flat, MICROSECOND_SECOND_COLUMN)? {
+        Some(second) => Some(second),
+        None => time_column(&flat, SECOND_COLUMN)?,
+    };
+
+    let value = |column: &Option<Vec<Option<f64>>>, row: usize| match column {
+        Some(values) => values[row],
+        None => Some(0.0),
+    };
+    let timestamps: TimestampMicrosecondArray = (0..record_batch.num_rows())
+        .map(|row| {
+            let seconds =
+                value(&hour, row)? * 3600.0 + value(&minute, row)? * 60.0 + value(&second, row)?;
+            unix_micros(year[row]?, month[row]?, day[row]?, seconds)
+        })
+        .collect();
+    let timestamps = timestamps.with_timezone("+00:00");
+
+    let schema = record_batch.schema();
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    fields.push(Field::new(
+        TIMESTAMP_COLUMN,
+        DataType::Timestamp(TimeUnit::Microsecond, Some("+00:00".into())),
+        true,
+    ));
+    let mut arrays = record_batch.columns().to_vec();
+    arrays.push(Arc::new(timestamps));
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+        .map_err(|e| Error::Fatal(format!("Failed to create RecordBatch: {e}")))
+}
+
+/// Returns the values of the first numeric column named `name` or `parent.name`.
+fn time_column(record_batch: &RecordBatch, name: &str) -> Result<Option<Vec<Option<f64>>>, Error> {
+    let schema = record_batch.schema();
+    let Some(index) = schema.fields().iter().position(|f| {
+        f.data_type().is_numeric()
+            && (f.name() == name
+                || f.name()
+                    .strip_suffix(name)
+                    .is_some_and(|p| p.ends_with('.')))
+    }) else {
+        return Ok(None);
+    };
+    let array = cast(record_batch.column(index), &DataType::Float64)
+        .map_err(|e| Error::Fatal(format!("Failed to cast column '{name}': {e}")))?;
+    Ok(Some(array.as_primitive::<Float64Type>().iter().collect()))
+}
@@ -0,0 +1,730 @@
+//! Columnar decoding path writing values directly into Arrow builders
+//!
+//! Templates made of elements, sequences and the 2 01/2 02 operators are compiled
+//! into a flat list of leaves with their effective width, reference value and scale.
+//! Data is then decoded leaf by leaf without materializing `DataEvent`s or `Value`s.
+//! Compressed columns are unpacked in one pass into values and a validity bitmap;
+//! compressed characters are left to the event-based path.
+//! Values of common widths are read by readers specialized for their width.
+//! The resulting schema and values are the same as those of [`super::convert_to_arrow`].
+
+use std::io::Read;
+use std::sync::Arc;
+
+use ::arrow::{
+    array::{
+        ArrayRef, Float64Array, Float64Builder, Int32Array, Int32Builder, NullBufferBuilder,
+        StringBuilder, StructArray,
+    },
+    datatypes::{DataType, Field, Schema},
+    record_batch::RecordBatch,
+};
+use bitstream_io::{BigEndian, BitRead, BitReader};
+
+use super::{
+    FieldNameContext, create_field_name, determine_arrow_type_from_table_b, kind_metadata,
+};
+use crate::{
+    CharacterDecoding, DataSpec, DecodeOptions, Error, ResolvedDescriptor, XY,
+    codec::effective_width_ref_scale,
+    reader::{
+        CountingReader, add_increment, check_nbinc, element_label, finish_data_section,
+        is_missing_increment,
+    },
+    sections::DataSectionHeader,
+    tables::{ElementKind, TableBEntry},
+    validation::Strictness,
+};
+
+/// An element with its effective encoding.
+struct Leaf {
+    xy: XY,
+    bit_width: u32,
+    ref_value: i32,
+    scale: i8,
+    data_type: DataType,
+    kind: ElementKind,
+}
+
+enum PlanNode {
+    Leaf(String, usize),
+    Struct(String, Vec<PlanNode>),
+}
+
+/// Compiled layout of a subset.
+struct Plan {
+    leaves: Vec<Leaf>,
+    nodes: Vec<PlanNode>,
+}
+
+impl Plan {
+    /// Compiles the descriptors, or returns `None` if they need the event-based path.
+    fn compile(
+        descriptors: &[ResolvedDescriptor],
+        is_compressed: bool,
+    ) -> Result<Option<Self>, Error> {
+        let mut plan = Plan {
+            leaves: vec![],
+            nodes: vec![],
+        };
+        let mut offsets = (0i8, 0i8);
+        let Some(nodes) = plan.compile_level(descriptors, &mut offsets, true)? else {
+            return Ok(None);
+        };
+        if is_compressed && plan.leaves.iter().any(|l| l.data_type == DataType::Utf8) {
+            return Ok(None);
+        }
+        plan.nodes = nodes;
+        Ok(Some(plan))
+    }
+
+    fn compile_level(
+        &mut self,
+        descriptors: &[ResolvedDescriptor],
+        offsets: &mut (i8, i8),
+        is_root: bool,
+    ) -> Result<Option<Vec<PlanNode>>, Error> {
+        let mut ctx = FieldNameContext::default();
+        let mut nodes = vec![];
+        let mut names = hashbrown::HashSet::new();
+        for desc in descriptors {
+            let node = match desc {
+                ResolvedDescriptor::Data(b) => {
+                    let count = ctx.track_element(b.element_name);
+                    let Some(leaf) = Self::compile_leaf(b, *offsets)? else {
+                        return Ok(None);
+                    };
+                    self.leaves.push(leaf);
+                    PlanNode::Leaf(create_field_name(b, count), self.leaves.len() - 1)
+                }
+                ResolvedDescriptor::Sequence(d, children) => {
+                    let count = ctx.track_sequence(d.title);
+                    let label = match count {
+                        0 | 1 => d.title.to_string(),
+                        _ => format!("{} ({})", d.title, count),
+                    };
+                    let Some(children) = self.compile_level(children, offsets, false)? else {
+                        return Ok(None);
+                    };
+                    if children.is_empty() {
+                        if is_root {
+                            // Empty structs are skipped at the top level
+                            continue;
+                        }
+                        return Ok(None);
+                    }
+                    PlanNode::Struct(label, children)
+                }
+                ResolvedDescriptor::Operator(xy, _) => {
+                    match (xy.x, xy.y) {
+                        (1, 0) => offsets.0 = 0,
+                        (1, y) => offsets.0 = ((y as i16) - 128) as i8,
+                        (2, 0) => offsets.1 = 0,
+                        (2, y) => offsets.1 = ((y as i16) - 128) as i8,
+                        _ => return Ok(None),
+                    }
+                    continue;
+                }
+                ResolvedDescriptor::Replication { .. } | ResolvedDescriptor::Unresolved(_) => {
+                    return Ok(None);
+                }
+            };
+            let name = match &node {
+                PlanNode::Leaf(name, _) | PlanNode::Struct(name, _) => name.clone(),
+            };
+            // Duplicate names overwrite each other in the event-based path
+            if !names.insert(name) {
+                return Ok(None);
+            }
+            nodes.push(node);
+        }
+        Ok(Some(nodes))
+    }
+
+    fn compile_leaf(b: &TableBEntry, offsets: (i8, i8)) -> Result<Option<Leaf>, Error> {
+        let (bit_width, ref_value, scale) =
+            effective_width_ref_scale(b, offsets.0, offsets.1, 0, 0);
+        let data_type = determine_arrow_type_from_table_b(b);
+        let valid = match data_type {
+            DataType::Utf8 => bit_width > 32 && bit_width % 8 == 0,
+            // Values out of the i32 range are left to the event-based path
+            DataType::Int32 => {
+                bit_width <= 32 && (1i64 << bit_width) - 2 + ref_value as i64 <= i32::MAX as i64
+            }
+            _ => bit_width <= 32,
+        };
+        if !valid {
+            // Let the event-based path report the error
+            return Ok(None);
+        }
+        Ok(Some(Leaf {
+            xy: b.xy,
+            bit_width,
+            ref_value,
+            scale,
+            data_type,
+            kind: b.kind(),
+        }))
+    }
+}
+
+impl Leaf {
+    fn int_value(&self, raw: u32) -> i32 {
+        let v = raw as i64 + self.ref_value as i64;
+        match self.scale {
+            0 => v as i32,
+            scale => (v as f64 * 10f64.powi(-scale as i32)) as i32,
+        }
+    }
+
+    fn float_value(&self, raw: u32) -> f64 {
+        let v = raw as i64 + self.ref_value as i64;
+        match self.scale {
+            0 => v as f64,
+            scale => v as f64 * 10f64.powi(-scale as i32),
+        }
+    }
+}
+
+enum ColumnBuilder {
+    Int32(Int32Builder),
+    Float64(Float64Builder),
+    Utf8(StringBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: &DataType, capacity: usize) -> Self {
+        match data_type {
+            DataType::Utf8 => ColumnBuilder::Utf8(StringBuilder::with_capacity(capacity, 0)),
+            DataType::Float64 => ColumnBuilder::Float64(Float64Builder::with_capacity(capacity)),
+            _ => ColumnBuilder::Int32(Int32Builder::with_capacity(capacity)),
+        }
+    }
+
+    /// Appends a numeric value from its raw encoded value (`None` if missing).
+    fn append_raw(&mut self, leaf: &Leaf, raw: Option<u32>) {
+        let Some(raw) = raw else {
+            match self {
+                ColumnBuilder::Int32(b) => b.append_null(),
+                ColumnBuilder::Float64(b) => b.append_null(),
+                ColumnBuilder::Utf8(b) => b.append_null(),
+            }
+            return;
+        };
+        match self {
+            ColumnBuilder::Int32(b) => b.append_value(leaf.int_value(raw)),
+            ColumnBuilder::Float64(b) => b.append_value(leaf.float_value(raw)),
+            ColumnBuilder::Utf8(b) => b.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Int32(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float64(b) => Arc::new(b.finish()),
+            ColumnBuilder::Utf8(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// Decodes a data section directly into an Arrow RecordBatch.
+///
+/// The reader must be positioned at the start of the data section (Section 4).
+/// Returns `Ok(None)` without reading anything if the template needs features only
+/// supported by the event-based path (e.g. replications); use [`super::convert_to_arrow`]
+/// then. Missing value policies and value hooks are only applied by the event-based path.
+pub fn convert_to_arrow_columnar<R: Read>(
+    reader: R,
+    data_spec: &DataSpec,
+) -> Result<Option<RecordBatch>, Error> {
+    convert_to_arrow_columnar_with_options(reader, data_spec, &DecodeOptions::default())
+}
+
+/// Decodes a data section directly into an Arrow RecordBatch with the given decode
+/// options (see [`convert_to_arrow_columnar`]).
+pub fn convert_to_arrow_columnar_with_options<R: Read>(
+    mut reader: R,
+    data_spec: &DataSpec,
+    options: &DecodeOptions,
+) -> Result<Option<RecordBatch>, Error> {
+    if data_spec.number_of_subsets == 0 {
+        return Ok(None);
+    }
+    let Some(plan) = Plan::compile(&data_spec.root_descriptors, data_spec.is_compressed)? else {
+        return Ok(None);
+    };
+
+    let num_subsets = data_spec.number_of_subsets as usize;
+    let section_header = DataSectionHeader::read(&mut reader)?;
+    let mut bits = BitReader::endian(CountingReader::new(reader), BigEndian);
+    let readers: Vec<RawReader<CountingReader<R>>> = plan
+        .leaves
+        .iter()
+        .map(|leaf| raw_reader(leaf.bit_width))
+        .collect();
+    let columns = if data_spec.is_compressed {
+        plan.leaves
+            .iter()
+            .map(|leaf| read_compressed_column(&mut bits, leaf, num_subsets))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        let mut builders: Vec<_> = plan
+            .leaves
+            .iter()
+            .map(|leaf| ColumnBuilder::new(&leaf.data_type, num_subsets))
+            .collect();
+        for _ in 0..num_subsets {
+            for ((leaf, builder), read_raw) in plan.leaves.iter().zip(&mut builders).zip(&readers) {
+                read_value(
+                    &mut bits,
+                    leaf,
+                    *read_raw,
+                    builder,
+                    options.character_decoding,
+                )?;
+            }
+        }
+        builders.iter_mut().map(ColumnBuilder::finish).collect()
+    };
+    // Deviations from the declared length are only reported in strict mode
+    if let Some(message) = finish_data_section(&mut bits, section_header.section_length)?
+        && options.strictness == Strictness::Strict
+    {
+        return Err(Error::Invalid(message));
+    }
+
+    let (fields, arrays): (Vec<_>, Vec<_>) = plan
+        .nodes
+        .iter()
+        .map(|node| build_node(node, &plan.leaves, &columns))
+        .unzip();
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+        .map(Some)
+        .map_err(|e| Error::Fatal(format!("Failed to create RecordBatch: {e}")))
+}
+
+/// Reads a raw value of the given width.
+type RawReader<R> = fn(&mut BitReader<R, BigEndian>, u32) -> std::io::Result<u32>;
+
+fn read_fixed<R: Read, const BITS: u32>(
+    bits: &mut BitReader<R, BigEndian>,
+    _width: u32,
+) -> std::io::Result<u32> {
+    bits.read::<BITS, u32>()
+}
+
+fn read_variable<R: Read>(bits: &mut BitReader<R, BigEndian>, width: u32) -> std::io::Result<u32> {
+    bits.read_var(width)
+}
+
+/// Selects the reader for a width, specialized for the widths common in Table B.
+fn raw_reader<R: Read>(width: u32) -> RawReader<R> {
+    match width {
+        1 => read_fixed::<R, 1>,
+        2 => read_fixed::<R, 2>,
+        3 => read_fixed::<R, 3>,
+        4 => read_fixed::<R, 4>,
+        5 => read_fixed::<R, 5>,
+        6 => read_fixed::<R, 6>,
+        7 => read_fixed::<R, 7>,
+        8 => read_fixed::<R, 8>,
+        9 => read_fixed::<R, 9>,
+        10 => read_fixed::<R, 10>,
+        11 => read_fixed::<R, 11>,
+        12 => read_fixed::<R, 12>,
+        13 => read_fixed::<R, 13>,
+        14 => read_fixed::<R, 14>,
+        15 => read_fixed::<R, 15>,
+        16 => read_fixed::<R, 16>,
+        18 => read_fixed::<R, 18>,
+        20 => read_fixed::<R, 20>,
+        24 => read_fixed::<R, 24>,
+        32 => read_fixed::<R, 32>,
+        _ => read_variable::<R>,
+    }
+}
+
+fn read_value<R: Read>(
+    bits: &mut BitReader<R, BigEndian>,
+    leaf: &Leaf,
+    read_raw: RawReader<R>,
+    builder: &mut ColumnBuilder,
+    character_decoding: CharacterDecoding,
+) -> Result<(), Error> {
+    if let ColumnBuilder::Utf8(b) = builder {
+        let bytes = bits.read_to_vec((leaf.bit_width / 8) as usize)?;
+        if bytes.iter().all(|&c| c == 0xff) {
+            b.append_null();
+            return Ok(());
+        }
+        match character_decoding.decode(bytes) {
+            Some(crate::Value::String(s)) => b.append_value(s),
+            // As in the event-based path
+            Some(crate::Value::Bytes(bytes)) => b.append_value(String::from_utf8_lossy(&bytes)),
+            _ => {
+                return Err(Error::Invalid(format!(
+                    "Failed to parse character string with bit width {}",
+                    leaf.bit_width
+                )));
+            }
+        }
+        return Ok(());
+    }
+    let raw = read_raw(bits, leaf.bit_width)?;
+    let missing = ((1u64 << leaf.bit_width) - 1) as u32;
+    builder.append_raw(leaf, (raw != missing).then_some(raw));
+    Ok(())
+}
+
+/// Reads a compressed numeric column, building its validity bitmap while unpacking.
+fn read_compressed_column<R: Read>(
+    bits: &mut BitReader<R, BigEndian>,
+    leaf: &Leaf,
+    num_subsets: usize,
+) -> Result<ArrayRef, Error> {
+    let missing = ((1u64 << leaf.bit_width) - 1) as u32;
+    let local_ref_value: u32 = bits.read_var(leaf.bit_width)?;
+    let nbinc = bits.read::<6, u8>()?;
+    check_nbinc(nbinc, leaf.bit_width, || element_label(leaf.xy))?;
+    let mut raws = Vec::with_capacity(num_subsets);
+    let mut validity = NullBufferBuilder::new(num_subsets);
+    if nbinc == 0 {
+        raws.resize(num_subsets, local_ref_value);
+        match local_ref_value == missing {
+            true => validity.append_n_nulls(num_subsets),
+            false => validity.append_n_non_nulls(num_subsets),
+        }
+    } else {
+        let read_inc = raw_reader(nbinc as u32);
+        for _ in 0..num_subsets {
+            let inc = read_inc(bits, nbinc as u32)?;
+            if is_missing_increment(inc as u64, nbinc) {
+                raws.push(missing);
+                validity.append_null();
+                continue;
+            }
+            let raw = add_increment(local_ref_value as u64, inc as u64, leaf.bit_width, || {
+                element_label(leaf.xy)
+            })? as u32;
+            raws.push(raw);
+            validity.append(raw != missing);
+        }
+    }
+    // Values under nulls are computed too, but never exposed
+    let nulls = validity.finish();
+    Ok(match leaf.data_type {
+        DataType::Float64 => Arc::new(Float64Array::new(
+            raws.iter().map(|&raw| leaf.float_value(raw)).collect(),
+            nulls,
+        )),
+        _ => Arc::new(Int32Array::new(
+            raws.iter().map(|&raw| leaf.int_value(raw)).collect(),
+            nulls,
+        )),
+    })
+}
+
+fn build_node(node: &PlanNode, leaves: &[Leaf], columns: &[ArrayRef]) -> (Field, ArrayRef) {
+    match node {
+        PlanNode::Leaf(name, index) => {
+            let array = columns[*index].clone();
+            let field = Field::new(name, array.data_type().clone(), true)
+                .with_metadata(kind_metadata(leaves[*index].kind));
+            (field, array)
+        }
+        PlanNode::Struct(name, children) => {
+            let (sub_fields, sub_arrays): (Vec<_>, Vec<_>) = children
+                .iter()
+                .map(|child| build_node(child, leaves, columns))
+                .unzip();
+            let struct_array = StructArray::new(sub_fields.clone().into(), sub_arrays, None);
+            (
+                Field::new(name, DataType::Struct(sub_fields.into()), true),
+                Arc::new(struct_array),
+            )
+        }
+    }
+}
+
+/// Options for [`convert_file_fast`].
+#[cfg(feature = "parquet")]
+#[derive(Clone)]
+pub struct FastConvertOptions<'a> {
+    /// Tables used for decoding (the default tables if `None`)
+    pub tables: Option<&'a crate::Tables>,
+    /// Add `_message_index`, `_subset_index` and `_source_file` columns
+    pub provenance_columns: bool,
+    /// Compression of the Parquet file
+    pub compression: parquet::basic::Compression,
+    /// Per-descriptor overrides of output types and values (disables the columnar path)
+    pub overrides: crate::overrides::ValueOverrides,
+    /// Recovery from errors in messages
+    pub recovery: crate::recovery::RecoveryPolicy,
+    /// Called with the index of the message and the error for each error recovered from
+    pub on_recovered_error: Option<&'a RecoveredErrorCallback<'a>>,
+}
+
+/// Callback called with the index of a message and the error recovered from.
+#[cfg(feature = "parquet")]
+pub type RecoveredErrorCallback<'a> = dyn Fn(usize, &Error) + Sync + 'a;
+
+#[cfg(feature = "parquet")]
+impl Default for FastConvertOptions<'_> {
+    fn default() -> Self {
+        Self {
+            tables: None,
+            provenance_columns: false,
+            compression: parquet::basic::Compression::SNAPPY,
+            overrides: Default::default(),
+            recovery: Default::default(),
+            on_recovered_error: None,
+        }
+    }
+}
+
+/// Converts all messages of a BUFR file into a Parquet file.
+///
+/// The messages, found as by [`open_any`](crate::input::open_any), are exported into a
+/// [`ParquetSink`] (see [`export_with_options`](crate::sink::export_with_options)),
+/// which decodes them with the columnar path when possible. All messages must share
+/// the same schema. Returns the number of rows written, or an error (removing the
+/// output file) if there is none.
+///
+/// Errors in messages are handled as set by `options.recovery` (see
+/// [`RecoveryPolicy`](crate::recovery::RecoveryPolicy)), and those recovered from are
+/// reported to `options.on_recovered_error`. When recovering, a message whose end
+/// section (`7777`) is missing keeps its rows.
+#[cfg(feature = "parquet")]
+pub fn convert_file_fast<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(
+    input: P,
+    output: Q,
+    options: &FastConvertOptions,
+) -> Result<usize, Error> {
+    use super::ConvertOptions;
+    use crate::{
+        Tables,
+        sink::{ExportOptions, export_with_options},
+    };
+
+    let default_tables;
+    let tables = match options.tables {
+        Some(tables) => tables,
+        None => {
+            default_tables = Tables::default();
+            &default_tables
+        }
+    };
+    let messages = crate::input::open_any_with_recovery(input.as_ref(), options.recovery)?;
+    let convert_options = ConvertOptions {
+        provenance_columns: options.provenance_columns,
+        source_file: Some(input.as_ref().display().to_string()),
+        overrides: options.overrides.clone(),
+        ..Default::default()
+    };
+    let sink = ParquetSink::new(
+        std::fs::File::create(output.as_ref())?,
+        convert_options,
+        options.compression,
+    );
+    let export_options = ExportOptions {
+        recovery: options.recovery,
+        ..Default::default()
+    };
+    let summary = export_with_options(sink, messages, tables, &export_options)?;
+    if let Some(on_error) = options.on_recovered_error {
+        for message in &summary.messages {
+            if let Some(error) = &message.error {
+                on_error(message.index as usize, error);
+            }
+        }
+    }
+    // The sink leaves the output empty, which is not a valid Parquet file
+    if summary.rows == 0 {
+        std::fs::remove_file(output.as_ref())?;
+        return Err(Error::Invalid("No data to write".to_string()));
+    }
+    Ok(summary.rows)
+}
+
+/// Writes messages into a Parquet file, converted as by [`super::RecordBatchSink`].
+///
+/// Messages are decoded with the columnar path when possible, falling back to the
+/// event-based path otherwise (or to keep the data converted before an error, as set
+/// by the policy of the export).
+///
+/// The schema of the file is that of the first message with subsets, which all the
+/// others must share. Messages without subsets are left out, and the output is left
+/// empty if no message has any.
+#[cfg(feature = "parquet")]
+pub struct ParquetSink<W: std::io::Write + Send> {
+    options: super::ConvertOptions,
+    properties: parquet::file::properties::WriterProperties,
+    /// The output, until the writer is created from the first message
+    output: Option<W>,
+    /// The writer, with the schema of the file
+    writer: Option<(
+        parquet::arrow::ArrowWriter<W>,
+        ::arrow::datatypes::SchemaRef,
+    )>,
+}
+
+#[cfg(feature = "parquet")]
+impl<W: std::io::Write + Send> ParquetSink<W> {
+    pub fn new(
+        output: W,
+        options: super::ConvertOptions,
+        compression: parquet::basic::Compression,
+    ) -> Self {
+        let properties = parquet::file::properties::WriterProperties::builder()
+            .set_compression(compression)
+            .set_key_value_metadata(Some(build_info_metadata()))
+            .build();
+        Self {
+            options,
+            properties,
+            output: Some(output),
+            writer: None,
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl<W: std::io::Write + Send> crate::sink::OutputSink for ParquetSink<W> {
+    type Output = W;
+
+    fn write_message(
+        &mut self,
+        input: &mut crate::sink::MessageInput<'_, '_>,
+    ) -> Result<usize, Error> {
+        if input.data_spec.number_of_subsets == 0 {
+            return Ok(0);
+        }
+        self.options.message_index = input.index;
+        self.options.recovery = input.recovery;
+        let columnar = match self.options.overrides.is_empty()
+            && input.missing_values.is_empty()
+            && input.value_hooks.is_empty()
+        {
+            true => convert_to_arrow_columnar_with_options(
+                input.data_section,
+                input.data_spec,
+                input.decode_options,
+            ),
+            false => Ok(None),
+        };
+        let record_batch = match columnar {
+            Ok(Some(record_batch)) => {
+                input.mark_data_decoded();
+                match self.options.provenance_columns {
+                    true => super::add_provenance_columns(
+                        record_batch,
+                        input.data_spec.number_of_subsets as usize,
+                        &self.options,
+                    )?,
+                    false => record_batch,
+                }
+            }
+            // The event-based path keeps the data converted before an error
+            Err(error) if !input.recovery.keeps_partial(input.data_spec.is_compressed) => {
+                input.recover(error)?;
+                return Ok(0);
+            }
+            Ok(None) | Err(_) => {
+                let partial = super::convert_to_arrow_with_recovery(
+                    &mut input.data_reader,
+                    input.tables,
+                    input.data_spec,
+                    &self.options,
+                )?;
+                if let Some(error) = partial.error {
+                    input.recover(error)?;
+                }
+                let Some(record_batch) = partial.record_batch else {
+                    return Ok(0);
+                };
+                record_batch
+            }
+        };
+        let writer = match (&mut self.writer, self.output.take()) {
+            (Some(writer), _) => writer,
+            (None, Some(output)) => self.writer.insert((
+                parquet::arrow::ArrowWriter::try_new(
+                    output,
+                    record_batch.schema(),
+                    Some(self.properties.clone()),
+                )
+                .map_err(|e| Error::Fatal(format!("Failed to create Parquet writer: {e}")))?,
+                record_batch.schema(),
+            )),
+            (None, None) => {
+                return Err(Error::Fatal(
+                    "The Parquet writer failed to be created".to_string(),
+                ));
+            }
+        };
+        let (writer, schema) = writer;
+        if *schema != record_batch.schema() {
+            return Err(Error::NotSupported(format!(
+                "Message {} has a different schema from the first message",
+                input.index
+            )));
+        }
+        writer
+            .write(&record_batch)
+            .map_err(|e| Error::Fatal(format!("Failed to write Parquet file: {e}")))?;
+        Ok(record_batch.num_rows())
+    }
+
+    fn finish(self) -> Result<W, Error> {
+        match (self.writer, self.output) {
+            (Some((writer, _)), _) => writer
+                .into_inner()
+                .map_err(|e| Error::Fatal(format!("Failed to write Parquet file: {e}"))),
+            (None, Some(output)) => Ok(output),
+            (None, None) => Err(Error::Fatal(
+                "The Parquet writer failed to be created".to_string(),
+            )),
+        }
+    }
+}
+
+/// Key-value metadata of Parquet files recording the build used for decoding
+#[cfg(feature = "parquet")]
+fn build_info_metadata() -> Vec<parquet::format::KeyValue> {
+    crate::build_info()
+        .key_values()
+        .into_iter()
+        .map(|(key, value)| parquet::format::KeyValue::new(key, value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bitstream_io::{BitWrite, BitWriter};
+
+    use super::*;
+
+    #[test]
+    fn test_raw_reader() {
+        let widths: Vec<u32> = (1..=32).collect();
+        let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+        for &width in &widths {
+            writer.write_var(width, 0u32).unwrap();
+            writer.write_var(width, u32::MAX >> (32 - width)).unwrap();
+            writer.write_var(width, 1u32).unwrap();
+        }
+        writer.byte_align().unwrap();
+        let data = writer.into_writer();
+
+        let mut bits = BitReader::endian(data.as_slice(), BigEndian);
+        for &width in &widths {
+            let read_raw = raw_reader(width);
+            assert_eq!(read_raw(&mut bits, width).unwrap(), 0);
+            assert_eq!(
+                read_raw(&mut bits, width).unwrap(),
+                u32::MAX >> (32 - width)
+            );
+            assert_eq!(read_raw(&mut bits, width).unwrap(), 1, "width {width}");
+        }
+    }
+}
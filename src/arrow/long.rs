@@ -0,0 +1,210 @@
+//! Long-format ("tidy") conversion
+//!
+//! Each value becomes a row of its own, so messages of heterogeneous templates share
+//! a single schema, as preferred by time-series databases and feature extraction.
+
+use std::io::Read;
+use std::sync::Arc;
+
+use ::arrow::{
+    array::{ArrayRef, Float64Builder, StringBuilder, UInt32Builder},
+    datatypes::{DataType, Field, Schema},
+    record_batch::RecordBatch,
+};
+
+use super::ConvertOptions;
+use crate::{DataEvent, DataReader, Error, Tables, Value, XY};
+
+/// A value of an element in a subset.
+struct Row {
+    subset: u32,
+    position: u32,
+    xy: XY,
+    value: Value,
+    qc: Option<f64>,
+}
+
+/// Converts BUFR data into a long-format Arrow RecordBatch.
+///
+/// Each row holds a value of a data element, with the columns `_message_index`,
+/// `_subset_index` (and `_source_file` if `source_file` is set in the options),
+/// `position` (the index of the element in its subset, as referred to by bit-maps),
+/// `descriptor` (`FXXYYY`), `name`, `unit`, `value` (numeric values), `text`
+/// (character values) and `qc`.
+///
+/// `qc` is the quality information of the element: the value of a class 33 element
+/// associated by a data present bit-map, which has no row of its own, or the value
+/// marked by 2 22 255. Other values marked by 2 YY 255 (e.g. statistics), data present
+/// indicators (0 31 031) of bit-maps and values of elements not found in the tables
+/// are left out, as are the padding items of delayed replications whose factors vary
+/// between the subsets of compressed data.
+pub fn convert_to_arrow_long<R: Read>(
+    data_reader: &mut DataReader<'_, R>,
+    tables: &Tables,
+    options: &ConvertOptions,
+) -> Result<RecordBatch, Error> {
+    // Rows of each subset, which compressed data yields element by element
+    let mut subsets: Vec<Subset> = vec![];
+    let mut subset = 0;
+    // Bit-map position the next data event is the quality information of
+    let mut target = None;
+    // Replications in compressed data: the factors of each subset, if they vary, and
+    // the index of the current item
+    let mut replications: Vec<(Option<Vec<u16>>, u16)> = vec![];
+    loop {
+        match data_reader.read_event()? {
+            DataEvent::SubsetStart(i) => {
+                subset = i as usize;
+                subsets.resize_with(subset + 1, Subset::default);
+            }
+            DataEvent::BitmapTarget { position, .. } => target = Some(position),
+            DataEvent::Data { xy, value, .. } => {
+                subsets[subset].push(subset, xy, value, target.take());
+            }
+            DataEvent::ReplicationStart { subset_counts, .. } => {
+                replications.push((subset_counts, 0));
+            }
+            DataEvent::ReplicationItemEnd => {
+                if let Some((_, item)) = replications.last_mut() {
+                    *item += 1;
+                }
+            }
+            DataEvent::ReplicationEnd => {
+                replications.pop();
+            }
+            DataEvent::CompressedData { xy, values, .. } => {
+                let target = target.take();
+                subsets.resize_with(values.len(), Subset::default);
+                for (i, value) in values.into_iter().enumerate() {
+                    let is_padding = replications.iter().any(|(counts, item)| {
+                        counts.as_ref().is_some_and(|counts| *item >= counts[i])
+                    });
+                    match is_padding {
+                        true => subsets[i].next_position += 1,
+                        false => subsets[i].push(i, xy, value, target),
+                    }
+                }
+            }
+            DataEvent::Quality {
+                x: 22,
+                position,
+                value,
+                ..
+            } => subsets[subset].set_qc(position, &value),
+            DataEvent::CompressedQuality {
+                x: 22,
+                position,
+                values,
+                ..
+            } => {
+                for (rows, value) in subsets.iter_mut().zip(&values) {
+                    rows.set_qc(position, value);
+                }
+            }
+            DataEvent::Eof => break,
+            _ => {}
+        }
+    }
+    let mut message_index = UInt32Builder::new();
+    let mut subset_index = UInt32Builder::new();
+    let mut source_file = StringBuilder::new();
+    let mut position = UInt32Builder::new();
+    let mut descriptor = StringBuilder::new();
+    let mut name = StringBuilder::new();
+    let mut unit = StringBuilder::new();
+    let mut value = Float64Builder::new();
+    let mut text = StringBuilder::new();
+    let mut qc = Float64Builder::new();
+    for row in subsets.into_iter().flat_map(|subset| subset.rows) {
+        let Some(b) = tables.table_b.get(&row.xy) else {
+            continue;
+        };
+        message_index.append_value(options.message_index);
+        subset_index.append_value(row.subset);
+        if let Some(file) = &options.source_file {
+            source_file.append_value(file);
+        }
+        position.append_value(row.position);
+        descriptor.append_value(format!("0{:02}{:03}", row.xy.x, row.xy.y));
+        name.append_value(b.element_name);
+        unit.append_value(b.unit);
+        match row.value {
+            Value::String(s) => {
+                value.append_null();
+                text.append_value(s);
+            }
+            v => {
+                value.append_option(v.as_f64());
+                text.append_null();
+            }
+        }
+        qc.append_option(row.qc);
+    }
+
+    let mut fields = vec![
+        Field::new("_message_index", DataType::UInt32, false),
+        Field::new("_subset_index", DataType::UInt32, false),
+    ];
+    let mut arrays: Vec<ArrayRef> = vec![
+        Arc::new(message_index.finish()),
+        Arc::new(subset_index.finish()),
+    ];
+    if options.source_file.is_some() {
+        fields.push(Field::new("_source_file", DataType::Utf8, false));
+        arrays.push(Arc::new(source_file.finish()));
+    }
+    fields.extend([
+        Field::new("position", DataType::UInt32, false),
+        Field::new("descriptor", DataType::Utf8, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("unit", DataType::Utf8, false),
+        Field::new("value", DataType::Float64, true),
+        Field::new("text", DataType::Utf8, true),
+        Field::new("qc", DataType::Float64, true),
+    ]);
+    arrays.extend([
+        Arc::new(position.finish()) as ArrayRef,
+        Arc::new(descriptor.finish()),
+        Arc::new(name.finish()),
+        Arc::new(unit.finish()),
+        Arc::new(value.finish()),
+        Arc::new(text.finish()),
+        Arc::new(qc.finish()),
+    ]);
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+        .map_err(|e| Error::Fatal(format!("Failed to create RecordBatch: {e}")))
+}
+
+/// Rows of a subset.
+#[derive(Default)]
+struct Subset {
+    rows: Vec<Row>,
+    /// Position of the next element, counting those without rows
+    next_position: u32,
+}
+
+impl Subset {
+    /// Adds a value, or sets it as the quality information of the element at the
+    /// `target` position.
+    fn push(&mut self, subset: usize, xy: XY, value: Value, target: Option<u32>) {
+        let position = self.next_position;
+        self.next_position += 1;
+        match target {
+            Some(target) if xy.x == 33 => self.set_qc(target, &value),
+            _ if xy == (XY { x: 31, y: 31 }) => {}
+            _ => self.rows.push(Row {
+                subset: subset as u32,
+                position,
+                xy,
+                value,
+                qc: None,
+            }),
+        }
+    }
+
+    fn set_qc(&mut self, position: u32, value: &Value) {
+        if let Some(row) = self.rows.iter_mut().find(|row| row.position == position) {
+            row.qc = value.as_f64();
+        }
+    }
+}
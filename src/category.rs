@@ -0,0 +1,214 @@
+//! Classification of messages by data category
+//!
+//! Section 1 gives the data category of a message (BUFR Table A), but messages of
+//! category 255 ("other", for local use) may carry any type of observation, and some
+//! of them mix several in one template. [`classify`] falls back to the report sequences
+//! of the template for those, so they can be routed like messages of the category
+//! their data belong to, and tells when the template mixes several categories.
+
+use crate::{Descriptor, HeaderSections};
+
+/// Defines the data categories of Table A, plus one for the other code figures.
+macro_rules! data_categories {
+    ($($(#[$meta:meta])* $variant:ident = $code:literal,)*) => {
+        /// Data category of a message (BUFR Table A).
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum DataCategory {
+            $($(#[$meta])* $variant,)*
+            /// Reserved, experimental (240 to 254) or local (255) code figure
+            Other(u8),
+        }
+
+        impl DataCategory {
+            pub fn from_code(code: u8) -> Self {
+                match code {
+                    $($code => Self::$variant,)*
+                    _ => Self::Other(code),
+                }
+            }
+
+            pub fn code(&self) -> u8 {
+                match *self {
+                    $(Self::$variant => $code,)*
+                    Self::Other(code) => code,
+                }
+            }
+        }
+    };
+}
+
+data_categories! {
+    SurfaceLand = 0,
+    SurfaceSea = 1,
+    /// Vertical soundings (other than satellite)
+    VerticalSoundings = 2,
+    VerticalSoundingsSatellite = 3,
+    /// Single level upper-air data (other than satellite)
+    SingleLevelUpperAir = 4,
+    SingleLevelUpperAirSatellite = 5,
+    Radar = 6,
+    SynopticFeatures = 7,
+    PhysicalChemicalConstituents = 8,
+    DispersalAndTransport = 9,
+    Radiological = 10,
+    /// BUFR tables, complete replacement or update
+    BufrTables = 11,
+    SurfaceSatellite = 12,
+    Forecasts = 13,
+    Warnings = 14,
+    StatusInformation = 20,
+    /// Radiances (satellite measured)
+    Radiances = 21,
+    /// Radar (satellite) but not altimeter and scatterometer
+    RadarSatellite = 22,
+    LidarSatellite = 23,
+    Scatterometry = 24,
+    Altimetry = 25,
+    Spectrometry = 26,
+    GravityMeasurement = 27,
+    PrecisionOrbit = 28,
+    SpaceEnvironment = 29,
+    CalibrationDatasets = 30,
+    Oceanographic = 31,
+    LidarGroundBased = 32,
+    /// Image data (satellite)
+    Image = 101,
+}
+
+impl DataCategory {
+    /// The category given by a report sequence (3 XX YYY of the classes of complete
+    /// reports in Table D), or `None` for other descriptors.
+    pub fn from_report_sequence(descriptor: &Descriptor) -> Option<Self> {
+        if descriptor.f != 3 {
+            return None;
+        }
+        Some(match descriptor.x {
+            7 => Self::SurfaceLand,
+            8 => Self::SurfaceSea,
+            9 => Self::VerticalSoundings,
+            10 => Self::VerticalSoundingsSatellite,
+            11 => Self::SingleLevelUpperAir,
+            12 => Self::SingleLevelUpperAirSatellite,
+            15 => Self::Oceanographic,
+            16 => Self::SynopticFeatures,
+            18 => Self::Radiological,
+            21 => Self::Radar,
+            _ => return None,
+        })
+    }
+}
+
+/// Classification of a message (see [`classify`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Classification {
+    /// The category of Section 1, or the one inferred from the template for category
+    /// 255 if its report sequences agree on one
+    pub category: DataCategory,
+    /// Whether the category was inferred from the template
+    pub inferred: bool,
+    /// Categories of the report sequences in the template, in order of appearance
+    pub template_categories: Vec<DataCategory>,
+}
+
+impl Classification {
+    /// Whether the template mixes report sequences of several categories.
+    pub fn is_mixed(&self) -> bool {
+        self.template_categories.len() > 1
+    }
+}
+
+/// Returns the categories of the report sequences at the top level of a template, in
+/// order of appearance and without duplicates.
+pub fn template_categories(descriptors: &[Descriptor]) -> Vec<DataCategory> {
+    let mut categories = vec![];
+    for category in descriptors
+        .iter()
+        .filter_map(DataCategory::from_report_sequence)
+    {
+        if !categories.contains(&category) {
+            categories.push(category);
+        }
+    }
+    categories
+}
+
+/// Classifies a message by its data category, falling back to the report sequences
+/// of its template for category 255.
+///
+/// A message of category 255 whose template mixes several categories keeps category
+/// 255 rather than being filed under one of them.
+pub fn classify(header: &HeaderSections) -> Classification {
+    let code = header.identification_section.data_category;
+    let template_categories = template_categories(&header.data_description_section.descriptors);
+    match template_categories.as_slice() {
+        &[category] if code == 255 => Classification {
+            category,
+            inferred: true,
+            template_categories,
+        },
+        _ => Classification {
+            category: DataCategory::from_code(code),
+            inferred: false,
+            template_categories,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_category() {
+        for code in 0..=255 {
+            assert_eq!(DataCategory::from_code(code).code(), code);
+        }
+        assert_eq!(DataCategory::from_code(31), DataCategory::Oceanographic);
+        assert_eq!(DataCategory::from_code(255), DataCategory::Other(255));
+    }
+
+    #[test]
+    fn test_template_categories() {
+        let d = |f, x, y| Descriptor { f, x, y };
+        assert_eq!(
+            template_categories(&[
+                d(3, 1, 90),
+                d(3, 7, 80),
+                d(0, 12, 101),
+                d(3, 9, 52),
+                d(3, 7, 96)
+            ]),
+            [DataCategory::SurfaceLand, DataCategory::VerticalSoundings]
+        );
+        assert!(template_categories(&[d(0, 1, 1), d(3, 1, 11), d(1, 7, 0)]).is_empty());
+    }
+
+    #[test]
+    fn test_classify() {
+        let message =
+            std::fs::read("./tests/testdata/dwd/synop_ISGD01_EDZW_2025_08_07_11_10.bufr").unwrap();
+        let mut header = HeaderSections::read(&mut message.as_slice()).unwrap();
+        let classification = classify(&header);
+        assert_eq!(classification.category, DataCategory::SurfaceLand);
+        assert!(!classification.inferred);
+
+        // Category 255 without report sequences stays as it is
+        header.identification_section.data_category = 255;
+        assert_eq!(classify(&header).category, DataCategory::Other(255));
+
+        let descriptors = &mut header.data_description_section.descriptors;
+        descriptors.insert(0, Descriptor { f: 3, x: 7, y: 80 });
+        let classification = classify(&header);
+        assert_eq!(classification.category, DataCategory::SurfaceLand);
+        assert!(classification.inferred);
+        assert!(!classification.is_mixed());
+
+        // Mixed templates are not filed under one of their categories
+        let descriptors = &mut header.data_description_section.descriptors;
+        descriptors.push(Descriptor { f: 3, x: 9, y: 52 });
+        let classification = classify(&header);
+        assert_eq!(classification.category, DataCategory::Other(255));
+        assert!(!classification.inferred);
+        assert!(classification.is_mixed());
+    }
+}
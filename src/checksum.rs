@@ -0,0 +1,233 @@
+//! Verification of checksums embedded in messages by some centres
+//!
+//! BUFR has no checksum of its own, but some centres store one (e.g. a CRC-32 of the
+//! message) in the local use part of Section 1 or in Section 2. A [`ChecksumVerifier`]
+//! is called with the bytes of each message, and its result is reported by
+//! [`scan_bytes_with_checksum`](crate::scan::scan_bytes_with_checksum) and, through
+//! [`DecodeOptions::checksum`](crate::DecodeOptions::checksum), by
+//! [`split_message`](crate::sections::split_message).
+//!
+//! [`Crc32`] verifies a CRC-32 stored at a fixed position; other schemes can be
+//! plugged in by implementing [`ChecksumVerifier`], or with a closure:
+//!
+//! ```
+//! use tinybufr::HeaderSections;
+//! use tinybufr::checksum::{ChecksumStatus, ChecksumVerifier};
+//!
+//! let verifier = |message: &[u8], header: &HeaderSections| match header.optional_section {
+//!     Some(_) => ChecksumStatus::Valid,
+//!     None => ChecksumStatus::Absent,
+//! };
+//! let message = std::fs::read("./tests/testdata/dwd/synop_ISGD01_EDZW_2025_08_07_11_10.bufr")?;
+//! let header = HeaderSections::from_bytes(&message)?;
+//! assert_eq!(verifier.verify(&message, &header), ChecksumStatus::Absent);
+//! # Ok::<(), tinybufr::Error>(())
+//! ```
+
+use crate::HeaderSections;
+use crate::sections::EditionLayout;
+
+/// Result of the verification of the checksum of a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ChecksumStatus {
+    /// The checksum matches the message
+    Valid,
+    /// The checksum does not match the message, or cannot be verified
+    Invalid(String),
+    /// The message has no checksum where it is expected
+    Absent,
+}
+
+/// Verifies the checksum of a message.
+pub trait ChecksumVerifier: Send + Sync {
+    /// Verifies the checksum of a complete message, given with its header sections
+    /// read by [`HeaderSections::from_bytes`].
+    fn verify(&self, message: &[u8], header: &HeaderSections) -> ChecksumStatus;
+}
+
+impl<F> ChecksumVerifier for F
+where
+    F: Fn(&[u8], &HeaderSections) -> ChecksumStatus + Send + Sync,
+{
+    fn verify(&self, message: &[u8], header: &HeaderSections) -> ChecksumStatus {
+        self(message, header)
+    }
+}
+
+/// Where a checksum is stored in a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumLocation {
+    /// At an offset in the local use part of Section 1
+    LocalUse { offset: usize },
+    /// At an offset in the contents of Section 2 (after its reserved octet)
+    OptionalSection { offset: usize },
+}
+
+/// CRC-32 (as in zlib) of the message, stored in 4 octets (big-endian) at a fixed
+/// location. The CRC is computed over the whole message, with the 4 octets set to zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crc32 {
+    pub location: ChecksumLocation,
+}
+
+impl Crc32 {
+    /// Returns the offset of the checksum in the message, or `None` if the message has
+    /// no room for it.
+    fn offset(&self, header: &HeaderSections) -> Option<usize> {
+        let raw = header.raw.as_ref()?;
+        let (start, len) = match self.location {
+            ChecksumLocation::LocalUse { offset } => {
+                let local_use_start = EditionLayout::of(header.indicator_section.edition_number)
+                    .ok()?
+                    .identification_length();
+                let section = raw.identification_section();
+                (
+                    raw.indicator_section().len() + local_use_start + offset,
+                    section.len().checked_sub(local_use_start + offset)?,
+                )
+            }
+            ChecksumLocation::OptionalSection { offset } => {
+                let section = raw.optional_section()?;
+                (
+                    raw.indicator_section().len() + raw.identification_section().len() + 4 + offset,
+                    section.len().checked_sub(4 + offset)?,
+                )
+            }
+        };
+        (len >= 4).then_some(start)
+    }
+}
+
+impl ChecksumVerifier for Crc32 {
+    fn verify(&self, message: &[u8], header: &HeaderSections) -> ChecksumStatus {
+        if header.raw.is_none() {
+            return ChecksumStatus::Invalid(
+                "The original bytes of the header sections are not available".to_string(),
+            );
+        }
+        let Some(offset) = self.offset(header) else {
+            return ChecksumStatus::Absent;
+        };
+        let stored = u32::from_be_bytes(message[offset..offset + 4].try_into().unwrap());
+        let computed = crc32_update(
+            crc32_update(crc32_update(!0, &message[..offset]), &[0; 4]),
+            &message[offset + 4..],
+        ) ^ !0;
+        match stored == computed {
+            true => ChecksumStatus::Valid,
+            false => ChecksumStatus::Invalid(format!(
+                "CRC-32 mismatch: stored {stored:08x}, computed {computed:08x}"
+            )),
+        }
+    }
+}
+
+/// Computes the CRC-32 (as in zlib) of the bytes.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(!0, bytes) ^ !0
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(crc, |crc, &b| {
+        CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sections::OptionalSection;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    /// Encodes a message with the CRC-32 stored at the given location.
+    fn message_with_crc(location: ChecksumLocation) -> Vec<u8> {
+        let message =
+            std::fs::read("./tests/testdata/dwd/synop_ISGD01_EDZW_2025_08_07_11_10.bufr").unwrap();
+        let mut rest = message.as_slice();
+        let mut header = HeaderSections::read(&mut rest).unwrap();
+        let data_section = &rest[..rest.len() - 4];
+        match location {
+            ChecksumLocation::LocalUse { offset } => {
+                header.identification_section.local_use = vec![0; offset + 4];
+            }
+            ChecksumLocation::OptionalSection { offset } => {
+                header.identification_section.flags.has_optional_section = true;
+                header.optional_section = Some(OptionalSection {
+                    section_length: 0,
+                    optional: vec![0; offset + 4],
+                });
+            }
+        }
+        let mut message = vec![];
+        crate::encode_message_into(&mut message, &header, data_section).unwrap();
+        let header = HeaderSections::from_bytes(&message).unwrap();
+        let offset = Crc32 { location }.offset(&header).unwrap();
+        let crc = crc32(&message);
+        message[offset..offset + 4].copy_from_slice(&crc.to_be_bytes());
+        message
+    }
+
+    #[test]
+    fn test_verify_crc32() {
+        for location in [
+            ChecksumLocation::LocalUse { offset: 2 },
+            ChecksumLocation::OptionalSection { offset: 0 },
+        ] {
+            let verifier = Crc32 { location };
+            let mut message = message_with_crc(location);
+            let header = HeaderSections::from_bytes(&message).unwrap();
+            assert_eq!(verifier.verify(&message, &header), ChecksumStatus::Valid);
+
+            let len = message.len();
+            message[len - 5] ^= 1;
+            assert!(matches!(
+                verifier.verify(&message, &header),
+                ChecksumStatus::Invalid(_)
+            ));
+            // Without the original bytes
+            let header = HeaderSections::read(message.as_slice()).unwrap();
+            assert!(matches!(
+                verifier.verify(&message, &header),
+                ChecksumStatus::Invalid(_)
+            ));
+        }
+
+        // No room for the checksum
+        let message = message_with_crc(ChecksumLocation::LocalUse { offset: 0 });
+        let header = HeaderSections::from_bytes(&message).unwrap();
+        let verifier = Crc32 {
+            location: ChecksumLocation::LocalUse { offset: 1 },
+        };
+        assert_eq!(verifier.verify(&message, &header), ChecksumStatus::Absent);
+        let verifier = Crc32 {
+            location: ChecksumLocation::OptionalSection { offset: 0 },
+        };
+        assert_eq!(verifier.verify(&message, &header), ChecksumStatus::Absent);
+    }
+}
@@ -0,0 +1,252 @@
+//! Conversions between values and the raw bits of elements
+//!
+//! A numeric element is encoded as the raw value `v * 10^scale - reference_value` in
+//! `bit_width` bits, where the raw value with all bits set is reserved for missing
+//! values (Regulation 94.1.3). The operators changing the width, scale and reference
+//! value are applied by [`effective_width_ref_scale`] beforehand. These functions are
+//! shared by [`DataReader`](crate::DataReader) and [`DataWriter`](crate::DataWriter),
+//! and can be used by other tools handling raw values.
+
+use crate::{TableBEntry, Value};
+
+/// Returns the bit width, reference value and scale of an element after
+/// applying the "Change data width" and "Change scale" offsets and the
+/// "Increase scale, reference value and data width" operator (2 07 YYY).
+///
+/// Those operators do not apply to CCITT IA5, code table or flag table elements.
+/// The width of CCITT IA5 elements is replaced by `string_width` characters
+/// (2 08 YYY) unless it is 0.
+pub fn effective_width_ref_scale(
+    b: &TableBEntry,
+    width_offset: i8,
+    scale_offset: i8,
+    scale_increase: u8,
+    string_width: u8,
+) -> (u32, i32, i8) {
+    match b.unit {
+        "CCITT IA5" if string_width > 0 => (string_width as u32 * 8, b.reference_value, b.scale),
+        "CCITT IA5" | "Code table" | "Flag table" => (b.bits as u32, b.reference_value, b.scale),
+        _ => {
+            // 2 07 YYY: scale + YYY, reference value * 10^YYY, width + (10 * YYY + 2) / 3
+            let increase = scale_increase as i32;
+            (
+                (b.bits as i32 + width_offset as i32 + (10 * increase + 2) / 3) as u32,
+                b.reference_value
+                    .saturating_mul(10i32.saturating_pow(scale_increase as u32)),
+                (b.scale as i16 + scale_offset as i16 + increase as i16) as i8,
+            )
+        }
+    }
+}
+
+/// The all-ones raw value reserved for "missing".
+pub fn missing_value(bit_width: u32) -> u64 {
+    ((1u128 << bit_width.min(64)) - 1) as u64
+}
+
+/// Returns the value of a numeric element of up to 32 bits from its raw value (or
+/// that of a subset in compressed data, i.e. the local reference value plus the
+/// increment).
+///
+/// The reference value is added in i64, as raw values of 32-bit elements with
+/// negative reference values do not fit in i32. Values out of the i32 range are
+/// returned as [`Value::Decimal64`].
+pub fn unpack(raw: u32, ref_value: i32, scale: i8) -> Value {
+    let v = raw as i64 + ref_value as i64;
+    match i32::try_from(v) {
+        Ok(v) if scale == 0 => Value::Integer(v),
+        Ok(v) => Value::Decimal(v, -scale),
+        Err(_) => Value::Decimal64(v, -scale),
+    }
+}
+
+/// Returns the value of a numeric element wider than 32 bits from its raw value, as a
+/// [`Value::Decimal64`], or `None` if it does not fit in 64 bits.
+pub fn unpack_wide(raw: u64, ref_value: i32, scale: i8) -> Option<Value> {
+    let v = i64::try_from(raw as i128 + ref_value as i128).ok()?;
+    Some(Value::Decimal64(v, -scale))
+}
+
+/// Why a value cannot be packed into the raw bits of a numeric element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackError {
+    /// The value is a string
+    NotNumeric,
+    /// The value needs more precision than the scale provides (2 02 YYY can add it)
+    Precision,
+    /// The value does not fit in the bit width (2 01 YYY can widen it)
+    Overflow,
+}
+
+/// Packs a numeric value into its raw bits, or returns `None` if it is missing.
+///
+/// Fails instead of silently truncating when the value needs more precision than
+/// the (effective) scale provides or does not fit into the (effective) bit width,
+/// the all-ones raw value being reserved for missing values.
+pub fn pack(
+    value: &Value,
+    bit_width: u32,
+    ref_value: i32,
+    scale: i8,
+) -> Result<Option<u64>, PackError> {
+    let (v, exp) = match *value {
+        Value::Missing => return Ok(None),
+        Value::Integer(v) => (v as i64, 0),
+        Value::Decimal(v, exp) => (v as i64, exp),
+        Value::Decimal64(v, exp) => (v, exp),
+        Value::String(_) | Value::Bytes(_) => return Err(PackError::NotNumeric),
+    };
+    // v * 10^exp * 10^scale
+    let shift = exp as i32 + scale as i32;
+    let scaled = if shift >= 0 {
+        10i64
+            .checked_pow(shift as u32)
+            .and_then(|m| v.checked_mul(m))
+    } else {
+        match 10i64.checked_pow((-shift) as u32) {
+            Some(d) if v % d == 0 => Some(v / d),
+            None if v == 0 => Some(0),
+            _ => return Err(PackError::Precision),
+        }
+    };
+    let raw = scaled.map(|s| s as i128 - ref_value as i128);
+    match raw {
+        Some(raw) if raw >= 0 && raw < missing_value(bit_width) as i128 => Ok(Some(raw as u64)),
+        _ => Err(PackError::Overflow),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Tables, XY};
+
+    #[test]
+    fn test_missing_value() {
+        assert_eq!(missing_value(0), 0);
+        assert_eq!(missing_value(1), 1);
+        assert_eq!(missing_value(12), 4095);
+        assert_eq!(missing_value(32), u32::MAX as u64);
+        assert_eq!(missing_value(64), u64::MAX);
+    }
+
+    #[test]
+    fn test_unpack() {
+        assert_eq!(unpack(27315, 0, 2), Value::Decimal(27315, -2));
+        assert_eq!(unpack(150, -100, 0), Value::Integer(50));
+        assert_eq!(unpack(0, -5000, -1), Value::Decimal(-5000, 1));
+        // 32-bit raw values with a negative reference value
+        assert_eq!(
+            unpack(u32::MAX - 1, -1024, 0),
+            Value::Decimal64(u32::MAX as i64 - 1025, 0)
+        );
+        assert_eq!(unpack(0, i32::MIN, 0), Value::Integer(i32::MIN));
+
+        assert_eq!(
+            unpack_wide(1 << 40, -1, 3),
+            Some(Value::Decimal64((1 << 40) - 1, -3))
+        );
+        assert_eq!(unpack_wide(u64::MAX - 1, 0, 0), None);
+        assert_eq!(
+            unpack_wide(i64::MAX as u64 + 1, i32::MIN, 0),
+            Some(Value::Decimal64(i64::MAX - i32::MAX as i64, 0))
+        );
+    }
+
+    #[test]
+    fn test_pack() {
+        assert_eq!(pack(&Value::Missing, 16, 0, 2), Ok(None));
+        assert_eq!(pack(&Value::Decimal(27315, -2), 16, 0, 2), Ok(Some(27315)));
+        // Scaled values are converted to the scale of the element
+        assert_eq!(pack(&Value::Decimal(2731, -1), 16, 0, 2), Ok(Some(27310)));
+        assert_eq!(pack(&Value::Integer(273), 16, 0, 2), Ok(Some(27300)));
+        assert_eq!(pack(&Value::Decimal64(50000, 0), 14, 0, -1), Ok(Some(5000)));
+        assert_eq!(pack(&Value::Integer(-40), 12, -1024, 0), Ok(Some(984)));
+        assert_eq!(pack(&Value::Integer(0), 8, 0, -20), Ok(Some(0)));
+
+        assert_eq!(
+            pack(&Value::Decimal(27315, -2), 16, 0, 1),
+            Err(PackError::Precision)
+        );
+        assert_eq!(
+            pack(&Value::Integer(5), 8, 0, -20),
+            Err(PackError::Precision)
+        );
+        // The all-ones raw value is reserved for missing values
+        assert_eq!(pack(&Value::Integer(254), 8, 0, 0), Ok(Some(254)));
+        assert_eq!(
+            pack(&Value::Integer(255), 8, 0, 0),
+            Err(PackError::Overflow)
+        );
+        assert_eq!(pack(&Value::Integer(-1), 8, 0, 0), Err(PackError::Overflow));
+        assert_eq!(
+            pack(&Value::Decimal64(i64::MAX, 0), 16, 0, 2),
+            Err(PackError::Overflow)
+        );
+        assert_eq!(
+            pack(&Value::String("1".to_string()), 8, 0, 0),
+            Err(PackError::NotNumeric)
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (bit_width, ref_value, scale) in [
+            (7, 0, 0),
+            (12, -1024, 0),
+            (16, 0, 2),
+            (31, -40, 5),
+            (32, -1, -3),
+        ] {
+            let max = missing_value(bit_width) - 1;
+            for raw in [0, 1, max / 3, max - 1, max] {
+                let value = unpack(raw as u32, ref_value, scale);
+                assert_eq!(
+                    pack(&value, bit_width, ref_value, scale),
+                    Ok(Some(raw)),
+                    "{value:?}"
+                );
+            }
+        }
+        for raw in [0, 1 << 40, missing_value(48) - 1] {
+            let value = unpack_wide(raw, -7, 4).unwrap();
+            assert_eq!(pack(&value, 48, -7, 4), Ok(Some(raw)));
+        }
+    }
+
+    #[test]
+    fn test_effective_width_ref_scale() {
+        let tables = Tables::default();
+        // Air temperature: 16 bits, scale 2
+        let temperature = &tables.table_b[&XY { x: 12, y: 101 }];
+        assert_eq!(
+            effective_width_ref_scale(temperature, 0, 0, 0, 0),
+            (16, 0, 2)
+        );
+        assert_eq!(
+            effective_width_ref_scale(temperature, 4, -1, 0, 0),
+            (20, 0, 1)
+        );
+        // 2 07 002: scale + 2, reference value * 100, width + 7
+        assert_eq!(
+            effective_width_ref_scale(temperature, 0, 0, 2, 0),
+            (23, 0, 4)
+        );
+        // Height: 15 bits, reference value -400
+        let height = &tables.table_b[&XY { x: 7, y: 1 }];
+        assert_eq!(
+            effective_width_ref_scale(height, 0, 0, 1, 0),
+            (19, -4000, 1)
+        );
+        // Operators other than 2 08 do not apply to characters
+        let name = &tables.table_b[&XY { x: 1, y: 15 }];
+        assert_eq!(effective_width_ref_scale(name, 8, 1, 1, 0), (160, 0, 0));
+        assert_eq!(effective_width_ref_scale(name, 0, 0, 0, 5), (40, 0, 0));
+        // Nor to code tables
+        let code = &tables.table_b[&XY { x: 2, y: 1 }];
+        assert_eq!(
+            effective_width_ref_scale(code, 8, 1, 1, 0),
+            (code.bits as u32, 0, 0)
+        );
+    }
+}
@@ -0,0 +1,442 @@
+//! Generation of Rust source code specialized for a fixed template
+//!
+//! This is intended to be used from build scripts by applications that decode a single
+//! known template, so its elements can be accessed as typed struct fields:
+//!
+//! ```no_run
+//! use tinybufr::{Descriptor, Tables, codegen::generate_struct};
+//!
+//! let descriptors = [Descriptor { f: 3, x: 7, y: 80 }];
+//! let source = generate_struct("Synop", &descriptors, &Tables::default()).unwrap();
+//! let out_dir = std::env::var("OUT_DIR").unwrap();
+//! std::fs::write(format!("{out_dir}/synop.rs"), source).unwrap();
+//! ```
+
+use std::fmt::Write;
+
+use crate::{
+    Descriptor, Error, ResolvedDescriptor, Tables, XY, resolve_descriptors, unresolved_error,
+};
+
+/// Generates the source code of a struct specialized for the given template.
+///
+/// The generated struct has a `read` function that decodes one subset from a
+/// [`DataReader`](crate::DataReader) of an uncompressed message. Elements of
+/// sequences are flattened into the enclosing struct, and replications become
+/// `Vec`s of generated item structs named `{name}Item{n}`.
+pub fn generate_struct(
+    name: &str,
+    descriptors: &[Descriptor],
+    tables: &Tables,
+) -> Result<String, Error> {
+    let resolved = resolve_descriptors(tables, descriptors)?;
+
+    let mut generator = Generator {
+        root_name: name.to_string(),
+        structs: vec![],
+        scale_offset: 0,
+    };
+    let root = generator.build_struct(name.to_string(), &resolved)?;
+    generator.structs.insert(0, root);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "// This file is generated by tinybufr::codegen. Do not edit."
+    )
+    .unwrap();
+    for def in &generator.structs {
+        generator.write_struct(&mut out, def);
+    }
+    generator.write_root_impl(&mut out);
+    Ok(out)
+}
+
+struct Generator {
+    root_name: String,
+    /// Item structs of replications (and the root struct, once built)
+    structs: Vec<StructDef>,
+    /// Scale offset set by the "Change scale" operator, known statically
+    scale_offset: i8,
+}
+
+struct StructDef {
+    name: String,
+    fields: Vec<FieldDef>,
+}
+
+struct FieldDef {
+    name: String,
+    doc: String,
+    kind: FieldKind,
+}
+
+enum FieldKind {
+    Integer(XY),
+    Float(XY),
+    String(XY),
+    Items(String),
+}
+
+impl Generator {
+    fn build_struct(
+        &mut self,
+        name: String,
+        descriptors: &[ResolvedDescriptor<'_>],
+    ) -> Result<StructDef, Error> {
+        let mut def = StructDef {
+            name,
+            fields: vec![],
+        };
+        self.collect_fields(&mut def, descriptors)?;
+        Ok(def)
+    }
+
+    fn collect_fields(
+        &mut self,
+        def: &mut StructDef,
+        descriptors: &[ResolvedDescriptor<'_>],
+    ) -> Result<(), Error> {
+        for desc in descriptors {
+            match desc {
+                ResolvedDescriptor::Data(b) => {
+                    let scale = b.scale as i16 + self.scale_offset as i16;
+                    let kind = match b.unit {
+                        "CCITT IA5" => FieldKind::String(b.xy),
+                        "Code table" | "Flag table" => FieldKind::Integer(b.xy),
+                        _ if scale > 0 => FieldKind::Float(b.xy),
+                        _ => FieldKind::Integer(b.xy),
+                    };
+                    let unit = match b.unit {
+                        "Numeric" => String::new(),
+                        unit => format!(" [{unit}]"),
+                    };
+                    def.fields.push(FieldDef {
+                        name: unique_name(def, &field_name(b.element_name, b.xy)),
+                        doc: format!("0 {:02} {:03}: {}{}", b.xy.x, b.xy.y, b.element_name, unit),
+                        kind,
+                    });
+                }
+                ResolvedDescriptor::Replication { descriptors, .. } => {
+                    let n = self.structs.len() + 1;
+                    let item_name = format!("{}Item{}", self.root_name, n);
+                    // Reserve the slot so nested replications are numbered after this one
+                    self.structs.push(StructDef {
+                        name: String::new(),
+                        fields: vec![],
+                    });
+                    let item = self.build_struct(item_name.clone(), descriptors)?;
+                    self.structs[n - 1] = item;
+                    def.fields.push(FieldDef {
+                        name: unique_name(def, &format!("replication_{n}")),
+                        doc: "Replicated items".to_string(),
+                        kind: FieldKind::Items(item_name),
+                    });
+                }
+                ResolvedDescriptor::Operator(xy, _) => match (xy.x, xy.y) {
+                    (2, 0) => self.scale_offset = 0,
+                    (2, y) => self.scale_offset = ((y as i16) - 128) as i8,
+                    _ => {}
+                },
+                ResolvedDescriptor::Unresolved(desc) => return Err(unresolved_error(desc)),
+                ResolvedDescriptor::Sequence(_, elements) => {
+                    self.collect_fields(def, elements)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_struct(&self, out: &mut String, def: &StructDef) {
+        let root = &self.root_name;
+        writeln!(out).unwrap();
+        writeln!(out, "#[derive(Debug, Clone, PartialEq)]").unwrap();
+        writeln!(out, "pub struct {} {{", def.name).unwrap();
+        for field in &def.fields {
+            let ty = match &field.kind {
+                FieldKind::Integer(_) => "Option<i32>".to_string(),
+                FieldKind::Float(_) => "Option<f64>".to_string(),
+                FieldKind::String(_) => "Option<String>".to_string(),
+                FieldKind::Items(item) => format!("Vec<{item}>"),
+            };
+            writeln!(out, "    /// {}", field.doc).unwrap();
+            writeln!(out, "    pub {}: {},", field.name, ty).unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+
+        writeln!(out).unwrap();
+        writeln!(out, "impl {} {{", def.name).unwrap();
+        writeln!(out, "    fn read_fields<R: std::io::Read>(").unwrap();
+        writeln!(out, "        reader: &mut tinybufr::DataReader<'_, R>,").unwrap();
+        writeln!(out, "    ) -> Result<Self, tinybufr::Error> {{").unwrap();
+        for field in &def.fields {
+            let name = &field.name;
+            match &field.kind {
+                FieldKind::Integer(xy) | FieldKind::Float(xy) | FieldKind::String(xy) => {
+                    let conv = match field.kind {
+                        FieldKind::Integer(_) => "as_i32",
+                        FieldKind::Float(_) => "as_f64",
+                        _ => "as_string",
+                    };
+                    writeln!(
+                        out,
+                        "        let {name} = {root}::{conv}({root}::next_value(reader, tinybufr::XY {{ x: {}, y: {} }})?)?;",
+                        xy.x, xy.y
+                    )
+                    .unwrap();
+                }
+                FieldKind::Items(item) => {
+                    writeln!(
+                        out,
+                        "        let count = {root}::next_replication(reader)?;"
+                    )
+                    .unwrap();
+                    writeln!(
+                        out,
+                        "        let mut {name} = Vec::with_capacity(count as usize);"
+                    )
+                    .unwrap();
+                    writeln!(out, "        for _ in 0..count {{").unwrap();
+                    writeln!(
+                        out,
+                        "            {name}.push({item}::read_fields(reader)?);"
+                    )
+                    .unwrap();
+                    writeln!(out, "        }}").unwrap();
+                }
+            }
+        }
+        writeln!(out, "        Ok(Self {{").unwrap();
+        for field in &def.fields {
+            writeln!(out, "            {},", field.name).unwrap();
+        }
+        writeln!(out, "        }})").unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+    }
+
+    fn write_root_impl(&self, out: &mut String) {
+        writeln!(out).unwrap();
+        write!(out, "{}", ROOT_IMPL.replace("{name}", &self.root_name)).unwrap();
+    }
+}
+
+const ROOT_IMPL: &str = r#"#[allow(dead_code)]
+impl {name} {
+    /// Reads the next subset from the data reader.
+    pub fn read<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+    ) -> Result<Self, tinybufr::Error> {
+        match reader.read_event()? {
+            tinybufr::DataEvent::SubsetStart(_) => {}
+            ev => return Err(Self::unexpected(ev)),
+        }
+        let subset = Self::read_fields(reader)?;
+        loop {
+            match reader.read_event()? {
+                tinybufr::DataEvent::SubsetEnd => return Ok(subset),
+                tinybufr::DataEvent::SequenceEnd | tinybufr::DataEvent::OperatorHandled { .. } => {}
+                ev => return Err(Self::unexpected(ev)),
+            }
+        }
+    }
+
+    fn next_value<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+        expected: tinybufr::XY,
+    ) -> Result<tinybufr::Value, tinybufr::Error> {
+        loop {
+            match reader.read_event()? {
+                tinybufr::DataEvent::Data { xy, value, .. } if xy == expected => return Ok(value),
+                tinybufr::DataEvent::AssociatedField { .. }
+                | tinybufr::DataEvent::BitmapTarget { .. }
+                | tinybufr::DataEvent::Quality { .. }
+                | tinybufr::DataEvent::SequenceStart { .. }
+                | tinybufr::DataEvent::SequenceEnd
+                | tinybufr::DataEvent::ReplicationItemStart
+                | tinybufr::DataEvent::ReplicationItemEnd
+                | tinybufr::DataEvent::ReplicationEnd
+                | tinybufr::DataEvent::OperatorHandled { .. } => {}
+                ev => return Err(Self::unexpected(ev)),
+            }
+        }
+    }
+
+    fn next_replication<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+    ) -> Result<u16, tinybufr::Error> {
+        loop {
+            match reader.read_event()? {
+                tinybufr::DataEvent::ReplicationStart { count, .. } => return Ok(count),
+                tinybufr::DataEvent::SequenceStart { .. }
+                | tinybufr::DataEvent::SequenceEnd
+                | tinybufr::DataEvent::ReplicationItemStart
+                | tinybufr::DataEvent::ReplicationItemEnd
+                | tinybufr::DataEvent::ReplicationEnd
+                | tinybufr::DataEvent::OperatorHandled { .. } => {}
+                ev => return Err(Self::unexpected(ev)),
+            }
+        }
+    }
+
+    fn as_i32(value: tinybufr::Value) -> Result<Option<i32>, tinybufr::Error> {
+        match value {
+            tinybufr::Value::Missing => Ok(None),
+            tinybufr::Value::Integer(v) => Ok(Some(v)),
+            tinybufr::Value::Decimal(v, s) if s >= 0 => 10i32
+                .checked_pow(s as u32)
+                .and_then(|m| v.checked_mul(m))
+                .map(Some)
+                .ok_or_else(|| tinybufr::Error::Invalid("Integer value out of range".to_string())),
+            tinybufr::Value::Decimal64(v, s) if s >= 0 => 10i64
+                .checked_pow(s as u32)
+                .and_then(|m| v.checked_mul(m))
+                .and_then(|v| i32::try_from(v).ok())
+                .map(Some)
+                .ok_or_else(|| tinybufr::Error::Invalid("Integer value out of range".to_string())),
+            v => Err(Self::unexpected_value(v)),
+        }
+    }
+
+    fn as_f64(value: tinybufr::Value) -> Result<Option<f64>, tinybufr::Error> {
+        match value {
+            tinybufr::Value::Missing => Ok(None),
+            tinybufr::Value::Integer(v) => Ok(Some(v as f64)),
+            tinybufr::Value::Decimal(v, s) => Ok(Some(v as f64 * 10f64.powi(s as i32))),
+            tinybufr::Value::Decimal64(v, s) => Ok(Some(v as f64 * 10f64.powi(s as i32))),
+            v => Err(Self::unexpected_value(v)),
+        }
+    }
+
+    fn as_string(value: tinybufr::Value) -> Result<Option<String>, tinybufr::Error> {
+        match value {
+            tinybufr::Value::Missing => Ok(None),
+            tinybufr::Value::String(s) => Ok(Some(s)),
+            v => Err(Self::unexpected_value(v)),
+        }
+    }
+
+    fn unexpected(ev: tinybufr::DataEvent) -> tinybufr::Error {
+        tinybufr::Error::Invalid(format!("Unexpected data event for {name}: {ev:?}"))
+    }
+
+    fn unexpected_value(value: tinybufr::Value) -> tinybufr::Error {
+        tinybufr::Error::Invalid(format!("Unexpected value for {name}: {value:?}"))
+    }
+}
+"#;
+
+/// Makes a snake_case field name from an element name.
+fn field_name(element_name: &str, xy: XY) -> String {
+    let mut name = String::new();
+    for c in element_name.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_lowercase());
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    let name = name.trim_end_matches('_');
+    match name.chars().next() {
+        None => format!("e_{:02}{:03}", xy.x, xy.y),
+        Some(c) if c.is_ascii_digit() => format!("e_{name}"),
+        _ if is_keyword(name) => format!("{name}_"),
+        _ => name.to_string(),
+    }
+}
+
+/// Appends a counter to the name if the struct already has a field with the same name.
+fn unique_name(def: &StructDef, name: &str) -> String {
+    let exists = |n: &str| def.fields.iter().any(|f| f.name == n);
+    if !exists(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|i| format!("{name}_{i}"))
+        .find(|n| !exists(n))
+        .unwrap()
+}
+
+fn is_keyword(name: &str) -> bool {
+    matches!(
+        name,
+        "as" | "break"
+            | "const"
+            | "continue"
+            | "crate"
+            | "else"
+            | "enum"
+            | "extern"
+            | "false"
+            | "fn"
+            | "for"
+            | "if"
+            | "impl"
+            | "in"
+            | "let"
+            | "loop"
+            | "match"
+            | "mod"
+            | "move"
+            | "mut"
+            | "pub"
+            | "ref"
+            | "return"
+            | "self"
+            | "static"
+            | "struct"
+            | "super"
+            | "trait"
+            | "true"
+            | "type"
+            | "unsafe"
+            | "use"
+            | "where"
+            | "while"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_name() {
+        let xy = XY { x: 12, y: 101 };
+        assert_eq!(
+            field_name("Temperature/air temperature", xy),
+            "temperature_air_temperature"
+        );
+        assert_eq!(field_name("Type", xy), "type_");
+        assert_eq!(field_name("10-minute mean", xy), "e_10_minute_mean");
+        assert_eq!(field_name("jma:地点", xy), "jma");
+        assert_eq!(field_name("地点", xy), "e_12101");
+    }
+
+    #[test]
+    fn test_generate_struct() {
+        let descriptors = [
+            Descriptor { f: 0, x: 1, y: 15 },
+            Descriptor { f: 1, x: 1, y: 0 },
+            Descriptor { f: 0, x: 31, y: 1 },
+            Descriptor { f: 0, x: 7, y: 30 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+        ];
+        let source = generate_struct("Example", &descriptors, &Tables::default()).unwrap();
+        assert!(source.contains("pub struct Example {"));
+        assert!(source.contains("pub station_or_site_name: Option<String>,"));
+        assert!(source.contains("pub replication_1: Vec<ExampleItem1>,"));
+        assert!(source.contains("pub struct ExampleItem1 {"));
+        assert!(source.contains("pub height_of_station_ground_above_mean_sea_level: Option<f64>,"));
+        assert!(source.contains("pub temperature_air_temperature_2: Option<f64>,"));
+    }
+}
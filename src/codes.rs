@@ -0,0 +1,291 @@
+//! Typed values of widely used code tables
+//!
+//! Code table elements are decoded as plain integers. For a few code tables found in
+//! most observations, this module provides enums implementing [`CodeTable`], and
+//! [`Codes`] adds typed accessors to the subsets of a decoded [`Message`](crate::query::Message):
+//!
+//! ```no_run
+//! # fn main() -> Result<(), tinybufr::Error> {
+//! use tinybufr::codes::{Codes, PresentWeather};
+//! use tinybufr::{Tables, query::Message};
+//!
+//! let file = std::fs::File::open("synop.bufr")?;
+//! let message = Message::decode(std::io::BufReader::new(file), &Tables::default())?;
+//! for subset in &message.subsets {
+//!     if let Some(PresentWeather::Manned(code @ 50..=99)) = subset.present_weather() {
+//!         println!("precipitation (ww = {code:02})");
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Value, XY, query::Element};
+
+/// A code table with a typed value.
+pub trait CodeTable: Sized {
+    /// The element descriptor of the code table
+    const XY: XY;
+
+    /// Returns the entry of a code figure, or `None` if it is missing.
+    fn from_code(code: u32) -> Option<Self>;
+
+    /// Returns the code figure of the entry.
+    fn code(&self) -> u32;
+
+    /// Returns the entry of a decoded value, or `None` if it is missing or not an integer.
+    fn from_value(value: &Value) -> Option<Self> {
+        match *value {
+            Value::Integer(code) => u32::try_from(code).ok().and_then(Self::from_code),
+            _ => None,
+        }
+    }
+}
+
+/// Defines an enum of a code table whose entries all have their own variant, plus
+/// one for reserved code figures.
+macro_rules! code_table {
+    (
+        $(#[$meta:meta])*
+        $name:ident = $x:literal $y:literal {
+            $($(#[$variant_meta:meta])* $variant:ident = $code:literal,)*
+        }
+        missing = $missing:literal
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// Reserved or local code figure
+            Reserved(u32),
+        }
+
+        impl CodeTable for $name {
+            const XY: XY = XY { x: $x, y: $y };
+
+            fn from_code(code: u32) -> Option<Self> {
+                match code {
+                    $($code => Some(Self::$variant),)*
+                    $missing => None,
+                    _ => Some(Self::Reserved(code)),
+                }
+            }
+
+            fn code(&self) -> u32 {
+                match *self {
+                    $(Self::$variant => $code,)*
+                    Self::Reserved(code) => code,
+                }
+            }
+        }
+    };
+}
+
+code_table! {
+    /// Type of station (0 02 001)
+    StationType = 2 1 {
+        Automatic = 0,
+        Manned = 1,
+        /// Both manned and automatic
+        Hybrid = 2,
+    }
+    missing = 3
+}
+
+code_table! {
+    /// Vertical significance (surface observations) (0 08 002)
+    VerticalSignificance = 8 2 {
+        /// Observing rules for base of lowest cloud and cloud types of FM 12 SYNOP
+        /// and FM 14 SYNOP MOBIL
+        SynopRules = 0,
+        /// First non-cumulonimbus significant layer
+        FirstLayer = 1,
+        /// Second non-cumulonimbus significant layer
+        SecondLayer = 2,
+        /// Third non-cumulonimbus significant layer
+        ThirdLayer = 3,
+        /// Cumulonimbus layer
+        CumulonimbusLayer = 4,
+        Ceiling = 5,
+        /// Clouds not detected below the following height(s)
+        CloudsNotDetectedBelow = 6,
+        LowCloud = 7,
+        MiddleCloud = 8,
+        HighCloud = 9,
+        /// Cloud layer with base below and top above station level
+        LayerAcrossStationLevel = 10,
+        /// Cloud layer with base and top below station level
+        LayerBelowStationLevel = 11,
+        /// No clouds detected by the cloud detection system
+        NoCloudsDetected = 20,
+        /// First instrument detected cloud layer
+        FirstDetectedLayer = 21,
+        /// Second instrument detected cloud layer
+        SecondDetectedLayer = 22,
+        /// Third instrument detected cloud layer
+        ThirdDetectedLayer = 23,
+        /// Fourth instrument detected cloud layer
+        FourthDetectedLayer = 24,
+        NotApplicable = 62,
+    }
+    missing = 63
+}
+
+/// Present weather (0 20 003)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PresentWeather {
+    /// Present weather reported from a manned station (code table 4677, ww = 00 to 99)
+    Manned(u8),
+    /// Present weather reported from an automatic station (code table 4680, wawa = 00
+    /// to 99, encoded as 100 to 199)
+    Automatic(u8),
+    /// No significant phenomenon to report, present and past weather omitted
+    NoSignificantPhenomenon,
+    /// No observation, data not available, present and past weather omitted
+    NotObserved,
+    /// Present and past weather missing, but expected
+    ExpectedButMissing,
+    /// Other code figures (200 to 507)
+    Other(u16),
+}
+
+impl CodeTable for PresentWeather {
+    const XY: XY = XY { x: 20, y: 3 };
+
+    fn from_code(code: u32) -> Option<Self> {
+        Some(match code {
+            0..=99 => Self::Manned(code as u8),
+            100..=199 => Self::Automatic((code - 100) as u8),
+            508 => Self::NoSignificantPhenomenon,
+            509 => Self::NotObserved,
+            510 => Self::ExpectedButMissing,
+            511.. => return None,
+            _ => Self::Other(code as u16),
+        })
+    }
+
+    fn code(&self) -> u32 {
+        match *self {
+            Self::Manned(ww) => ww as u32,
+            Self::Automatic(wawa) => 100 + wawa as u32,
+            Self::NoSignificantPhenomenon => 508,
+            Self::NotObserved => 509,
+            Self::ExpectedButMissing => 510,
+            Self::Other(code) => code as u32,
+        }
+    }
+}
+
+/// Typed accessors of code table elements in the subsets of a decoded message.
+pub trait Codes {
+    /// Returns the first non-missing value of a code table in the subset.
+    fn code_value<T: CodeTable>(&self) -> Option<T>;
+
+    /// Returns all non-missing values of a code table in the subset, in order.
+    fn code_values<T: CodeTable>(&self) -> Vec<T>;
+
+    fn station_type(&self) -> Option<StationType> {
+        self.code_value()
+    }
+
+    fn present_weather(&self) -> Option<PresentWeather> {
+        self.code_value()
+    }
+
+    /// Returns the first vertical significance in the subset. Cloud layers usually
+    /// come with their own ones, which [`Codes::code_values`] returns.
+    fn vertical_significance(&self) -> Option<VerticalSignificance> {
+        self.code_value()
+    }
+}
+
+impl Codes for [Element] {
+    fn code_value<T: CodeTable>(&self) -> Option<T> {
+        self.iter()
+            .filter(|element| element.xy == T::XY)
+            .find_map(|element| T::from_value(&element.value))
+    }
+
+    fn code_values<T: CodeTable>(&self) -> Vec<T> {
+        self.iter()
+            .filter(|element| element.xy == T::XY)
+            .filter_map(|element| T::from_value(&element.value))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_code_tables() {
+        assert_eq!(StationType::from_code(1), Some(StationType::Manned));
+        assert_eq!(StationType::from_code(3), None);
+        assert_eq!(
+            VerticalSignificance::from_code(21),
+            Some(VerticalSignificance::FirstDetectedLayer)
+        );
+        assert_eq!(
+            VerticalSignificance::from_code(15),
+            Some(VerticalSignificance::Reserved(15))
+        );
+        assert_eq!(
+            PresentWeather::from_code(61),
+            Some(PresentWeather::Manned(61))
+        );
+        assert_eq!(
+            PresentWeather::from_code(161),
+            Some(PresentWeather::Automatic(61))
+        );
+        assert_eq!(
+            PresentWeather::from_code(508),
+            Some(PresentWeather::NoSignificantPhenomenon)
+        );
+        assert_eq!(PresentWeather::from_code(511), None);
+        assert_eq!(PresentWeather::from_value(&Value::Missing), None);
+        assert_eq!(PresentWeather::from_value(&Value::Integer(-1)), None);
+
+        for code in 0..512 {
+            if let Some(ww) = PresentWeather::from_code(code) {
+                assert_eq!(ww.code(), code);
+            }
+        }
+        for code in 0..64 {
+            if let Some(significance) = VerticalSignificance::from_code(code) {
+                assert_eq!(significance.code(), code);
+            }
+        }
+    }
+
+    #[test]
+    fn test_codes() {
+        let element = |x, y, value| Element {
+            position: 0,
+            xy: XY { x, y },
+            value,
+            sequences: Arc::new([]),
+        };
+        let subset = [
+            element(2, 1, Value::Integer(0)),
+            element(8, 2, Value::Missing),
+            element(8, 2, Value::Integer(7)),
+            element(8, 2, Value::Integer(8)),
+            element(12, 101, Value::Decimal(27315, -2)),
+        ];
+        assert_eq!(subset.station_type(), Some(StationType::Automatic));
+        assert_eq!(
+            subset.vertical_significance(),
+            Some(VerticalSignificance::LowCloud)
+        );
+        assert_eq!(
+            subset.code_values::<VerticalSignificance>(),
+            [
+                VerticalSignificance::LowCloud,
+                VerticalSignificance::MiddleCloud
+            ]
+        );
+        assert_eq!(subset.present_weather(), None);
+    }
+}
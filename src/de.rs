@@ -0,0 +1,552 @@
+//! Deserialization of subsets into user-defined types
+//!
+//! [`from_subset`] deserializes a subset read by
+//! [`DataReader::read_subset`](crate::DataReader::read_subset) into any type
+//! implementing [`serde::Deserialize`], like `serde_json::from_value` does for JSON.
+//! The fields of a struct are looked up among the elements and sequences of the
+//! subset, wherever they are nested, by descriptor (`"012101"`, `"301011"`), by the
+//! element name or sequence title of the tables, or by the key given by [`Aliases`]:
+//!
+//! ```no_run
+//! use tinybufr::{DataReader, DataSpec, HeaderSections, Tables, de::from_subset};
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Observation {
+//!     #[serde(rename = "WMO block number")]
+//!     block: i32,
+//!     #[serde(rename = "001002")]
+//!     station: i32,
+//!     #[serde(rename = "012101")]
+//!     temperature: Option<f64>,
+//!     #[serde(rename = "007004", default)]
+//!     pressures: Vec<Option<f64>>,
+//! }
+//!
+//! let mut reader = std::io::BufReader::new(std::fs::File::open("example.bufr")?);
+//! let header = HeaderSections::read(&mut reader)?;
+//! let tables = Tables::default();
+//! let spec = DataSpec::from_data_description(&header.data_description_section, &tables)?;
+//! let mut data_reader = DataReader::new(&mut reader, &spec)?;
+//! while let Some(subset) = data_reader.read_subset()? {
+//!     let observation: Observation = from_subset(&subset, &tables)?;
+//! }
+//! # Ok::<(), tinybufr::Error>(())
+//! ```
+//!
+//! A field of a sequence type (e.g. `Vec<T>`) gets all the values found for it in
+//! order of appearance, and any other field the first one. Missing values deserialize
+//! as `None`, sequences as structs or maps, and replications as sequences of their
+//! items. Fields not found in the subset are missing, so they should be `Option`s or
+//! have a `#[serde(default)]`.
+//!
+//! Replications are found by the key `"replication"`, which gets the items of the
+//! first one in a field of a sequence type. Types deserialized as maps (e.g.
+//! `HashMap<String, T>` or `serde_json::Value`) get the values at their level keyed by
+//! descriptor, and the items of replications under `"replication"`.
+
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{Deserialize, Deserializer, IntoDeserializer, Visitor};
+
+use crate::aliases::Aliases;
+use crate::{Error, SubsetValue, Tables, Value, XY};
+
+impl serde::de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::Invalid(msg.to_string())
+    }
+}
+
+/// Deserializes a subset read by [`DataReader::read_subset`](crate::DataReader::read_subset)
+/// into a value of type `T`, looking up fields by descriptor or by name in `tables`.
+pub fn from_subset<'s, T: Deserialize<'s>>(
+    subset: &'s [SubsetValue],
+    tables: &Tables,
+) -> Result<T, Error> {
+    T::deserialize(SubsetDeserializer::new(subset, tables))
+}
+
+/// Deserializer of a subset (see the [module documentation](self)).
+#[derive(Clone, Copy)]
+pub struct SubsetDeserializer<'s, 'n> {
+    values: &'s [SubsetValue],
+    names: Names<'n>,
+}
+
+impl<'s, 'n> SubsetDeserializer<'s, 'n> {
+    pub fn new(subset: &'s [SubsetValue], tables: &'n Tables) -> Self {
+        Self {
+            values: subset,
+            names: Names {
+                tables,
+                aliases: None,
+            },
+        }
+    }
+
+    /// Also looks up fields by the keys of `aliases`.
+    pub fn with_aliases(mut self, aliases: &'n Aliases) -> Self {
+        self.names.aliases = Some(aliases);
+        self
+    }
+}
+
+/// How fields are matched with the values of a subset.
+#[derive(Clone, Copy)]
+struct Names<'n> {
+    tables: &'n Tables,
+    aliases: Option<&'n Aliases>,
+}
+
+impl Names<'_> {
+    fn matches(&self, value: &SubsetValue, key: &str) -> bool {
+        match value {
+            SubsetValue::Scalar { xy, .. } => {
+                parse_code(key) == Some((0, *xy))
+                    || self
+                        .tables
+                        .table_b
+                        .get(xy)
+                        .is_some_and(|b| b.element_name == key)
+                    || self.aliases.and_then(|a| a.key(*xy)) == Some(key)
+            }
+            SubsetValue::Sequence { xy, .. } => {
+                parse_code(key) == Some((3, *xy))
+                    || self.tables.table_d.get(xy).is_some_and(|d| d.title == key)
+            }
+            SubsetValue::Replication { .. } => key == "replication",
+        }
+    }
+
+    /// Collects the values matching a key, not looking into the matched ones.
+    fn find<'s>(&self, values: &'s [SubsetValue], key: &str, found: &mut Vec<&'s SubsetValue>) {
+        for value in values {
+            if self.matches(value, key) {
+                found.push(value);
+                continue;
+            }
+            match value {
+                SubsetValue::Sequence { values, .. } => self.find(values, key, found),
+                SubsetValue::Replication { items } => {
+                    for item in items {
+                        self.find(item, key, found);
+                    }
+                }
+                SubsetValue::Scalar { .. } => {}
+            }
+        }
+    }
+}
+
+/// Parses a descriptor written as six digits (FXXYYY).
+fn parse_code(key: &str) -> Option<(u8, XY)> {
+    if key.len() != 6 || !key.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((
+        key[..1].parse().ok()?,
+        XY {
+            x: key[1..3].parse().ok()?,
+            y: key[3..].parse().ok()?,
+        },
+    ))
+}
+
+fn key_of(value: &SubsetValue) -> String {
+    match value {
+        SubsetValue::Scalar { xy, .. } => format!("0{:02}{:03}", xy.x, xy.y),
+        SubsetValue::Sequence { xy, .. } => format!("3{:02}{:03}", xy.x, xy.y),
+        SubsetValue::Replication { .. } => "replication".to_string(),
+    }
+}
+
+impl<'de> Deserializer<'de> for SubsetDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let names = self.names;
+        let mut map = MapDeserializer::new(
+            self.values
+                .iter()
+                .map(|value| (key_of(value), ValueDeserializer { value, names })),
+        );
+        let value = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let names = self.names;
+        let mut map = MapDeserializer::new(fields.iter().filter_map(|&field| {
+            let mut values = vec![];
+            names.find(self.values, field, &mut values);
+            (!values.is_empty()).then_some((field, FoundDeserializer { values, names }))
+        }));
+        let value = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let names = self.names;
+        visit_seq(
+            self.values
+                .iter()
+                .map(|value| ValueDeserializer { value, names }),
+            visitor,
+        )
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+        byte_buf option unit unit_struct tuple tuple_struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for SubsetDeserializer<'de, '_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Deserializer of the values found for a field.
+struct FoundDeserializer<'s, 'n> {
+    values: Vec<&'s SubsetValue>,
+    names: Names<'n>,
+}
+
+impl<'s, 'n> FoundDeserializer<'s, 'n> {
+    fn first(&self) -> ValueDeserializer<'s, 'n> {
+        ValueDeserializer {
+            value: self.values[0],
+            names: self.names,
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for FoundDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.first().deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.values.first() {
+            None
+            | Some(SubsetValue::Scalar {
+                value: Value::Missing,
+                ..
+            }) => visitor.visit_none(),
+            Some(_) => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if let [SubsetValue::Replication { .. }] = self.values[..] {
+            return self.first().deserialize_any(visitor);
+        }
+        let names = self.names;
+        visit_seq(
+            self.values
+                .into_iter()
+                .map(|value| ValueDeserializer { value, names }),
+            visitor,
+        )
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.first().deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+        byte_buf unit unit_struct tuple_struct map enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for FoundDeserializer<'de, '_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Deserializer of a single value of a subset.
+#[derive(Clone, Copy)]
+struct ValueDeserializer<'s, 'n> {
+    value: &'s SubsetValue,
+    names: Names<'n>,
+}
+
+impl<'s, 'n> ValueDeserializer<'s, 'n> {
+    fn values(&self, values: &'s [SubsetValue]) -> SubsetDeserializer<'s, 'n> {
+        SubsetDeserializer {
+            values,
+            names: self.names,
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            SubsetValue::Scalar { value, .. } => visit_value(value, visitor),
+            SubsetValue::Sequence { values, .. } => self.values(values).deserialize_map(visitor),
+            SubsetValue::Replication { items } => {
+                visit_seq(items.iter().map(|item| self.values(item)), visitor)
+            }
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            SubsetValue::Scalar {
+                value: Value::Missing,
+                ..
+            } => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            SubsetValue::Sequence { values, .. } => self
+                .values(values)
+                .deserialize_struct(name, fields, visitor),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+        byte_buf unit unit_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer<'de, '_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+fn visit_seq<'de, V, I>(iter: I, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+    I: Iterator,
+    I::Item: IntoDeserializer<'de, Error>,
+{
+    let mut seq = SeqDeserializer::new(iter);
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+fn visit_value<'de, V: Visitor<'de>>(value: &'de Value, visitor: V) -> Result<V::Value, Error> {
+    match value {
+        Value::Missing => visitor.visit_none(),
+        &Value::Integer(v) => visitor.visit_i64(v as i64),
+        &Value::Decimal(v, scale) => visit_decimal(v as i64, scale, visitor),
+        &Value::Decimal64(v, scale) => visit_decimal(v, scale, visitor),
+        Value::String(s) => visitor.visit_borrowed_str(s),
+        Value::Bytes(b) => visitor.visit_borrowed_bytes(b),
+    }
+}
+
+/// Visits a scaled decimal as an integer if it is one, or as a float.
+fn visit_decimal<'de, V: Visitor<'de>>(v: i64, scale: i8, visitor: V) -> Result<V::Value, Error> {
+    let integer = (scale >= 0)
+        .then(|| 10i64.checked_pow(scale as u32))
+        .flatten()
+        .and_then(|m| v.checked_mul(m));
+    match integer {
+        Some(v) => visitor.visit_i64(v),
+        None => visitor.visit_f64(Value::Decimal64(v, scale).as_f64().unwrap_or_default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::subset::tests::sounding;
+
+    #[test]
+    fn test_from_subset() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Level {
+            #[serde(rename = "007004")]
+            pressure: f64,
+            #[serde(rename = "012101")]
+            temperature: Option<f64>,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Observation<'a> {
+            #[serde(rename = "WMO block number")]
+            block: i32,
+            #[serde(rename = "001002")]
+            station: u32,
+            #[serde(rename = "001015")]
+            name: &'a str,
+            #[serde(rename = "012101")]
+            temperature: Option<f64>,
+            #[serde(rename = "007004")]
+            pressures: Vec<i64>,
+            #[serde(rename = "Temperature/air temperature")]
+            temperatures: Vec<Option<f64>>,
+            #[serde(rename = "301001")]
+            identification: BTreeMap<String, i32>,
+            #[serde(rename = "020001")]
+            visibility: Option<f64>,
+        }
+
+        let tables = Tables::default();
+        let subset = sounding();
+        let observation: Observation = from_subset(&subset, &tables).unwrap();
+        assert_eq!(
+            observation,
+            Observation {
+                block: 47,
+                station: 662,
+                name: "TOKYO",
+                temperature: Some(280.15),
+                pressures: vec![100000, 85000],
+                temperatures: vec![Some(280.15), None],
+                identification: BTreeMap::from([
+                    ("001001".to_string(), 47),
+                    ("001002".to_string(), 662)
+                ]),
+                visibility: None,
+            }
+        );
+
+        // Replications as sequences of their items
+        let levels: BTreeMap<String, serde_json::Value> = from_subset(&subset, &tables).unwrap();
+        assert_eq!(
+            levels["replication"],
+            serde_json::json!([
+                {"007004": 100000, "012101": 280.15},
+                {"007004": 85000, "012101": null},
+            ])
+        );
+        #[derive(Debug, Deserialize)]
+        struct Sounding {
+            #[serde(rename = "replication")]
+            levels: Vec<Level>,
+        }
+        let sounding: Sounding = from_subset(&subset, &tables).unwrap();
+        assert_eq!(sounding.levels.len(), 2);
+        assert_eq!(
+            sounding.levels[1],
+            Level {
+                pressure: 85000.0,
+                temperature: None
+            }
+        );
+
+        // Missing fields and values
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Visibility {
+            #[serde(rename = "020001")]
+            visibility: f64,
+        }
+        let error = from_subset::<Visibility>(&subset, &tables).unwrap_err();
+        assert!(matches!(error, Error::Invalid(_)), "{error:?}");
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Temperatures {
+            #[serde(rename = "012101")]
+            temperatures: Vec<f64>,
+        }
+        assert!(from_subset::<Temperatures>(&subset, &tables).is_err());
+    }
+
+    #[test]
+    fn test_with_aliases() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Observation {
+            #[serde(rename = "stationNumber")]
+            station: i32,
+            #[serde(rename = "airTemperature")]
+            temperatures: Vec<Option<f64>>,
+        }
+
+        let tables = Tables::default();
+        let mut aliases = Aliases::new();
+        aliases.insert(XY { x: 1, y: 2 }, "stationNumber").unwrap();
+        aliases
+            .insert(XY { x: 12, y: 101 }, "airTemperature")
+            .unwrap();
+        let subset = sounding();
+        let deserializer = SubsetDeserializer::new(&subset, &tables).with_aliases(&aliases);
+        assert_eq!(
+            Observation::deserialize(deserializer).unwrap(),
+            Observation {
+                station: 662,
+                temperatures: vec![Some(280.15), None],
+            }
+        );
+    }
+}
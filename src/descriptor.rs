@@ -1,13 +1,13 @@
 //! Descriptors (FXY)
 
 use std::fmt::Debug;
-use std::io::Read;
+use std::io::{Read, Write};
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::{
     Error,
-    tables::{TableBEntry, TableDEntry, Tables},
+    tables::{TableBEntry, TableCEntry, TableDEntry, Tables},
 };
 
 /// Descriptor (FXY).
@@ -28,6 +28,101 @@ impl Descriptor {
             y: (val & 0xff) as u8,
         })
     }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let val = (self.f as u16) << 14 | (self.x as u16) << 8 | self.y as u16;
+        writer.write_u16::<BigEndian>(val)?;
+        Ok(())
+    }
+}
+
+/// Returns the error for reading a descriptor missing from the tables.
+pub(crate) fn unresolved_error(desc: &Descriptor) -> Error {
+    let table = if desc.f == 3 { "D" } else { "B" };
+    Error::Table(format!(
+        "Table {table} entry not found for xy: {:?}",
+        desc.xy()
+    ))
+}
+
+/// Formats an operator descriptor for messages, e.g. `2 01 129 (Change data width)`.
+pub(crate) fn describe_operator(xy: XY, entry: Option<&TableCEntry>) -> String {
+    match entry {
+        Some(c) => format!("2 {:02} {:03} ({})", xy.x, xy.y, c.operator_name),
+        None => format!("2 {:02} {:03}", xy.x, xy.y),
+    }
+}
+
+/// Operation of an operator descriptor 2 XX YYY.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    /// 2 01 YYY: add YYY - 128 bits to the width of elements (0 for 2 01 000)
+    ChangeWidth(i8),
+    /// 2 02 YYY: add YYY - 128 to the scale of elements (0 for 2 02 000)
+    ChangeScale(i8),
+    /// 2 03 YYY: new reference values of YYY bits follow
+    ChangeReferenceValues(u8),
+    /// 2 03 255: end of the new reference values
+    ConcludeReferenceValues,
+    /// 2 03 000: cancel the new reference values
+    CancelReferenceValues,
+    /// 2 04 YYY: add an associated field of YYY bits
+    AddAssociatedField(u8),
+    /// 2 04 000: cancel the last associated field
+    CancelAssociatedField,
+    /// 2 05 YYY: YYY characters follow
+    SignifyCharacter(u8),
+    /// 2 06 YYY: the following local element is YYY bits wide
+    SignifyWidth(u8),
+    /// 2 07 YYY: increase scale, reference value and width (0 to cancel)
+    IncreaseScaleReferenceWidth(u8),
+    /// 2 08 YYY: change the width of CCITT IA5 elements to YYY characters (0 to cancel)
+    ChangeStringWidth(u8),
+    /// 2 21 YYY: no data for the following YYY elements
+    DataNotPresent(u8),
+    /// 2 22 000, 2 23 000, 2 24 000, 2 25 000 or 2 32 000: values related to the
+    /// elements of a data present bit-map follow (XX of the operator)
+    BitmapFollows(u8),
+    /// 2 23 255, 2 24 255, 2 25 255 or 2 32 255: marker of a related value (XX of the operator)
+    MarkerOperator(u8),
+    /// 2 35 000: cancel backward data reference
+    CancelBackwardReference,
+    /// 2 36 000: define a data present bit-map for reuse
+    DefineBitmap,
+    /// 2 37 000: use the defined data present bit-map
+    UseDefinedBitmap,
+    /// 2 37 255: cancel the defined data present bit-map
+    CancelDefinedBitmap,
+    /// Operator not interpreted by this crate
+    Other(XY),
+}
+
+impl Operator {
+    pub fn from_xy(xy: XY) -> Self {
+        match (xy.x, xy.y) {
+            (1, 0) => Operator::ChangeWidth(0),
+            (1, y) => Operator::ChangeWidth((y as i16 - 128) as i8),
+            (2, 0) => Operator::ChangeScale(0),
+            (2, y) => Operator::ChangeScale((y as i16 - 128) as i8),
+            (3, 0) => Operator::CancelReferenceValues,
+            (3, 255) => Operator::ConcludeReferenceValues,
+            (3, y) => Operator::ChangeReferenceValues(y),
+            (4, 0) => Operator::CancelAssociatedField,
+            (4, y) => Operator::AddAssociatedField(y),
+            (5, y) => Operator::SignifyCharacter(y),
+            (6, y) => Operator::SignifyWidth(y),
+            (7, y) => Operator::IncreaseScaleReferenceWidth(y),
+            (8, y) => Operator::ChangeStringWidth(y),
+            (21, y) => Operator::DataNotPresent(y),
+            (x @ (22 | 23 | 24 | 25 | 32), 0) => Operator::BitmapFollows(x),
+            (x @ (23 | 24 | 25 | 32), 255) => Operator::MarkerOperator(x),
+            (35, 0) => Operator::CancelBackwardReference,
+            (36, 0) => Operator::DefineBitmap,
+            (37, 0) => Operator::UseDefinedBitmap,
+            (37, 255) => Operator::CancelDefinedBitmap,
+            _ => Operator::Other(xy),
+        }
+    }
 }
 
 impl Debug for Descriptor {
@@ -46,7 +141,9 @@ impl Descriptor {
 }
 
 /// X and Y parts of a descriptor.
-#[derive(Hash, Debug, Clone, Copy, Eq, PartialEq)]
+///
+/// Descriptors are ordered by X, then by Y.
+#[derive(Hash, Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
 pub struct XY {
     pub x: u8,
     pub y: u8,
@@ -55,39 +152,71 @@ pub struct XY {
 /// A descriptor that has been resolved with table lookups.
 #[derive(Debug)]
 pub enum ResolvedDescriptor<'a> {
-    Data(&'a TableBEntry),
+    Data(&'static TableBEntry),
     Replication {
         y: u8,
         delayed_bits: u8,
         descriptors: Vec<ResolvedDescriptor<'a>>,
     },
-    Operator(XY),
+    /// Operator with its Table C entry (`None` if the operator is unknown)
+    Operator(XY, Option<&'static TableCEntry>),
     Sequence(&'a TableDEntry, Vec<ResolvedDescriptor<'a>>),
+    /// Element or sequence descriptor not found in the tables
+    ///
+    /// It is an error only when the descriptor is actually read, so data whose
+    /// unknown descriptors are never reached (e.g. in a zero-count delayed replication)
+    /// can still be decoded.
+    Unresolved(Descriptor),
 }
 
 impl<'a> ResolvedDescriptor<'a> {
     pub fn from_descriptor(desc: &Descriptor, tables: &Tables) -> Result<Self, Error> {
+        Self::resolve(desc, tables, &mut vec![], 0)
+    }
+
+    /// Resolves a descriptor within the Table D sequences in `path`, nested in `depth`
+    /// sequences and replications.
+    fn resolve(
+        desc: &Descriptor,
+        tables: &Tables,
+        path: &mut Vec<XY>,
+        depth: usize,
+    ) -> Result<Self, Error> {
         Ok(match desc.f {
             0 => {
                 let Some(b) = tables.table_b.get(&desc.xy()) else {
-                    return Err(Error::Table(format!(
-                        "Table B entry not found for xy: {:?}",
-                        desc.xy()
-                    )));
+                    return Ok(ResolvedDescriptor::Unresolved(*desc));
                 };
                 ResolvedDescriptor::Data(b)
             }
             1 => unreachable!(),
-            2 => ResolvedDescriptor::Operator(desc.xy()),
+            2 => ResolvedDescriptor::Operator(desc.xy(), tables.operator(desc.x, desc.y)),
             3 => {
                 let Some(d) = tables.table_d.get(&desc.xy()) else {
-                    return Err(Error::Table(format!(
-                        "Table D entry not found for xy: {:?}",
-                        desc.xy()
-                    )));
+                    return Ok(ResolvedDescriptor::Unresolved(*desc));
                 };
-                let resolved_elements = resolve_descriptors(tables, d.elements)?;
-                ResolvedDescriptor::Sequence(d, resolved_elements)
+                if path.contains(&desc.xy()) {
+                    return Err(Error::Invalid(format!(
+                        "Table D sequence {} refers to itself (through {})",
+                        sequence_label(desc.xy()),
+                        path.iter()
+                            .map(|&xy| sequence_label(xy))
+                            .collect::<Vec<_>>()
+                            .join(" > ")
+                    )));
+                }
+                if path.len() >= tables.max_sequence_depth {
+                    return Err(Error::Invalid(format!(
+                        "Table D sequence {} nested deeper than {} levels",
+                        sequence_label(desc.xy()),
+                        tables.max_sequence_depth
+                    )));
+                }
+                check_nesting_depth(tables, depth)?;
+                path.push(desc.xy());
+                let resolved_elements = resolve_descriptors_in(tables, d.elements, path, depth + 1);
+                path.pop();
+                ResolvedDescriptor::Sequence(d, resolved_elements?)
             }
             _ => {
                 return Err(Error::Table(format!(
@@ -99,9 +228,41 @@ impl<'a> ResolvedDescriptor<'a> {
     }
 }
 
+/// Formats a sequence descriptor for messages, e.g. `3 01 001`.
+fn sequence_label(xy: XY) -> String {
+    format!("3 {:02} {:03}", xy.x, xy.y)
+}
+
+fn check_nesting_depth(tables: &Tables, depth: usize) -> Result<(), Error> {
+    if depth >= tables.max_nesting_depth {
+        return Err(Error::Invalid(format!(
+            "Sequences and replications nested deeper than {} levels",
+            tables.max_nesting_depth
+        )));
+    }
+    Ok(())
+}
+
+/// Resolves descriptors, expanding Table D sequences.
+///
+/// Sequences referring to themselves (directly or not) and sequences nested deeper
+/// than [`Tables::max_sequence_depth`] are rejected with [`Error::Invalid`], as are
+/// sequences and replications nested deeper than [`Tables::max_nesting_depth`]
+/// (whichever limit is reached first).
+/// More than 65535 descriptors at the same level are not supported, as the events of
+/// [`DataReader`](crate::DataReader) index them with `u16`.
 pub(crate) fn resolve_descriptors<'a>(
     tables: &Tables,
     descriptors: &'a [Descriptor],
+) -> Result<Vec<ResolvedDescriptor<'a>>, Error> {
+    resolve_descriptors_in(tables, descriptors, &mut vec![], 0)
+}
+
+fn resolve_descriptors_in<'a>(
+    tables: &Tables,
+    descriptors: &'a [Descriptor],
+    path: &mut Vec<XY>,
+    depth: usize,
 ) -> Result<Vec<ResolvedDescriptor<'a>>, Error> {
     let mut resolved = vec![];
     let mut pos = 0;
@@ -112,12 +273,14 @@ pub(crate) fn resolve_descriptors<'a>(
                     // delayed replication when YYY = 0
                     0 => {
                         pos += 1;
-                        match descriptors[pos] {
-                            Descriptor { f: 0, x: 31, y: 0 } => 1,
-                            Descriptor { f: 0, x: 31, y: 1 } => 8,
-                            Descriptor { f: 0, x: 31, y: 2 } => 16,
-                            Descriptor { f: 0, x: 31, y: 3 } => 8, // Note: JMA-local?
-                            desc => {
+                        let Some(desc) = descriptors.get(pos) else {
+                            return Err(Error::Invalid(
+                                "Delayed replication without a replication factor".to_string(),
+                            ));
+                        };
+                        match tables.delayed_replication_factors.get(&desc.xy()) {
+                            Some(&bits) if desc.f == 0 => bits,
+                            _ => {
                                 return Err(Error::NotSupported(format!(
                                     "Unsupported delayed descriptor replication factor: {desc:#?}",
                                 )));
@@ -132,19 +295,117 @@ pub(crate) fn resolve_descriptors<'a>(
                         "Replication range out of bounds".to_string(),
                     ));
                 }
+                check_nesting_depth(tables, depth)?;
                 resolved.push(ResolvedDescriptor::Replication {
                     y,
-                    descriptors: resolve_descriptors(tables, &descriptors[pos..pos + x as usize])?,
+                    descriptors: resolve_descriptors_in(
+                        tables,
+                        &descriptors[pos..pos + x as usize],
+                        path,
+                        depth + 1,
+                    )?,
                     delayed_bits,
                 });
                 pos += x as usize;
             }
             desc => {
-                resolved.push(ResolvedDescriptor::from_descriptor(desc, tables)?);
+                resolved.push(ResolvedDescriptor::resolve(desc, tables, path, depth)?);
                 pos += 1;
             }
         }
     }
+    if resolved.len() > u16::MAX as usize {
+        return Err(Error::NotSupported(format!(
+            "{} descriptors at the same level (up to {} are supported)",
+            resolved.len(),
+            u16::MAX
+        )));
+    }
 
     Ok(resolved)
 }
+
+/// Returns the maximum number of sequences and replications nested in `descriptors`.
+pub(crate) fn nesting_depth(descriptors: &[ResolvedDescriptor]) -> usize {
+    descriptors
+        .iter()
+        .map(|desc| match desc {
+            ResolvedDescriptor::Replication { descriptors, .. }
+            | ResolvedDescriptor::Sequence(_, descriptors) => 1 + nesting_depth(descriptors),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operator_from_xy() {
+        let operator = |x, y| Operator::from_xy(XY { x, y });
+        assert_eq!(operator(1, 129), Operator::ChangeWidth(1));
+        assert_eq!(operator(1, 0), Operator::ChangeWidth(0));
+        assert_eq!(operator(2, 126), Operator::ChangeScale(-2));
+        assert_eq!(operator(3, 12), Operator::ChangeReferenceValues(12));
+        assert_eq!(operator(3, 255), Operator::ConcludeReferenceValues);
+        assert_eq!(operator(4, 0), Operator::CancelAssociatedField);
+        assert_eq!(operator(6, 16), Operator::SignifyWidth(16));
+        assert_eq!(operator(22, 0), Operator::BitmapFollows(22));
+        assert_eq!(operator(24, 255), Operator::MarkerOperator(24));
+        assert_eq!(operator(22, 255), Operator::Other(XY { x: 22, y: 255 }));
+        assert_eq!(operator(41, 0), Operator::Other(XY { x: 41, y: 0 }));
+    }
+
+    /// Nests `depth` replications of one item around `inner`.
+    fn nest(depth: usize, inner: Vec<Descriptor>) -> Vec<Descriptor> {
+        (0..depth).fold(inner, |inner, _| {
+            let mut outer = vec![Descriptor {
+                f: 1,
+                x: inner.len() as u8,
+                y: 1,
+            }];
+            outer.extend(inner);
+            outer
+        })
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        let mut tables = Tables::default();
+        let temperature = Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        };
+        // WMO block and station numbers
+        let station = Descriptor { f: 3, x: 1, y: 1 };
+
+        let descriptors = [temperature];
+        let resolved = resolve_descriptors(&tables, &descriptors).unwrap();
+        assert_eq!(nesting_depth(&resolved), 0);
+        let descriptors = nest(3, vec![temperature, station]);
+        let resolved = resolve_descriptors(&tables, &descriptors).unwrap();
+        assert_eq!(nesting_depth(&resolved), 4);
+
+        // Sequences count towards the limit as well as replications
+        let descriptors = nest(tables.max_nesting_depth - 1, vec![station]);
+        assert!(resolve_descriptors(&tables, &descriptors).is_ok());
+        let descriptors = nest(tables.max_nesting_depth, vec![station]);
+        assert!(matches!(
+            resolve_descriptors(&tables, &descriptors),
+            Err(Error::Invalid(msg)) if msg.contains("nested deeper than 64 levels")
+        ));
+        tables.max_nesting_depth = 80;
+        assert!(resolve_descriptors(&tables, &descriptors).is_ok());
+
+        let descriptors = vec![temperature; u16::MAX as usize];
+        assert!(resolve_descriptors(&tables, &descriptors).is_ok());
+        let descriptors = vec![temperature; u16::MAX as usize + 1];
+        assert!(matches!(
+            resolve_descriptors(&tables, &descriptors),
+            Err(Error::NotSupported(_))
+        ));
+    }
+}
@@ -0,0 +1,395 @@
+//! Searching archives for values matching a predicate
+//!
+//! [`Grep`] finds the values of an element satisfying a [`Predicate`] (written as in
+//! the selectors of [`crate::query`], e.g. `012101>313.15`) in BUFR files, as `grep`
+//! does for lines of text. Each [`Hit`] gives where the value is (file, message and
+//! subset) along with the station and time of the observation, and hits are streamed
+//! file by file, so whole archives can be searched:
+//!
+//! ```no_run
+//! use tinybufr::{Tables, grep::Grep};
+//!
+//! let tables = Tables::default();
+//! let grep = Grep::new("012101>313.15".parse()?, &tables);
+//! for hit in grep.search_dir("archive")? {
+//!     println!("{}", hit?);
+//! }
+//! # Ok::<(), tinybufr::Error>(())
+//! ```
+//!
+//! Messages whose templates do not have the element are skipped without reading their
+//! data, and the others are read with a projection on the element and the station and
+//! time elements (see [`DataReaderBuilder::filter`](crate::DataReaderBuilder::filter)).
+//!
+//! The station is given by the latest WMO block and station numbers (0 01 001 and
+//! 0 01 002, as `47662`) before the value in its subset, or else by the latest station
+//! name or identifier (0 01 015, 0 01 018, 0 01 011 or 0 01 019). The time is given by
+//! the latest date and time elements (0 04 001 to 0 04 006).
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{
+    DataReader, DataSpec, DecodeOptions, Error, Tables, Value, XY, input::open_any,
+    query::Predicate, sections::split_message,
+};
+
+/// Searches BUFR messages for values of an element satisfying a predicate.
+pub struct Grep<'t> {
+    predicate: Predicate,
+    tables: &'t Tables,
+    options: DecodeOptions,
+}
+
+/// A value found in a message by [`Grep::search_message`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageHit {
+    pub subset: u16,
+    /// Identifier of the station (see the [module documentation](self))
+    pub station: Option<String>,
+    /// Time of the observation
+    pub time: Option<DateTime>,
+    pub value: Value,
+}
+
+/// A value found in a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub path: Arc<Path>,
+    /// Index of the message in the file
+    pub message: usize,
+    pub hit: MessageHit,
+}
+
+impl std::fmt::Display for Hit {
+    /// Formats the hit as a line `path:message:subset: station time value`, with `-` for
+    /// an unknown station or time.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hit = &self.hit;
+        write!(
+            f,
+            "{}:{}:{}: {} ",
+            self.path.display(),
+            self.message,
+            hit.subset,
+            hit.station.as_deref().unwrap_or("-")
+        )?;
+        match &hit.time {
+            Some(time) => write!(f, "{time}")?,
+            None => write!(f, "-")?,
+        }
+        write!(f, " {:?}", hit.value)
+    }
+}
+
+/// Date and time of an observation, with the hour, minute and second defaulting to 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl std::fmt::Display for DateTime {
+    /// Formats the time in ISO 8601 (`2025-08-07T11:00:00Z`).
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Station identifiers, by order of preference after the WMO block and station numbers
+const STATION_NAMES: [XY; 4] = [
+    XY { x: 1, y: 15 },
+    XY { x: 1, y: 18 },
+    XY { x: 1, y: 11 },
+    XY { x: 1, y: 19 },
+];
+
+/// Station and time elements read before the values of a subset.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Context {
+    block: Option<i32>,
+    station: Option<i32>,
+    names: [Option<String>; 4],
+    /// Year, month, day, hour, minute and second
+    time: [Option<i64>; 6],
+}
+
+impl Context {
+    pub(crate) fn update(&mut self, xy: XY, value: &Value) {
+        let integer = || match *value {
+            Value::Integer(v) => Some(v as i64),
+            Value::Decimal(v, 0) => Some(v as i64),
+            Value::Decimal64(v, 0) => Some(v),
+            _ => None,
+        };
+        match (xy.x, xy.y) {
+            (1, 1) => self.block = integer().map(|v| v as i32),
+            (1, 2) => self.station = integer().map(|v| v as i32),
+            (4, y @ 1..=6) => self.time[y as usize - 1] = integer(),
+            _ => {
+                if let Some(i) = STATION_NAMES.iter().position(|name| *name == xy) {
+                    self.names[i] = match value {
+                        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+                        _ => None,
+                    };
+                }
+            }
+        }
+    }
+
+    pub(crate) fn station(&self) -> Option<String> {
+        match (self.block, self.station) {
+            (Some(block), Some(station)) => Some(format!("{block:02}{station:03}")),
+            _ => self.names.iter().flatten().next().cloned(),
+        }
+    }
+
+    pub(crate) fn time(&self) -> Option<DateTime> {
+        let [year, month, day, hour, minute, second] = self.time;
+        let field = |v: Option<i64>| v.and_then(|v| u8::try_from(v).ok());
+        Some(DateTime {
+            year: u16::try_from(year?).ok()?,
+            month: field(month)?,
+            day: field(day)?,
+            hour: field(hour.or(Some(0)))?,
+            minute: field(minute.or(Some(0)))?,
+            second: field(second.or(Some(0)))?,
+        })
+    }
+}
+
+fn is_context_element(xy: XY) -> bool {
+    matches!((xy.x, xy.y), (1, 1 | 2) | (4, 1..=6)) || STATION_NAMES.contains(&xy)
+}
+
+impl<'t> Grep<'t> {
+    /// Creates a search decoding messages strictly.
+    pub fn new(predicate: Predicate, tables: &'t Tables) -> Self {
+        Self {
+            predicate,
+            tables,
+            options: DecodeOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the matching values in a message.
+    pub fn search_message(&self, message: &[u8]) -> Result<Vec<MessageHit>, Error> {
+        let sections = split_message(message, &self.options)?;
+        let data_spec = DataSpec::from_data_description(
+            &sections.header.data_description_section,
+            self.tables,
+        )?;
+        let target = self.predicate.xy;
+        if !data_spec.iter_elements().any(|e| e.entry.xy == target) {
+            return Ok(vec![]);
+        }
+        let mut data_reader: DataReader<'_, &[u8]> = DataReader::builder(&data_spec)
+            .decode_options(&self.options)
+            .filter(move |xy| xy == target || is_context_element(xy))
+            .build(sections.data_section)?;
+
+        let mut hits = vec![];
+        let mut contexts: Vec<Context> = vec![];
+        data_reader.for_each_element(|subset, _, xy, value| {
+            if contexts.len() <= subset as usize {
+                contexts.resize_with(subset as usize + 1, Context::default);
+            }
+            let context = &mut contexts[subset as usize];
+            if xy == target && self.predicate.matches_value(value) {
+                hits.push(MessageHit {
+                    subset,
+                    station: context.station(),
+                    time: context.time(),
+                    value: value.clone(),
+                });
+            }
+            context.update(xy, value);
+            Ok(())
+        })?;
+        Ok(hits)
+    }
+
+    /// Searches the files of a directory and its subdirectories, in the order of their
+    /// paths (see [`Grep::search_files`]).
+    pub fn search_dir<P: AsRef<Path>>(&self, dir: P) -> Result<Hits<'_, 't>, Error> {
+        let mut files = vec![];
+        collect_files(dir.as_ref(), &mut files)?;
+        files.sort();
+        Ok(self.search_files(files))
+    }
+
+    /// Searches files of any format supported by [`open_any`].
+    ///
+    /// A file or message that cannot be read yields an error, and the search goes on
+    /// with the next one.
+    pub fn search_files<I: IntoIterator<Item = PathBuf>>(&self, paths: I) -> Hits<'_, 't> {
+        Hits {
+            grep: self,
+            paths: paths.into_iter().collect(),
+            current: None,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Iterator over the values found in files (see [`Grep::search_files`]).
+pub struct Hits<'g, 't> {
+    grep: &'g Grep<'t>,
+    paths: VecDeque<PathBuf>,
+    /// The file being searched, with its remaining messages and the index of the next
+    current: Option<(Arc<Path>, crate::input::MessageIterator, usize)>,
+    pending: VecDeque<Hit>,
+}
+
+impl Iterator for Hits<'_, '_> {
+    type Item = Result<Hit, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(hit) = self.pending.pop_front() {
+                return Some(Ok(hit));
+            }
+            let Some((path, messages, index)) = &mut self.current else {
+                let path = self.paths.pop_front()?;
+                match open_any(&path) {
+                    Ok(messages) => self.current = Some((path.into(), messages, 0)),
+                    Err(e) => return Some(Err(with_location(e, &path, None))),
+                }
+                continue;
+            };
+            let Some(message) = messages.next() else {
+                self.current = None;
+                continue;
+            };
+            let message_index = *index;
+            *index += 1;
+            match self.grep.search_message(&message) {
+                Ok(hits) => self.pending.extend(hits.into_iter().map(|hit| Hit {
+                    path: path.clone(),
+                    message: message_index,
+                    hit,
+                })),
+                Err(e) => return Some(Err(with_location(e, path, Some(message_index)))),
+            }
+        }
+    }
+}
+
+/// Adds the file (and message) to an error.
+fn with_location(error: Error, path: &Path, message: Option<usize>) -> Error {
+    let location = match message {
+        Some(i) => format!("{}: message {i}", path.display()),
+        None => path.display().to_string(),
+    };
+    match error {
+        Error::Io(e) => Error::Io(std::io::Error::new(e.kind(), format!("{location}: {e}"))),
+        Error::Table(s) => Error::Table(format!("{location}: {s}")),
+        Error::Invalid(s) => Error::Invalid(format!("{location}: {s}")),
+        Error::NotSupported(s) => Error::NotSupported(format!("{location}: {s}")),
+        Error::Fatal(s) => Error::Fatal(format!("{location}: {s}")),
+        Error::Timeout(s) => Error::Timeout(format!("{location}: {s}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WPR: &str = "./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin";
+
+    #[test]
+    fn test_search_message() {
+        let message = std::fs::read(WPR).unwrap();
+        let tables = Tables::default();
+        // Eastward wind speeds
+        let all = Grep::new("011003!=missing".parse().unwrap(), &tables)
+            .search_message(&message)
+            .unwrap();
+        assert!(!all.is_empty());
+        let hit = &all[0];
+        assert!(hit.station.as_ref().unwrap().starts_with("47"));
+        let time = hit.time.unwrap();
+        assert_eq!((time.year, time.month, time.day), (2020, 7, 28));
+        assert!(all.iter().any(|hit| hit.subset > 0));
+
+        let strong = Grep::new("011003>20".parse().unwrap(), &tables)
+            .search_message(&message)
+            .unwrap();
+        assert!(!strong.is_empty() && strong.len() < all.len());
+        let predicate: Predicate = "011003>20".parse().unwrap();
+        assert!(strong.iter().all(|hit| predicate.matches_value(&hit.value)));
+
+        // Elements not in the template
+        let none = Grep::new("012101>0".parse().unwrap(), &tables)
+            .search_message(&message)
+            .unwrap();
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_search_files() {
+        let tables = Tables::default();
+        let grep = Grep::new("011003>20".parse().unwrap(), &tables);
+        let expected = grep
+            .search_message(&std::fs::read(WPR).unwrap())
+            .unwrap()
+            .len();
+        let hits: Vec<_> = grep
+            .search_files([PathBuf::from(WPR), PathBuf::from("./nonexistent.bufr")])
+            .collect();
+        assert_eq!(hits.len(), expected + 1);
+        let first = hits[0].as_ref().unwrap();
+        assert_eq!(first.message, 0);
+        let line = first.to_string();
+        assert!(
+            line.starts_with(&format!("{WPR}:0:{}: 47", first.hit.subset)),
+            "{line}"
+        );
+        assert!(line.contains(" 2020-07-28T04:"), "{line}");
+        assert!(matches!(
+            hits.last().unwrap(),
+            Err(Error::Io(e)) if e.to_string().contains("nonexistent")
+        ));
+    }
+
+    #[test]
+    fn test_date_time() {
+        let mut context = Context::default();
+        assert_eq!(context.time(), None);
+        for (y, v) in [(1, 2025), (2, 8), (3, 7), (4, 11)] {
+            context.update(XY { x: 4, y }, &Value::Integer(v));
+        }
+        assert_eq!(context.time().unwrap().to_string(), "2025-08-07T11:00:00Z");
+        context.update(XY { x: 1, y: 15 }, &Value::String("Tokyo   ".to_string()));
+        assert_eq!(context.station().as_deref(), Some("Tokyo"));
+        context.update(XY { x: 1, y: 1 }, &Value::Integer(47));
+        context.update(XY { x: 1, y: 2 }, &Value::Integer(662));
+        assert_eq!(context.station().as_deref(), Some("47662"));
+    }
+}
@@ -0,0 +1,150 @@
+//! Reader for the WMO GTS TCP/IP socket protocol
+//!
+//! On a GTS socket, each message is preceded by an envelope of an 8-digit ASCII message
+//! length and a 2-character format identifier (`BI` binary, `AN` alphanumeric, `FX` facsimile).
+//! The message itself starts with SOH, the channel sequence number and the abbreviated
+//! heading, and ends with ETX.
+
+use std::io::{ErrorKind, Read};
+
+use crate::Error;
+
+/// A message received from a GTS socket stream.
+#[derive(Debug, Clone)]
+pub struct GtsMessage {
+    /// Format identifier (e.g. `BI` for binary)
+    pub format: [u8; 2],
+    /// The message following the envelope, from SOH to ETX
+    pub content: Vec<u8>,
+}
+
+impl GtsMessage {
+    /// Returns the abbreviated heading (e.g. `ISMD01 RJTD 010000`), if present.
+    pub fn heading(&self) -> Option<&str> {
+        let rest = self.content.strip_prefix(b"\x01\r\r\n")?;
+        // Skip the channel sequence number
+        let pos = find(rest, b"\r\r\n")?;
+        let rest = &rest[pos + 3..];
+        let end = find(rest, b"\r\r\n")?;
+        std::str::from_utf8(&rest[..end]).ok()
+    }
+
+    /// Returns the BUFR message contained in the message, if any.
+    pub fn bufr(&self) -> Option<&[u8]> {
+        let start = find(&self.content, b"BUFR")?;
+        let data = &self.content[start..];
+        if data.len() < 8 {
+            return None;
+        }
+        let total_length = u32::from_be_bytes([0, data[4], data[5], data[6]]) as usize;
+        let message = data.get(..total_length)?;
+        message.ends_with(b"7777").then_some(message)
+    }
+}
+
+/// Reads messages from a GTS socket stream.
+///
+/// It is also an iterator over the messages, ending at the end of the stream.
+pub struct GtsReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> GtsReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    /// Unwraps this `GtsReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the next message, or returns `None` at the end of the stream.
+    pub fn read_message(&mut self) -> Result<Option<GtsMessage>, Error> {
+        let mut envelope = [0u8; 10];
+        match self.reader.read(&mut envelope[..1]) {
+            Ok(0) => return Ok(None),
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::Interrupted => return self.read_message(),
+            Err(e) => return Err(e.into()),
+        }
+        self.reader.read_exact(&mut envelope[1..])?;
+
+        let Some(length) = std::str::from_utf8(&envelope[..8])
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+        else {
+            return Err(Error::Invalid(format!(
+                "Invalid GTS message length: {:?}",
+                String::from_utf8_lossy(&envelope[..8])
+            )));
+        };
+        let mut content = vec![0u8; length];
+        self.reader.read_exact(&mut content)?;
+        Ok(Some(GtsMessage {
+            format: [envelope[8], envelope[9]],
+            content,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for GtsReader<R> {
+    type Item = Result<GtsMessage, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_message().transpose()
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(format: &[u8; 2], content: &[u8]) -> Vec<u8> {
+        let mut buf = format!("{:08}", content.len()).into_bytes();
+        buf.extend_from_slice(format);
+        buf.extend_from_slice(content);
+        buf
+    }
+
+    #[test]
+    fn test_read_messages() {
+        let bufr = b"BUFR\x00\x00\x0c\x047777";
+        let mut content = b"\x01\r\r\n123\r\r\nISMD01 RJTD 010000\r\r\n".to_vec();
+        content.extend_from_slice(bufr);
+        content.extend_from_slice(b"\r\r\n\x03");
+
+        let mut stream = frame(b"BI", &content);
+        stream.extend(frame(
+            b"AN",
+            b"\x01\r\r\n124\r\r\nSMJP01 RJTD 010000\r\r\n\x03",
+        ));
+
+        let messages: Vec<_> = GtsReader::new(stream.as_slice())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(&messages[0].format, b"BI");
+        assert_eq!(messages[0].heading(), Some("ISMD01 RJTD 010000"));
+        assert_eq!(messages[0].bufr(), Some(&bufr[..]));
+        assert_eq!(&messages[1].format, b"AN");
+        assert_eq!(messages[1].heading(), Some("SMJP01 RJTD 010000"));
+        assert_eq!(messages[1].bufr(), None);
+    }
+
+    #[test]
+    fn test_truncated_stream() {
+        let stream = frame(b"BI", b"\x01\r\r\n123\r\r\n");
+        let mut reader = GtsReader::new(&stream[..stream.len() - 1]);
+        assert!(matches!(reader.read_message(), Err(Error::Io(_))));
+
+        let mut reader = GtsReader::new(&b"0000ABCDBI"[..]);
+        assert!(matches!(reader.read_message(), Err(Error::Invalid(_))));
+    }
+}
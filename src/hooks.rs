@@ -0,0 +1,266 @@
+//! Hooks transforming values as they are decoded
+//!
+//! Hooks set with [`DataReaderBuilder::value_hooks`](crate::DataReaderBuilder::value_hooks)
+//! are applied to the values of the data events emitted by
+//! [`DataReader`](crate::DataReader), so every output built on the events (Arrow, JSON,
+//! SQL, ...) sees the same transformed values. Typical uses are unit conversions,
+//! mapping code figures to names and anonymizing call signs.
+//!
+//! Values used by the reader itself (e.g. replication factors, bit-maps and new
+//! reference values) are not transformed. When a hook changes the type of values,
+//! set the output type with [`ValueOverrides`](crate::overrides::ValueOverrides)
+//! for the Arrow output.
+//!
+//! [`ValueHooks::redact`] sets ready-made hooks redacting the identifiers of aircraft
+//! and ships, as often required by data-sharing agreements.
+
+use crate::{
+    DataEvent, Value, XY,
+    manifest::{Sha256, hex},
+    overrides::ValueTransform,
+};
+
+/// Elements identifying aircraft and ships: aircraft flight numbers (0 01 006),
+/// aircraft registration (tail) numbers (0 01 008) and ship call signs (0 01 011).
+pub const SENSITIVE_IDENTIFIERS: [XY; 3] =
+    [XY { x: 1, y: 6 }, XY { x: 1, y: 8 }, XY { x: 1, y: 11 }];
+
+/// How [`ValueHooks::redact`] redacts identifiers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redaction {
+    /// Replace identifiers with the first 16 hexadecimal digits of the SHA-256 digest
+    /// of the salt and the identifier (without trailing spaces), so that the same
+    /// identifier can still be followed across messages
+    Hash { salt: String },
+    /// Replace identifiers with missing values
+    Drop,
+}
+
+impl std::str::FromStr for Redaction {
+    type Err = crate::Error;
+
+    /// Parses `drop` or `hash` (without salt).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(Redaction::Drop),
+            "hash" => Ok(Redaction::Hash {
+                salt: String::new(),
+            }),
+            _ => Err(crate::Error::Invalid(format!("Unknown redaction: {s:?}"))),
+        }
+    }
+}
+
+impl Redaction {
+    /// Redacts a value (missing values are kept).
+    pub fn apply(&self, value: Value) -> Value {
+        match (self, value) {
+            (_, Value::Missing) | (Redaction::Drop, _) => Value::Missing,
+            (Redaction::Hash { salt }, value) => {
+                let identifier = match value {
+                    Value::String(s) => s.trim_end().to_string(),
+                    v => format!("{v:?}"),
+                };
+                let mut hasher = Sha256::new();
+                hasher.update(salt.as_bytes());
+                hasher.update(identifier.as_bytes());
+                Value::String(hex(&hasher.finalize()[..8]))
+            }
+        }
+    }
+}
+
+/// Hooks keyed by element descriptor or by class (XX of 0 XX YYY).
+///
+/// A hook of an element takes precedence over the hook of its class.
+#[derive(Clone, Default)]
+pub struct ValueHooks {
+    elements: hashbrown::HashMap<XY, ValueTransform>,
+    classes: hashbrown::HashMap<u8, ValueTransform>,
+}
+
+impl std::fmt::Debug for ValueHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut elements: Vec<_> = self.elements.keys().collect();
+        elements.sort_unstable();
+        let mut classes: Vec<_> = self.classes.keys().collect();
+        classes.sort_unstable();
+        f.debug_struct("ValueHooks")
+            .field("elements", &elements)
+            .field("classes", &classes)
+            .finish()
+    }
+}
+
+impl ValueHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the hook of an element, which is called with every value including missing ones.
+    pub fn insert(&mut self, xy: XY, f: impl Fn(Value) -> Value + Send + Sync + 'static) {
+        self.elements.insert(xy, std::sync::Arc::new(f));
+    }
+
+    /// Sets the hook of all elements of a class without their own hook.
+    pub fn insert_class(&mut self, x: u8, f: impl Fn(Value) -> Value + Send + Sync + 'static) {
+        self.classes.insert(x, std::sync::Arc::new(f));
+    }
+
+    /// Returns the hook applied to an element, if any.
+    pub fn get(&self, xy: &XY) -> Option<&ValueTransform> {
+        self.elements.get(xy).or_else(|| self.classes.get(&xy.x))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty() && self.classes.is_empty()
+    }
+
+    /// Sets hooks redacting the [`SENSITIVE_IDENTIFIERS`], replacing their own hooks.
+    pub fn redact(&mut self, redaction: Redaction) {
+        for xy in SENSITIVE_IDENTIFIERS {
+            let redaction = redaction.clone();
+            self.insert(xy, move |value| redaction.apply(value));
+        }
+    }
+
+    /// Applies the hook of an element to a value (unchanged if none).
+    pub fn apply(&self, xy: &XY, value: Value) -> Value {
+        match self.get(xy) {
+            Some(f) => f(value),
+            None => value,
+        }
+    }
+
+    /// Applies the hooks to the values of a data event.
+    pub(crate) fn apply_to_event(&self, event: DataEvent) -> DataEvent {
+        if self.is_empty() {
+            return event;
+        }
+        match event {
+            DataEvent::Data {
+                idx,
+                xy,
+                entry,
+                value,
+            } => DataEvent::Data {
+                idx,
+                xy,
+                entry,
+                value: self.apply(&xy, value),
+            },
+            DataEvent::CompressedData {
+                idx,
+                xy,
+                entry,
+                values,
+            } => match self.get(&xy) {
+                Some(f) => DataEvent::CompressedData {
+                    idx,
+                    xy,
+                    entry,
+                    values: values.into_iter().map(|v| f(v)).collect(),
+                },
+                None => DataEvent::CompressedData {
+                    idx,
+                    xy,
+                    entry,
+                    values,
+                },
+            },
+            event => event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::tests::make_dds;
+    use crate::{DataReader, DataSpec, DataWriter, Descriptor, Tables};
+
+    #[test]
+    fn test_value_hooks() {
+        let tables = Tables::default();
+        let descriptors = vec![
+            Descriptor { f: 0, x: 1, y: 1 },
+            Descriptor { f: 0, x: 1, y: 2 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+        ];
+        let dds = make_dds(descriptors, 1);
+        let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut writer = DataWriter::new(&data_spec);
+        writer
+            .write_subset(&[
+                Value::Integer(47),
+                Value::Integer(662),
+                Value::Decimal(27315, -2),
+            ])
+            .unwrap();
+        let section = writer.finish().unwrap();
+
+        // Kelvin to degree Celsius, and anonymized station identifiers
+        let mut hooks = ValueHooks::default();
+        hooks.insert(XY { x: 12, y: 101 }, |v| match v {
+            Value::Decimal(v, -2) => Value::Decimal(v - 27315, -2),
+            v => v,
+        });
+        hooks.insert_class(1, |_| Value::Missing);
+        hooks.insert(XY { x: 1, y: 1 }, |v| v);
+
+        let mut reader = DataReader::builder(&data_spec)
+            .value_hooks(hooks)
+            .build(section.as_slice())
+            .unwrap();
+        let mut values = vec![];
+        loop {
+            match reader.read_event().unwrap() {
+                DataEvent::Data { value, .. } => values.push(format!("{value:?}")),
+                DataEvent::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(values, ["47", "Missing", "0.00"]);
+    }
+
+    #[test]
+    fn test_redact() {
+        let mut hooks = ValueHooks::new();
+        hooks.redact(Redaction::Hash {
+            salt: "salt".to_string(),
+        });
+        let call_sign = XY { x: 1, y: 11 };
+        let hashed = hooks.apply(&call_sign, Value::String("JGQH    ".to_string()));
+        let Value::String(digest) = &hashed else {
+            panic!("Hashed identifiers should be strings");
+        };
+        assert_eq!(digest.len(), 16);
+        // Trailing spaces do not matter, but the salt does
+        assert_eq!(
+            hooks.apply(&call_sign, Value::String("JGQH".to_string())),
+            hashed
+        );
+        assert_eq!(hooks.apply(&call_sign, Value::Missing), Value::Missing);
+        let mut unsalted = ValueHooks::new();
+        unsalted.redact("hash".parse().unwrap());
+        assert_ne!(
+            unsalted.apply(&call_sign, Value::String("JGQH".to_string())),
+            hashed
+        );
+
+        hooks.redact(Redaction::Drop);
+        assert_eq!(
+            hooks.apply(&XY { x: 1, y: 8 }, Value::String("JA8089".to_string())),
+            Value::Missing
+        );
+        // Other elements are unchanged
+        assert_eq!(
+            hooks.apply(&XY { x: 1, y: 1 }, Value::Integer(47)),
+            Value::Integer(47)
+        );
+    }
+}
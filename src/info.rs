@@ -0,0 +1,118 @@
+//! Version and table information of the build
+//!
+//! Outputs record this information so that they can be reproduced against
+//! the exact table snapshot used for decoding.
+
+use std::sync::OnceLock;
+
+use crate::manifest::{Sha256, hex};
+use crate::tables::{table_b::TABLE_B, table_c::TABLE_C, table_d::TABLE_D};
+
+/// Version and table information of this build of tinybufr.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BuildInfo {
+    /// Version of the crate
+    pub version: &'static str,
+    /// Embedded WMO master tables
+    pub master_tables: TablesInfo,
+    /// Local tables enabled by features (e.g. `jma`)
+    pub local_tables: Vec<&'static str>,
+}
+
+/// Snapshot of the embedded WMO master tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TablesInfo {
+    pub table_b_entries: usize,
+    pub table_c_entries: usize,
+    pub table_d_entries: usize,
+    /// SHA-256 digest of the table entries in lowercase hex
+    ///
+    /// Two builds decode identically if their digests match.
+    pub sha256: String,
+}
+
+/// Returns the version and table information of this build.
+pub fn build_info() -> BuildInfo {
+    static MASTER_TABLES: OnceLock<TablesInfo> = OnceLock::new();
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        master_tables: MASTER_TABLES.get_or_init(master_tables_info).clone(),
+        local_tables: vec![
+            #[cfg(feature = "jma")]
+            "jma",
+        ],
+    }
+}
+
+impl BuildInfo {
+    /// Flattens the information into `tinybufr.*` key-value pairs (e.g. for Parquet metadata).
+    pub fn key_values(&self) -> Vec<(String, String)> {
+        vec![
+            ("tinybufr.version".to_string(), self.version.to_string()),
+            (
+                "tinybufr.master_tables".to_string(),
+                format!(
+                    "B:{} C:{} D:{} sha256:{}",
+                    self.master_tables.table_b_entries,
+                    self.master_tables.table_c_entries,
+                    self.master_tables.table_d_entries,
+                    self.master_tables.sha256
+                ),
+            ),
+            (
+                "tinybufr.local_tables".to_string(),
+                self.local_tables.join(","),
+            ),
+        ]
+    }
+}
+
+fn master_tables_info() -> TablesInfo {
+    let mut hasher = Sha256::new();
+    for b in &TABLE_B {
+        hasher.update(
+            format!(
+                "B {} {} {}|{}|{}|{}|{}|{}\n",
+                b.xy.x,
+                b.xy.y,
+                b.class_name,
+                b.element_name,
+                b.unit,
+                b.scale,
+                b.reference_value,
+                b.bits
+            )
+            .as_bytes(),
+        );
+    }
+    for c in &TABLE_C {
+        hasher.update(format!("C {:?}|{}\n", c.xy, c.operator_name).as_bytes());
+    }
+    for d in &TABLE_D {
+        hasher.update(format!("D {} {}|{:?}\n", d.xy.x, d.xy.y, d.elements).as_bytes());
+    }
+    TablesInfo {
+        table_b_entries: TABLE_B.len(),
+        table_c_entries: TABLE_C.len(),
+        table_d_entries: TABLE_D.len(),
+        sha256: hex(&hasher.finalize()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.master_tables.table_b_entries, TABLE_B.len());
+        assert_eq!(info.master_tables.sha256.len(), 64);
+        assert_eq!(info, build_info());
+        assert_eq!(info.local_tables.contains(&"jma"), cfg!(feature = "jma"));
+        assert_eq!(info.key_values()[0].1, info.version);
+    }
+}
@@ -0,0 +1,519 @@
+//! Opening BUFR inputs of various formats
+//!
+//! [`open_any`] detects the format of a file and returns the BUFR messages in it. Supported are:
+//!
+//! - bare BUFR messages, possibly concatenated (multi-message files)
+//! - messages preceded by local header lines or GTS bulletin headings
+//! - WMO GTS TCP/IP socket streams (see [`crate::gts`])
+//! - tar archives of any of the above
+//! - gzip-compressed files of any of the above (requires the `gzip` feature)
+//! - bzip2-compressed files of any of the above (requires the `bzip2` feature)
+//!
+//! With a [`RecoveryPolicy`] other than fail-fast, broken parts of an input (e.g. a
+//! corrupted archive entry) are skipped and reported by [`MessageIterator::warnings`].
+//!
+//! [`ChunkedReader`] reads a message split over several buffers or files (e.g. chunked
+//! downloads) without joining them in memory.
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::{
+    Error, gts::GtsReader, recovery::RecoveryPolicy, sections::unterminated_message_length,
+};
+
+/// Iterator over the raw BUFR messages (from `BUFR` to `7777`) found in an input.
+#[derive(Debug, Default)]
+pub struct MessageIterator {
+    messages: VecDeque<Vec<u8>>,
+    skipped_bytes: usize,
+    recovery: RecoveryPolicy,
+    warnings: Vec<String>,
+}
+
+impl MessageIterator {
+    /// Extracts the messages from the contents of an input.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        Self::from_bytes_with_recovery(data, RecoveryPolicy::FailFast)
+    }
+
+    /// Extracts the messages from the contents of an input, recovering from errors as
+    /// set by the policy.
+    ///
+    /// Broken archive entries and GTS envelopes are skipped. Unless failing fast,
+    /// messages whose end section (`7777`) is missing or truncated after a complete data
+    /// section are kept, to be decoded leniently; when failing fast, they are an error.
+    /// With [`RecoveryPolicy::BestEffort`], truncated messages (running past the end of
+    /// the input or into the next message) are kept too, so that their first subsets can
+    /// still be decoded.
+    pub fn from_bytes_with_recovery(
+        data: Vec<u8>,
+        recovery: RecoveryPolicy,
+    ) -> Result<Self, Error> {
+        let mut iter = Self {
+            recovery,
+            ..Default::default()
+        };
+        iter.extract(data)?;
+        if iter.messages.is_empty() {
+            return Err(Error::Invalid("No BUFR message found".to_string()));
+        }
+        Ok(iter)
+    }
+
+    /// Number of bytes outside of BUFR messages (e.g. headings), excluding container formats.
+    pub fn skipped_bytes(&self) -> usize {
+        self.skipped_bytes
+    }
+
+    /// Problems recovered from while extracting the messages.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Returns the error, or records it as a warning if the policy recovers from errors.
+    fn recover(&mut self, error: Error) -> Result<(), Error> {
+        match self.recovery.recovers() {
+            true => {
+                self.warnings.push(error.to_string());
+                Ok(())
+            }
+            false => Err(error),
+        }
+    }
+
+    fn extract(&mut self, data: Vec<u8>) -> Result<(), Error> {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            return match gunzip(&data) {
+                Ok(data) => self.extract(data),
+                Err(e) => self.recover(e),
+            };
+        }
+        if data.starts_with(b"BZh") {
+            return match bunzip2(&data) {
+                Ok(data) => self.extract(data),
+                Err(e) => self.recover(e),
+            };
+        }
+        if is_tar(&data) {
+            match tar_entries(&data) {
+                Ok(entries) => {
+                    for entry in entries {
+                        self.extract(entry.to_vec())?;
+                    }
+                }
+                // Messages stored in the archive can still be found in the raw bytes
+                Err(e) => {
+                    self.recover(e)?;
+                    self.scan(&data)?;
+                }
+            }
+            return Ok(());
+        }
+        if is_gts_stream(&data) {
+            for message in GtsReader::new(data.as_slice()) {
+                match message {
+                    Ok(message) => self.scan(&message.content)?,
+                    // The rest of the stream cannot be framed
+                    Err(e) => return self.recover(e),
+                }
+            }
+            return Ok(());
+        }
+        self.scan(&data)
+    }
+
+    /// Collects the BUFR messages in a byte sequence, skipping anything between them.
+    fn scan(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        while let Some(start) = find_bufr(data) {
+            self.skipped_bytes += start;
+            data = &data[start..];
+            match message_length(data) {
+                Some(len) => {
+                    self.messages.push_back(data[..len].to_vec());
+                    data = &data[len..];
+                }
+                None if let Some(len) = unterminated_message_length(until_next_message(data)) => {
+                    if !self.recovery.recovers() {
+                        return Err(Error::Invalid(format!(
+                            "Message of {len} bytes without its end section (7777)"
+                        )));
+                    }
+                    self.warnings.push(format!(
+                        "Kept a message of {len} bytes without its end section (7777)"
+                    ));
+                    self.messages.push_back(data[..len].to_vec());
+                    data = &data[len..];
+                }
+                None if self.recovery == RecoveryPolicy::BestEffort
+                    && let Some(len) = truncated_message_length(data) =>
+                {
+                    self.warnings
+                        .push(format!("Kept a truncated message of {len} bytes"));
+                    self.messages.push_back(data[..len].to_vec());
+                    data = &data[len..];
+                }
+                None => {
+                    // Not a message (e.g. "BUFR" in a heading)
+                    self.skipped_bytes += 4;
+                    data = &data[4..];
+                }
+            }
+        }
+        self.skipped_bytes += data.len();
+        Ok(())
+    }
+}
+
+impl Iterator for MessageIterator {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.messages.pop_front()
+    }
+}
+
+/// Opens a file of any supported format and returns the BUFR messages in it.
+pub fn open_any<P: AsRef<Path>>(path: P) -> Result<MessageIterator, Error> {
+    MessageIterator::from_bytes(std::fs::read(path)?)
+}
+
+/// Opens a file of any supported format and returns the BUFR messages in it,
+/// recovering from errors as set by the policy (see [`MessageIterator::from_bytes_with_recovery`]).
+pub fn open_any_with_recovery<P: AsRef<Path>>(
+    path: P,
+    recovery: RecoveryPolicy,
+) -> Result<MessageIterator, Error> {
+    MessageIterator::from_bytes_with_recovery(std::fs::read(path)?, recovery)
+}
+
+/// Reader over a sequence of chunks read one after another.
+///
+/// Unlike [`Read::chain`], any number of chunks can be chained. Values spanning
+/// chunk boundaries are read as if the chunks were contiguous, so a
+/// [`crate::DataReader`] can decode a message split anywhere. Running out of
+/// chunks in the middle of a message results in an `UnexpectedEof` I/O error.
+pub struct ChunkedReader<I: Iterator> {
+    chunks: I,
+    current: Option<I::Item>,
+}
+
+impl<I> ChunkedReader<I>
+where
+    I: Iterator,
+    I::Item: Read,
+{
+    /// Creates a reader over the chunks (e.g. byte slices or files).
+    pub fn new<C: IntoIterator<IntoIter = I>>(chunks: C) -> Self {
+        let mut chunks = chunks.into_iter();
+        let current = chunks.next();
+        Self { chunks, current }
+    }
+}
+
+impl<I> Read for ChunkedReader<I>
+where
+    I: Iterator,
+    I::Item: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while let Some(chunk) = &mut self.current {
+            match chunk.read(buf)? {
+                0 => self.current = self.chunks.next(),
+                n => return Ok(n),
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// Opens files to be read one after another as a single input.
+pub fn open_parts<P: AsRef<Path>>(
+    paths: &[P],
+) -> Result<ChunkedReader<std::vec::IntoIter<std::fs::File>>, Error> {
+    let files = paths
+        .iter()
+        .map(std::fs::File::open)
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(ChunkedReader::new(files))
+}
+
+fn find_bufr(data: &[u8]) -> Option<usize> {
+    data.windows(4).position(|w| w == b"BUFR")
+}
+
+/// Returns the length of the BUFR message at the start of `data` if it is complete.
+fn message_length(data: &[u8]) -> Option<usize> {
+    if data.len() < 8 {
+        return None;
+    }
+    let len = match data[7] {
+        // Edition 1 has no total length; not supported
+        2.. => u32::from_be_bytes([0, data[4], data[5], data[6]]) as usize,
+        _ => return None,
+    };
+    (len >= 12 && data.get(..len)?.ends_with(b"7777")).then_some(len)
+}
+
+/// Returns the length of the incomplete BUFR message at the start of `data`, up to the
+/// end of the data or the next message.
+fn truncated_message_length(data: &[u8]) -> Option<usize> {
+    if data.len() < 8 || !(2..=4).contains(&data[7]) {
+        return None;
+    }
+    let declared = u32::from_be_bytes([0, data[4], data[5], data[6]]) as usize;
+    if declared < 12 {
+        return None;
+    }
+    Some(declared.min(until_next_message(data).len()))
+}
+
+/// Returns the bytes from the message at the start of `data` up to the next one.
+fn until_next_message(data: &[u8]) -> &[u8] {
+    let next = find_bufr(&data[4..]).map_or(data.len(), |pos| pos + 4);
+    &data[..next]
+}
+
+/// Whether the data starts with a GTS socket envelope (8 digits and a format identifier).
+fn is_gts_stream(data: &[u8]) -> bool {
+    data.len() >= 10
+        && data[..8].iter().all(u8::is_ascii_digit)
+        && matches!(&data[8..10], b"BI" | b"AN" | b"FX")
+}
+
+fn is_tar(data: &[u8]) -> bool {
+    data.len() >= 512 && &data[257..262] == b"ustar"
+}
+
+/// Returns the contents of the regular files in a tar archive.
+fn tar_entries(mut data: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    let mut entries = vec![];
+    while data.len() >= 512 && data[..512].iter().any(|&b| b != 0) {
+        let header = &data[..512];
+        let size_field = std::str::from_utf8(&header[124..136])
+            .map_err(|_| Error::Invalid("Invalid tar header".to_string()))?;
+        let size_field = size_field.trim_matches(|c: char| c == '\0' || c == ' ');
+        let size = usize::from_str_radix(size_field, 8)
+            .map_err(|_| Error::Invalid(format!("Invalid tar entry size: {size_field:?}")))?;
+        let Some(contents) = data.get(512..512 + size) else {
+            return Err(Error::Invalid("Truncated tar archive".to_string()));
+        };
+        // Regular files only
+        if matches!(header[156], b'0' | 0) {
+            entries.push(contents);
+        }
+        data = data
+            .get(512 + size.div_ceil(512) * 512..)
+            .unwrap_or_default();
+    }
+    Ok(entries)
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, Error> {
+    use std::io::Read;
+    let mut out = vec![];
+    flate2::read::MultiGzDecoder::new(data).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip(_data: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(Error::NotSupported(
+        "gzip-compressed input requires the \"gzip\" feature".to_string(),
+    ))
+}
+
+#[cfg(feature = "bzip2")]
+fn bunzip2(data: &[u8]) -> Result<Vec<u8>, Error> {
+    use std::io::Read;
+    let mut out = vec![];
+    bzip2::read::MultiBzDecoder::new(data).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+#[cfg(not(feature = "bzip2"))]
+fn bunzip2(_data: &[u8]) -> Result<Vec<u8>, Error> {
+    Err(Error::NotSupported(
+        "bzip2-compressed input requires the \"bzip2\" feature".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &[u8] = b"BUFR\x00\x00\x0c\x047777";
+
+    fn tar(files: &[&[u8]]) -> Vec<u8> {
+        let mut data = vec![];
+        for (i, contents) in files.iter().enumerate() {
+            let mut header = [0u8; 512];
+            header[..6].copy_from_slice(format!("f{i}.bin").as_bytes());
+            header[124..135].copy_from_slice(format!("{:011o}", contents.len()).as_bytes());
+            header[156] = b'0';
+            header[257..262].copy_from_slice(b"ustar");
+            data.extend_from_slice(&header);
+            data.extend_from_slice(contents);
+            data.resize(data.len().div_ceil(512) * 512, 0);
+        }
+        data.extend_from_slice(&[0; 1024]);
+        data
+    }
+
+    #[test]
+    fn test_multiple_messages() {
+        let mut data = b"IUKS01 RJTD 010000\r\r\n".to_vec();
+        data.extend_from_slice(MESSAGE);
+        data.extend_from_slice(b"\r\r\nBUFR?");
+        data.extend_from_slice(MESSAGE);
+        let iter = MessageIterator::from_bytes(data).unwrap();
+        assert_eq!(iter.skipped_bytes(), 21 + 8);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![MESSAGE, MESSAGE]);
+
+        assert!(matches!(
+            MessageIterator::from_bytes(b"no messages".to_vec()),
+            Err(Error::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_recovery() {
+        // A truncated message followed by a complete one
+        let mut data = MESSAGE[..10].to_vec();
+        data.extend_from_slice(MESSAGE);
+        let iter = MessageIterator::from_bytes(data.clone()).unwrap();
+        assert_eq!(iter.collect::<Vec<_>>(), vec![MESSAGE]);
+        let iter =
+            MessageIterator::from_bytes_with_recovery(data, RecoveryPolicy::BestEffort).unwrap();
+        assert_eq!(iter.warnings().len(), 1);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![&MESSAGE[..10], MESSAGE]);
+
+        // A broken archive entry
+        let data = tar(&[b"\x1f\x8b\x08broken", MESSAGE]);
+        assert!(MessageIterator::from_bytes(data.clone()).is_err());
+        let iter =
+            MessageIterator::from_bytes_with_recovery(data, RecoveryPolicy::SkipMessage).unwrap();
+        assert_eq!(iter.warnings().len(), 1);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![MESSAGE]);
+
+        // A message without its end section, followed by a complete one
+        let message = std::fs::read(
+            "./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin",
+        )
+        .unwrap();
+        let unterminated = &message[..message.len() - 4];
+        let mut data = unterminated.to_vec();
+        data.extend_from_slice(MESSAGE);
+        assert!(MessageIterator::from_bytes(data.clone()).is_err());
+        let iter =
+            MessageIterator::from_bytes_with_recovery(data, RecoveryPolicy::SkipMessage).unwrap();
+        assert_eq!(
+            iter.warnings(),
+            [format!(
+                "Kept a message of {} bytes without its end section (7777)",
+                unterminated.len()
+            )]
+        );
+        assert_eq!(iter.collect::<Vec<_>>(), vec![unterminated, MESSAGE]);
+    }
+
+    #[test]
+    fn test_gts_stream() {
+        let mut content = b"\x01\r\r\n123\r\r\nISMD01 RJTD 010000\r\r\n".to_vec();
+        content.extend_from_slice(MESSAGE);
+        content.extend_from_slice(b"\r\r\n\x03");
+        let mut data = format!("{:08}BI", content.len()).into_bytes();
+        data.extend_from_slice(&content);
+        let messages: Vec<_> = MessageIterator::from_bytes(data).unwrap().collect();
+        assert_eq!(messages, vec![MESSAGE]);
+    }
+
+    #[test]
+    fn test_tar() {
+        let data = tar(&[MESSAGE, b"README", MESSAGE]);
+        let messages: Vec<_> = MessageIterator::from_bytes(data).unwrap().collect();
+        assert_eq!(messages, vec![MESSAGE, MESSAGE]);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip() {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&tar(&[MESSAGE])).unwrap();
+        let data = encoder.finish().unwrap();
+        let messages: Vec<_> = MessageIterator::from_bytes(data).unwrap().collect();
+        assert_eq!(messages, vec![MESSAGE]);
+    }
+
+    #[cfg(feature = "bzip2")]
+    #[test]
+    fn test_bzip2() {
+        use std::io::Write;
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(&tar(&[MESSAGE])).unwrap();
+        let data = encoder.finish().unwrap();
+        let messages: Vec<_> = MessageIterator::from_bytes(data).unwrap().collect();
+        assert_eq!(messages, vec![MESSAGE]);
+    }
+
+    fn read_events<R: Read>(mut reader: R) -> Result<Vec<String>, Error> {
+        let tables = crate::Tables::default();
+        let header = crate::HeaderSections::read(&mut reader)?;
+        let data_spec =
+            crate::DataSpec::from_data_description(&header.data_description_section, &tables)?;
+        let mut data_reader = crate::DataReader::new(reader, &data_spec)?;
+        let mut events = vec![];
+        loop {
+            match data_reader.read_event()? {
+                crate::DataEvent::Eof => return Ok(events),
+                ev => events.push(format!("{ev:?}")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunked_reader() {
+        let message = std::fs::read(
+            "./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin",
+        )
+        .unwrap();
+        let expected = read_events(message.as_slice()).unwrap();
+
+        // Chunk boundaries fall in the middle of sections and elements
+        for chunk_size in [1, 3, 7, 1000] {
+            let chunks = message.chunks(chunk_size);
+            assert_eq!(read_events(ChunkedReader::new(chunks)).unwrap(), expected);
+        }
+
+        // Empty chunks are skipped
+        let (head, tail) = message.split_at(100);
+        let chunks = [head, &[], &[], tail];
+        assert_eq!(read_events(ChunkedReader::new(chunks)).unwrap(), expected);
+
+        // Parts in separate files
+        let dir = std::env::temp_dir();
+        let paths = [
+            dir.join("tinybufr_test_chunked_reader.1"),
+            dir.join("tinybufr_test_chunked_reader.2"),
+        ];
+        std::fs::write(&paths[0], head).unwrap();
+        std::fs::write(&paths[1], tail).unwrap();
+        assert_eq!(read_events(open_parts(&paths).unwrap()).unwrap(), expected);
+        for path in &paths {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        // Missing chunks
+        let chunks = message.chunks(1000).take(2);
+        assert!(matches!(
+            read_events(ChunkedReader::new(chunks)),
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+}
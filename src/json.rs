@@ -0,0 +1,375 @@
+//! Export of decoded messages as JSON
+//!
+//! [`JsonWriter`] writes a JSON array with an object per message: its `header`
+//! sections, and the decoded data as `subsets` (an object per subset) or, for
+//! compressed data, as `compressed` (an object whose values are arrays with a value
+//! per subset).
+//!
+//! The data objects keep the order of the elements. Keys are labelled after the
+//! elements (name and unit, e.g. `Temperature/air temperature [K]`, numbered from
+//! ` (2)` when repeated), the titles of sequences, and `replication:N` for the Nth
+//! replication, which holds an array of objects, one per item.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use serde::{Serialize, Serializer, ser::SerializeMap};
+
+use crate::{
+    DataEvent, DataReader, Error, HeaderSections, Tables, Value,
+    overrides::{OutputType, ValueOverrides},
+    recovery::RecoveryPolicy,
+    sink::{MessageInput, OutputSink},
+};
+
+/// Writes decoded messages as a JSON array.
+pub struct JsonWriter<W: Write> {
+    writer: W,
+    overrides: ValueOverrides,
+    pretty: bool,
+    messages: usize,
+}
+
+/// A message as written by [`JsonWriter`].
+#[derive(Serialize)]
+struct Message<'a> {
+    header: &'a HeaderSections,
+    subsets: Option<Vec<Sequence>>,
+    compressed: Option<Sequence>,
+}
+
+/// The elements of a subset, sequence or replication item, in order.
+#[derive(Debug, Default)]
+struct Sequence(Vec<(String, Node)>);
+
+#[derive(Debug)]
+enum Node {
+    Missing,
+    Float(f64),
+    Integer(i64),
+    String(String),
+    Replication(Vec<Sequence>),
+    Sequence(Sequence),
+    CompressedData(Vec<Node>),
+}
+
+impl Serialize for Sequence {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (label, node) in &self.0 {
+            map.serialize_entry(label, node)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for Node {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Node::Missing => serializer.serialize_unit(),
+            Node::Float(v) => serializer.serialize_f64(*v),
+            Node::Integer(v) => serializer.serialize_i64(*v),
+            Node::String(s) => serializer.serialize_str(s),
+            Node::Replication(items) => items.serialize(serializer),
+            Node::Sequence(sequence) => sequence.serialize(serializer),
+            Node::CompressedData(values) => values.serialize(serializer),
+        }
+    }
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            overrides: ValueOverrides::new(),
+            pretty: false,
+            messages: 0,
+        }
+    }
+
+    /// Sets per-descriptor overrides of output types and values.
+    pub fn overrides(mut self, overrides: ValueOverrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Indents the output.
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Writes a message and returns the number of subsets written.
+    pub fn write_message<R: Read>(
+        &mut self,
+        header: &HeaderSections,
+        data_reader: &mut DataReader<'_, R>,
+        tables: &Tables,
+    ) -> Result<usize, Error> {
+        let mut message = Message {
+            header,
+            subsets: None,
+            compressed: None,
+        };
+        self.read_data(data_reader, tables, &mut message)?;
+        self.write_json(&message)
+    }
+
+    /// Closes the array and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        match self.messages {
+            0 => writeln!(self.writer, "[]")?,
+            _ => writeln!(self.writer, "\n]")?,
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Writes a message and returns the number of subsets in it.
+    fn write_json(&mut self, message: &Message) -> Result<usize, Error> {
+        let json = match self.pretty {
+            // Indented as an item of the array (JSON strings have no raw line breaks)
+            true => serde_json::to_string_pretty(message).map(|json| json.replace('\n', "\n  ")),
+            false => serde_json::to_string(message),
+        };
+        let json = json.map_err(|e| Error::Fatal(format!("Failed to serialize to JSON: {e}")))?;
+        let separator = match self.messages {
+            0 => "[\n",
+            _ => ",\n",
+        };
+        write!(self.writer, "{separator}  {json}")?;
+        self.messages += 1;
+        Ok(match &message.compressed {
+            Some(_) => message.header.number_of_subsets() as usize,
+            None => message.subsets.as_ref().map_or(0, Vec::len),
+        })
+    }
+
+    /// Reads the subsets (or the compressed data) into the message, keeping what was
+    /// read before an error.
+    fn read_data<R: Read>(
+        &self,
+        data_reader: &mut DataReader<'_, R>,
+        tables: &Tables,
+        message: &mut Message,
+    ) -> Result<(), Error> {
+        loop {
+            match data_reader.read_event()? {
+                DataEvent::SubsetStart(_) => {
+                    let subsets = message.subsets.get_or_insert_default();
+                    subsets.push(Sequence::default());
+                    let subset = subsets.last_mut().expect("a subset was pushed");
+                    self.read_sequence(data_reader, tables, subset)?;
+                }
+                DataEvent::CompressedStart => {
+                    let compressed = message.compressed.insert(Sequence::default());
+                    self.read_sequence(data_reader, tables, compressed)?;
+                }
+                DataEvent::Eof => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    fn read_sequence<R: Read>(
+        &self,
+        data_reader: &mut DataReader<'_, R>,
+        tables: &Tables,
+        sequence: &mut Sequence,
+    ) -> Result<(), Error> {
+        let mut element_counts: HashMap<&str, usize> = HashMap::new();
+        let mut sequence_counts: HashMap<&str, usize> = HashMap::new();
+        let mut replications = 0;
+        loop {
+            match data_reader.read_event()? {
+                DataEvent::SubsetEnd
+                | DataEvent::SequenceEnd
+                | DataEvent::ReplicationItemEnd
+                | DataEvent::Eof => return Ok(()),
+                DataEvent::Data {
+                    xy, value, entry, ..
+                } => {
+                    let count = element_counts.entry(entry.element_name).or_insert(0);
+                    *count += 1;
+                    let node = to_node(
+                        self.overrides.apply(&xy, value),
+                        self.overrides.output_type(&xy),
+                    );
+                    sequence.0.push((element_label(entry, *count), node));
+                }
+                DataEvent::CompressedData {
+                    xy, values, entry, ..
+                } => {
+                    let count = element_counts.entry(entry.element_name).or_insert(0);
+                    *count += 1;
+                    let nodes = values
+                        .into_iter()
+                        .map(|v| {
+                            to_node(
+                                self.overrides.apply(&xy, v),
+                                self.overrides.output_type(&xy),
+                            )
+                        })
+                        .collect();
+                    sequence
+                        .0
+                        .push((element_label(entry, *count), Node::CompressedData(nodes)));
+                }
+                DataEvent::SequenceStart { xy, .. } => {
+                    let Some(d) = tables.table_d.get(&xy) else {
+                        return Err(Error::Table(format!(
+                            "Table D entry not found for xy: {xy:?}"
+                        )));
+                    };
+                    let count = sequence_counts.entry(d.title).or_insert(0);
+                    *count += 1;
+                    let label = match *count {
+                        1 => d.title.to_string(),
+                        count => format!("{} ({count})", d.title),
+                    };
+                    // What was read before an error is kept
+                    let mut child = Sequence::default();
+                    let result = self.read_sequence(data_reader, tables, &mut child);
+                    sequence.0.push((label, Node::Sequence(child)));
+                    result?;
+                }
+                DataEvent::ReplicationStart { .. } => {
+                    replications += 1;
+                    let mut items = vec![];
+                    let result = self.read_replication(data_reader, tables, &mut items);
+                    sequence.0.push((
+                        format!("replication:{replications}"),
+                        Node::Replication(items),
+                    ));
+                    result?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_replication<R: Read>(
+        &self,
+        data_reader: &mut DataReader<'_, R>,
+        tables: &Tables,
+        items: &mut Vec<Sequence>,
+    ) -> Result<(), Error> {
+        loop {
+            match data_reader.read_event()? {
+                DataEvent::ReplicationEnd | DataEvent::Eof => return Ok(()),
+                DataEvent::ReplicationItemStart => {
+                    items.push(Sequence::default());
+                    let item = items.last_mut().expect("an item was pushed");
+                    self.read_sequence(data_reader, tables, item)?;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<W: Write> OutputSink for JsonWriter<W> {
+    type Output = W;
+
+    fn write_message(&mut self, input: &mut MessageInput<'_, '_>) -> Result<usize, Error> {
+        let mut message = Message {
+            header: input.header,
+            subsets: None,
+            compressed: None,
+        };
+        if let Err(error) = self.read_data(&mut input.data_reader, input.tables, &mut message) {
+            input.recover(error)?;
+            match input.recovery {
+                _ if !input.recovery.keeps_partial(input.data_spec.is_compressed) => {
+                    return Ok(0);
+                }
+                // The subset read when the error occurred is incomplete
+                RecoveryPolicy::SkipSubset => {
+                    message.subsets.as_mut().and_then(Vec::pop);
+                }
+                _ => {}
+            }
+        }
+        self.write_json(&message)
+    }
+
+    fn finish(self) -> Result<W, Error> {
+        JsonWriter::finish(self)
+    }
+}
+
+/// Labels an element by its name and unit (but `Numeric`), numbered when repeated.
+fn element_label(entry: &crate::TableBEntry, count: usize) -> String {
+    let label = match entry.unit {
+        "Numeric" => entry.element_name.to_string(),
+        unit => format!("{} [{unit}]", entry.element_name),
+    };
+    match count {
+        1 => label,
+        count => format!("{label} ({count})"),
+    }
+}
+
+fn to_node(value: Value, output_type: Option<OutputType>) -> Node {
+    match (value, output_type) {
+        (Value::Missing, _) => Node::Missing,
+        (Value::Decimal(v, s), Some(OutputType::Float)) => {
+            Node::Float(v as f64 * 10f64.powi(s as i32))
+        }
+        (Value::Decimal(v, s), _) if s >= 0 => {
+            Node::Integer((v as f64 * 10f64.powi(s as i32)) as i64)
+        }
+        (Value::Decimal(v, s), _) => Node::Float(v as f64 * 10f64.powi(s as i32)),
+        (Value::Decimal64(v, s), Some(OutputType::Float)) => {
+            Node::Float(v as f64 * 10f64.powi(s as i32))
+        }
+        (Value::Decimal64(v, s), _) => match 10i64.checked_pow(s as u32) {
+            Some(m) if s >= 0 => Node::Integer(v.saturating_mul(m)),
+            _ => Node::Float(v as f64 * 10f64.powi(s as i32)),
+        },
+        (Value::Integer(v), Some(OutputType::Float)) => Node::Float(v as f64),
+        (Value::Integer(v), _) => Node::Integer(v as i64),
+        (Value::String(s), _) => Node::String(s),
+        (Value::Bytes(b), _) => Node::String(String::from_utf8_lossy(&b).into_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::export;
+
+    #[test]
+    fn test_json_writer() {
+        let message = std::fs::read(
+            "./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin",
+        )
+        .unwrap();
+        let tables = Tables::default();
+        let (json, subsets) =
+            export(JsonWriter::new(vec![]), [&message, &message], &tables).unwrap();
+        assert_eq!(subsets, 64);
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        let messages = json.as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        let subsets = messages[1]["subsets"].as_array().unwrap();
+        assert_eq!(subsets.len(), 32);
+        assert_eq!(subsets[0]["WMO block number"], 47);
+        assert!(messages[1]["compressed"].is_null());
+        assert_eq!(
+            messages[1]["header"]["indicator_section"]["edition_number"],
+            4
+        );
+
+        // The elements keep their order
+        let json = export(JsonWriter::new(vec![]), [&message], &tables)
+            .unwrap()
+            .0;
+        let json = String::from_utf8(json).unwrap();
+        let station = json.find("\"WMO station number\"").unwrap();
+        assert!(json.find("\"WMO block number\"").unwrap() < station);
+
+        let (json, _) = export(JsonWriter::new(vec![]), [] as [&[u8]; 0], &tables).unwrap();
+        assert_eq!(json, b"[]\n");
+    }
+}
@@ -1,14 +1,57 @@
 //! A decoder for BUFR meteorological data format.
 
+pub mod aliases;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod category;
+pub mod checksum;
+pub mod codec;
+#[cfg(feature = "codegen")]
+pub mod codegen;
+pub mod codes;
+#[cfg(feature = "serde")]
+pub mod de;
 mod descriptor;
+pub mod grep;
+pub mod gts;
+pub mod hooks;
+mod info;
+pub mod input;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod manifest;
+pub mod merge;
+pub mod missing;
+pub mod optional_section;
+pub mod overrides;
+pub mod postgres;
+pub mod query;
 mod reader;
+pub mod record;
+pub mod recovery;
+pub mod scan;
 pub mod sections;
+pub mod series;
+pub mod sink;
+pub mod snapshot;
+pub mod sqlite;
+mod subset;
 pub mod tables;
+mod time;
+pub mod tsdb;
+pub mod validation;
+mod writer;
 
 pub use descriptor::*;
-pub use reader::{DataEvent, DataReader, DataSpec};
-pub use sections::{HeaderSections, ensure_end_section};
+pub use info::{BuildInfo, TablesInfo, build_info};
+pub use reader::{
+    CharacterDecoding, Checkpoint, DataEvent, DataReader, DataReaderBuilder, DataSpec,
+    DecodeOptions, ElementSpec, Events, Hints, Limits, PathSegment, RawValue,
+};
+pub use sections::{HeaderSections, check_end_section, ensure_end_section};
+pub use subset::{Elements, FilterDecision, SubsetLookup, SubsetPath, SubsetValue};
 pub use tables::{TableBEntry, TableDEntry, Tables};
+pub use writer::{DataWriter, MessageWriter, encode_message_into, write_message};
 
 /// The error type used by this crate.
 #[derive(thiserror::Error, Debug)]
@@ -23,6 +66,8 @@ pub enum Error {
     NotSupported(String),
     #[error("Fatal error: {0}")]
     Fatal(String),
+    #[error("Timeout: {0}")]
+    Timeout(String),
 }
 
 /// Primitive value in BUFR data.
@@ -34,8 +79,12 @@ pub enum Value {
     Decimal(i32, i8),
     /// Integer value
     Integer(i32),
+    /// Scaled decimal value of a numeric element wider than 32 bits
+    Decimal64(i64, i8),
     /// String value
     String(String),
+    /// Characters kept as bytes (see [`CharacterDecoding::Bytes`])
+    Bytes(Vec<u8>),
 }
 
 impl std::fmt::Debug for Value {
@@ -51,7 +100,56 @@ impl std::fmt::Debug for Value {
                 )
             }
             Value::Integer(v) => write!(f, "{v}"),
+            // Formatted exactly, as f64 cannot hold all 64-bit values
+            &Value::Decimal64(v, s) if s >= 0 => {
+                match 10i128
+                    .checked_pow(s as u32)
+                    .and_then(|p| (v as i128).checked_mul(p))
+                {
+                    Some(v) => write!(f, "{v}"),
+                    // Too large for i128
+                    None => write!(f, "{v}e{s}"),
+                }
+            }
+            &Value::Decimal64(v, s) => {
+                let frac_len = s.unsigned_abs() as usize;
+                let digits = format!("{:0>1$}", v.unsigned_abs(), frac_len + 1);
+                let (int_part, frac_part) = digits.split_at(digits.len() - frac_len);
+                let sign = if v < 0 { "-" } else { "" };
+                write!(f, "{sign}{int_part}.{frac_part}")
+            }
             Value::String(s) => write!(f, "\"{s}\""),
+            Value::Bytes(b) => write!(f, "b\"{}\"", b.escape_ascii()),
+        }
+    }
+}
+
+impl Value {
+    /// The nearest f64 to a number, or `None` if the value is not a number.
+    pub fn as_f64(&self) -> Option<f64> {
+        let (v, scale) = match *self {
+            Value::Integer(v) => return Some(v as f64),
+            Value::Decimal(v, scale) => (v as i64, scale),
+            Value::Decimal64(v, scale) => (v, scale),
+            Value::Missing | Value::String(_) | Value::Bytes(_) => return None,
+        };
+        // Dividing by an exact power of ten gives the nearest f64 to the decimal value,
+        // which multiplying by its inexact reciprocal does not
+        Some(match scale < 0 {
+            true => v as f64 / 10f64.powi(-(scale as i32)),
+            false => v as f64 * 10f64.powi(scale as i32),
+        })
+    }
+
+    /// A number exactly in decimal notation (e.g. `280.15` for `Decimal(28015, -2)`),
+    /// or `None` if the value is not a number.
+    pub fn to_decimal_string(&self) -> Option<String> {
+        match self {
+            // The Debug representation of numbers is exact
+            Value::Integer(_) | Value::Decimal(..) | Value::Decimal64(..) => {
+                Some(format!("{self:?}"))
+            }
+            Value::Missing | Value::String(_) | Value::Bytes(_) => None,
         }
     }
 }
@@ -0,0 +1,264 @@
+//! Manifests describing how converted outputs were produced
+//!
+//! A manifest records the input file (size and SHA-256 digest), the header
+//! information of each decoded message (including the table versions it refers to),
+//! the conversion options, row counts and warnings. Its JSON form is stable:
+//! fields are always serialized in the same order.
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+
+use crate::{BuildInfo, Error, HeaderSections, build_info, category::classify};
+
+/// Manifest of a single conversion.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Manifest {
+    /// Version of tinybufr used for decoding
+    pub tinybufr_version: &'static str,
+    /// Tables embedded in the build of tinybufr used for decoding
+    pub build: BuildInfo,
+    pub input: InputManifest,
+    /// Conversion options (e.g. output format)
+    pub options: BTreeMap<String, String>,
+    pub messages: Vec<MessageManifest>,
+    /// Total number of output rows
+    pub rows: usize,
+    pub warnings: Vec<String>,
+}
+
+/// The input file of a conversion.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct InputManifest {
+    pub path: String,
+    pub size: u64,
+    /// SHA-256 digest of the file contents in lowercase hex
+    pub sha256: String,
+}
+
+/// A decoded message.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MessageManifest {
+    pub edition_number: u8,
+    pub centre: u16,
+    pub sub_centre: u16,
+    pub data_category: u8,
+    /// Category inferred from the report sequences of the template for category 255
+    /// (see [`classify`])
+    pub inferred_data_category: Option<u8>,
+    pub master_table_number: u8,
+    pub master_table_version: u8,
+    pub local_tables_version: u8,
+    pub number_of_subsets: u16,
+    pub is_observed_data: bool,
+    pub is_compressed: bool,
+    /// Number of output rows produced from this message
+    pub rows: usize,
+}
+
+impl Manifest {
+    /// Creates a manifest for the given input file, computing its digest.
+    pub fn for_input<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut file = std::fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            size += n as u64;
+        }
+        Ok(Self {
+            tinybufr_version: env!("CARGO_PKG_VERSION"),
+            build: build_info(),
+            input: InputManifest {
+                path: path.display().to_string(),
+                size,
+                sha256: hex(&hasher.finalize()),
+            },
+            options: BTreeMap::new(),
+            messages: vec![],
+            rows: 0,
+            warnings: vec![],
+        })
+    }
+
+    /// Records a decoded message and the number of rows produced from it.
+    pub fn add_message(&mut self, header: &HeaderSections, rows: usize) {
+        let ids = &header.identification_section;
+        self.messages.push(MessageManifest {
+            edition_number: header.indicator_section.edition_number,
+            centre: ids.centre,
+            sub_centre: ids.sub_centre,
+            data_category: ids.data_category,
+            inferred_data_category: Some(classify(header))
+                .filter(|c| c.inferred)
+                .map(|c| c.category.code()),
+            master_table_number: ids.master_table_number,
+            master_table_version: ids.master_table_version,
+            local_tables_version: ids.local_tables_version,
+            number_of_subsets: header.number_of_subsets(),
+            is_observed_data: header.is_observed_data(),
+            is_compressed: header.is_compressed(),
+            rows,
+        });
+        self.rows += rows;
+    }
+
+    /// Serializes the manifest into pretty-printed JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Fatal(format!("Failed to serialize manifest: {e}")))
+    }
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Minimal streaming SHA-256 (FIPS 180-4).
+pub(crate) struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+impl Sha256 {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            block: [0; 64],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+        while !data.is_empty() {
+            let n = (64 - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&data[..n]);
+            self.block_len += n;
+            data = &data[n..];
+            if self.block_len == 64 {
+                let block = self.block;
+                self.compress(&block);
+                self.block_len = 0;
+            }
+        }
+    }
+
+    pub(crate) fn finalize(mut self) -> [u8; 32] {
+        let bit_len = self.total_len.wrapping_mul(8);
+        let mut padding = vec![0x80u8];
+        padding.resize(1 + (119 - self.block_len) % 64, 0);
+        padding.extend_from_slice(&bit_len.to_be_bytes());
+        let total_len = self.total_len;
+        self.update(&padding);
+        self.total_len = total_len;
+
+        let mut digest = [0u8; 32];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut w = [0u32; 64];
+        for (i, chunk) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha256_hex(data: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        hex(&hasher.finalize())
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256_hex(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+
+        // Feeding the input in pieces gives the same digest
+        let data = vec![0x61u8; 1000];
+        let mut hasher = Sha256::new();
+        for chunk in data.chunks(7) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hex(&hasher.finalize()), sha256_hex(&data));
+    }
+}
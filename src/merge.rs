@@ -0,0 +1,173 @@
+//! Merging the messages of several inputs in chronological order
+//!
+//! [`MergedMessages`] merges inputs whose messages are each in chronological order
+//! (e.g. archives split by bulletin) into a single chronological sequence, as needed to
+//! reconstruct the history of a station. Only the header sections of the messages are
+//! read: messages are ordered by the typical time in Section 1, and messages with the
+//! same time keep the order of their inputs.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::Path;
+
+use crate::{
+    Error, HeaderSections, input::MessageIterator, sections::EditionLayout, time::days_from_civil,
+};
+
+/// A message with its typical time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedMessage {
+    /// Seconds since the Unix epoch (UTC), or `None` if the header cannot be read or
+    /// the time is invalid
+    pub time: Option<i64>,
+    /// Index of the input the message comes from
+    pub input: usize,
+    pub message: Vec<u8>,
+}
+
+/// Returns the typical time of a message (Section 1) in seconds since the Unix epoch.
+///
+/// Editions 2 and 3 give the year of the century, which is taken to be in 1950–2049
+/// (with 100 for 2000).
+pub fn typical_time(header: &HeaderSections) -> Option<i64> {
+    let ids = &header.identification_section;
+    let year_of_century = EditionLayout::of(header.indicator_section.edition_number)
+        .is_ok_and(|layout| layout.year_of_century);
+    let year = match (year_of_century, ids.typical_year) {
+        (false, year) => year as i64,
+        (true, year @ 0..=49) => 2000 + year as i64,
+        (true, year) => 1900 + year as i64,
+    };
+    if ids.typical_hour > 23 || ids.typical_minute > 59 || ids.typical_second > 59 {
+        return None;
+    }
+    let days = days_from_civil(year, ids.typical_month as i64, ids.typical_day as i64)?;
+    Some(
+        days * 86_400
+            + ids.typical_hour as i64 * 3600
+            + ids.typical_minute as i64 * 60
+            + ids.typical_second as i64,
+    )
+}
+
+/// Iterator merging the messages of several inputs by their typical time.
+///
+/// Each input is expected to be in chronological order; the messages of an input
+/// going back in time are yielded as they come, after those of the other inputs
+/// already yielded. Messages without a valid time come before the others.
+pub struct MergedMessages<I: Iterator<Item = Vec<u8>>> {
+    inputs: Vec<I>,
+    /// The next message of each input
+    heads: Vec<Option<TimedMessage>>,
+    heap: BinaryHeap<Reverse<(Option<i64>, usize)>>,
+}
+
+impl<I: Iterator<Item = Vec<u8>>> MergedMessages<I> {
+    pub fn new<C: IntoIterator<Item = I>>(inputs: C) -> Self {
+        let inputs: Vec<I> = inputs.into_iter().collect();
+        let mut merged = Self {
+            heads: vec![None; inputs.len()],
+            inputs,
+            heap: BinaryHeap::new(),
+        };
+        for input in 0..merged.inputs.len() {
+            merged.pull(input);
+        }
+        merged
+    }
+
+    /// Reads the next message of an input.
+    fn pull(&mut self, input: usize) {
+        let Some(message) = self.inputs[input].next() else {
+            return;
+        };
+        let time = HeaderSections::read(message.as_slice())
+            .ok()
+            .and_then(|header| typical_time(&header));
+        self.heap.push(Reverse((time, input)));
+        self.heads[input] = Some(TimedMessage {
+            time,
+            input,
+            message,
+        });
+    }
+}
+
+impl<I: Iterator<Item = Vec<u8>>> Iterator for MergedMessages<I> {
+    type Item = TimedMessage;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, input)) = self.heap.pop()?;
+        let message = self.heads[input].take();
+        self.pull(input);
+        message
+    }
+}
+
+/// Opens files of any format supported by [`open_any`](crate::input::open_any) and
+/// merges their messages by time.
+pub fn merge_files<P: AsRef<Path>>(paths: &[P]) -> Result<MergedMessages<MessageIterator>, Error> {
+    let inputs = paths
+        .iter()
+        .map(crate::input::open_any)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(MergedMessages::new(inputs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A message without data at a typical time (on the hour).
+    fn message(edition: u8, year: u16, hour: u8) -> Vec<u8> {
+        let mut data = std::fs::read(
+            "./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin",
+        )
+        .unwrap();
+        let mut header = HeaderSections::read(data.as_slice()).unwrap();
+        header.indicator_section.edition_number = edition;
+        header.identification_section.typical_year = year;
+        header.identification_section.typical_hour = hour;
+        header.identification_section.typical_minute = 0;
+        header.identification_section.typical_second = 0;
+        data.clear();
+        crate::write_message(&mut data, &header, &[0, 0, 4, 0]).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_typical_time() {
+        let time = |message: Vec<u8>| typical_time(&HeaderSections::read(&message[..]).unwrap());
+        assert_eq!(time(message(4, 2020, 4)), Some(1_595_908_800));
+        assert_eq!(time(message(3, 20, 4)), Some(1_595_908_800));
+        assert_eq!(time(message(3, 99, 4)), time(message(4, 1999, 4)));
+        assert_eq!(time(message(3, 100, 4)), time(message(4, 2000, 4)));
+        assert_eq!(time(message(4, 2020, 24)), None);
+    }
+
+    #[test]
+    fn test_merged_messages() {
+        let a = vec![
+            message(4, 2020, 1),
+            message(4, 2020, 3),
+            message(4, 2020, 5),
+        ];
+        let b = vec![message(3, 20, 2), message(4, 2020, 3), message(4, 2020, 6)];
+        let c = vec![b"BUFR broken".to_vec()];
+        let merged: Vec<_> = MergedMessages::new([a, b, c].map(Vec::into_iter))
+            .map(|m| (m.input, m.time.map(|t| t % 86_400 / 3600)))
+            .collect();
+        assert_eq!(
+            merged,
+            [
+                (2, None),
+                (0, Some(1)),
+                (1, Some(2)),
+                (0, Some(3)),
+                (1, Some(3)),
+                (0, Some(5)),
+                (1, Some(6)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Per-descriptor policies for detecting missing values on decode
+//!
+//! Regulation 94.1.3 reserves the raw value with all bits set for missing values,
+//! but some producers encode missing values of certain elements otherwise (e.g. as
+//! zero). Policies set with
+//! [`DataReaderBuilder::missing_values`](crate::DataReaderBuilder::missing_values) let
+//! such feeds be decoded correctly without cleaning the values afterwards.
+
+use crate::{Error, XY, codec::missing_value, overrides::parse_element_descriptor};
+
+/// How missing values of a numeric element are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingValue {
+    /// All bits set, as required by Regulation 94.1.3
+    #[default]
+    AllOnes,
+    /// The given raw value (before adding the reference value), instead of all bits set
+    Sentinel(u64),
+    /// No value is missing
+    None,
+}
+
+impl MissingValue {
+    /// Whether a raw value of the given width is missing.
+    pub fn is_missing(self, raw: u64, bit_width: u32) -> bool {
+        match self {
+            MissingValue::AllOnes => raw == missing_value(bit_width),
+            MissingValue::Sentinel(sentinel) => raw == sentinel,
+            MissingValue::None => false,
+        }
+    }
+}
+
+impl std::str::FromStr for MissingValue {
+    type Err = Error;
+
+    /// Parses `all-ones`, `none` or a raw sentinel value.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all-ones" => Ok(MissingValue::AllOnes),
+            "none" => Ok(MissingValue::None),
+            _ => s
+                .parse()
+                .map(MissingValue::Sentinel)
+                .map_err(|_| Error::Invalid(format!("Invalid missing value policy: {s:?}"))),
+        }
+    }
+}
+
+/// Missing value policies keyed by element descriptor.
+///
+/// Elements without a policy follow [`MissingValue::AllOnes`].
+#[derive(Clone, Default)]
+pub struct MissingValues {
+    policies: hashbrown::HashMap<XY, MissingValue>,
+}
+
+impl std::fmt::Debug for MissingValues {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut policies: Vec<_> = self.policies.iter().collect();
+        policies.sort_unstable_by_key(|&(xy, _)| xy);
+        f.debug_map().entries(policies).finish()
+    }
+}
+
+impl MissingValues {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, xy: XY, policy: MissingValue) {
+        self.policies.insert(xy, policy);
+    }
+
+    /// Returns the policy of an element.
+    pub fn get(&self, xy: &XY) -> MissingValue {
+        self.policies.get(xy).copied().unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+
+    /// Whether a raw value of an element is missing.
+    pub fn is_missing(&self, xy: &XY, raw: u64, bit_width: u32) -> bool {
+        self.get(xy).is_missing(raw, bit_width)
+    }
+}
+
+/// Parses a policy of an element written as `DESCRIPTOR=POLICY` (e.g. `012101=0`).
+pub fn parse_missing_value_policy(s: &str) -> Result<(XY, MissingValue), Error> {
+    let Some((descriptor, policy)) = s.split_once('=') else {
+        return Err(Error::Invalid(format!(
+            "Invalid missing value policy: {s:?} (expected DESCRIPTOR=POLICY)"
+        )));
+    };
+    Ok((parse_element_descriptor(descriptor)?, policy.parse()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_value() {
+        assert!(MissingValue::AllOnes.is_missing(127, 7));
+        assert!(!MissingValue::AllOnes.is_missing(0, 7));
+        assert!(MissingValue::AllOnes.is_missing(u64::MAX, 64));
+        assert!(MissingValue::Sentinel(0).is_missing(0, 7));
+        assert!(!MissingValue::Sentinel(0).is_missing(127, 7));
+        assert!(!MissingValue::None.is_missing(127, 7));
+
+        assert_eq!(
+            parse_missing_value_policy("012101=0").unwrap(),
+            (XY { x: 12, y: 101 }, MissingValue::Sentinel(0))
+        );
+        assert_eq!(
+            parse_missing_value_policy("12101=none").unwrap(),
+            (XY { x: 12, y: 101 }, MissingValue::None)
+        );
+        assert!(parse_missing_value_policy("012101").is_err());
+        assert!(parse_missing_value_policy("012101=zero").is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! Decoding of the optional section (Section 2) with a user-supplied layout
+//!
+//! The contents of Section 2 are defined by each centre (e.g. the ECMWF RDB key), so
+//! they are read as bytes ([`OptionalSection::optional`]). A [`Schema`] declares where
+//! the fields of a centre's layout are, and decodes them into typed values:
+//!
+//! ```
+//! use tinybufr::optional_section::{FieldValue, Schema};
+//!
+//! let schema: Schema = "
+//!     ## name    offset width type (offsets and widths in bits)
+//!     rdb_type   0      8     uint
+//!     subtype    8      8     uint
+//!     ident      16     32    ascii
+//! "
+//! .parse()
+//! .unwrap();
+//! let fields = schema.decode(b"\x01\x8cSHIP").unwrap();
+//! assert_eq!(fields.get("subtype"), Some(&FieldValue::Unsigned(140)));
+//! assert_eq!(fields.get("ident"), Some(&FieldValue::Text("SHIP".to_string())));
+//! ```
+//!
+//! [`OptionalSection::optional`]: crate::sections::OptionalSection::optional
+
+use crate::Error;
+
+/// Type of a field of Section 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    /// Unsigned integer of up to 64 bits
+    Unsigned,
+    /// Two's complement signed integer of up to 64 bits
+    Signed,
+    /// Characters, with trailing spaces and NULs removed (whole octets only)
+    Ascii,
+    /// Raw bytes (whole octets only)
+    Bytes,
+}
+
+impl std::str::FromStr for FieldType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uint" => Ok(Self::Unsigned),
+            "int" => Ok(Self::Signed),
+            "ascii" => Ok(Self::Ascii),
+            "bytes" => Ok(Self::Bytes),
+            _ => Err(Error::Invalid(format!("Unknown field type: {s:?}"))),
+        }
+    }
+}
+
+/// A field of Section 2, located by bits from the start of its contents (octet 5 of
+/// the section, after the section length and the reserved octet).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    /// Offset in bits
+    pub offset: usize,
+    /// Width in bits
+    pub width: usize,
+    pub ty: FieldType,
+}
+
+/// A decoded value of a field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldValue {
+    Unsigned(u64),
+    Signed(i64),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+/// Decoded fields in the order of the schema.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fields(pub Vec<(String, FieldValue)>);
+
+impl Fields {
+    /// Returns the value of a field by name.
+    pub fn get(&self, name: &str) -> Option<&FieldValue> {
+        self.0.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &FieldValue)> {
+        self.0.iter().map(|(n, v)| (n.as_str(), v))
+    }
+}
+
+/// Layout of the fields of a centre's Section 2.
+///
+/// A schema is built field by field with [`Schema::field`], or parsed from lines of
+/// `name offset width type`, where the type is `uint`, `int`, `ascii` or `bytes`.
+/// Blank lines and lines starting with `#` are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schema {
+    fields: Vec<Field>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a field, checking that it can be decoded as its type.
+    pub fn field(
+        mut self,
+        name: &str,
+        offset: usize,
+        width: usize,
+        ty: FieldType,
+    ) -> Result<Self, Error> {
+        let valid = match ty {
+            FieldType::Unsigned | FieldType::Signed => (1..=64).contains(&width),
+            FieldType::Ascii | FieldType::Bytes => {
+                offset.is_multiple_of(8) && width.is_multiple_of(8)
+            }
+        };
+        if !valid {
+            return Err(Error::Invalid(format!(
+                "Field {name:?} of {width} bits at bit {offset} cannot be {ty:?}"
+            )));
+        }
+        if self.fields.iter().any(|f| f.name == name) {
+            return Err(Error::Invalid(format!("Duplicate field {name:?}")));
+        }
+        self.fields.push(Field {
+            name: name.to_string(),
+            offset,
+            width,
+            ty,
+        });
+        Ok(self)
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Decodes the contents of Section 2 ([`OptionalSection::optional`]).
+    ///
+    /// Fails if a field lies beyond the end of the contents.
+    ///
+    /// [`OptionalSection::optional`]: crate::sections::OptionalSection::optional
+    pub fn decode(&self, contents: &[u8]) -> Result<Fields, Error> {
+        let mut fields = Vec::with_capacity(self.fields.len());
+        for field in &self.fields {
+            let end = field.offset + field.width;
+            if end > contents.len() * 8 {
+                return Err(Error::Invalid(format!(
+                    "Field {:?} (bits {}..{end}) beyond the {} octets of Section 2",
+                    field.name,
+                    field.offset,
+                    contents.len()
+                )));
+            }
+            let bytes = &contents[field.offset / 8..end.div_ceil(8)];
+            let value = match field.ty {
+                FieldType::Unsigned => FieldValue::Unsigned(read_bits(contents, field)),
+                FieldType::Signed => {
+                    // Sign-extends from the width of the field
+                    let shift = 64 - field.width;
+                    FieldValue::Signed(((read_bits(contents, field) << shift) as i64) >> shift)
+                }
+                FieldType::Ascii => FieldValue::Text(
+                    String::from_utf8_lossy(bytes)
+                        .trim_end_matches([' ', '\0'])
+                        .to_string(),
+                ),
+                FieldType::Bytes => FieldValue::Bytes(bytes.to_vec()),
+            };
+            fields.push((field.name.clone(), value));
+        }
+        Ok(Fields(fields))
+    }
+}
+
+/// Reads a field of up to 64 bits as an unsigned integer.
+fn read_bits(contents: &[u8], field: &Field) -> u64 {
+    (field.offset..field.offset + field.width).fold(0, |acc, i| {
+        acc << 1 | ((contents[i / 8] >> (7 - i % 8)) & 1) as u64
+    })
+}
+
+impl std::str::FromStr for Schema {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut schema = Schema::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || Error::Invalid(format!("Invalid schema line {}: {line:?}", i + 1));
+            let &[name, offset, width, ty] = line.split_whitespace().collect::<Vec<_>>().as_slice()
+            else {
+                return Err(invalid());
+            };
+            let offset = offset.parse().map_err(|_| invalid())?;
+            let width = width.parse().map_err(|_| invalid())?;
+            schema = schema.field(name, offset, width, ty.parse()?)?;
+        }
+        Ok(schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let schema = Schema::new()
+            .field("type", 0, 8, FieldType::Unsigned)
+            .unwrap()
+            .field("flag", 8, 1, FieldType::Unsigned)
+            .unwrap()
+            .field("offset", 9, 7, FieldType::Signed)
+            .unwrap()
+            .field("latitude", 16, 24, FieldType::Signed)
+            .unwrap()
+            .field("ident", 40, 48, FieldType::Ascii)
+            .unwrap()
+            .field("spare", 88, 16, FieldType::Bytes)
+            .unwrap();
+        let contents = [
+            0x02,
+            0b1111_1110,
+            0xff,
+            0xfc,
+            0x18,
+            b'4',
+            b'7',
+            b'6',
+            b'6',
+            b'2',
+            b' ',
+            0xab,
+            0xcd,
+        ];
+        let fields = schema.decode(&contents).unwrap();
+        assert_eq!(
+            fields.0,
+            [
+                ("type".to_string(), FieldValue::Unsigned(2)),
+                ("flag".to_string(), FieldValue::Unsigned(1)),
+                ("offset".to_string(), FieldValue::Signed(-2)),
+                ("latitude".to_string(), FieldValue::Signed(-1000)),
+                ("ident".to_string(), FieldValue::Text("47662".to_string())),
+                ("spare".to_string(), FieldValue::Bytes(vec![0xab, 0xcd])),
+            ]
+        );
+        assert_eq!(fields.get("type"), Some(&FieldValue::Unsigned(2)));
+        assert_eq!(fields.get("unknown"), None);
+
+        assert!(matches!(
+            schema.decode(&contents[..12]),
+            Err(Error::Invalid(msg)) if msg.contains("\"spare\" (bits 88..104)")
+        ));
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+        let schema = Schema::new();
+        assert!(
+            schema
+                .clone()
+                .field("a", 0, 0, FieldType::Unsigned)
+                .is_err()
+        );
+        assert!(schema.clone().field("a", 0, 65, FieldType::Signed).is_err());
+        assert!(schema.clone().field("a", 4, 8, FieldType::Ascii).is_err());
+        assert!(schema.clone().field("a", 0, 12, FieldType::Bytes).is_err());
+        let schema = schema.field("a", 0, 8, FieldType::Bytes).unwrap();
+        assert!(schema.field("a", 8, 8, FieldType::Bytes).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let schema: Schema = "
+            # ECMWF RDB key (partly)
+            rdb_type 0 8 uint
+
+            subtype 8 8 uint
+        "
+        .parse()
+        .unwrap();
+        assert_eq!(schema.fields().len(), 2);
+        assert_eq!(
+            schema.fields()[1],
+            Field {
+                name: "subtype".to_string(),
+                offset: 8,
+                width: 8,
+                ty: FieldType::Unsigned,
+            }
+        );
+        assert!("a 0 8".parse::<Schema>().is_err());
+        assert!("a 0 8 float".parse::<Schema>().is_err());
+        assert!("a x 8 uint".parse::<Schema>().is_err());
+    }
+}
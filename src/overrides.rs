@@ -0,0 +1,203 @@
+//! Per-descriptor overrides of how decoded values are output
+//!
+//! Overrides cover local descriptors whose Table B metadata is wrong or missing
+//! without patching the tables. They are consumed by the conversion layers.
+
+use std::sync::Arc;
+
+use crate::{Error, Value, XY};
+
+/// Output type forced by a [`ValueOverride`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputType {
+    Integer,
+    Float,
+    String,
+}
+
+/// Transform applied to each decoded value.
+pub type ValueTransform = Arc<dyn Fn(Value) -> Value + Send + Sync>;
+
+/// Override of how values of an element are output.
+#[derive(Clone, Default)]
+pub struct ValueOverride {
+    /// Output type (the one derived from Table B if `None`)
+    pub output_type: Option<OutputType>,
+    /// Transform applied before the conversion to the output type
+    pub transform: Option<ValueTransform>,
+}
+
+impl std::fmt::Debug for ValueOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValueOverride")
+            .field("output_type", &self.output_type)
+            .field("transform", &self.transform.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl ValueOverride {
+    /// Forces the values to be output as the given type.
+    pub fn output_type(output_type: OutputType) -> Self {
+        Self {
+            output_type: Some(output_type),
+            transform: None,
+        }
+    }
+
+    /// Applies a custom transform to the values.
+    pub fn transform(f: impl Fn(Value) -> Value + Send + Sync + 'static) -> Self {
+        Self {
+            output_type: None,
+            transform: Some(Arc::new(f)),
+        }
+    }
+
+    /// Transforms a value and converts it to the output type.
+    ///
+    /// Values that cannot be converted become missing.
+    pub fn apply(&self, value: Value) -> Value {
+        let value = match &self.transform {
+            Some(f) => f(value),
+            None => value,
+        };
+        match (self.output_type, value) {
+            (_, Value::Missing) => Value::Missing,
+            (
+                Some(OutputType::String),
+                v @ (Value::Integer(_) | Value::Decimal(..) | Value::Decimal64(..)),
+            ) => Value::String(format!("{v:?}")),
+            (Some(OutputType::Integer | OutputType::Float), Value::String(s)) => {
+                parse_decimal(&s).unwrap_or(Value::Missing)
+            }
+            (_, v) => v,
+        }
+    }
+}
+
+/// Overrides keyed by element descriptor.
+#[derive(Clone, Default)]
+pub struct ValueOverrides {
+    overrides: hashbrown::HashMap<XY, ValueOverride>,
+}
+
+impl std::fmt::Debug for ValueOverrides {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut overrides: Vec<_> = self.overrides.iter().collect();
+        overrides.sort_unstable_by_key(|&(xy, _)| xy);
+        f.debug_map().entries(overrides).finish()
+    }
+}
+
+impl ValueOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, xy: XY, value_override: ValueOverride) {
+        self.overrides.insert(xy, value_override);
+    }
+
+    pub fn get(&self, xy: &XY) -> Option<&ValueOverride> {
+        self.overrides.get(xy)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+
+    /// Returns the forced output type of an element, if any.
+    pub fn output_type(&self, xy: &XY) -> Option<OutputType> {
+        self.get(xy).and_then(|o| o.output_type)
+    }
+
+    /// Applies the override of an element to a value (unchanged if none).
+    pub fn apply(&self, xy: &XY, value: Value) -> Value {
+        match self.get(xy) {
+            Some(o) => o.apply(value),
+            None => value,
+        }
+    }
+}
+
+/// Parses a descriptor of an element written as `FXXYYY` or `XXYYY` (e.g. `012101`).
+pub fn parse_element_descriptor(s: &str) -> Result<XY, Error> {
+    if !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Error::Invalid(format!("Invalid element descriptor: {s}")));
+    }
+    let digits = match s.len() {
+        6 if s.starts_with('0') => &s[1..],
+        5 => s,
+        _ => return Err(Error::Invalid(format!("Invalid element descriptor: {s}"))),
+    };
+    match (digits[..2].parse(), digits[2..].parse()) {
+        (Ok(x), Ok(y)) => Ok(XY { x, y }),
+        _ => Err(Error::Invalid(format!("Invalid element descriptor: {s}"))),
+    }
+}
+
+/// Parses a decimal number such as `-12.5` into an exact value.
+fn parse_decimal(s: &str) -> Option<Value> {
+    let s = s.trim();
+    let (int_part, frac_part) = s.split_once('.').unwrap_or((s, ""));
+    if frac_part.is_empty() {
+        return int_part.parse().ok().map(Value::Integer);
+    }
+    if !frac_part.bytes().all(|b| b.is_ascii_digit()) || frac_part.len() > 9 {
+        return None;
+    }
+    let v: i32 = format!("{int_part}{frac_part}").parse().ok()?;
+    Some(Value::Decimal(v, -(frac_part.len() as i8)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let as_string = ValueOverride::output_type(OutputType::String);
+        assert_eq!(
+            as_string.apply(Value::Decimal(-105, -1)),
+            Value::String("-10.5".to_string())
+        );
+        assert_eq!(as_string.apply(Value::Missing), Value::Missing);
+
+        let as_float = ValueOverride::output_type(OutputType::Float);
+        assert_eq!(
+            as_float.apply(Value::String(" 12.50 ".to_string())),
+            Value::Decimal(1250, -2)
+        );
+        assert_eq!(
+            as_float.apply(Value::String("-3".to_string())),
+            Value::Integer(-3)
+        );
+        assert_eq!(
+            as_float.apply(Value::String("N/A".to_string())),
+            Value::Missing
+        );
+
+        let kelvin_to_celsius = ValueOverride::transform(|v| match v {
+            Value::Decimal(v, -2) => Value::Decimal(v - 27315, -2),
+            v => v,
+        });
+        assert_eq!(
+            kelvin_to_celsius.apply(Value::Decimal(27415, -2)),
+            Value::Decimal(100, -2)
+        );
+    }
+
+    #[test]
+    fn test_parse_element_descriptor() {
+        assert_eq!(
+            parse_element_descriptor("012101").unwrap(),
+            XY { x: 12, y: 101 }
+        );
+        assert_eq!(
+            parse_element_descriptor("01202").unwrap(),
+            XY { x: 1, y: 202 }
+        );
+        assert!(parse_element_descriptor("312101").is_err());
+        assert!(parse_element_descriptor("12x01").is_err());
+    }
+}
@@ -0,0 +1,370 @@
+//! Export of decoded data in the PostgreSQL COPY format
+//!
+//! Subsets are written as long-format rows (one row per element), in either the
+//! text or the binary COPY format, so they can be bulk-loaded with
+//! `COPY ... FROM STDIN`. Optionally, each row carries a PostGIS point built from
+//! the latitude and longitude of its subset.
+
+use std::io::{Read, Write};
+
+use crate::{
+    DataReader, Error, Tables, Value, XY,
+    sink::{ElementVisitor, MessageInput, OutputSink},
+};
+
+/// COPY data format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyFormat {
+    /// Tab-separated text (`FORMAT text`)
+    Text,
+    /// PostgreSQL binary format (`FORMAT binary`)
+    Binary,
+}
+
+/// Writes decoded messages as PostgreSQL COPY data.
+///
+/// Use [`create_table_sql`] and [`copy_sql`] for the matching table definition and statement.
+pub struct PgCopyWriter<W: Write> {
+    writer: W,
+    format: CopyFormat,
+    geometry: bool,
+}
+
+const BINARY_SIGNATURE: &[u8] = b"PGCOPY\n\xff\r\n\0";
+
+/// SRID of WGS 84
+const SRID_WGS84: u32 = 4326;
+
+/// Returns a `CREATE TABLE` statement for the rows written by [`PgCopyWriter`].
+pub fn create_table_sql(table_name: &str, geometry: bool) -> String {
+    let geom = if geometry {
+        ", geom geometry(Point, 4326)"
+    } else {
+        ""
+    };
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (message_index integer NOT NULL, \
+         subset_index integer NOT NULL, position integer NOT NULL, descriptor text NOT NULL, \
+         name text NOT NULL, unit text NOT NULL, value double precision, text_value text{geom});",
+        quote_identifier(table_name)
+    )
+}
+
+/// Returns a `COPY ... FROM STDIN` statement for the rows written by [`PgCopyWriter`].
+pub fn copy_sql(table_name: &str, format: CopyFormat) -> String {
+    let format = match format {
+        CopyFormat::Text => "text",
+        CopyFormat::Binary => "binary",
+    };
+    format!(
+        "COPY {} FROM STDIN (FORMAT {format});",
+        quote_identifier(table_name)
+    )
+}
+
+/// A row waiting for the coordinates of its subset.
+struct Row {
+    subset_index: u16,
+    position: u32,
+    xy: XY,
+    value: Value,
+}
+
+impl<W: Write> PgCopyWriter<W> {
+    /// Starts COPY data. With `geometry`, a `geom` column is appended to every row.
+    pub fn new(mut writer: W, format: CopyFormat, geometry: bool) -> Result<Self, Error> {
+        if format == CopyFormat::Binary {
+            writer.write_all(BINARY_SIGNATURE)?;
+            writer.write_all(&0i32.to_be_bytes())?; // flags
+            writer.write_all(&0i32.to_be_bytes())?; // header extension length
+        }
+        Ok(Self {
+            writer,
+            format,
+            geometry,
+        })
+    }
+
+    /// Writes all subsets of a message and returns the number of rows written.
+    ///
+    /// The point of a subset is taken from its first latitude (0 05 001 / 0 05 002)
+    /// and longitude (0 06 001 / 0 06 002). It is NULL if either is missing.
+    pub fn write_message<R: Read>(
+        &mut self,
+        data_reader: &mut DataReader<'_, R>,
+        tables: &Tables,
+        message_index: u32,
+    ) -> Result<usize, Error> {
+        self.write_elements(tables, message_index, |f| data_reader.for_each_element(f))
+    }
+
+    /// Writes the rows of the elements visited by `for_each_element`.
+    fn write_elements(
+        &mut self,
+        tables: &Tables,
+        message_index: u32,
+        for_each_element: impl FnOnce(&mut ElementVisitor<'_>) -> Result<(), Error>,
+    ) -> Result<usize, Error> {
+        let mut rows = vec![];
+        let mut coordinates: Vec<(Option<f64>, Option<f64>)> = vec![];
+        for_each_element(&mut |subset_index, position, xy, value| {
+            if self.geometry {
+                if coordinates.len() <= subset_index as usize {
+                    coordinates.resize(subset_index as usize + 1, (None, None));
+                }
+                let (lat, lon) = &mut coordinates[subset_index as usize];
+                match (xy.x, xy.y) {
+                    (5, 1 | 2) if lat.is_none() => *lat = value.as_f64(),
+                    (6, 1 | 2) if lon.is_none() => *lon = value.as_f64(),
+                    _ => {}
+                }
+            }
+            rows.push(Row {
+                subset_index,
+                position,
+                xy,
+                value: value.clone(),
+            });
+            Ok(())
+        })?;
+
+        for row in &rows {
+            let Some(b) = tables.table_b.get(&row.xy) else {
+                return Err(Error::Table(format!(
+                    "Table B entry not found for xy: {:?}",
+                    row.xy
+                )));
+            };
+            let geom = match coordinates.get(row.subset_index as usize) {
+                Some(&(Some(lat), Some(lon))) => Some(ewkb_point(lon, lat)),
+                _ => None,
+            };
+            let descriptor = format!("0{:02}{:03}", row.xy.x, row.xy.y);
+            let text = match &row.value {
+                Value::String(s) => Some(std::borrow::Cow::Borrowed(s.as_str())),
+                Value::Bytes(b) => Some(String::from_utf8_lossy(b)),
+                _ => None,
+            };
+            let fields = [
+                Field::Int(message_index as i32),
+                Field::Int(row.subset_index as i32),
+                Field::Int(row.position as i32),
+                Field::Text(&descriptor),
+                Field::Text(b.element_name),
+                Field::Text(b.unit),
+                match &row.value {
+                    Value::String(_) | Value::Bytes(_) | Value::Missing => Field::Null,
+                    v => Field::Float(v),
+                },
+                match &text {
+                    Some(s) => Field::Text(s),
+                    None => Field::Null,
+                },
+                match &geom {
+                    Some(geom) => Field::Geometry(geom),
+                    None => Field::Null,
+                },
+            ];
+            let num_fields = if self.geometry { 9 } else { 8 };
+            self.write_row(&fields[..num_fields])?;
+        }
+        Ok(rows.len())
+    }
+
+    /// Writes the trailer (for the binary format) and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        if self.format == CopyFormat::Binary {
+            self.writer.write_all(&(-1i16).to_be_bytes())?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_row(&mut self, fields: &[Field]) -> Result<(), Error> {
+        match self.format {
+            CopyFormat::Text => {
+                let line = fields
+                    .iter()
+                    .map(Field::to_text)
+                    .collect::<Vec<_>>()
+                    .join("\t");
+                writeln!(self.writer, "{line}")?;
+            }
+            CopyFormat::Binary => {
+                self.writer
+                    .write_all(&(fields.len() as i16).to_be_bytes())?;
+                for field in fields {
+                    match field.to_binary() {
+                        Some(bytes) => {
+                            self.writer.write_all(&(bytes.len() as i32).to_be_bytes())?;
+                            self.writer.write_all(&bytes)?;
+                        }
+                        None => self.writer.write_all(&(-1i32).to_be_bytes())?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> OutputSink for PgCopyWriter<W> {
+    type Output = W;
+
+    fn write_message(&mut self, input: &mut MessageInput<'_, '_>) -> Result<usize, Error> {
+        let (tables, message_index) = (input.tables, input.index);
+        self.write_elements(tables, message_index, |f| input.for_each_element(f))
+    }
+
+    fn finish(self) -> Result<W, Error> {
+        PgCopyWriter::finish(self)
+    }
+}
+
+/// A column value of a row.
+enum Field<'a> {
+    Null,
+    Int(i32),
+    Float(&'a Value),
+    Text(&'a str),
+    Geometry(&'a [u8]),
+}
+
+impl Field<'_> {
+    fn to_text(&self) -> String {
+        match self {
+            Field::Null => "\\N".to_string(),
+            Field::Int(v) => v.to_string(),
+            Field::Float(v) => v.to_decimal_string().unwrap_or_default(),
+            Field::Text(s) => escape_text(s),
+            Field::Geometry(bytes) => bytes.iter().map(|b| format!("{b:02X}")).collect(),
+        }
+    }
+
+    fn to_binary(&self) -> Option<Vec<u8>> {
+        match self {
+            Field::Null => None,
+            Field::Int(v) => Some(v.to_be_bytes().to_vec()),
+            Field::Float(v) => v.as_f64().map(|v| v.to_be_bytes().to_vec()),
+            Field::Text(s) => Some(s.as_bytes().to_vec()),
+            Field::Geometry(bytes) => Some(bytes.to_vec()),
+        }
+    }
+}
+
+/// Encodes a point in PostGIS extended WKB (little endian, with SRID).
+fn ewkb_point(x: f64, y: f64) -> Vec<u8> {
+    const WKB_POINT: u32 = 1;
+    const EWKB_SRID_FLAG: u32 = 0x2000_0000;
+    let mut buf = Vec::with_capacity(25);
+    buf.push(1); // little endian
+    buf.extend_from_slice(&(WKB_POINT | EWKB_SRID_FLAG).to_le_bytes());
+    buf.extend_from_slice(&SRID_WGS84.to_le_bytes());
+    buf.extend_from_slice(&x.to_le_bytes());
+    buf.extend_from_slice(&y.to_le_bytes());
+    buf
+}
+
+fn escape_text(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn quote_identifier(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::tests::make_dds;
+    use crate::sections::DataDescriptionSection;
+    use crate::{DataSpec, DataWriter, Descriptor};
+
+    fn sample() -> (DataDescriptionSection, Vec<Vec<Value>>) {
+        let descriptors = vec![
+            Descriptor { f: 0, x: 1, y: 15 },
+            Descriptor { f: 0, x: 5, y: 1 },
+            Descriptor { f: 0, x: 6, y: 1 },
+        ];
+        let dds = make_dds(descriptors, 2);
+        let subsets = vec![
+            vec![
+                Value::String("A\tB".to_string()),
+                Value::Decimal(3568, -2),
+                Value::Decimal(13976, -2),
+            ],
+            vec![Value::Missing, Value::Missing, Value::Decimal(13976, -2)],
+        ];
+        (dds, subsets)
+    }
+
+    fn write_copy(format: CopyFormat, geometry: bool) -> Vec<u8> {
+        let tables = Tables::default();
+        let (dds, subsets) = sample();
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut writer = DataWriter::new(&spec);
+        for subset in &subsets {
+            writer.write_subset(subset).unwrap();
+        }
+        let section = writer.finish().unwrap();
+
+        let mut copy = PgCopyWriter::new(Vec::new(), format, geometry).unwrap();
+        let mut data_reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        assert_eq!(copy.write_message(&mut data_reader, &tables, 0).unwrap(), 6);
+        copy.finish().unwrap()
+    }
+
+    #[test]
+    fn test_text_format() {
+        let output = String::from_utf8(write_copy(CopyFormat::Text, true)).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 6);
+        let point = "0101000020E6100000B81E85EB51786140D7A3703D0AD74140";
+        assert_eq!(
+            lines[0],
+            format!(
+                "0\t0\t0\t001015\tStation or site name\tCCITT IA5\t\\N\tA\\tB{}\t{point}",
+                " ".repeat(17)
+            )
+        );
+        assert_eq!(
+            lines[1],
+            format!("0\t0\t1\t005001\tLatitude (high accuracy)\tdeg\t35.68000\t\\N\t{point}")
+        );
+        // No point without a latitude
+        assert!(lines[5].ends_with("\t139.76000\t\\N\t\\N"));
+
+        let output = String::from_utf8(write_copy(CopyFormat::Text, false)).unwrap();
+        assert_eq!(output.lines().next().unwrap().split('\t').count(), 8);
+    }
+
+    #[test]
+    fn test_binary_format() {
+        let output = write_copy(CopyFormat::Binary, true);
+        assert!(output.starts_with(BINARY_SIGNATURE));
+        assert!(output.ends_with(&[0xff, 0xff]));
+
+        // First tuple: 9 fields, message_index = 0
+        let tuple = &output[BINARY_SIGNATURE.len() + 8..];
+        assert_eq!(&tuple[..2], &9i16.to_be_bytes());
+        assert_eq!(&tuple[2..6], &4i32.to_be_bytes());
+        assert_eq!(&tuple[6..10], &0i32.to_be_bytes());
+    }
+
+    #[test]
+    fn test_ewkb_point() {
+        let point = ewkb_point(1.0, 2.0);
+        assert_eq!(point.len(), 25);
+        assert_eq!(&point[..9], &[1, 1, 0, 0, 0x20, 0xe6, 0x10, 0, 0]);
+    }
+}
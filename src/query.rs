@@ -0,0 +1,710 @@
+//! Querying decoded messages in memory
+//!
+//! [`Message::decode`] reads a whole message (header sections, data and end section)
+//! into a [`Message`], which holds its values along with the Table D sequences they
+//! were read in, and [`Message::query`] extracts values by a selector instead of
+//! walking the data events. A selector is an element descriptor, optionally preceded
+//! by sequence descriptors it must be nested in and followed by predicates on its
+//! context:
+//!
+//! ```text
+//! 012101                              all air temperatures
+//! 303054/012101                       air temperatures in the sequence 3 03 054
+//! 012101[007004=50000]                air temperatures at the pressure of 500 hPa
+//! 011002[001001=47,007004>=10000]     wind speeds of WMO block 47 at or below 100 hPa
+//! ```
+//!
+//! The context of a value is the latest value of each element read before it in its
+//! subset, which is how BUFR templates give the coordinates of measurements. Predicates
+//! compare context values with `=`, `!=`, `<`, `<=`, `>` or `>=`: numerically with a
+//! number (scaled values as they are, e.g. `012101<273.15`), by text with a string
+//! (trailing spaces ignored, quotes optional) and by missingness with `missing`.
+//!
+//! [`MessageIter`] decodes the messages of an input (e.g. a GTS file) one at a time.
+//!
+//! For workloads accessing the same columns of large messages again and again, a
+//! [`Decoder`] with a column cache keeps the decoded values of each element, so only
+//! the first access to a message decodes it.
+
+mod cache;
+
+use std::io::Read;
+use std::sync::Arc;
+
+pub use cache::{CacheStats, Column, ColumnCache, fingerprint};
+
+use crate::{
+    DataEvent, DataReader, DataSpec, DecodeOptions, Error, HeaderSections, Tables, Value, XY,
+    input::MessageIterator, overrides::parse_element_descriptor, recovery::RecoveryPolicy,
+    sections::split_message, validation::Strictness,
+};
+
+/// A value of an element in a subset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    /// Index of the element in its subset, counted as by bit-maps
+    pub position: u32,
+    pub xy: XY,
+    pub value: Value,
+    /// Table D sequences the element was read in, outermost first
+    pub sequences: Arc<[XY]>,
+}
+
+/// A message decoded into memory.
+#[derive(Debug)]
+pub struct Message {
+    pub header: HeaderSections,
+    /// Elements of each subset in the order they were read
+    pub subsets: Vec<Vec<Element>>,
+    /// Deviations from the regulations tolerated in lenient decoding
+    pub warnings: Vec<String>,
+    replications: Vec<ReplicationSummary>,
+}
+
+/// Counts of a replication in the template observed in a message (see
+/// [`Message::replication_summary`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicationSummary {
+    /// Indices of the replication descriptor and of the sequences and replications it
+    /// is nested in, outermost first (as the `idx` of [`DataEvent`]s)
+    pub path: Vec<u16>,
+    /// Table D sequences the replication is nested in, outermost first
+    pub sequences: Arc<[XY]>,
+    /// Width of the delayed replication factor in bits, or 0 for a regular replication
+    pub factor_bits: u8,
+    /// Number of times the replication was read, over all subsets (and all items of
+    /// the replications it is nested in)
+    pub occurrences: u64,
+    pub min: u16,
+    pub max: u16,
+    /// Sum of the counts
+    pub total: u64,
+}
+
+impl ReplicationSummary {
+    fn add(&mut self, count: u16) {
+        self.occurrences += 1;
+        self.min = self.min.min(count);
+        self.max = self.max.max(count);
+        self.total += count as u64;
+    }
+}
+
+/// A value picked by [`Message::query`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryMatch<'a> {
+    pub subset: usize,
+    pub element: &'a Element,
+    /// Latest values of the coordinate elements (classes 04 to 07) read before the
+    /// value in its subset, in the order they were first read
+    pub coordinates: Vec<(XY, &'a Value)>,
+}
+
+impl Message {
+    /// Reads and decodes a whole message strictly, from `BUFR` to the end section.
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), tinybufr::Error> {
+    /// use tinybufr::{Tables, query::Message};
+    ///
+    /// let file = std::fs::File::open("message.bufr")?;
+    /// let message = Message::decode(std::io::BufReader::new(file), &Tables::default())?;
+    /// for (i, subset) in message.subsets.iter().enumerate() {
+    ///     println!("subset {i}: {} values", subset.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn decode<R: Read>(reader: R, tables: &Tables) -> Result<Self, Error> {
+        Self::decode_with_options(reader, tables, &DecodeOptions::default())
+    }
+
+    /// Reads and decodes a whole message as set by the options.
+    ///
+    /// In lenient mode, a message running past the end of the input is decoded as far
+    /// as it goes.
+    pub fn decode_with_options<R: Read>(
+        mut reader: R,
+        tables: &Tables,
+        options: &DecodeOptions,
+    ) -> Result<Self, Error> {
+        let message = read_message(&mut reader, options.strictness)?
+            .ok_or_else(|| Error::Io(std::io::ErrorKind::UnexpectedEof.into()))?;
+        Self::decode_bytes(&message, tables, options)
+    }
+
+    /// Decodes a whole message in memory.
+    fn decode_bytes(
+        message: &[u8],
+        tables: &Tables,
+        options: &DecodeOptions,
+    ) -> Result<Self, Error> {
+        let sections = split_message(message, options)?;
+        let mut warnings = sections.warnings;
+        let header = sections.header;
+        let subsets = {
+            let data_spec =
+                DataSpec::from_data_description(&header.data_description_section, tables)?;
+            let mut data_reader = DataReader::builder(&data_spec)
+                .decode_options(options)
+                .build(sections.data_section)?;
+            let subsets = read_subsets(&mut data_reader, &data_spec)?;
+            warnings.extend_from_slice(data_reader.warnings());
+            subsets
+        };
+        Ok(Message {
+            header,
+            subsets: subsets.elements,
+            warnings,
+            replications: subsets.replications,
+        })
+    }
+
+    /// Returns the counts of each replication in the template, in the order they were
+    /// first read.
+    ///
+    /// Replications never read (e.g. nested in a replication of zero items) are not
+    /// included.
+    pub fn replication_summary(&self) -> &[ReplicationSummary] {
+        &self.replications
+    }
+
+    /// Returns the values matching a selector (see the [module documentation](self)).
+    pub fn query(&self, selector: &str) -> Result<Vec<QueryMatch<'_>>, Error> {
+        Ok(self.select(&selector.parse()?))
+    }
+
+    /// Returns the values matching a parsed selector.
+    pub fn select(&self, selector: &Selector) -> Vec<QueryMatch<'_>> {
+        let mut matches = vec![];
+        for (subset, elements) in self.subsets.iter().enumerate() {
+            let mut context: Vec<(XY, &Value)> = vec![];
+            for element in elements {
+                if selector.matches_path(element)
+                    && selector.predicates.iter().all(|p| p.matches(&context))
+                {
+                    matches.push(QueryMatch {
+                        subset,
+                        element,
+                        coordinates: context
+                            .iter()
+                            .filter(|(xy, _)| (4..=7).contains(&xy.x))
+                            .copied()
+                            .collect(),
+                    });
+                }
+                match context.iter_mut().find(|(xy, _)| *xy == element.xy) {
+                    Some(latest) => latest.1 = &element.value,
+                    None => context.push((element.xy, &element.value)),
+                }
+            }
+        }
+        matches
+    }
+
+    /// Returns the values of an element in each subset.
+    pub fn column(&self, xy: XY) -> Column {
+        self.subsets
+            .iter()
+            .map(|elements| {
+                elements
+                    .iter()
+                    .filter(|element| element.xy == xy)
+                    .map(|element| element.value.clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the values of each element in each subset, in the order the elements
+    /// were first read.
+    pub fn columns(&self) -> Vec<(XY, Column)> {
+        let mut indices = hashbrown::HashMap::new();
+        let mut columns: Vec<(XY, Vec<Vec<Value>>)> = vec![];
+        for (subset, elements) in self.subsets.iter().enumerate() {
+            for element in elements {
+                let i = *indices.entry(element.xy).or_insert_with(|| {
+                    columns.push((element.xy, vec![vec![]; self.subsets.len()]));
+                    columns.len() - 1
+                });
+                columns[i].1[subset].push(element.value.clone());
+            }
+        }
+        columns
+            .into_iter()
+            .map(|(xy, values)| (xy, values.into()))
+            .collect()
+    }
+}
+
+/// Decodes messages with common tables and options, optionally caching columns.
+pub struct Decoder<'t> {
+    tables: &'t Tables,
+    options: DecodeOptions,
+    cache: Option<ColumnCache>,
+}
+
+impl<'t> Decoder<'t> {
+    /// Creates a decoder decoding strictly without a cache.
+    pub fn new(tables: &'t Tables) -> Self {
+        Self {
+            tables,
+            options: DecodeOptions::default(),
+            cache: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Enables the column cache holding up to `capacity` bytes of values (estimated).
+    pub fn with_column_cache(mut self, capacity: usize) -> Self {
+        self.cache = Some(ColumnCache::new(capacity));
+        self
+    }
+
+    pub fn cache(&self) -> Option<&ColumnCache> {
+        self.cache.as_ref()
+    }
+
+    /// Decodes a whole message.
+    pub fn decode(&self, message: &[u8]) -> Result<Message, Error> {
+        Message::decode_bytes(message, self.tables, &self.options)
+    }
+
+    /// Returns the values of an element in each subset of a message.
+    ///
+    /// With the column cache, the message is decoded only if the column is not cached,
+    /// and then the columns of all its elements are cached.
+    pub fn column(&mut self, message: &[u8], xy: XY) -> Result<Column, Error> {
+        let Some(cache) = &mut self.cache else {
+            return Ok(self.decode(message)?.column(xy));
+        };
+        let fingerprint = fingerprint(message);
+        if let Some(column) = cache.get(fingerprint, xy) {
+            return Ok(column);
+        }
+        let message = Message::decode_bytes(message, self.tables, &self.options)?;
+        let mut column = None;
+        for (column_xy, values) in message.columns() {
+            match column_xy == xy {
+                true => column = Some(values),
+                false => cache.insert(fingerprint, column_xy, values),
+            }
+        }
+        // The requested column goes last, so that it is the most recently used
+        let column = column.unwrap_or_else(|| message.column(xy));
+        cache.insert(fingerprint, xy, column.clone());
+        Ok(column)
+    }
+}
+
+/// Iterator decoding the messages of an input one at a time.
+///
+/// The messages are found as by [`MessageIterator`], which reads the whole input
+/// first and skips anything between the messages (e.g. headings or GTS envelopes).
+/// Messages are extracted failing fast in strict mode, and keeping truncated
+/// messages in lenient mode. A message that cannot be decoded yields an error and
+/// the iteration goes on with the next one, but it ends after an error reading the
+/// input.
+///
+/// ```no_run
+/// # fn main() -> Result<(), tinybufr::Error> {
+/// use tinybufr::{Tables, query::MessageIter};
+///
+/// let file = std::fs::File::open("messages.bufr")?;
+/// let tables = Tables::default();
+/// for message in MessageIter::new(std::io::BufReader::new(file), &tables) {
+///     println!("{} subsets", message?.subsets.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct MessageIter<'t, R> {
+    /// The input, until the messages are extracted from it
+    reader: Option<R>,
+    messages: MessageIterator,
+    tables: &'t Tables,
+    options: DecodeOptions,
+}
+
+impl<'t, R: Read> MessageIter<'t, R> {
+    /// Creates an iterator decoding messages strictly.
+    pub fn new(reader: R, tables: &'t Tables) -> Self {
+        Self::with_options(reader, tables, DecodeOptions::default())
+    }
+
+    pub fn with_options(reader: R, tables: &'t Tables, options: DecodeOptions) -> Self {
+        Self {
+            reader: Some(reader),
+            messages: MessageIterator::default(),
+            tables,
+            options,
+        }
+    }
+
+    /// Reads the next message without decoding it, or returns `None` at the end of the
+    /// input.
+    pub fn next_raw(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        if let Some(reader) = self.reader.take()
+            && let Err(error) = self.extract(reader)
+        {
+            return Some(Err(error));
+        }
+        self.messages.next().map(Ok)
+    }
+
+    fn extract(&mut self, mut reader: R) -> Result<(), Error> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        let recovery = match self.options.strictness {
+            Strictness::Strict => RecoveryPolicy::FailFast,
+            Strictness::Lenient => RecoveryPolicy::BestEffort,
+        };
+        self.messages = MessageIterator::from_bytes_with_recovery(data, recovery)?;
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for MessageIter<'_, R> {
+    type Item = Result<Message, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let message = self.next_raw()?;
+        Some(
+            message.and_then(|message| Message::decode_bytes(&message, self.tables, &self.options)),
+        )
+    }
+}
+
+/// Reads a message up to its total length, or returns `None` at the end of the input.
+///
+/// In lenient mode, a message running past the end of the input is returned as it is.
+fn read_message<R: Read>(reader: &mut R, strictness: Strictness) -> Result<Option<Vec<u8>>, Error> {
+    let mut message = vec![0; 8];
+    let mut filled = 0;
+    while filled < message.len() {
+        match reader.read(&mut message[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    match filled {
+        0 => return Ok(None),
+        8 => {}
+        _ => return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into())),
+    }
+    if !message.starts_with(b"BUFR") {
+        return Err(Error::Invalid(
+            "Message does not start with BUFR".to_string(),
+        ));
+    }
+    if message[7] < 2 {
+        return Err(Error::NotSupported(format!(
+            "BUFR edition {} without total length",
+            message[7]
+        )));
+    }
+    let total_length = u32::from_be_bytes([0, message[4], message[5], message[6]]) as usize;
+    let rest = total_length.saturating_sub(message.len());
+    match strictness {
+        Strictness::Strict => {
+            message.resize(message.len() + rest, 0);
+            reader.read_exact(&mut message[8..])?;
+        }
+        Strictness::Lenient => {
+            reader.take(rest as u64).read_to_end(&mut message)?;
+        }
+    }
+    Ok(Some(message))
+}
+
+/// The elements of all subsets and the counts of the replications.
+struct Subsets {
+    elements: Vec<Vec<Element>>,
+    replications: Vec<ReplicationSummary>,
+}
+
+/// Reads the elements of all subsets.
+fn read_subsets<R: std::io::Read>(
+    data_reader: &mut DataReader<'_, R>,
+    data_spec: &DataSpec,
+) -> Result<Subsets, Error> {
+    let mut subsets: Vec<Vec<Element>> = vec![];
+    let mut summaries: Vec<ReplicationSummary> = vec![];
+    let mut subset = 0;
+    let mut position = 0;
+    let mut sequences: Vec<XY> = vec![];
+    let mut path: Arc<[XY]> = Arc::new([]);
+    // Indices of the sequences and replications being read
+    let mut indices: Vec<u16> = vec![];
+    // Replications in compressed data: the factors of each subset, if they vary, and
+    // the index of the current item
+    let mut replications: Vec<(Option<Vec<u16>>, u16)> = vec![];
+    loop {
+        match data_reader.read_event()? {
+            DataEvent::SubsetStart(i) => {
+                subset = i as usize;
+                position = 0;
+                subsets.resize_with(subset + 1, Vec::new);
+            }
+            DataEvent::SequenceStart { idx, xy } => {
+                indices.push(idx);
+                sequences.push(xy);
+                path = sequences.as_slice().into();
+            }
+            DataEvent::SequenceEnd => {
+                indices.pop();
+                sequences.pop();
+                path = sequences.as_slice().into();
+            }
+            DataEvent::ReplicationStart {
+                idx,
+                count,
+                factor_bits,
+                subset_counts,
+            } => {
+                indices.push(idx);
+                let summary = match summaries.iter().position(|s| s.path == indices) {
+                    Some(i) => &mut summaries[i],
+                    None => {
+                        summaries.push(ReplicationSummary {
+                            path: indices.clone(),
+                            sequences: path.clone(),
+                            factor_bits,
+                            occurrences: 0,
+                            min: u16::MAX,
+                            max: 0,
+                            total: 0,
+                        });
+                        summaries
+                            .last_mut()
+                            .expect("Summary should have been added")
+                    }
+                };
+                if data_spec.is_compressed {
+                    for i in 0..data_spec.number_of_subsets as usize {
+                        // Not read in the padding items of enclosing replications
+                        let is_padding = replications.iter().any(|(counts, item)| {
+                            counts.as_ref().is_some_and(|counts| *item >= counts[i])
+                        });
+                        if !is_padding {
+                            summary.add(subset_counts.as_ref().map_or(count, |counts| counts[i]));
+                        }
+                    }
+                } else {
+                    summary.add(count);
+                }
+                replications.push((subset_counts, 0));
+            }
+            DataEvent::ReplicationItemEnd => {
+                if let Some((_, item)) = replications.last_mut() {
+                    *item += 1;
+                }
+            }
+            DataEvent::ReplicationEnd => {
+                indices.pop();
+                replications.pop();
+            }
+            DataEvent::Data { xy, value, .. } => {
+                subsets[subset].push(Element {
+                    position,
+                    xy,
+                    value,
+                    sequences: path.clone(),
+                });
+                position += 1;
+            }
+            DataEvent::CompressedData { xy, values, .. } => {
+                subsets.resize_with(values.len(), Vec::new);
+                for (i, value) in values.into_iter().enumerate() {
+                    // Padding items of replications whose factors vary between subsets
+                    let is_padding = replications.iter().any(|(counts, item)| {
+                        counts.as_ref().is_some_and(|counts| *item >= counts[i])
+                    });
+                    if !is_padding {
+                        subsets[i].push(Element {
+                            position,
+                            xy,
+                            value,
+                            sequences: path.clone(),
+                        });
+                    }
+                }
+                position += 1;
+            }
+            DataEvent::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(Subsets {
+        elements: subsets,
+        replications: summaries,
+    })
+}
+
+/// A parsed selector of [`Message::query`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    /// Sequences the element must be nested in, outermost first
+    pub sequences: Vec<XY>,
+    pub element: XY,
+    pub predicates: Vec<Predicate>,
+}
+
+/// A condition on the latest value of an element before the selected value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    pub xy: XY,
+    pub op: Comparison,
+    pub operand: Operand,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Right-hand side of a predicate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Number(f64),
+    Text(String),
+    Missing,
+}
+
+impl Selector {
+    /// Whether an element is the selected one, nested in the selected sequences.
+    fn matches_path(&self, element: &Element) -> bool {
+        if element.xy != self.element {
+            return false;
+        }
+        let mut sequences = element.sequences.iter();
+        self.sequences
+            .iter()
+            .all(|xy| sequences.any(|sequence| sequence == xy))
+    }
+}
+
+impl Predicate {
+    fn matches(&self, context: &[(XY, &Value)]) -> bool {
+        context
+            .iter()
+            .find(|(xy, _)| *xy == self.xy)
+            .is_some_and(|(_, value)| self.matches_value(value))
+    }
+
+    /// Whether a value of the element satisfies the predicate.
+    pub fn matches_value(&self, value: &Value) -> bool {
+        let ordering = match (&self.operand, value) {
+            (Operand::Missing, Value::Missing) => Some(std::cmp::Ordering::Equal),
+            (Operand::Missing, _) => None,
+            (Operand::Text(text), Value::String(s)) => Some(s.trim_end().cmp(text.as_str())),
+            (Operand::Number(number), value) => value.as_f64().and_then(|v| {
+                // Scaled values are not exact in binary floating point
+                match (v - number).abs() <= 1e-9 * number.abs().max(1.0) {
+                    true => Some(std::cmp::Ordering::Equal),
+                    false => v.partial_cmp(number),
+                }
+            }),
+            _ => None,
+        };
+        match ordering {
+            Some(ordering) => match self.op {
+                Comparison::Eq => ordering.is_eq(),
+                Comparison::Ne => ordering.is_ne(),
+                Comparison::Lt => ordering.is_lt(),
+                Comparison::Le => ordering.is_le(),
+                Comparison::Gt => ordering.is_gt(),
+                Comparison::Ge => ordering.is_ge(),
+            },
+            // Values of other kinds are unequal to the operand
+            None => self.op == Comparison::Ne,
+        }
+    }
+}
+
+impl std::str::FromStr for Selector {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::Invalid(format!("Invalid selector: {s:?}"));
+        let s = s.trim();
+        let (path, predicates) = match s.split_once('[') {
+            Some((path, rest)) => (path, Some(rest.strip_suffix(']').ok_or_else(invalid)?)),
+            None => (s, None),
+        };
+        let mut descriptors: Vec<&str> = path.split('/').map(str::trim).collect();
+        let element = parse_element_descriptor(descriptors.pop().ok_or_else(invalid)?)?;
+        let sequences = descriptors
+            .into_iter()
+            .map(|d| match d.strip_prefix('3') {
+                Some(xy) if d.len() == 6 => parse_element_descriptor(xy),
+                _ => Err(Error::Invalid(format!("Invalid sequence descriptor: {d}"))),
+            })
+            .collect::<Result<_, _>>()?;
+        let predicates = match predicates {
+            Some(predicates) => predicates
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+            None => vec![],
+        };
+        Ok(Selector {
+            sequences,
+            element,
+            predicates,
+        })
+    }
+}
+
+impl std::str::FromStr for Predicate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(index) = s.find(['=', '!', '<', '>']) else {
+            return Err(Error::Invalid(format!("Invalid predicate: {s:?}")));
+        };
+        let (descriptor, rest) = s.split_at(index);
+        let (op, operand) = [
+            ("!=", Comparison::Ne),
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("=", Comparison::Eq),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ]
+        .into_iter()
+        .find_map(|(token, op)| rest.strip_prefix(token).map(|operand| (op, operand)))
+        .ok_or_else(|| Error::Invalid(format!("Invalid predicate: {s:?}")))?;
+        let operand = operand.trim();
+        let operand = match operand.parse() {
+            Ok(number) => Operand::Number(number),
+            Err(_) if operand == "missing" => Operand::Missing,
+            Err(_) => {
+                let text = operand
+                    .strip_prefix('"')
+                    .and_then(|s| s.strip_suffix('"'))
+                    .unwrap_or(operand);
+                Operand::Text(text.trim_end().to_string())
+            }
+        };
+        Ok(Predicate {
+            xy: parse_element_descriptor(descriptor.trim())?,
+            op,
+            operand,
+        })
+    }
+}
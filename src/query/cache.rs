@@ -0,0 +1,194 @@
+//! Size-bounded cache of decoded columns
+//!
+//! Columns are kept by the fingerprint of their message and their element descriptor,
+//! and the least recently used ones are evicted when the estimated size of the cached
+//! values exceeds the capacity.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+use crate::{Value, XY};
+
+/// Values of an element in each subset, in the order they were read.
+pub type Column = Arc<[Vec<Value>]>;
+
+/// Returns the fingerprint of a message used as part of cache keys.
+///
+/// The fingerprint is a 64-bit hash of the whole message, so two different messages
+/// are taken to be the same only in the unlikely case of a hash collision.
+pub fn fingerprint(message: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    message.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Counters of a [`ColumnCache`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Number of columns cached
+    pub entries: usize,
+    /// Estimated size of the cached columns in bytes
+    pub bytes: usize,
+}
+
+struct Entry {
+    column: Column,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// LRU cache of decoded columns keyed by (message fingerprint, element descriptor).
+pub struct ColumnCache {
+    capacity: usize,
+    entries: hashbrown::HashMap<(u64, XY), Entry>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl ColumnCache {
+    /// Creates a cache holding columns of up to `capacity` bytes in total (estimated).
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: hashbrown::HashMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Returns a cached column, marking it as the most recently used.
+    pub fn get(&mut self, fingerprint: u64, xy: XY) -> Option<Column> {
+        self.clock += 1;
+        match self.entries.get_mut(&(fingerprint, xy)) {
+            Some(entry) => {
+                entry.last_used = self.clock;
+                self.stats.hits += 1;
+                Some(entry.column.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches a column, evicting the least recently used ones to make room for it.
+    ///
+    /// Columns larger than the capacity are not cached.
+    pub fn insert(&mut self, fingerprint: u64, xy: XY, column: Column) {
+        let bytes = column_size(&column);
+        if bytes > self.capacity {
+            return;
+        }
+        self.remove(fingerprint, xy);
+        while self.stats.bytes + bytes > self.capacity {
+            let Some(&lru) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key)
+            else {
+                break;
+            };
+            self.remove(lru.0, lru.1);
+        }
+        self.clock += 1;
+        self.entries.insert(
+            (fingerprint, xy),
+            Entry {
+                column,
+                bytes,
+                last_used: self.clock,
+            },
+        );
+        self.stats.bytes += bytes;
+        self.stats.entries = self.entries.len();
+    }
+
+    fn remove(&mut self, fingerprint: u64, xy: XY) {
+        if let Some(entry) = self.entries.remove(&(fingerprint, xy)) {
+            self.stats.bytes -= entry.bytes;
+            self.stats.entries = self.entries.len();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.stats.bytes = 0;
+        self.stats.entries = 0;
+    }
+}
+
+/// Estimates the memory used by a column.
+fn column_size(column: &[Vec<Value>]) -> usize {
+    column
+        .iter()
+        .map(|values| {
+            size_of::<Vec<Value>>()
+                + values
+                    .iter()
+                    .map(|value| match value {
+                        Value::String(s) => size_of::<Value>() + s.len(),
+                        _ => size_of::<Value>(),
+                    })
+                    .sum::<usize>()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(len: usize) -> Column {
+        vec![vec![Value::Integer(1); len]].into()
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let xy = |y| XY { x: 12, y };
+        let size = column_size(&column(10));
+        let mut cache = ColumnCache::new(size * 2);
+        cache.insert(1, xy(101), column(10));
+        cache.insert(1, xy(103), column(10));
+        assert!(cache.get(1, xy(101)).is_some());
+        // Evicts 0 12 103, the least recently used
+        cache.insert(2, xy(101), column(10));
+        assert!(cache.get(1, xy(103)).is_none());
+        assert!(cache.get(1, xy(101)).is_some());
+        assert!(cache.get(2, xy(101)).is_some());
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 3,
+                misses: 1,
+                entries: 2,
+                bytes: size * 2
+            }
+        );
+
+        // Too large to be cached
+        cache.insert(3, xy(101), column(100));
+        assert!(cache.get(3, xy(101)).is_none());
+        assert_eq!(cache.stats().entries, 2);
+
+        cache.clear();
+        assert_eq!(cache.stats().bytes, 0);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(fingerprint(b"BUFR"), fingerprint(b"BUFR"));
+        assert_ne!(fingerprint(b"BUFR"), fingerprint(b"BUFS"));
+    }
+}
@@ -1,27 +1,425 @@
 //! Reader for the data section of BUFR files
 
-use std::io::Read;
+use std::io::{Read, Seek, SeekFrom};
 
 use bitstream_io::{BigEndian, BitRead, BitReader};
 
 use crate::{
-    Error, ResolvedDescriptor, Value, XY, resolve_descriptors,
+    Error, Operator, ResolvedDescriptor, Value, XY,
+    checksum::ChecksumVerifier,
+    codec::{effective_width_ref_scale, missing_value, unpack, unpack_wide},
+    describe_operator,
+    hooks::ValueHooks,
+    missing::{MissingValue, MissingValues},
+    nesting_depth, resolve_descriptors,
     sections::{DataDescriptionSection, DataSectionHeader},
-    tables::{TableBEntry, TableDEntry, Tables},
+    tables::{TableBEntry, TableCEntry, TableDEntry, Tables},
+    unresolved_error,
+    validation::Strictness,
 };
 
 /// A reader for parsing BUFR data sections.
+#[derive(Clone)]
 pub struct DataReader<'a, R: Read> {
     data_spec: &'a DataSpec<'a>,
     current_subset_index: u16,
-    reader: BitReader<R, BigEndian>,
+    reader: BitReader<CountingReader<R>, BigEndian>,
+    /// Length of the data section declared in its header
+    section_length: u32,
+    /// Whether the padding at the end of the data section has been skipped
+    section_finished: bool,
+    /// Deviations from the declared structure found in lenient mode
+    warnings: Vec<String>,
     /// Stack for parsing nested data
     stack: smallvec::SmallVec<[StackEntry<'a>; 8]>,
+    /// Sequences and replication items the reader is in (see [`DataReader::path`])
+    path: smallvec::SmallVec<[PathSegment; 8]>,
+    /// "Signify data width" operator (2 06 YYY) for the immediately following descriptor
     temporary_operator: Option<XY>,
-    /// Current offset set by the "Change scale" operator
-    width_offset: i8,
     /// Current offset set by the "Change data width" operator
+    width_offset: i8,
+    /// Current offset set by the "Change scale" operator
     scale_offset: i8,
+    /// Current YYY of the "Increase scale, reference value and data width" operator
+    scale_increase: u8,
+    /// Width in characters set by the "Change width of CCITT IA5 field" operator (0 if unset)
+    string_width: u8,
+    /// Widths of the fields added by the "Add associated field" operators in effect
+    associated_field_widths: smallvec::SmallVec<[u8; 2]>,
+    /// Whether the associated field of the current element has already been read
+    associated_field_read: bool,
+    /// Bit width of new reference values while they are being defined by 2 03 YYY
+    reference_value_width: Option<u8>,
+    /// Reference values changed by the "Change reference values" operator
+    changed_reference_values: hashbrown::HashMap<XY, i32>,
+    /// Data present bit-map state
+    bitmap: Bitmap,
+    /// Whether the bit-map target of the current element has already been emitted
+    bitmap_target_read: bool,
+    /// Number of the following elements affected by the "Data not present" operator
+    data_not_present: u8,
+    /// Options set by the builder
+    options: ReaderOptions,
+    /// Number of values read so far
+    values_read: u64,
+    /// Number of events read so far
+    events_read: u64,
+    /// Bit offset in the data section at which the last event started
+    event_offset: u64,
+    /// Whether the values of the current subset are skipped (see [`DataReader::skip_subset`])
+    skipping: bool,
+    /// Events preceding an element (associated fields and bit-map targets), held until
+    /// the projection accepts or drops the element
+    held_events: Vec<DataEvent>,
+    /// Events to emit before reading further, i.e. held events and their element
+    queued_events: std::collections::VecDeque<DataEvent>,
+}
+
+/// State of a [`DataReader`] saved by [`DataReader::checkpoint`].
+pub struct Checkpoint<'a, R: Read> {
+    reader: DataReader<'a, R>,
+}
+
+impl<R: Read> std::fmt::Debug for Checkpoint<'_, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("subset", &self.reader.current_subset_index)
+            .field("event_offset", &self.reader.event_offset)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Number of events between checks of the deadline
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+/// Options of a [`DataReader`] (see [`DataReaderBuilder`]).
+#[derive(Debug, Clone, Default)]
+struct ReaderOptions {
+    strictness: Strictness,
+    character_decoding: CharacterDecoding,
+    unknown_widths: hashbrown::HashMap<XY, u8>,
+    missing_values: MissingValues,
+    value_hooks: ValueHooks,
+    max_replication_count: Option<u16>,
+    max_values: Option<u64>,
+    hints: Hints,
+    projection: Option<Projection>,
+    deadline: Option<std::time::Instant>,
+}
+
+/// Elements whose data events are emitted (see [`DataReaderBuilder::projection`] and
+/// [`DataReaderBuilder::filter`]).
+#[derive(Clone)]
+enum Projection {
+    Elements(hashbrown::HashSet<XY>),
+    Filter(std::sync::Arc<dyn Fn(XY) -> bool + Send + Sync>),
+}
+
+impl Projection {
+    fn contains(&self, xy: &XY) -> bool {
+        match self {
+            Projection::Elements(elements) => elements.contains(xy),
+            Projection::Filter(f) => f(*xy),
+        }
+    }
+}
+
+impl std::fmt::Debug for Projection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Projection::Elements(elements) => {
+                let mut elements: Vec<_> = elements.iter().collect();
+                elements.sort_unstable();
+                f.debug_tuple("Elements").field(&elements).finish()
+            }
+            Projection::Filter(_) => f.write_str("Filter(..)"),
+        }
+    }
+}
+
+/// Limits guarding a [`DataReader`] against corrupted or malicious data (see
+/// [`DataReaderBuilder::limits`]).
+///
+/// The limits are unset by default, so new ones can be added without changing the
+/// behavior of existing configurations:
+///
+/// ```
+/// use tinybufr::Limits;
+///
+/// let limits = Limits {
+///     max_values: Some(10_000_000),
+///     time_limit: Some(std::time::Duration::from_secs(10)),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// See [`DataReaderBuilder::max_replication_count`]
+    pub max_replication_count: Option<u16>,
+    /// See [`DataReaderBuilder::max_values`]
+    pub max_values: Option<u64>,
+    /// See [`DataReaderBuilder::time_limit`]
+    pub time_limit: Option<std::time::Duration>,
+}
+
+/// Hints on the data read by a [`DataReader`], which let it reserve memory up front
+/// (see [`DataReaderBuilder::hints`]).
+///
+/// Hints only affect the allocations of the reader, never the events it emits. They
+/// are unset by default, as are [`Limits`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hints {
+    /// Number of elements in a subset (e.g. as read from a previous message with the
+    /// same template), which the data present bit-maps refer back to
+    pub elements_per_subset: Option<usize>,
+}
+
+/// Builder of a [`DataReader`] with options.
+///
+/// The options default to those of [`DataReader::new`]: lenient section lengths, UTF-8
+/// characters and no missing value policies, value hooks, limits, hints or projection.
+/// Options are grouped by what they are for: how the data are decoded
+/// ([`DataReaderBuilder::decode_options`], missing value policies and value hooks), the
+/// [`Limits`] on the resources used, the [`Hints`] on the data, and which events are
+/// emitted ([`DataReaderBuilder::projection`] and [`DataReaderBuilder::filter`]). Each
+/// option can also be set on its own.
+///
+/// There is no [`RecoveryPolicy`](crate::recovery::RecoveryPolicy) among the options: a
+/// reader cannot go on after an error, as it no longer knows where the following data
+/// are, so recovering is up to what drives the readers of the messages (the input
+/// iterators, converters and sinks taking a policy). The events read before an error
+/// are what a policy keeps or drops. The deviations a reader can recover from by itself
+/// are those of the section length, reported as set by
+/// [`DataReaderBuilder::strictness`] (lenient when recovering).
+///
+/// ```no_run
+/// # fn example(spec: &tinybufr::DataSpec, data: &[u8]) -> Result<(), tinybufr::Error> {
+/// use tinybufr::{CharacterDecoding, DataReader, Hints, Limits, XY};
+///
+/// let mut reader = DataReader::builder(spec)
+///     .character_decoding(CharacterDecoding::Latin1)
+///     .limits(Limits {
+///         max_replication_count: Some(1000),
+///         ..Default::default()
+///     })
+///     .hints(Hints {
+///         elements_per_subset: Some(200),
+///     })
+///     .filter(|xy| xy.x == 12)
+///     .build(data)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DataReaderBuilder<'a, R> {
+    spec: &'a DataSpec<'a>,
+    options: ReaderOptions,
+    reader: std::marker::PhantomData<fn() -> R>,
+}
+
+impl<'a, R: Read> DataReaderBuilder<'a, R> {
+    /// Sets how deviations from the declared length of the data section are reported.
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.options.strictness = strictness;
+        self
+    }
+
+    /// Sets how character data (CCITT IA5) that is not valid UTF-8 is decoded.
+    pub fn character_decoding(mut self, character_decoding: CharacterDecoding) -> Self {
+        self.options.character_decoding = character_decoding;
+        self
+    }
+
+    /// Sets the widths of element descriptors not found in the tables, read as raw
+    /// values in lenient mode, as if signified by 2 06 YYY.
+    pub fn unknown_widths(mut self, unknown_widths: hashbrown::HashMap<XY, u8>) -> Self {
+        self.options.unknown_widths = unknown_widths;
+        self
+    }
+
+    /// Sets how missing values are encoded, for elements not following Regulation 94.1.3
+    /// (see [`crate::missing`]).
+    pub fn missing_values(mut self, missing_values: MissingValues) -> Self {
+        self.options.missing_values = missing_values;
+        self
+    }
+
+    /// Sets the hooks transforming the values of the emitted data events (see
+    /// [`crate::hooks`]).
+    pub fn value_hooks(mut self, value_hooks: ValueHooks) -> Self {
+        self.options.value_hooks = value_hooks;
+        self
+    }
+
+    /// Sets the strictness, the character decoding and the unknown widths at once.
+    pub fn decode_options(self, options: &DecodeOptions) -> Self {
+        self.strictness(options.strictness)
+            .character_decoding(options.character_decoding)
+            .unknown_widths(options.unknown_widths.clone())
+    }
+
+    /// Fails on delayed replication factors larger than `max`, which corrupted data
+    /// may have.
+    pub fn max_replication_count(mut self, max: u16) -> Self {
+        self.options.max_replication_count = Some(max);
+        self
+    }
+
+    /// Fails after reading more than `max` values (counting each value of compressed
+    /// data), bounding the memory used by consumers keeping them.
+    pub fn max_values(mut self, max: u64) -> Self {
+        self.options.max_values = Some(max);
+        self
+    }
+
+    /// Fails with [`Error::Timeout`] once the deadline has passed, so that a
+    /// pathological message cannot stall a worker.
+    ///
+    /// The deadline is checked every 1024 events, the first one included.
+    pub fn deadline(mut self, deadline: std::time::Instant) -> Self {
+        self.options.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline to `limit` from now (see [`DataReaderBuilder::deadline`]).
+    pub fn time_limit(self, limit: std::time::Duration) -> Self {
+        self.deadline(std::time::Instant::now() + limit)
+    }
+
+    /// Sets the limits that are set in `limits`, leaving the others as they are.
+    ///
+    /// The time limit starts from now.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        if let Some(max) = limits.max_replication_count {
+            self = self.max_replication_count(max);
+        }
+        if let Some(max) = limits.max_values {
+            self = self.max_values(max);
+        }
+        if let Some(limit) = limits.time_limit {
+            self = self.time_limit(limit);
+        }
+        self
+    }
+
+    /// Emits the data events of the given elements only.
+    ///
+    /// The other elements are still read, as the data must be read in order, but their
+    /// `Data` and `CompressedData` events are not emitted, nor are the associated fields
+    /// and bit-map targets preceding them.
+    pub fn projection(mut self, elements: impl IntoIterator<Item = XY>) -> Self {
+        self.options.projection = Some(Projection::Elements(elements.into_iter().collect()));
+        self
+    }
+
+    /// Emits the data events of the elements accepted by `f` only, as
+    /// [`DataReaderBuilder::projection`] does for a set of elements.
+    pub fn filter(mut self, f: impl Fn(XY) -> bool + Send + Sync + 'static) -> Self {
+        self.options.projection = Some(Projection::Filter(std::sync::Arc::new(f)));
+        self
+    }
+
+    /// Sets hints on the data, which let the reader reserve memory up front.
+    pub fn hints(mut self, hints: Hints) -> Self {
+        self.options.hints = hints;
+        self
+    }
+
+    /// Creates the reader, reading the header of the data section.
+    pub fn build(self, mut reader: R) -> Result<DataReader<'a, R>, Error> {
+        let elements_per_subset = self.options.hints.elements_per_subset.unwrap_or(0);
+        let data_section_header = DataSectionHeader::read(&mut reader)?;
+        Ok(DataReader {
+            data_spec: self.spec,
+            current_subset_index: 0,
+            reader: BitReader::endian(CountingReader::new(reader), BigEndian),
+            section_length: data_section_header.section_length,
+            section_finished: false,
+            warnings: vec![],
+            // Reserved up front so that deeply nested templates do not reallocate it
+            // while reading
+            stack: smallvec::SmallVec::with_capacity(
+                nesting_depth(&self.spec.root_descriptors) + 1,
+            ),
+            path: smallvec::SmallVec::with_capacity(nesting_depth(&self.spec.root_descriptors)),
+            temporary_operator: None,
+            scale_offset: 0,
+            width_offset: 0,
+            scale_increase: 0,
+            string_width: 0,
+            associated_field_widths: smallvec::SmallVec::new(),
+            associated_field_read: false,
+            reference_value_width: None,
+            changed_reference_values: hashbrown::HashMap::new(),
+            bitmap: Bitmap {
+                elements: Vec::with_capacity(elements_per_subset),
+                ..Default::default()
+            },
+            bitmap_target_read: false,
+            data_not_present: 0,
+            options: self.options,
+            values_read: 0,
+            events_read: 0,
+            event_offset: 32,
+            skipping: false,
+            held_events: vec![],
+            queued_events: std::collections::VecDeque::new(),
+        })
+    }
+}
+
+/// State of the data present bit-map (2 22 000 to 2 25 000 and 2 32 000, followed by 0 31 031).
+#[derive(Default, Clone)]
+struct Bitmap {
+    /// Elements read in the current subset, which bit-maps refer back to
+    elements: Vec<&'static TableBEntry>,
+    /// Number of elements preceding the first bit-map operator, which the following
+    /// bit-maps refer back from until 2 35 000
+    back_reference: Option<u32>,
+    /// Bits read so far, while a bit-map is being read
+    defining: Option<Vec<bool>>,
+    /// Whether the bit-map being read is kept for reuse (2 36 000)
+    define_for_reuse: bool,
+    /// Bit-map kept for reuse by 2 37 000
+    reusable: Option<Vec<u32>>,
+    /// Positions of the elements the remaining quality values relate to
+    targets: std::collections::VecDeque<u32>,
+    /// Latest first-order statistics (0 08 023), which applies to the values marked by 2 24 255
+    first_order_statistics: Option<i32>,
+    /// Latest difference statistics (0 08 024), which applies to the values marked by 2 25 255
+    difference_statistics: Option<i32>,
+}
+
+impl Bitmap {
+    /// Resets the state for a new subset, keeping the memory of the elements.
+    fn reset(&mut self) {
+        let mut elements = std::mem::take(&mut self.elements);
+        elements.clear();
+        *self = Bitmap {
+            elements,
+            ..Default::default()
+        };
+    }
+
+    /// Resolves the bits read so far into the positions of the elements marked as present.
+    fn finish_definition(&mut self) {
+        let Some(bits) = self.defining.take() else {
+            return;
+        };
+        let num_preceding = self.back_reference.unwrap_or(0);
+        let start = num_preceding.saturating_sub(bits.len() as u32);
+        let targets: Vec<u32> = bits
+            .iter()
+            .enumerate()
+            .filter(|(_, present)| **present)
+            .map(|(i, _)| start + i as u32)
+            .collect();
+        if self.define_for_reuse {
+            self.define_for_reuse = false;
+            self.reusable = Some(targets.clone());
+        }
+        self.targets = targets.into();
+    }
 }
 
 /// Data specification for reading BUFR data section.
@@ -31,6 +429,8 @@ pub struct DataSpec<'a> {
     pub number_of_subsets: u16,
     /// Indicates if the data is stored in "compressed" format (column oriented) or not
     pub is_compressed: bool,
+    /// Indicates if the data is observed data or other data
+    pub is_observed_data: bool,
     /// The sequence of descriptors stored in the header
     pub root_descriptors: Vec<ResolvedDescriptor<'a>>,
 }
@@ -43,40 +443,335 @@ impl<'a> DataSpec<'a> {
         Ok(Self {
             number_of_subsets: dds.number_of_subsets,
             is_compressed: dds.flags.is_compressed,
+            is_observed_data: dds.flags.is_observed_data,
             root_descriptors: resolve_descriptors(tables, &dds.descriptors)?,
         })
     }
+
+    /// Returns the leaf data elements in expansion order, with their effective encoding.
+    ///
+    /// Sequences and regular replications are expanded, and the descriptors of a delayed
+    /// replication are yielded once, as their count is only known from the data.
+    /// The operators changing the width, scale and reference value (2 01, 2 02, 2 07
+    /// and 2 08) are applied. Delayed replication factors, elements defining new
+    /// reference values (2 03 YYY) and unresolved descriptors are not yielded, and
+    /// the new reference values themselves are not applied as they are in the data.
+    pub fn iter_elements(&self) -> std::vec::IntoIter<ElementSpec<'a>> {
+        let mut walker = ElementWalker::default();
+        walker.walk(&self.root_descriptors);
+        walker.elements.into_iter()
+    }
+}
+
+/// Options for decoding messages that deviate slightly from the regulations.
+///
+/// Strict decoding (the default) stops at the first deviation. Lenient decoding goes
+/// on with a warning when:
+///
+/// - the total length or the length of the data section does not match the message
+///   (see [`split_message`](crate::sections::split_message) and
+///   [`DataReaderBuilder::strictness`])
+/// - there are extra bytes before the end section (`7777`)
+/// - an element descriptor not found in the tables has a width in `unknown_widths`,
+///   whose data is then read as raw values
+/// - the checksum verified by `checksum` is invalid or absent
+#[derive(Clone)]
+pub struct DecodeOptions {
+    pub strictness: Strictness,
+    /// Widths in bits of element descriptors not found in the tables
+    pub unknown_widths: hashbrown::HashMap<XY, u8>,
+    /// How character data that is not valid UTF-8 is decoded
+    pub character_decoding: CharacterDecoding,
+    /// Verifier of the checksum that the centre embeds in its messages, if any
+    pub checksum: Option<std::sync::Arc<dyn ChecksumVerifier>>,
+}
+
+impl std::fmt::Debug for DecodeOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecodeOptions")
+            .field("strictness", &self.strictness)
+            .field("unknown_widths", &self.unknown_widths)
+            .field("character_decoding", &self.character_decoding)
+            .field("checksum", &self.checksum.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self::strict()
+    }
+}
+
+impl DecodeOptions {
+    pub fn strict() -> Self {
+        Self {
+            strictness: Strictness::Strict,
+            unknown_widths: hashbrown::HashMap::new(),
+            character_decoding: CharacterDecoding::default(),
+            checksum: None,
+        }
+    }
+
+    pub fn lenient() -> Self {
+        Self {
+            strictness: Strictness::Lenient,
+            unknown_widths: hashbrown::HashMap::new(),
+            character_decoding: CharacterDecoding::default(),
+            checksum: None,
+        }
+    }
+}
+
+/// How character data (CCITT IA5) that is not valid UTF-8 is decoded.
+///
+/// Some centres put Latin-1 text or binary junk into character elements.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CharacterDecoding {
+    /// Fail on invalid UTF-8
+    #[default]
+    Utf8,
+    /// Replace invalid UTF-8 sequences with U+FFFD
+    Lossy,
+    /// Decode every byte as a Latin-1 (ISO 8859-1) character
+    Latin1,
+    /// Keep the characters as [`Value::Bytes`] if they are not valid UTF-8
+    Bytes,
+}
+
+impl CharacterDecoding {
+    /// Decodes characters into a value, or returns `None` if they are not valid UTF-8
+    /// in [`CharacterDecoding::Utf8`].
+    pub fn decode(self, bytes: Vec<u8>) -> Option<Value> {
+        match self {
+            Self::Latin1 => Some(Value::String(bytes.into_iter().map(char::from).collect())),
+            _ => match String::from_utf8(bytes) {
+                Ok(s) => Some(Value::String(s)),
+                Err(e) => match self {
+                    Self::Lossy => Some(Value::String(
+                        String::from_utf8_lossy(e.as_bytes()).into_owned(),
+                    )),
+                    Self::Bytes => Some(Value::Bytes(e.into_bytes())),
+                    _ => None,
+                },
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for CharacterDecoding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "utf8" => Ok(Self::Utf8),
+            "lossy" => Ok(Self::Lossy),
+            "latin1" => Ok(Self::Latin1),
+            "bytes" => Ok(Self::Bytes),
+            _ => Err(Error::Invalid(format!("Unknown character decoding: {s:?}"))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ElementSpec<'a> {
+    /// The Table B entry of the element
+    pub entry: &'a TableBEntry,
+    /// The bit width after the operators in effect
+    pub bit_width: u32,
+    /// The reference value after the operators in effect
+    pub reference_value: i32,
+    /// The scale after the operators in effect
+    pub scale: i8,
+    /// Indicates if the element is in a delayed replication
+    pub in_delayed_replication: bool,
+}
+
+/// Walks the descriptors of a [`DataSpec`] for [`DataSpec::iter_elements`].
+#[derive(Default)]
+struct ElementWalker<'a> {
+    width_offset: i8,
+    scale_offset: i8,
+    scale_increase: u8,
+    string_width: u8,
+    defining_reference_values: bool,
+    delayed_depth: usize,
+    elements: Vec<ElementSpec<'a>>,
+}
+
+impl<'a> ElementWalker<'a> {
+    fn walk(&mut self, descriptors: &[ResolvedDescriptor<'a>]) {
+        for desc in descriptors {
+            match desc {
+                ResolvedDescriptor::Data(b) if !self.defining_reference_values => {
+                    let (bit_width, reference_value, scale) = effective_width_ref_scale(
+                        b,
+                        self.width_offset,
+                        self.scale_offset,
+                        self.scale_increase,
+                        self.string_width,
+                    );
+                    self.elements.push(ElementSpec {
+                        entry: b,
+                        bit_width,
+                        reference_value,
+                        scale,
+                        in_delayed_replication: self.delayed_depth > 0,
+                    });
+                }
+                ResolvedDescriptor::Replication {
+                    y: 0, descriptors, ..
+                } => {
+                    self.delayed_depth += 1;
+                    self.walk(descriptors);
+                    self.delayed_depth -= 1;
+                }
+                ResolvedDescriptor::Replication { y, descriptors, .. } => {
+                    for _ in 0..*y {
+                        self.walk(descriptors);
+                    }
+                }
+                ResolvedDescriptor::Sequence(_, descriptors) => self.walk(descriptors),
+                ResolvedDescriptor::Operator(xy, _) => match Operator::from_xy(*xy) {
+                    Operator::ChangeWidth(offset) => self.width_offset = offset,
+                    Operator::ChangeScale(offset) => self.scale_offset = offset,
+                    Operator::CancelReferenceValues | Operator::ConcludeReferenceValues => {
+                        self.defining_reference_values = false
+                    }
+                    Operator::ChangeReferenceValues(_) => self.defining_reference_values = true,
+                    Operator::IncreaseScaleReferenceWidth(y) => self.scale_increase = y,
+                    Operator::ChangeStringWidth(width) => self.string_width = width,
+                    _ => {}
+                },
+                ResolvedDescriptor::Data(_) | ResolvedDescriptor::Unresolved(_) => {}
+            }
+        }
+    }
 }
 
 impl<'a, R: Read> DataReader<'a, R> {
-    pub fn new(mut reader: R, spec: &'a DataSpec<'a>) -> Result<DataReader<'a, R>, Error> {
-        let _data_section_header = DataSectionHeader::read(&mut reader)?;
-        Ok(DataReader {
-            data_spec: spec,
-            current_subset_index: 0,
-            reader: BitReader::endian(reader, BigEndian),
-            stack: smallvec::SmallVec::new(),
-            temporary_operator: None,
-            scale_offset: 0,
-            width_offset: 0,
-        })
+    /// Returns a builder of a reader with options, whose type of reader is given by
+    /// [`DataReaderBuilder::build`].
+    pub fn builder(spec: &'a DataSpec<'a>) -> DataReaderBuilder<'a, R> {
+        DataReaderBuilder {
+            spec,
+            options: ReaderOptions::default(),
+            reader: std::marker::PhantomData,
+        }
+    }
+
+    /// Creates a reader with the default options (see [`DataReader::builder`]).
+    pub fn new(reader: R, spec: &'a DataSpec<'a>) -> Result<DataReader<'a, R>, Error> {
+        DataReader::builder(spec).build(reader)
     }
 
     /// Unwraps this `DataReader`, returning the underlying reader.
     pub fn into_inner(self) -> R {
-        self.reader.into_reader()
+        self.reader.into_reader().inner
+    }
+
+    /// Reads the remaining events, if any, and returns the underlying reader positioned
+    /// after the data section, at the end section (Section 5).
+    ///
+    /// Unlike [`DataReader::into_inner`], this also skips the padding of a data section
+    /// whose last event has not been read yet, with a different length than declared
+    /// reported as by [`DataReader::read_event`].
+    pub fn finish(mut self) -> Result<R, Error> {
+        while !matches!(self.read_event()?, DataEvent::Eof) {}
+        Ok(self.into_inner())
+    }
+
+    /// Saves the state of the reader, to go back to it with [`DataReader::restore`],
+    /// e.g. to decode a subset again after inspecting it.
+    ///
+    /// This needs a reader whose clones read the same data from the same position,
+    /// such as `&[u8]`. The state of the reader is copied, along with the data present
+    /// bit-map of the current subset, but not the data.
+    pub fn checkpoint(&self) -> Checkpoint<'a, R>
+    where
+        R: Clone,
+    {
+        Checkpoint {
+            reader: self.clone(),
+        }
+    }
+
+    /// Goes back to the state saved by [`DataReader::checkpoint`], which can be restored
+    /// any number of times.
+    ///
+    /// The warnings found since the checkpoint are forgotten, and found again when the
+    /// same data is read.
+    pub fn restore(&mut self, checkpoint: &Checkpoint<'a, R>)
+    where
+        R: Clone,
+    {
+        *self = checkpoint.reader.clone();
+    }
+
+    /// Finishes the data section as [`DataReader::finish`] does, then checks the end
+    /// section of a message of the given edition with [`ensure_end_section`].
+    ///
+    /// Returns the underlying reader positioned after the message.
+    ///
+    /// [`ensure_end_section`]: crate::ensure_end_section
+    pub fn finish_message(self, edition: u8) -> Result<R, Error> {
+        let mut reader = self.finish()?;
+        crate::ensure_end_section(edition, &mut reader)?;
+        Ok(reader)
+    }
+
+    /// Reads all remaining elements, calling `f(subset_index, position, xy, value)` for each.
+    ///
+    /// `position` is the index of the element in the expanded subset.
+    /// Compressed values are visited element by element.
+    pub(crate) fn for_each_element(
+        &mut self,
+        mut f: impl FnMut(u16, u32, XY, &Value) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut subset_index = 0;
+        let mut position = 0;
+        loop {
+            match self.read_event()? {
+                DataEvent::SubsetStart(idx) => {
+                    subset_index = idx;
+                    position = 0;
+                }
+                DataEvent::Data { xy, value, .. } => {
+                    f(subset_index, position, xy, &value)?;
+                    position += 1;
+                }
+                DataEvent::CompressedData { xy, values, .. } => {
+                    for (subset_index, value) in values.iter().enumerate() {
+                        f(subset_index as u16, position, xy, value)?;
+                    }
+                    position += 1;
+                }
+                DataEvent::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(())
     }
 }
 
+#[derive(Clone)]
 struct StackEntry<'a> {
     ty: StackEntryType,
     descriptors: &'a [ResolvedDescriptor<'a>],
     next: u16,
 }
 
+#[derive(Clone)]
 enum StackEntryType {
     Sequence,
-    Replication { remaining: u16, in_item: bool },
+    Replication {
+        /// Index of the replication descriptor in its parent
+        idx: u16,
+        remaining: u16,
+        /// Number of the items started so far
+        items: u16,
+        in_item: bool,
+    },
 }
 
 impl<'a> StackEntry<'a> {
@@ -88,10 +783,12 @@ impl<'a> StackEntry<'a> {
         }
     }
 
-    fn new_replication(descriptors: &'a [ResolvedDescriptor<'a>], count: u16) -> Self {
+    fn new_replication(idx: u16, descriptors: &'a [ResolvedDescriptor<'a>], count: u16) -> Self {
         Self {
             ty: StackEntryType::Replication {
+                idx,
                 remaining: count,
+                items: 0,
                 in_item: false,
             },
             descriptors,
@@ -100,19 +797,221 @@ impl<'a> StackEntry<'a> {
     }
 }
 
+/// Whether the value of an element is needed to read the following data, i.e. it is
+/// a data present indicator or another element of class 31, or an element signifying
+/// statistics of the following bit-map (0 08 023 and 0 08 024), or quality information
+/// (class 33) whose reading depends on the bit-map.
+fn is_needed_for_skipping(xy: XY) -> bool {
+    matches!(xy, XY { x: 31 | 33, .. } | XY { x: 8, y: 23 | 24 })
+}
+
 pub(crate) fn three_bytes_to_u32(bytes: [u8; 3]) -> u32 {
     (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | (bytes[2] as u32)
 }
 
+/// A reader counting the bytes read, to check the data section against its declared length.
+#[derive(Clone)]
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R> CountingReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// Only reports the number of bytes read as the position, for
+/// [`BitReader::position_in_bits`].
+impl<R> Seek for CountingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.count),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Data sections are read sequentially",
+            )),
+        }
+    }
+}
+
+/// Skips the padding up to the declared length of a data section whose data has
+/// been read, the 4 bytes of its header excluded.
+///
+/// Up to an octet of padding is expected after the last bit of data, plus one more
+/// for the even length required by editions 2 and 3. Returns a description of any
+/// other difference from the declared length.
+pub(crate) fn finish_data_section<R: Read>(
+    reader: &mut BitReader<CountingReader<R>, BigEndian>,
+    section_length: u32,
+) -> Result<Option<String>, Error> {
+    reader.byte_align();
+    let reader = reader.reader().expect("Reader should be byte-aligned");
+    let consumed = 4 + reader.count;
+    let declared = section_length as u64;
+    if consumed > declared {
+        return Ok(Some(format!(
+            "Data section overruns its declared length of {declared} bytes by {} bytes",
+            consumed - declared
+        )));
+    }
+    let remaining = declared - consumed;
+    let skipped = std::io::copy(
+        &mut reader.inner.by_ref().take(remaining),
+        &mut std::io::sink(),
+    )?;
+    Ok(if skipped < remaining {
+        Some(format!(
+            "Data section ends {} bytes before its declared length of {declared} bytes",
+            remaining - skipped
+        ))
+    } else if remaining > 1 {
+        Some(format!(
+            "Data section has {remaining} bytes left after the data (declared length {declared} bytes)"
+        ))
+    } else {
+        None
+    })
+}
+
+/// Describes a data element in errors about compressed data.
+pub(crate) fn element_label(xy: XY) -> String {
+    format!("element 0 {:02} {:03}", xy.x, xy.y)
+}
+
+/// Checks that the increments (NBINC bits each) of a compressed value are not
+/// wider than the value itself.
+pub(crate) fn check_nbinc(
+    nbinc: u8,
+    bit_width: u32,
+    what: impl FnOnce() -> String,
+) -> Result<(), Error> {
+    if nbinc as u32 > bit_width {
+        return Err(Error::Invalid(format!(
+            "NBINC {nbinc} exceeds the width {bit_width} of {} in compressed data",
+            what()
+        )));
+    }
+    Ok(())
+}
+
+/// Whether an increment of compressed data has all its bits set, which means the value
+/// is missing for its subset only.
+pub(crate) fn is_missing_increment(inc: u64, nbinc: u8) -> bool {
+    inc == missing_value(nbinc as u32)
+}
+
+/// Adds an increment to the local reference value of a compressed value.
+///
+/// The sum must fit in the width of the value; otherwise the data is corrupted
+/// and would silently decode to a wrong value.
+pub(crate) fn add_increment(
+    local_ref_value: u64,
+    inc: u64,
+    bit_width: u32,
+    what: impl FnOnce() -> String,
+) -> Result<u64, Error> {
+    match local_ref_value.checked_add(inc) {
+        Some(v) if bit_width >= 64 || v >> bit_width == 0 => Ok(v),
+        _ => Err(Error::Invalid(format!(
+            "Increment {inc} added to the local reference value {local_ref_value} \
+             overflows the width {bit_width} of {} in compressed data",
+            what()
+        ))),
+    }
+}
+
+/// Raw bits of an element whose encoding is unknown.
+#[derive(Clone, PartialEq, Eq)]
+pub enum RawValue {
+    /// Value of up to 64 bits
+    Integer(u64),
+    /// Value wider than 64 bits in big-endian octets, the first of which holds the
+    /// leading bits when the width is not a multiple of 8
+    Bytes(Vec<u8>),
+}
+
+impl RawValue {
+    /// The value with all bits set, which is missing for known elements.
+    pub fn all_ones(bit_width: u32) -> Self {
+        if bit_width <= 64 {
+            return RawValue::Integer(u64::MAX.checked_shr(64 - bit_width).unwrap_or(0));
+        }
+        let mut bytes = vec![0xff; bit_width.div_ceil(8) as usize];
+        if !bit_width.is_multiple_of(8) {
+            bytes[0] = (1 << (bit_width % 8)) - 1;
+        }
+        RawValue::Bytes(bytes)
+    }
+
+    /// Adds an increment of compressed data to the local reference value.
+    fn add_increment(
+        &self,
+        inc: u64,
+        bit_width: u32,
+        what: impl FnOnce() -> String,
+    ) -> Result<Self, Error> {
+        let mut bytes = match self {
+            RawValue::Integer(v) => {
+                return add_increment(*v, inc, bit_width, what).map(RawValue::Integer);
+            }
+            RawValue::Bytes(bytes) => bytes.clone(),
+        };
+        let mut carry = inc as u128;
+        for byte in bytes.iter_mut().rev() {
+            carry += *byte as u128;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        if carry > 0 || (!bit_width.is_multiple_of(8) && bytes[0] >> (bit_width % 8) > 0) {
+            return Err(Error::Invalid(format!(
+                "Increment {inc} added to the local reference value overflows the width \
+                 {bit_width} of {} in compressed data",
+                what()
+            )));
+        }
+        Ok(RawValue::Bytes(bytes))
+    }
+}
+
+impl std::fmt::Debug for RawValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RawValue::Integer(v) => write!(f, "{v}"),
+            RawValue::Bytes(bytes) => {
+                write!(f, "0x")?;
+                bytes.iter().try_for_each(|b| write!(f, "{b:02x}"))
+            }
+        }
+    }
+}
+
 /// Event emitted by [`DataReader`].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DataEvent {
     SubsetStart(u16),
     SubsetEnd,
     CompressedStart,
     ReplicationStart {
         idx: u16,
+        /// Number of items, which is the raw factor read from the data for delayed replications
         count: u16,
+        /// Width of the delayed replication factor (1 for 0 31 000), or 0 for regular replications
+        factor_bits: u8,
+        /// Factors of each subset, when they vary between the subsets of compressed data.
+        ///
+        /// `count` is then the largest of them, and the items beyond the factor of a
+        /// subset are padding for that subset.
+        subset_counts: Option<Vec<u16>>,
     },
     ReplicationItemStart,
     ReplicationItemEnd,
@@ -126,179 +1025,735 @@ pub enum DataEvent {
         idx: u16,
         x: u8,
         value: i32,
+        /// Operator name from Table C
+        name: Option<&'static str>,
+        /// The operation, as interpreted by the reader
+        operator: Operator,
     },
     Data {
         idx: u16,
         xy: XY,
+        /// The Table B entry of the element
+        entry: &'static TableBEntry,
         value: Value,
     },
     CompressedData {
         idx: u16,
         xy: XY,
+        /// The Table B entry of the element
+        entry: &'static TableBEntry,
+        values: Vec<Value>,
+    },
+    /// Associated field (2 04 YYY) of the element in the immediately following `Data` event
+    AssociatedField {
+        idx: u16,
+        value: Value,
+    },
+    /// Associated fields (2 04 YYY) of the element in the immediately following `CompressedData` event
+    CompressedAssociatedField {
+        idx: u16,
+        values: Vec<Value>,
+    },
+    /// Characters signified by 2 05 YYY, which are data without an element descriptor
+    SignifiedCharacters {
+        idx: u16,
+        value: Value,
+    },
+    /// Characters signified by 2 05 YYY in compressed data
+    CompressedSignifiedCharacters {
+        idx: u16,
+        values: Vec<Value>,
+    },
+    /// New reference value of an element defined by 2 03 YYY
+    ReferenceValueChanged {
+        idx: u16,
+        xy: XY,
+        reference_value: i32,
+    },
+    /// Element that the quality information (class 33) in the immediately following
+    /// `Data` or `CompressedData` event relates to, according to the data present bit-map
+    ///
+    /// `position` is the index of the element among the elements of the subset,
+    /// counted in the order of `Data` or `CompressedData` events.
+    BitmapTarget {
+        idx: u16,
+        position: u32,
+    },
+    /// Value marked by 2 XX 255 (substituted, statistical or replaced/retained value) of
+    /// the element at `position` in the subset, whose descriptor is `xy`
+    ///
+    /// For first-order statistical values (2 24 255), `significance` is the preceding
+    /// first-order statistics (0 08 023), e.g. 2 for maximum or 4 for mean. For difference
+    /// statistical values (2 25 255), it is the preceding difference statistics (0 08 024).
+    Quality {
+        idx: u16,
+        x: u8,
+        position: u32,
+        xy: XY,
+        significance: Option<i32>,
+        value: Value,
+    },
+    /// Values marked by 2 XX 255 (see [`DataEvent::Quality`]) in compressed data
+    CompressedQuality {
+        idx: u16,
+        x: u8,
+        position: u32,
+        xy: XY,
+        significance: Option<i32>,
         values: Vec<Value>,
     },
+    /// Local element descriptor `xy` not found in Table B, whose data of `width` bits
+    /// is signified by 2 06 YYY (or given by [`DataReaderBuilder::unknown_widths`])
+    ///
+    /// `values` are the raw values of the element (one for each subset in compressed
+    /// data), which are opaque as the encoding of the element is unknown.
+    UnknownLocalElement {
+        idx: u16,
+        xy: XY,
+        width: u8,
+        values: Vec<RawValue>,
+    },
     Eof,
 }
 
-impl<'a, R: Read> DataReader<'a, R> {
-    /// Reads the next data event.
-    pub fn read_event(&mut self) -> Result<DataEvent, Error> {
-        if self.stack.is_empty() {
-            if self.data_spec.is_compressed {
-                if self.current_subset_index > 0 {
-                    return Ok(DataEvent::Eof);
-                }
-            } else if self.current_subset_index == self.data_spec.number_of_subsets {
-                return Ok(DataEvent::Eof);
-            }
+/// Iterator over the events of a [`DataReader`], returned by [`DataReader::events`].
+pub struct Events<'r, 'a, R: Read> {
+    reader: &'r mut DataReader<'a, R>,
+    finished: bool,
+}
 
-            self.stack
-                .push(StackEntry::new_sequence(&self.data_spec.root_descriptors));
-            let subset_idx = self.current_subset_index;
-            self.current_subset_index += 1;
-            if self.data_spec.is_compressed {
-                return Ok(DataEvent::CompressedStart);
-            } else {
-                return Ok(DataEvent::SubsetStart(subset_idx));
+impl<R: Read> Iterator for Events<'_, '_, R> {
+    type Item = Result<DataEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.reader.read_event() {
+            Ok(DataEvent::Eof) => {
+                self.finished = true;
+                None
+            }
+            Ok(event) => Some(Ok(event)),
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
             }
         }
-        self.process_next_descriptor()
     }
+}
 
-    fn process_next_descriptor(&mut self) -> Result<DataEvent, Error> {
-        let top = self.stack.last_mut().expect("Stack should not be empty");
-        if let StackEntryType::Replication { remaining, in_item } = &mut top.ty {
-            if top.next as usize >= top.descriptors.len() {
-                if *in_item {
-                    *in_item = false;
-                    return Ok(DataEvent::ReplicationItemEnd);
+impl<R: Read> std::iter::FusedIterator for Events<'_, '_, R> {}
+
+/// A level of the path of [`DataReader::path`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// A Table D sequence
+    Sequence(XY),
+    /// An item of a replication
+    ReplicationItem {
+        /// Index of the replication descriptor in its sequence
+        idx: u16,
+        /// Index of the item, from 0
+        item: u16,
+    },
+}
+
+impl<'a, R: Read> DataReader<'a, R> {
+    /// Reads the next data event.
+    ///
+    /// Once all subsets are read, the padding up to the declared length of the data
+    /// section is skipped, and a different length is reported as specified by
+    /// [`DataReaderBuilder::strictness`] (see [`DataReader::warnings`]).
+    pub fn read_event(&mut self) -> Result<DataEvent, Error> {
+        if let Some(event) = self.queued_events.pop_front() {
+            return Ok(event);
+        }
+        loop {
+            let event = self.read_unprojected_event()?;
+            if self.options.projection.is_some()
+                && matches!(
+                    event,
+                    DataEvent::AssociatedField { .. }
+                        | DataEvent::CompressedAssociatedField { .. }
+                        | DataEvent::BitmapTarget { .. }
+                )
+            {
+                self.held_events.push(event);
+                continue;
+            }
+            let values = match &event {
+                DataEvent::Data { xy, .. } => Some((xy, 1)),
+                DataEvent::CompressedData { xy, values, .. } => Some((xy, values.len() as u64)),
+                _ => None,
+            };
+            if let Some((xy, count)) = values {
+                self.values_read += count;
+                if let Some(max) = self.options.max_values
+                    && self.values_read > max
+                {
+                    return Err(Error::Invalid(format!(
+                        "More values in the data section than the limit of {max}"
+                    )));
                 }
-                if *remaining > 0 {
-                    *remaining -= 1;
-                    top.next = 0;
-                    *in_item = true;
-                    return Ok(DataEvent::ReplicationItemStart);
-                } else {
-                    self.stack.pop();
-                    return Ok(DataEvent::ReplicationEnd);
+                if let Some(projection) = &self.options.projection
+                    && !projection.contains(xy)
+                {
+                    self.held_events.clear();
+                    continue;
+                }
+            }
+            if !self.held_events.is_empty() {
+                self.queued_events.extend(self.held_events.drain(..));
+                self.queued_events.push_back(event);
+                return Ok(self.queued_events.pop_front().unwrap());
+            }
+            return Ok(event);
+        }
+    }
+
+    /// Returns an iterator over the remaining events, up to [`DataEvent::Eof`] excluded.
+    ///
+    /// The iterator ends after the first error, as the reader cannot find where the
+    /// following data are.
+    pub fn events(&mut self) -> Events<'_, 'a, R> {
+        Events {
+            reader: self,
+            finished: false,
+        }
+    }
+
+    /// Skips the next subset, or the rest of the current one if its start has been read,
+    /// without decoding its values. Returns `false` if there are no more subsets.
+    ///
+    /// Only what determines the position of the following data is read: delayed
+    /// replication factors, operators and the elements defining bit-maps. Sequences
+    /// and regular replications of elements with fixed widths are skipped at once.
+    /// Compressed data is not supported, as its values are not stored subset by subset.
+    pub fn skip_subset(&mut self) -> Result<bool, Error> {
+        if self.data_spec.is_compressed {
+            return Err(Error::NotSupported(
+                "Skipping subsets of compressed data".to_string(),
+            ));
+        }
+        if self.stack.is_empty() {
+            match self.read_unprojected_event()? {
+                DataEvent::SubsetStart(_) => {}
+                DataEvent::Eof => return Ok(false),
+                ev => return Err(Error::Fatal(format!("Unexpected event: {ev:?}"))),
+            }
+        }
+        // Held and queued events belong to the skipped values
+        self.held_events.clear();
+        self.queued_events.clear();
+        self.skipping = true;
+        let result = loop {
+            match self.read_unprojected_event() {
+                Ok(DataEvent::SubsetEnd) => break Ok(true),
+                Ok(_) => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.skipping = false;
+        result
+    }
+
+    fn read_unprojected_event(&mut self) -> Result<DataEvent, Error> {
+        if let Some(deadline) = self.options.deadline
+            && self.events_read.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+            && std::time::Instant::now() >= deadline
+        {
+            return Err(Error::Timeout(format!(
+                "Deadline passed after {} events and {} values ({} of {} subsets started)",
+                self.events_read,
+                self.values_read,
+                self.current_subset_index,
+                self.data_spec.number_of_subsets
+            )));
+        }
+        self.events_read += 1;
+        self.event_offset = 32 + self.reader.position_in_bits()?;
+        let event = self.next_event()?;
+        if let DataEvent::Eof = event
+            && !self.section_finished
+        {
+            self.section_finished = true;
+            let section_length = self.section_length;
+            // Whatever a message without subsets has in its data section is skipped
+            if let Some(message) = finish_data_section(&mut self.reader, section_length)?
+                && self.data_spec.number_of_subsets > 0
+            {
+                match self.options.strictness {
+                    Strictness::Strict => return Err(Error::Invalid(message)),
+                    Strictness::Lenient => self.warnings.push(message),
+                }
+            }
+        }
+        Ok(self.options.value_hooks.apply_to_event(event))
+    }
+
+    /// Returns the deviations from the structure declared in the sections found so far
+    /// in lenient mode.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Returns the bit offset at which the data of the last event started, counted from
+    /// the start of the data section (Section 4), whose data start at bit 32 after the
+    /// section length and the reserved octet.
+    ///
+    /// Events without data of their own (e.g. [`DataEvent::SequenceStart`]) are at the
+    /// offset of the data that follows. After an error, this is the offset of the event
+    /// that failed to be read.
+    pub fn event_offset(&self) -> u64 {
+        self.event_offset
+    }
+
+    /// Returns the Table D sequences and replication items the last event was read in,
+    /// outermost first.
+    ///
+    /// After a [`DataEvent::SequenceStart`] or [`DataEvent::ReplicationItemStart`], the
+    /// path includes the sequence or item just started, and after the matching end event
+    /// it no longer does. Together with the `idx` of an event, the path identifies a
+    /// value within its subset regardless of the values of the other elements.
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    fn next_event(&mut self) -> Result<DataEvent, Error> {
+        if self.stack.is_empty() {
+            if self.data_spec.is_compressed {
+                // Messages without subsets (e.g. deletions or heartbeats) have no data
+                if self.current_subset_index > 0 || self.data_spec.number_of_subsets == 0 {
+                    return Ok(DataEvent::Eof);
                 }
+            } else if self.current_subset_index == self.data_spec.number_of_subsets {
+                return Ok(DataEvent::Eof);
+            }
+
+            self.stack
+                .push(StackEntry::new_sequence(&self.data_spec.root_descriptors));
+            self.path.clear();
+            self.bitmap.reset();
+            self.data_not_present = 0;
+            let subset_idx = self.current_subset_index;
+            self.current_subset_index += 1;
+            if self.data_spec.is_compressed {
+                return Ok(DataEvent::CompressedStart);
+            } else {
+                return Ok(DataEvent::SubsetStart(subset_idx));
+            }
+        }
+        self.process_next_descriptor()
+    }
+
+    fn process_next_descriptor(&mut self) -> Result<DataEvent, Error> {
+        let top = self.stack.last_mut().expect("Stack should not be empty");
+        if let StackEntryType::Replication {
+            idx,
+            remaining,
+            items,
+            in_item,
+        } = &mut top.ty
+            && top.next as usize >= top.descriptors.len()
+        {
+            if *in_item {
+                *in_item = false;
+                self.path.pop();
+                return Ok(DataEvent::ReplicationItemEnd);
+            }
+            if *remaining > 0 {
+                *remaining -= 1;
+                top.next = 0;
+                *in_item = true;
+                self.path.push(PathSegment::ReplicationItem {
+                    idx: *idx,
+                    item: *items,
+                });
+                *items += 1;
+                return Ok(DataEvent::ReplicationItemStart);
+            } else {
+                self.stack.pop();
+                return Ok(DataEvent::ReplicationEnd);
             }
         };
 
         if top.next as usize >= top.descriptors.len() {
             self.stack.pop();
             return match (self.stack.last(), self.data_spec.is_compressed) {
-                (Some(_), _) => Ok(DataEvent::SequenceEnd),
+                (Some(_), _) => {
+                    self.path.pop();
+                    Ok(DataEvent::SequenceEnd)
+                }
                 (None, true) => Ok(DataEvent::Eof),
                 (None, false) => Ok(DataEvent::SubsetEnd),
             };
         }
 
-        let descriptors = &top.descriptors;
+        let descriptors = top.descriptors;
         let current_desc = &descriptors[top.next as usize];
         let idx = top.next;
         top.next += 1;
         match current_desc {
-            ResolvedDescriptor::Data(b) => self.handle_data_descriptor(idx, b),
+            ResolvedDescriptor::Data(b) => {
+                // Known local descriptors are read as defined in the tables
+                self.temporary_operator = None;
+                let event = self.handle_data_descriptor(idx, b)?;
+                self.record_element(b, &event);
+                Ok(event)
+            }
+            ResolvedDescriptor::Replication { y, descriptors, .. }
+                if *y > 0 && self.skip_fixed_width(descriptors, *y)? =>
+            {
+                self.stack
+                    .push(StackEntry::new_replication(idx, descriptors, 0));
+                Ok(DataEvent::ReplicationStart {
+                    idx,
+                    count: 0,
+                    factor_bits: 0,
+                    subset_counts: None,
+                })
+            }
             ResolvedDescriptor::Replication {
                 y,
                 descriptors,
                 delayed_bits,
             } => self.handle_replication_descriptor(idx, *y, descriptors, *delayed_bits),
-            ResolvedDescriptor::Operator(xy) => self.handle_operator_descriptor(idx, *xy),
+            ResolvedDescriptor::Operator(xy, c) => self.handle_operator_descriptor(idx, *xy, *c),
+            ResolvedDescriptor::Sequence(d, elements) if self.skip_fixed_width(elements, 1)? => {
+                self.stack.push(StackEntry::new_sequence(&[]));
+                self.path.push(PathSegment::Sequence(d.xy));
+                Ok(DataEvent::SequenceStart { idx, xy: d.xy })
+            }
             ResolvedDescriptor::Sequence(d, elements) => {
                 self.handle_sequence_descriptor(idx, d, elements)
             }
+            ResolvedDescriptor::Unresolved(desc) => match self.temporary_operator.take() {
+                Some(XY { x: 6, y }) if desc.f == 0 => self.read_local_element(idx, desc.xy(), y),
+                _ if desc.f == 0 && self.options.strictness == Strictness::Lenient => {
+                    let Some(&width) = self.options.unknown_widths.get(&desc.xy()) else {
+                        return Err(unresolved_error(desc));
+                    };
+                    self.warnings.push(format!(
+                        "Unknown {} read as raw values ({width} bits)",
+                        element_label(desc.xy())
+                    ));
+                    self.read_local_element(idx, desc.xy(), width)
+                }
+                _ => Err(unresolved_error(desc)),
+            },
+        }
+    }
+
+    /// Reads the data of an unknown local element, whose width is given by 2 06 YYY,
+    /// as raw values.
+    ///
+    /// Compressed data is taken to be compressed as numeric values are.
+    fn read_local_element(&mut self, idx: u16, xy: XY, width: u8) -> Result<DataEvent, Error> {
+        let bit_width = width as u32;
+        let local_ref_value = self.read_raw_value(bit_width)?;
+        if !self.data_spec.is_compressed {
+            return Ok(DataEvent::UnknownLocalElement {
+                idx,
+                xy,
+                width,
+                values: vec![local_ref_value],
+            });
+        }
+        let nbinc = self.reader.read::<6, u8>()?;
+        check_nbinc(nbinc, bit_width, || element_label(xy))?;
+        let values = if nbinc == 0 {
+            vec![local_ref_value; self.data_spec.number_of_subsets as usize]
+        } else {
+            (0..self.data_spec.number_of_subsets)
+                .map(|_| {
+                    let inc: u64 = self.reader.read_var(nbinc as u32)?;
+                    // Missing values are all bits set, as in uncompressed data
+                    if is_missing_increment(inc, nbinc) {
+                        return Ok(RawValue::all_ones(bit_width));
+                    }
+                    local_ref_value.add_increment(inc, bit_width, || element_label(xy))
+                })
+                .collect::<Result<Vec<RawValue>, Error>>()?
+        };
+        Ok(DataEvent::UnknownLocalElement {
+            idx,
+            xy,
+            width,
+            values,
+        })
+    }
+
+    fn read_raw_value(&mut self, bit_width: u32) -> Result<RawValue, Error> {
+        if bit_width <= 64 {
+            return Ok(RawValue::Integer(self.reader.read_var(bit_width)?));
+        }
+        let mut bytes = Vec::with_capacity(bit_width.div_ceil(8) as usize);
+        if !bit_width.is_multiple_of(8) {
+            bytes.push(self.reader.read_var(bit_width % 8)?);
         }
+        for _ in 0..bit_width / 8 {
+            bytes.push(self.reader.read::<8, u8>()?);
+        }
+        Ok(RawValue::Bytes(bytes))
     }
 
     // f = 0
-    fn handle_data_descriptor(&mut self, idx: u16, b: &TableBEntry) -> Result<DataEvent, Error> {
-        let (bit_width, ref_value, scale) = (
-            (b.bits as i32 + self.width_offset as i32) as u32,
-            b.reference_value,
-            (b.scale as i16 + self.scale_offset as i16) as i8,
+    fn handle_data_descriptor(
+        &mut self,
+        idx: u16,
+        b: &'static TableBEntry,
+    ) -> Result<DataEvent, Error> {
+        // Elements between 2 03 YYY and 2 03 255 define new reference values
+        if let Some(width) = self.reference_value_width {
+            return self.read_new_reference_value(idx, b, width);
+        }
+
+        // After 2 21 YYY, only elements of classes 1-9 and 31 have data
+        // (not counted again when the same element is processed for its pre-events)
+        if self.data_not_present > 0 && !self.associated_field_read && !self.bitmap_target_read {
+            self.data_not_present -= 1;
+            if !matches!(b.xy.x, 1..=9 | 31) {
+                return Ok(self.missing_event(idx, b));
+            }
+        }
+
+        // Bit-map is complete when an element other than a data present indicator follows
+        if b.xy != (XY { x: 31, y: 31 }) {
+            self.bitmap.finish_definition();
+        }
+
+        // Quality information (class 33) relates to the next element marked in the bit-map
+        if b.xy.x == 33
+            && !self.bitmap_target_read
+            && let Some(position) = self.bitmap.targets.pop_front()
+        {
+            self.bitmap_target_read = true;
+            // Process the same descriptor again for the element itself
+            self.stack
+                .last_mut()
+                .expect("Stack should not be empty")
+                .next -= 1;
+            return Ok(DataEvent::BitmapTarget { idx, position });
+        }
+
+        // Associated fields precede every element except those of class 31
+        if !self.associated_field_widths.is_empty() && b.xy.x != 31 {
+            if !self.associated_field_read {
+                self.associated_field_read = true;
+                // Process the same descriptor again for the element itself
+                self.stack
+                    .last_mut()
+                    .expect("Stack should not be empty")
+                    .next -= 1;
+                return self.read_associated_field(idx, b.xy);
+            }
+            self.associated_field_read = false;
+        }
+        self.bitmap_target_read = false;
+
+        let (bit_width, mut ref_value, scale) = effective_width_ref_scale(
+            b,
+            self.width_offset,
+            self.scale_offset,
+            self.scale_increase,
+            self.string_width,
         );
+        if let Some(&new_ref_value) = self.changed_reference_values.get(&b.xy) {
+            ref_value = new_ref_value;
+        }
+        self.read_element(idx, b, bit_width, ref_value, scale)
+    }
+
+    /// While skipping a subset, skips `count` repetitions of descriptors at once if they
+    /// are only elements with fixed widths, and returns whether they were skipped.
+    fn skip_fixed_width(
+        &mut self,
+        descriptors: &'a [ResolvedDescriptor<'a>],
+        count: u8,
+    ) -> Result<bool, Error> {
+        // Operators in effect may add fields to or remove values from the elements
+        if !self.skipping
+            || self.temporary_operator.is_some()
+            || !self.associated_field_widths.is_empty()
+            || self.reference_value_width.is_some()
+            || self.data_not_present > 0
+        {
+            return Ok(false);
+        }
+        let mut elements = vec![];
+        let Some(bits) = self
+            .fixed_width(descriptors, &mut elements)
+            .and_then(|bits| bits.checked_mul(count as u32))
+        else {
+            return Ok(false);
+        };
+        self.reader.skip(bits)?;
+        // The elements are still counted for the bit-maps referring back to them
+        self.bitmap.finish_definition();
+        for _ in 0..count {
+            self.bitmap.elements.extend_from_slice(&elements);
+        }
+        Ok(true)
+    }
+
+    /// Returns the total width of descriptors made only of elements whose values are not
+    /// needed for reading the rest (i.e. not defining bit-maps or statistics), collecting
+    /// them, or `None` if there are others.
+    fn fixed_width(
+        &self,
+        descriptors: &'a [ResolvedDescriptor<'a>],
+        elements: &mut Vec<&'static TableBEntry>,
+    ) -> Option<u32> {
+        let mut bits = 0u32;
+        for desc in descriptors {
+            let width = match desc {
+                ResolvedDescriptor::Data(b) if !is_needed_for_skipping(b.xy) => {
+                    elements.push(b);
+                    self.element_width(b)
+                }
+                ResolvedDescriptor::Replication { y, descriptors, .. } if *y > 0 => {
+                    let start = elements.len();
+                    let item = self.fixed_width(descriptors, elements)?;
+                    for _ in 1..*y {
+                        elements.extend_from_within(start..);
+                    }
+                    item.checked_mul(*y as u32)?
+                }
+                ResolvedDescriptor::Sequence(_, descriptors) => {
+                    self.fixed_width(descriptors, elements)?
+                }
+                _ => return None,
+            };
+            bits = bits.checked_add(width)?;
+        }
+        Some(bits)
+    }
+
+    /// Returns the bit width of an element with the operators in effect.
+    fn element_width(&self, b: &TableBEntry) -> u32 {
+        effective_width_ref_scale(
+            b,
+            self.width_offset,
+            self.scale_offset,
+            self.scale_increase,
+            self.string_width,
+        )
+        .0
+    }
+
+    /// Returns the event of an element without data.
+    fn missing_event(&self, idx: u16, b: &'static TableBEntry) -> DataEvent {
+        if self.data_spec.is_compressed {
+            DataEvent::CompressedData {
+                idx,
+                xy: b.xy,
+                entry: b,
+                values: vec![Value::Missing; self.data_spec.number_of_subsets as usize],
+            }
+        } else {
+            DataEvent::Data {
+                idx,
+                xy: b.xy,
+                entry: b,
+                value: Value::Missing,
+            }
+        }
+    }
+
+    /// Reads the value(s) of an element with the given width, reference value and scale.
+    fn read_element(
+        &mut self,
+        idx: u16,
+        b: &'static TableBEntry,
+        bit_width: u32,
+        ref_value: i32,
+        scale: i8,
+    ) -> Result<DataEvent, Error> {
+        if self.skipping && !is_needed_for_skipping(b.xy) {
+            self.reader.skip(bit_width)?;
+            return Ok(self.missing_event(idx, b));
+        }
+        let missing = self.options.missing_values.get(&b.xy);
         match bit_width {
-            0..=32 => {
+            0..=32 if b.unit != "CCITT IA5" => {
                 if self.data_spec.is_compressed {
                     let local_ref_value: u32 = self.reader.read_var(bit_width)?;
                     let nbinc = self.reader.read::<6, u8>()?;
+                    check_nbinc(nbinc, bit_width, || element_label(b.xy))?;
 
                     Ok(DataEvent::CompressedData {
                         idx,
                         xy: b.xy,
+                        entry: b,
                         values: if nbinc == 0 {
                             // All values are the same if nbinc == 0
-                            let v = if local_ref_value == ((1u64 << bit_width) - 1) as u32 {
+                            let v = if missing.is_missing(local_ref_value as u64, bit_width) {
                                 Value::Missing
-                            } else if scale == 0 {
-                                Value::Integer(local_ref_value as i32 + ref_value)
                             } else {
-                                Value::Decimal(
-                                    (local_ref_value as i64 + ref_value as i64) as i32,
-                                    -scale,
-                                )
+                                unpack(local_ref_value, ref_value, scale)
                             };
                             vec![v; self.data_spec.number_of_subsets as usize]
                         } else {
                             (0..self.data_spec.number_of_subsets)
                                 .map(|_| {
                                     let inc: u32 = self.reader.read_var(nbinc as u32)?;
-                                    let v_raw = local_ref_value + inc;
-                                    Ok(if v_raw == ((1u64 << bit_width) - 1) as u32 {
+                                    if missing == MissingValue::AllOnes
+                                        && is_missing_increment(inc as u64, nbinc)
+                                    {
+                                        return Ok(Value::Missing);
+                                    }
+                                    let v_raw = add_increment(
+                                        local_ref_value as u64,
+                                        inc as u64,
+                                        bit_width,
+                                        || element_label(b.xy),
+                                    )? as u32;
+                                    Ok(if missing.is_missing(v_raw as u64, bit_width) {
                                         Value::Missing
-                                    } else if scale == 0 {
-                                        Value::Integer(v_raw as i32 + ref_value)
                                     } else {
-                                        Value::Decimal(
-                                            (v_raw as i64 + ref_value as i64) as i32,
-                                            -scale,
-                                        )
+                                        unpack(v_raw, ref_value, scale)
                                     })
                                 })
-                                .collect::<std::io::Result<Vec<Value>>>()?
+                                .collect::<Result<Vec<Value>, Error>>()?
                         },
                     })
                 } else {
                     let v_raw: u32 = self.reader.read_var(bit_width)?;
-                    let value = if v_raw == ((1u64 << bit_width) - 1) as u32 {
+                    let value = if missing.is_missing(v_raw as u64, bit_width) {
                         Value::Missing
-                    } else if scale == 0 {
-                        Value::Integer(v_raw as i32 + ref_value)
                     } else {
-                        Value::Decimal((v_raw as i64 + ref_value as i64) as i32, -scale)
+                        unpack(v_raw, ref_value, scale)
                     };
                     Ok(DataEvent::Data {
                         idx,
                         xy: b.xy,
+                        entry: b,
                         value,
                     })
                 }
             }
-            _ if bit_width % 8 == 0 => {
-                let vec = self.reader.read_to_vec((bit_width / 8) as usize)?;
-                if vec.iter().all(|it| *it == 0xff) {
-                    return Ok(DataEvent::Data {
+            33..=64 if b.unit != "CCITT IA5" => {
+                self.read_wide_element(idx, b, bit_width, ref_value, scale)
+            }
+            _ if bit_width.is_multiple_of(8) => {
+                let len = (bit_width / 8) as usize;
+                let what = || format!("character string with bit width {bit_width}");
+                if self.data_spec.is_compressed {
+                    let values = self.read_compressed_characters(len, what)?;
+                    Ok(DataEvent::CompressedData {
                         idx,
                         xy: b.xy,
-                        value: Value::Missing,
-                    });
-                }
-                let Ok(s) = String::from_utf8(vec) else {
-                    return Err(Error::Invalid(format!(
-                        "Failed to parse character string with bit width {bit_width}",
-                    )));
-                };
-                if self.data_spec.is_compressed {
-                    Err(Error::NotSupported(
-                        "Compressed data for characters not implemented yet".to_string(),
-                    ))
+                        entry: b,
+                        values,
+                    })
                 } else {
+                    let value = self.read_characters(len, what)?;
                     Ok(DataEvent::Data {
                         idx,
                         xy: b.xy,
-                        value: Value::String(s),
+                        entry: b,
+                        value,
                     })
                 }
             }
@@ -306,6 +1761,281 @@ impl<'a, R: Read> DataReader<'a, R> {
         }
     }
 
+    /// Reads the value(s) of a numeric element wider than 32 bits as [`Value::Decimal64`].
+    fn read_wide_element(
+        &mut self,
+        idx: u16,
+        b: &'static TableBEntry,
+        bit_width: u32,
+        ref_value: i32,
+        scale: i8,
+    ) -> Result<DataEvent, Error> {
+        let missing = self.options.missing_values.get(&b.xy);
+        let to_value = |v_raw: u64| {
+            if missing.is_missing(v_raw, bit_width) {
+                return Ok(Value::Missing);
+            }
+            unpack_wide(v_raw, ref_value, scale).ok_or_else(|| {
+                Error::Invalid(format!("Value of {:?} does not fit in 64 bits", b.xy))
+            })
+        };
+        if !self.data_spec.is_compressed {
+            let v_raw: u64 = self.reader.read_var(bit_width)?;
+            return Ok(DataEvent::Data {
+                idx,
+                xy: b.xy,
+                entry: b,
+                value: to_value(v_raw)?,
+            });
+        }
+
+        let local_ref_value: u64 = self.reader.read_var(bit_width)?;
+        let nbinc = self.reader.read::<6, u8>()?;
+        check_nbinc(nbinc, bit_width, || element_label(b.xy))?;
+        let values = if nbinc == 0 {
+            vec![to_value(local_ref_value)?; self.data_spec.number_of_subsets as usize]
+        } else {
+            (0..self.data_spec.number_of_subsets)
+                .map(|_| {
+                    let inc: u64 = self.reader.read_var(nbinc as u32)?;
+                    if missing == MissingValue::AllOnes && is_missing_increment(inc, nbinc) {
+                        return Ok(Value::Missing);
+                    }
+                    to_value(add_increment(local_ref_value, inc, bit_width, || {
+                        element_label(b.xy)
+                    })?)
+                })
+                .collect::<Result<Vec<Value>, Error>>()?
+        };
+        Ok(DataEvent::CompressedData {
+            idx,
+            xy: b.xy,
+            entry: b,
+            values,
+        })
+    }
+
+    /// Records an element read for bit-maps and collects the data present indicators.
+    fn record_element(&mut self, b: &'static TableBEntry, event: &DataEvent) {
+        let (xy, value) = match event {
+            DataEvent::Data { xy, value, .. } => (xy, Some(value)),
+            // Bit-maps are assumed to be the same for all subsets
+            DataEvent::CompressedData { xy, values, .. } => (xy, values.first()),
+            _ => return,
+        };
+        if *xy == (XY { x: 31, y: 31 })
+            && let Some(bits) = &mut self.bitmap.defining
+        {
+            // 0 means the data is present
+            bits.push(matches!(value, Some(Value::Integer(0))));
+        }
+        let statistics = match value {
+            Some(Value::Integer(v)) => Some(*v),
+            _ => None,
+        };
+        match *xy {
+            XY { x: 8, y: 23 } => self.bitmap.first_order_statistics = statistics,
+            XY { x: 8, y: 24 } => self.bitmap.difference_statistics = statistics,
+            _ => {}
+        }
+        self.bitmap.elements.push(b);
+    }
+
+    /// Reads a value marked by 2 YY 255, which relates to the next element in the bit-map
+    /// and is represented like that element.
+    fn read_marked_value(&mut self, idx: u16, x: u8) -> Result<DataEvent, Error> {
+        self.bitmap.finish_definition();
+        let Some(position) = self.bitmap.targets.pop_front() else {
+            return Err(Error::Invalid(format!(
+                "2 {x:02} 255 without a data present bit-map"
+            )));
+        };
+        let Some(&b) = self.bitmap.elements.get(position as usize) else {
+            return Err(Error::Invalid(format!(
+                "Data present bit-map refers to element {position}, which does not exist"
+            )));
+        };
+        let (mut bit_width, mut ref_value, scale) = effective_width_ref_scale(
+            b,
+            self.width_offset,
+            self.scale_offset,
+            self.scale_increase,
+            self.string_width,
+        );
+        if let Some(&new_ref_value) = self.changed_reference_values.get(&b.xy) {
+            ref_value = new_ref_value;
+        }
+        if x == 25 {
+            // Difference statistical values are centred around zero: one more bit than
+            // the element with the reference value -2^n (Table C, 2 25 255)
+            if bit_width >= 32 {
+                return Err(Error::NotSupported(format!(
+                    "Difference statistical values wider than 32 bits ({} bits)",
+                    bit_width + 1
+                )));
+            }
+            // -2^31 still fits in i32, but not its negation
+            ref_value = -(1i64 << bit_width) as i32;
+            bit_width += 1;
+        }
+        let significance = match x {
+            24 => self.bitmap.first_order_statistics,
+            25 => self.bitmap.difference_statistics,
+            _ => None,
+        };
+        Ok(
+            match self.read_element(idx, b, bit_width, ref_value, scale)? {
+                DataEvent::Data { xy, value, .. } => DataEvent::Quality {
+                    idx,
+                    x,
+                    position,
+                    xy,
+                    significance,
+                    value,
+                },
+                DataEvent::CompressedData { xy, values, .. } => DataEvent::CompressedQuality {
+                    idx,
+                    x,
+                    position,
+                    xy,
+                    significance,
+                    values,
+                },
+                ev => ev,
+            },
+        )
+    }
+
+    /// Reads a new reference value, whose leftmost bit is the sign bit.
+    fn read_new_reference_value(
+        &mut self,
+        idx: u16,
+        b: &'static TableBEntry,
+        width: u8,
+    ) -> Result<DataEvent, Error> {
+        let raw: u32 = self.reader.read_var(width as u32)?;
+        if self.data_spec.is_compressed {
+            // The value is compressed like an element, but must be the same for all subsets
+            let nbinc = self.reader.read::<6, u8>()?;
+            if nbinc != 0 {
+                return Err(Error::Invalid(
+                    "New reference values must be the same for all subsets".to_string(),
+                ));
+            }
+        }
+        let sign_bit = 1u32 << (width - 1);
+        let magnitude = (raw & (sign_bit - 1)) as i32;
+        let reference_value = if raw & sign_bit != 0 {
+            -magnitude
+        } else {
+            magnitude
+        };
+        self.changed_reference_values.insert(b.xy, reference_value);
+        Ok(DataEvent::ReferenceValueChanged {
+            idx,
+            xy: b.xy,
+            reference_value,
+        })
+    }
+
+    fn read_associated_field(&mut self, idx: u16, xy: XY) -> Result<DataEvent, Error> {
+        let bit_width: u32 = self.associated_field_widths.iter().map(|&w| w as u32).sum();
+        if bit_width > 31 {
+            return Err(Error::NotSupported(format!(
+                "Associated fields wider than 31 bits ({bit_width} bits)"
+            )));
+        }
+        let all_ones = ((1u64 << bit_width) - 1) as u32;
+        let to_value = |v: u32| {
+            if v == all_ones {
+                Value::Missing
+            } else {
+                Value::Integer(v as i32)
+            }
+        };
+        if self.data_spec.is_compressed {
+            let local_ref_value: u32 = self.reader.read_var(bit_width)?;
+            let nbinc = self.reader.read::<6, u8>()?;
+            let what = || format!("the associated field of {}", element_label(xy));
+            check_nbinc(nbinc, bit_width, what)?;
+            let values = if nbinc == 0 {
+                vec![to_value(local_ref_value); self.data_spec.number_of_subsets as usize]
+            } else {
+                (0..self.data_spec.number_of_subsets)
+                    .map(|_| {
+                        let inc: u32 = self.reader.read_var(nbinc as u32)?;
+                        Ok(if inc == ((1u64 << nbinc) - 1) as u32 {
+                            Value::Missing
+                        } else {
+                            to_value(add_increment(
+                                local_ref_value as u64,
+                                inc as u64,
+                                bit_width,
+                                what,
+                            )? as u32)
+                        })
+                    })
+                    .collect::<Result<Vec<Value>, Error>>()?
+            };
+            Ok(DataEvent::CompressedAssociatedField { idx, values })
+        } else {
+            let v_raw: u32 = self.reader.read_var(bit_width)?;
+            Ok(DataEvent::AssociatedField {
+                idx,
+                value: to_value(v_raw),
+            })
+        }
+    }
+
+    /// Reads `len` bytes of characters, all ones meaning a missing value.
+    fn read_characters(
+        &mut self,
+        len: usize,
+        what: impl FnOnce() -> String,
+    ) -> Result<Value, Error> {
+        let vec = self.reader.read_to_vec(len)?;
+        if vec.iter().all(|it| *it == 0xff) {
+            return Ok(Value::Missing);
+        }
+        match self.options.character_decoding.decode(vec) {
+            Some(value) => Ok(value),
+            None => Err(Error::Invalid(format!("Failed to parse {}", what()))),
+        }
+    }
+
+    /// Reads compressed characters: the local reference of `len` bytes and NBINC, then
+    /// NBINC bytes for each subset, or the reference for all subsets when NBINC is 0.
+    fn read_compressed_characters(
+        &mut self,
+        len: usize,
+        what: impl Fn() -> String,
+    ) -> Result<Vec<Value>, Error> {
+        let local_ref = self.read_characters(len, &what)?;
+        let nbinc: u8 = self.reader.read::<6, u8>()?;
+        let n = self.data_spec.number_of_subsets as usize;
+        if nbinc == 0 {
+            return Ok(vec![local_ref; n]);
+        }
+        if nbinc as usize != len {
+            return Err(Error::Invalid(format!(
+                "NBINC {nbinc} does not match the {len} bytes of {}",
+                what()
+            )));
+        }
+        (0..n).map(|_| self.read_characters(len, &what)).collect()
+    }
+
+    /// Reads the characters inserted by 2 05 YYY.
+    fn read_signified_characters(&mut self, idx: u16, len: u8) -> Result<DataEvent, Error> {
+        let what = || format!("{len} characters signified by 2 05 {len:03}");
+        if self.data_spec.is_compressed {
+            let values = self.read_compressed_characters(len as usize, what)?;
+            return Ok(DataEvent::CompressedSignifiedCharacters { idx, values });
+        }
+        let value = self.read_characters(len as usize, what)?;
+        Ok(DataEvent::SignifiedCharacters { idx, value })
+    }
+
     // f = 1
     fn handle_replication_descriptor(
         &mut self,
@@ -314,30 +2044,131 @@ impl<'a, R: Read> DataReader<'a, R> {
         elements: &'a [ResolvedDescriptor<'_>],
         delayed_bits: u8,
     ) -> Result<DataEvent, Error> {
-        let count = match y {
-            0 => self.reader.read_var::<u16>(delayed_bits as u32)?,
-            _ => y as u16,
+        let (count, subset_counts) = match y {
+            0 if self.data_spec.is_compressed => {
+                self.read_compressed_delayed_factor(delayed_bits)?
+            }
+            0 => (self.reader.read_var::<u16>(delayed_bits as u32)?, None),
+            _ => (y as u16, None),
         };
+        if let Some(max) = self.options.max_replication_count
+            && y == 0
+            && count > max
+        {
+            return Err(Error::Invalid(format!(
+                "Delayed replication factor {count} exceeds the limit of {max}"
+            )));
+        }
         self.stack
-            .push(StackEntry::new_replication(elements, count));
-        Ok(DataEvent::ReplicationStart { idx, count })
+            .push(StackEntry::new_replication(idx, elements, count));
+        Ok(DataEvent::ReplicationStart {
+            idx,
+            count,
+            factor_bits: if y == 0 { delayed_bits } else { 0 },
+            subset_counts,
+        })
+    }
+
+    /// Reads the factor of a delayed replication in compressed data, which is itself
+    /// compressed like a data element.
+    ///
+    /// Returns the largest factor, and the factors of each subset if they vary.
+    fn read_compressed_delayed_factor(
+        &mut self,
+        delayed_bits: u8,
+    ) -> Result<(u16, Option<Vec<u16>>), Error> {
+        let what = || "the delayed replication factor".to_string();
+        let local_ref: u64 = self.reader.read_var(delayed_bits as u32)?;
+        let nbinc = self.reader.read::<6, u8>()?;
+        check_nbinc(nbinc, delayed_bits as u32, what)?;
+        if nbinc == 0 {
+            return Ok((local_ref as u16, None));
+        }
+        let counts = (0..self.data_spec.number_of_subsets)
+            .map(|_| {
+                let inc: u64 = self.reader.read_var(nbinc as u32)?;
+                Ok(add_increment(local_ref, inc, delayed_bits as u32, what)? as u16)
+            })
+            .collect::<Result<Vec<u16>, Error>>()?;
+        let count = counts.iter().copied().max().unwrap_or(local_ref as u16);
+        match counts.iter().all(|&c| c == count) {
+            true => Ok((count, None)),
+            false => Ok((count, Some(counts))),
+        }
     }
 
     // f = 2
-    fn handle_operator_descriptor(&mut self, idx: u16, xy: XY) -> Result<DataEvent, Error> {
-        match (xy.x, xy.y) {
-            // Change data width
-            (1, 0) => self.width_offset = 0,
-            (1, y) => self.width_offset = ((y as i16) - 128) as i8,
-            // Change scale
-            (2, 0) => self.scale_offset = 0,
-            (2, y) => self.scale_offset = ((y as i16) - 128) as i8,
-            // Signify data width for the immediately following local descriptor
-            (6, _) => self.temporary_operator = Some(xy),
-            // Not supported
-            _ => {
+    fn handle_operator_descriptor(
+        &mut self,
+        idx: u16,
+        xy: XY,
+        entry: Option<&'static TableCEntry>,
+    ) -> Result<DataEvent, Error> {
+        let operator = Operator::from_xy(xy);
+        match operator {
+            Operator::ChangeWidth(offset) => self.width_offset = offset,
+            Operator::ChangeScale(offset) => self.scale_offset = offset,
+            // Nested associated fields add up, 2 04 000 cancels the last one
+            Operator::CancelAssociatedField => {
+                if self.associated_field_widths.pop().is_none() {
+                    return Err(Error::Invalid(
+                        "2 04 000 without a preceding 2 04 YYY".to_string(),
+                    ));
+                }
+            }
+            Operator::AddAssociatedField(width) => self.associated_field_widths.push(width),
+            Operator::CancelReferenceValues => {
+                self.reference_value_width = None;
+                self.changed_reference_values.clear();
+            }
+            Operator::ConcludeReferenceValues => self.reference_value_width = None,
+            Operator::ChangeReferenceValues(width) if width > 32 => {
+                return Err(Error::NotSupported(format!(
+                    "New reference values wider than 32 bits ({width} bits)"
+                )));
+            }
+            Operator::ChangeReferenceValues(width) => self.reference_value_width = Some(width),
+            Operator::IncreaseScaleReferenceWidth(y) => self.scale_increase = y,
+            Operator::ChangeStringWidth(width) => self.string_width = width,
+            Operator::SignifyCharacter(len) => {
+                return self.read_signified_characters(idx, len);
+            }
+            // Applies to the immediately following local descriptor
+            Operator::SignifyWidth(_) => self.temporary_operator = Some(xy),
+            Operator::DataNotPresent(count) => self.data_not_present = count,
+            // Quality information, substituted values, first-order statistical values,
+            // difference statistical values or replaced/retained values follow
+            // (with a data present bit-map)
+            Operator::BitmapFollows(_) => {
+                self.bitmap.finish_definition();
+                self.bitmap.targets.clear();
+                let num_elements = self.bitmap.elements.len() as u32;
+                self.bitmap.back_reference.get_or_insert(num_elements);
+                self.bitmap.defining = Some(vec![]);
+            }
+            // Markers of the values related to the elements in the bit-map
+            Operator::MarkerOperator(x) => return self.read_marked_value(idx, x),
+            Operator::CancelBackwardReference => {
+                self.bitmap = Bitmap {
+                    elements: std::mem::take(&mut self.bitmap.elements),
+                    ..Default::default()
+                }
+            }
+            Operator::DefineBitmap => self.bitmap.define_for_reuse = true,
+            Operator::UseDefinedBitmap => {
+                let Some(targets) = self.bitmap.reusable.clone() else {
+                    return Err(Error::Invalid(
+                        "2 37 000 without a preceding 2 36 000".to_string(),
+                    ));
+                };
+                self.bitmap.defining = None;
+                self.bitmap.targets = targets.into();
+            }
+            Operator::CancelDefinedBitmap => self.bitmap.reusable = None,
+            Operator::Other(_) => {
                 return Err(Error::NotSupported(format!(
-                    "Operator descriptor {xy:#?} not supported yet.",
+                    "Operator descriptor {} not supported yet.",
+                    describe_operator(xy, entry)
                 )));
             }
         }
@@ -345,6 +2176,8 @@ impl<'a, R: Read> DataReader<'a, R> {
             idx,
             x: xy.x,
             value: xy.y as i32,
+            name: entry.map(|c| c.operator_name),
+            operator,
         })
     }
 
@@ -356,23 +2189,2243 @@ impl<'a, R: Read> DataReader<'a, R> {
         elements: &'a [ResolvedDescriptor<'_>],
     ) -> Result<DataEvent, Error> {
         self.stack.push(StackEntry::new_sequence(elements));
+        self.path.push(PathSegment::Sequence(d.xy));
         Ok(DataEvent::SequenceStart { idx, xy: d.xy })
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::Descriptor;
+    use bitstream_io::{BitWrite, BitWriter};
+
+    pub(crate) fn make_dds(
+        descriptors: Vec<Descriptor>,
+        number_of_subsets: u16,
+    ) -> DataDescriptionSection {
+        DataDescriptionSection {
+            section_length: 7 + 2 * descriptors.len() as u32,
+            number_of_subsets,
+            flags: Default::default(),
+            descriptors,
+            _padding: vec![],
+        }
+    }
+
+    /// Builds a data section from (bit width, value) pairs.
+    fn make_section(fields: &[(u32, u32)]) -> Vec<u8> {
+        let mut writer = BitWriter::endian(Vec::new(), bitstream_io::BigEndian);
+        for &(bits, value) in fields {
+            writer.write_var(bits, value).unwrap();
+        }
+        writer.byte_align().unwrap();
+        let data = writer.into_writer();
+        let len = (4 + data.len()) as u32;
+        let mut section = vec![(len >> 16) as u8, (len >> 8) as u8, len as u8, 0];
+        section.extend(data);
+        section
+    }
+
+    fn read_all(section: &[u8], spec: &DataSpec) -> Vec<DataEvent> {
+        let mut reader = DataReader::new(section, spec).unwrap();
+        let mut events = vec![];
+        loop {
+            match reader.read_event().unwrap() {
+                DataEvent::Eof => break,
+                ev @ (DataEvent::Data { .. }
+                | DataEvent::CompressedData { .. }
+                | DataEvent::AssociatedField { .. }
+                | DataEvent::CompressedAssociatedField { .. }
+                | DataEvent::BitmapTarget { .. }
+                | DataEvent::Quality { .. }
+                | DataEvent::CompressedQuality { .. }
+                | DataEvent::SignifiedCharacters { .. }
+                | DataEvent::CompressedSignifiedCharacters { .. }
+                | DataEvent::UnknownLocalElement { .. }) => events.push(ev),
+                _ => {}
+            }
+        }
+        events
+    }
+
+    fn associated_field_descriptors() -> Vec<Descriptor> {
+        vec![
+            Descriptor { f: 2, x: 4, y: 2 },
+            Descriptor { f: 0, x: 31, y: 21 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+            Descriptor { f: 2, x: 4, y: 0 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+        ]
+    }
 
     #[test]
-    fn test_value_fmt() {
-        assert_eq!(format!("{:?}", Value::Missing), "Missing");
-        assert_eq!(format!("{:?}", Value::Decimal(1234, -2)), "12.34");
-        assert_eq!(format!("{:?}", Value::Decimal(1234, 2)), "123400");
-        assert_eq!(format!("{:?}", Value::Integer(42)), "42");
+    fn test_associated_field() {
+        let tables = Tables::default();
+        let dds = make_dds(associated_field_descriptors(), 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        // 0 31 021 is not preceded by an associated field, 0 12 101 is (until 2 04 000)
+        let section = make_section(&[(6, 1), (2, 2), (16, 27315), (16, 27415)]);
+        let events = read_all(&section, &spec);
         assert_eq!(
-            format!("{:?}", Value::String("Hello".to_string())),
-            "\"Hello\""
+            format!("{events:?}"),
+            format!(
+                "{:?}",
+                [
+                    DataEvent::Data {
+                        idx: 1,
+                        xy: XY { x: 31, y: 21 },
+                        entry: tables.table_b[&XY { x: 31, y: 21 }],
+                        value: Value::Integer(1)
+                    },
+                    DataEvent::AssociatedField {
+                        idx: 2,
+                        value: Value::Integer(2)
+                    },
+                    DataEvent::Data {
+                        idx: 2,
+                        xy: XY { x: 12, y: 101 },
+                        entry: tables.table_b[&XY { x: 12, y: 101 }],
+                        value: Value::Decimal(27315, -2)
+                    },
+                    DataEvent::Data {
+                        idx: 4,
+                        xy: XY { x: 12, y: 101 },
+                        entry: tables.table_b[&XY { x: 12, y: 101 }],
+                        value: Value::Decimal(27415, -2)
+                    },
+                ]
+            )
         );
     }
+
+    #[test]
+    fn test_projection_with_associated_fields() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 2, x: 4, y: 2 },
+                Descriptor { f: 0, x: 31, y: 21 },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+                Descriptor { f: 0, x: 7, y: 4 },
+                Descriptor { f: 2, x: 4, y: 0 },
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(6, 1), (2, 1), (16, 27315), (2, 2), (14, 5000)]);
+        let read = |xy: XY| {
+            let mut reader = DataReader::builder(&spec)
+                .projection([xy])
+                .build(section.as_slice())
+                .unwrap();
+            let mut events = vec![];
+            loop {
+                match reader.read_event().unwrap() {
+                    DataEvent::Eof => return events,
+                    ev @ (DataEvent::Data { .. } | DataEvent::AssociatedField { .. }) => {
+                        events.push(format!("{ev:?}"))
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        // The associated field of a dropped element is dropped with it
+        let pressure = XY { x: 7, y: 4 };
+        let expected = [
+            DataEvent::AssociatedField {
+                idx: 3,
+                value: Value::Integer(2),
+            },
+            DataEvent::Data {
+                idx: 3,
+                xy: pressure,
+                entry: tables.table_b[&pressure],
+                value: Value::Decimal(5000, 1),
+            },
+        ];
+        assert_eq!(read(pressure), expected.map(|ev| format!("{ev:?}")));
+
+        let temperature = XY { x: 12, y: 101 };
+        let expected = [
+            DataEvent::AssociatedField {
+                idx: 2,
+                value: Value::Integer(1),
+            },
+            DataEvent::Data {
+                idx: 2,
+                xy: temperature,
+                entry: tables.table_b[&temperature],
+                value: Value::Decimal(27315, -2),
+            },
+        ];
+        assert_eq!(read(temperature), expected.map(|ev| format!("{ev:?}")));
+    }
+
+    #[test]
+    fn test_compressed_associated_field() {
+        let tables = Tables::default();
+        let mut dds = make_dds(associated_field_descriptors(), 2);
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[
+            // 0 31 021: all subsets equal
+            (6, 1),
+            (6, 0),
+            // Associated field: 0 and missing
+            (2, 0),
+            (6, 1),
+            (1, 0),
+            (1, 1),
+            // 0 12 101
+            (16, 27315),
+            (6, 0),
+            (16, 27415),
+            (6, 0),
+        ]);
+        let events = read_all(&section, &spec);
+        assert_eq!(events.len(), 4);
+        let DataEvent::CompressedAssociatedField { idx: 2, values } = &events[1] else {
+            panic!("Unexpected event: {:?}", events[1]);
+        };
+        assert_eq!(values, &[Value::Integer(0), Value::Missing]);
+        assert!(matches!(
+            events[2],
+            DataEvent::CompressedData { idx: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn test_change_reference_values() {
+        let tables = Tables::default();
+        let t = Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        };
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 2, x: 3, y: 12 },
+                t,
+                Descriptor { f: 2, x: 3, y: 255 },
+                t,
+                Descriptor { f: 2, x: 3, y: 0 },
+                t,
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        // New reference value -1000 (sign bit set), then two values
+        let section = make_section(&[(12, 0x800 | 1000), (16, 1500), (16, 27315)]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let mut events = vec![];
+        loop {
+            match reader.read_event().unwrap() {
+                DataEvent::Eof => break,
+                DataEvent::Data { idx, xy, value, .. } => events.push(format!(
+                    "Data {{ idx: {idx}, xy: {xy:?}, value: {value:?} }}"
+                )),
+                ev @ DataEvent::ReferenceValueChanged { .. } => events.push(format!("{ev:?}")),
+                _ => {}
+            }
+        }
+        assert_eq!(
+            events,
+            [
+                "ReferenceValueChanged { idx: 1, xy: XY { x: 12, y: 101 }, reference_value: -1000 }",
+                "Data { idx: 3, xy: XY { x: 12, y: 101 }, value: 5.00 }",
+                // Cancelled by 2 03 000
+                "Data { idx: 5, xy: XY { x: 12, y: 101 }, value: 273.15 }",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cancel_without_associated_field() {
+        let tables = Tables::default();
+        let dds = make_dds(vec![Descriptor { f: 2, x: 4, y: 0 }], 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        reader.read_event().unwrap();
+        assert!(matches!(reader.read_event(), Err(Error::Invalid(_))));
+    }
+
+    #[test]
+    fn test_signify_character() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 2, x: 5, y: 3 },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[
+            (8, b'A' as u32),
+            (8, b'B' as u32),
+            (8, b'C' as u32),
+            (16, 27315),
+        ]);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{events:?}"),
+            format!(
+                "{:?}",
+                [
+                    DataEvent::SignifiedCharacters {
+                        idx: 0,
+                        value: Value::String("ABC".to_string())
+                    },
+                    DataEvent::Data {
+                        idx: 1,
+                        xy: XY { x: 12, y: 101 },
+                        entry: tables.table_b[&XY { x: 12, y: 101 }],
+                        value: Value::Decimal(27315, -2)
+                    },
+                ]
+            )
+        );
+
+        // Compressed, with differing and equal characters
+        let mut dds = make_dds(
+            vec![
+                Descriptor { f: 2, x: 5, y: 3 },
+                Descriptor { f: 2, x: 5, y: 2 },
+            ],
+            2,
+        );
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut fields = vec![(24, 0), (6, 3)];
+        fields.extend(b"ABCXYZ".map(|c| (8, c as u32)));
+        fields.extend([(8, b'O' as u32), (8, b'K' as u32), (6, 0)]);
+        let section = make_section(&fields);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{events:?}"),
+            format!(
+                "{:?}",
+                [
+                    DataEvent::CompressedSignifiedCharacters {
+                        idx: 0,
+                        values: vec![
+                            Value::String("ABC".to_string()),
+                            Value::String("XYZ".to_string())
+                        ]
+                    },
+                    DataEvent::CompressedSignifiedCharacters {
+                        idx: 1,
+                        values: vec![Value::String("OK".to_string()); 2]
+                    },
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_increase_scale_ref_width() {
+        let tables = Tables::default();
+        let latitude = Descriptor { f: 0, x: 5, y: 1 };
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 2, x: 7, y: 1 },
+                latitude,
+                Descriptor { f: 2, x: 7, y: 0 },
+                latitude,
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        // Scale 5 -> 6, reference value -9000000 -> -90000000, width 25 -> 29
+        let section = make_section(&[(29, 102345678), (25, 10234567)]);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{events:?}"),
+            format!(
+                "{:?}",
+                [
+                    DataEvent::Data {
+                        idx: 1,
+                        xy: XY { x: 5, y: 1 },
+                        entry: tables.table_b[&XY { x: 5, y: 1 }],
+                        value: Value::Decimal(12345678, -6)
+                    },
+                    DataEvent::Data {
+                        idx: 3,
+                        xy: XY { x: 5, y: 1 },
+                        entry: tables.table_b[&XY { x: 5, y: 1 }],
+                        value: Value::Decimal(1234567, -5)
+                    },
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_change_string_width() {
+        let tables = Tables::default();
+        let station_name = Descriptor { f: 0, x: 1, y: 15 };
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 2, x: 8, y: 4 },
+                station_name,
+                Descriptor { f: 2, x: 8, y: 0 },
+                station_name,
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let fields: Vec<_> = "NAHAOSAKA               "
+            .bytes()
+            .map(|b| (8, b as u32))
+            .collect();
+        let section = make_section(&fields);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{events:?}"),
+            format!(
+                "{:?}",
+                [
+                    DataEvent::Data {
+                        idx: 1,
+                        xy: XY { x: 1, y: 15 },
+                        entry: tables.table_b[&XY { x: 1, y: 15 }],
+                        value: Value::String("NAHA".to_string())
+                    },
+                    DataEvent::Data {
+                        idx: 3,
+                        xy: XY { x: 1, y: 15 },
+                        entry: tables.table_b[&XY { x: 1, y: 15 }],
+                        value: Value::String("OSAKA               ".to_string())
+                    },
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_data_present_bitmap() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 103,
+                },
+                Descriptor { f: 2, x: 22, y: 0 },
+                Descriptor { f: 1, x: 1, y: 2 },
+                Descriptor { f: 0, x: 31, y: 31 },
+                Descriptor { f: 0, x: 1, y: 31 },
+                Descriptor { f: 0, x: 33, y: 7 },
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        // Only the dew-point temperature is marked as present
+        let section = make_section(&[(16, 27315), (16, 27015), (1, 1), (1, 0), (16, 98), (7, 70)]);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{:?}", &events[4..]),
+            format!(
+                "{:?}",
+                [
+                    DataEvent::Data {
+                        idx: 4,
+                        xy: XY { x: 1, y: 31 },
+                        entry: tables.table_b[&XY { x: 1, y: 31 }],
+                        value: Value::Integer(98)
+                    },
+                    DataEvent::BitmapTarget {
+                        idx: 5,
+                        position: 1
+                    },
+                    DataEvent::Data {
+                        idx: 5,
+                        xy: XY { x: 33, y: 7 },
+                        entry: tables.table_b[&XY { x: 33, y: 7 }],
+                        value: Value::Integer(70)
+                    },
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_bitmap_back_reference() {
+        let tables = Tables::default();
+        let quality_information = [
+            Descriptor { f: 2, x: 22, y: 0 },
+            Descriptor { f: 1, x: 1, y: 2 },
+            Descriptor { f: 0, x: 31, y: 31 },
+            Descriptor { f: 0, x: 33, y: 7 },
+        ];
+        let mut descriptors = vec![
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 103,
+            },
+        ];
+        descriptors.extend(quality_information);
+        descriptors.extend(quality_information);
+        let dds = make_dds(descriptors, 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[
+            (16, 27315),
+            (16, 27015),
+            (1, 0),
+            (1, 1),
+            (7, 70),
+            // The second bit-map also refers back from the first 2 22 000
+            (1, 1),
+            (1, 0),
+            (7, 80),
+        ]);
+        let events = read_all(&section, &spec);
+        let targets: Vec<_> = events
+            .iter()
+            .filter_map(|ev| match ev {
+                DataEvent::BitmapTarget { position, .. } => Some(*position),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(targets, [0, 1]);
+    }
+
+    #[test]
+    fn test_reuse_bitmap() {
+        let tables = Tables::default();
+        let temperature = Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        };
+        let quality = Descriptor { f: 0, x: 33, y: 7 };
+        let dds = make_dds(
+            vec![
+                temperature,
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 103,
+                },
+                Descriptor { f: 2, x: 22, y: 0 },
+                Descriptor { f: 2, x: 36, y: 0 },
+                Descriptor { f: 1, x: 1, y: 2 },
+                Descriptor { f: 0, x: 31, y: 31 },
+                quality,
+                // Substituted value for the element of the reused bit-map
+                Descriptor { f: 2, x: 23, y: 0 },
+                Descriptor { f: 2, x: 37, y: 0 },
+                Descriptor {
+                    f: 2,
+                    x: 23,
+                    y: 255,
+                },
+                Descriptor {
+                    f: 2,
+                    x: 37,
+                    y: 255,
+                },
+                // New bit-maps refer back from here
+                Descriptor { f: 2, x: 35, y: 0 },
+                temperature,
+                Descriptor { f: 2, x: 22, y: 0 },
+                Descriptor { f: 0, x: 31, y: 31 },
+                quality,
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[
+            (16, 27315),
+            (16, 27015),
+            (1, 0),
+            (1, 1),
+            (7, 70),
+            (16, 27415),
+            (16, 27515),
+            (1, 0),
+            (7, 80),
+        ]);
+        let events: Vec<_> = read_all(&section, &spec)
+            .into_iter()
+            .filter(|ev| {
+                matches!(
+                    ev,
+                    DataEvent::BitmapTarget { .. } | DataEvent::Quality { .. }
+                )
+            })
+            .collect();
+        assert_eq!(
+            format!("{events:?}"),
+            format!(
+                "{:?}",
+                [
+                    DataEvent::BitmapTarget {
+                        idx: 5,
+                        position: 0
+                    },
+                    DataEvent::Quality {
+                        idx: 8,
+                        x: 23,
+                        position: 0,
+                        xy: XY { x: 12, y: 101 },
+                        significance: None,
+                        value: Value::Decimal(27415, -2)
+                    },
+                    DataEvent::BitmapTarget {
+                        idx: 14,
+                        position: 5
+                    },
+                ]
+            )
+        );
+
+        // The bit-map is no longer available after 2 37 255
+        let dds = make_dds(
+            vec![
+                temperature,
+                Descriptor { f: 2, x: 22, y: 0 },
+                Descriptor { f: 2, x: 36, y: 0 },
+                Descriptor { f: 0, x: 31, y: 31 },
+                quality,
+                Descriptor {
+                    f: 2,
+                    x: 37,
+                    y: 255,
+                },
+                Descriptor { f: 2, x: 22, y: 0 },
+                Descriptor { f: 2, x: 37, y: 0 },
+                quality,
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(16, 27315), (1, 0), (7, 70), (7, 70)]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let result = std::iter::repeat_with(|| reader.read_event())
+            .find(|ev| !matches!(ev, Ok(ev) if !matches!(ev, DataEvent::Eof)))
+            .unwrap();
+        assert!(matches!(result, Err(Error::Invalid(_))));
+    }
+
+    #[test]
+    fn test_data_not_present() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 2, x: 21, y: 3 },
+                // Station number (class 1) has data
+                Descriptor { f: 0, x: 1, y: 2 },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 103,
+                },
+                // No longer affected
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(10, 662), (16, 27315)]);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{events:?}"),
+            format!(
+                "{:?}",
+                [
+                    DataEvent::Data {
+                        idx: 1,
+                        xy: XY { x: 1, y: 2 },
+                        entry: tables.table_b[&XY { x: 1, y: 2 }],
+                        value: Value::Integer(662)
+                    },
+                    DataEvent::Data {
+                        idx: 2,
+                        xy: XY { x: 12, y: 101 },
+                        entry: tables.table_b[&XY { x: 12, y: 101 }],
+                        value: Value::Missing
+                    },
+                    DataEvent::Data {
+                        idx: 3,
+                        xy: XY { x: 12, y: 103 },
+                        entry: tables.table_b[&XY { x: 12, y: 103 }],
+                        value: Value::Missing
+                    },
+                    DataEvent::Data {
+                        idx: 4,
+                        xy: XY { x: 12, y: 101 },
+                        entry: tables.table_b[&XY { x: 12, y: 101 }],
+                        value: Value::Decimal(27315, -2)
+                    },
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_marked_values() {
+        let tables = Tables::default();
+        let bitmap = [
+            Descriptor { f: 1, x: 1, y: 2 },
+            Descriptor { f: 0, x: 31, y: 31 },
+        ];
+        let mut descriptors = vec![
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 103,
+            },
+            Descriptor { f: 2, x: 23, y: 0 },
+        ];
+        descriptors.extend(bitmap);
+        descriptors.extend([
+            Descriptor {
+                f: 2,
+                x: 23,
+                y: 255,
+            },
+            Descriptor { f: 2, x: 25, y: 0 },
+        ]);
+        descriptors.extend(bitmap);
+        descriptors.push(Descriptor {
+            f: 2,
+            x: 25,
+            y: 255,
+        });
+        let dds = make_dds(descriptors, 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[
+            (16, 27315),
+            (16, 27015),
+            // Substituted value of the air temperature
+            (1, 0),
+            (1, 1),
+            (16, 27400),
+            // Difference of the dew-point temperature (17 bits, reference value -65536)
+            (1, 1),
+            (1, 0),
+            (17, 65536 - 50),
+        ]);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{:?}", &events[4..]),
+            format!(
+                "{:?}",
+                [
+                    DataEvent::Quality {
+                        idx: 4,
+                        x: 23,
+                        position: 0,
+                        xy: XY { x: 12, y: 101 },
+                        significance: None,
+                        value: Value::Decimal(27400, -2)
+                    },
+                    DataEvent::Data {
+                        idx: 0,
+                        xy: XY { x: 31, y: 31 },
+                        entry: tables.table_b[&XY { x: 31, y: 31 }],
+                        // 1 means not present (all bits set)
+                        value: Value::Missing
+                    },
+                    DataEvent::Data {
+                        idx: 0,
+                        xy: XY { x: 31, y: 31 },
+                        entry: tables.table_b[&XY { x: 31, y: 31 }],
+                        value: Value::Integer(0)
+                    },
+                    DataEvent::Quality {
+                        idx: 7,
+                        x: 25,
+                        position: 1,
+                        xy: XY { x: 12, y: 103 },
+                        significance: None,
+                        value: Value::Decimal(-50, -2)
+                    },
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_compressed_substituted_values() {
+        let tables = Tables::default();
+        let mut dds = make_dds(
+            vec![
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+                Descriptor { f: 2, x: 23, y: 0 },
+                Descriptor { f: 1, x: 1, y: 1 },
+                Descriptor { f: 0, x: 31, y: 31 },
+                Descriptor {
+                    f: 2,
+                    x: 23,
+                    y: 255,
+                },
+            ],
+            2,
+        );
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[
+            (16, 27315),
+            (6, 0),
+            // Bit-map
+            (1, 0),
+            (6, 0),
+            // Substituted values
+            (16, 27000),
+            (6, 2),
+            (2, 0),
+            (2, 1),
+        ]);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{:?}", &events[2..]),
+            format!(
+                "{:?}",
+                [DataEvent::CompressedQuality {
+                    idx: 3,
+                    x: 23,
+                    position: 0,
+                    xy: XY { x: 12, y: 101 },
+                    significance: None,
+                    values: vec![Value::Decimal(27000, -2), Value::Decimal(27001, -2)]
+                }]
+            )
+        );
+    }
+
+    #[test]
+    fn test_first_order_statistics() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 103,
+                },
+                Descriptor { f: 2, x: 24, y: 0 },
+                Descriptor { f: 1, x: 1, y: 2 },
+                Descriptor { f: 0, x: 31, y: 31 },
+                Descriptor { f: 0, x: 8, y: 23 },
+                Descriptor {
+                    f: 2,
+                    x: 24,
+                    y: 255,
+                },
+                Descriptor { f: 0, x: 8, y: 23 },
+                Descriptor {
+                    f: 2,
+                    x: 24,
+                    y: 255,
+                },
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[
+            (16, 27315),
+            (16, 27015),
+            (1, 0),
+            (1, 0),
+            // Mean value
+            (6, 4),
+            (16, 27400),
+            // Missing first-order statistics
+            (6, 63),
+            (16, 27500),
+        ]);
+        let events = read_all(&section, &spec);
+        let qualities: Vec<_> = events
+            .iter()
+            .filter_map(|ev| match ev {
+                DataEvent::Quality {
+                    x: 24,
+                    position,
+                    significance,
+                    value,
+                    ..
+                } => Some((*position, *significance, value.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            format!("{qualities:?}"),
+            format!(
+                "{:?}",
+                [
+                    (0, Some(4), Value::Decimal(27400, -2)),
+                    (1, None, Value::Decimal(27500, -2))
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_difference_statistics() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![
+                // Air temperature (16 bits, scale 2, reference 0)
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+                // Pressure (14 bits, scale -1, reference 0)
+                Descriptor { f: 0, x: 10, y: 4 },
+                Descriptor { f: 2, x: 25, y: 0 },
+                Descriptor { f: 1, x: 1, y: 2 },
+                Descriptor { f: 0, x: 31, y: 31 },
+                Descriptor { f: 0, x: 8, y: 24 },
+                Descriptor { f: 1, x: 1, y: 2 },
+                Descriptor {
+                    f: 2,
+                    x: 25,
+                    y: 255,
+                },
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[
+            (16, 27315),
+            (14, 10132),
+            (1, 0),
+            (1, 0),
+            // Observed minus background
+            (6, 11),
+            // 17 bits with the reference value -65536
+            (17, 65536 + 125),
+            // 15 bits with the reference value -16384, the first value and all ones
+            (15, 0),
+        ]);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{:?}", &events[5..]),
+            format!(
+                "{:?}",
+                [
+                    DataEvent::Quality {
+                        idx: 0,
+                        x: 25,
+                        position: 0,
+                        xy: XY { x: 12, y: 101 },
+                        significance: Some(11),
+                        value: Value::Decimal(125, -2),
+                    },
+                    DataEvent::Quality {
+                        idx: 0,
+                        x: 25,
+                        position: 1,
+                        xy: XY { x: 10, y: 4 },
+                        significance: Some(11),
+                        value: Value::Decimal(-16384, 1),
+                    },
+                ]
+            )
+        );
+
+        // All ones are missing
+        let section = make_section(&[
+            (16, 27315),
+            (14, 10132),
+            (1, 0),
+            (1, 0),
+            (6, 11),
+            (17, (1 << 17) - 1),
+            (15, (1 << 15) - 1),
+        ]);
+        let events = read_all(&section, &spec);
+        assert!(events[5..].iter().all(|ev| matches!(
+            ev,
+            DataEvent::Quality {
+                value: Value::Missing,
+                ..
+            }
+        )));
+
+        // A 31-bit element gives 32-bit values with the reference value -2^31
+        let dds = make_dds(
+            vec![
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+                Descriptor { f: 2, x: 25, y: 0 },
+                Descriptor { f: 0, x: 31, y: 31 },
+                Descriptor { f: 0, x: 8, y: 24 },
+                Descriptor {
+                    f: 2,
+                    x: 1,
+                    y: 128 + 15,
+                },
+                Descriptor {
+                    f: 2,
+                    x: 25,
+                    y: 255,
+                },
+                Descriptor { f: 2, x: 1, y: 0 },
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(16, 27315), (1, 0), (6, 11), (32, (1 << 31) + 125)]);
+        let events = read_all(&section, &spec);
+        assert!(matches!(
+            events[3],
+            DataEvent::Quality {
+                value: Value::Decimal(125, -2),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_replaced_retained_values() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+                Descriptor { f: 2, x: 32, y: 0 },
+                Descriptor { f: 0, x: 31, y: 31 },
+                // Identification of originating/generating centre
+                Descriptor { f: 0, x: 1, y: 31 },
+                Descriptor {
+                    f: 2,
+                    x: 32,
+                    y: 255,
+                },
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[
+            // Corrected value
+            (16, 27315),
+            (1, 0),
+            (16, 34),
+            // Original value retained
+            (16, 27515),
+        ]);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{:?}", &events[3..]),
+            format!(
+                "{:?}",
+                [DataEvent::Quality {
+                    idx: 4,
+                    x: 32,
+                    position: 0,
+                    xy: XY { x: 12, y: 101 },
+                    significance: None,
+                    value: Value::Decimal(27515, -2)
+                }]
+            )
+        );
+    }
+
+    #[test]
+    fn test_signify_data_width() {
+        let tables = Tables::default();
+        let descriptors = vec![
+            Descriptor { f: 2, x: 6, y: 12 },
+            Descriptor {
+                f: 0,
+                x: 63,
+                y: 250,
+            },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+        ];
+        let expected = |raw_values: &[u64], values: &[Value]| {
+            let temperature = match values {
+                [value] => DataEvent::Data {
+                    idx: 2,
+                    xy: XY { x: 12, y: 101 },
+                    entry: tables.table_b[&XY { x: 12, y: 101 }],
+                    value: value.clone(),
+                },
+                _ => DataEvent::CompressedData {
+                    idx: 2,
+                    xy: XY { x: 12, y: 101 },
+                    entry: tables.table_b[&XY { x: 12, y: 101 }],
+                    values: values.to_vec(),
+                },
+            };
+            format!(
+                "{:?}",
+                [
+                    DataEvent::UnknownLocalElement {
+                        idx: 1,
+                        xy: XY { x: 63, y: 250 },
+                        width: 12,
+                        values: raw_values.iter().map(|&v| RawValue::Integer(v)).collect(),
+                    },
+                    temperature
+                ]
+            )
+        };
+
+        let dds = make_dds(descriptors.clone(), 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(12, 999), (16, 27315)]);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{events:?}"),
+            expected(&[999], &[Value::Decimal(27315, -2)])
+        );
+
+        let mut dds = make_dds(descriptors.clone(), 2);
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(12, 999), (6, 3), (3, 1), (3, 2), (16, 27315), (6, 0)]);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{events:?}"),
+            expected(
+                &[1000, 1001],
+                &[Value::Decimal(27315, -2), Value::Decimal(27315, -2)]
+            )
+        );
+
+        // Increments with all bits set are missing
+        let section = make_section(&[(12, 999), (6, 3), (3, 7), (3, 0), (16, 27315), (6, 0)]);
+        let events = read_all(&section, &spec);
+        assert_eq!(
+            format!("{events:?}"),
+            expected(
+                &[4095, 999],
+                &[Value::Decimal(27315, -2), Value::Decimal(27315, -2)]
+            )
+        );
+
+        // Values wider than 64 bits
+        let mut descriptors = descriptors;
+        descriptors[0].y = 68;
+        let mut dds = make_dds(descriptors, 2);
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[
+            (4, 0xa),
+            (32, 0xffff_ffff),
+            (32, 0xffff_fffe),
+            (6, 2),
+            (2, 1),
+            (2, 3),
+            (16, 27315),
+            (6, 0),
+        ]);
+        let events = read_all(&section, &spec);
+        let DataEvent::UnknownLocalElement { values, .. } = &events[0] else {
+            panic!("Unexpected event: {:?}", events[0]);
+        };
+        assert_eq!(
+            format!("{values:?}"),
+            "[0x0affffffffffffffff, 0x0fffffffffffffffff]"
+        );
+        assert!(matches!(&events[1], DataEvent::CompressedData { .. }));
+    }
+
+    #[test]
+    fn test_unresolved_descriptor() {
+        let tables = Tables::default();
+        // 0 63 250 is not in Table B, but is only read when replicated
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 1, x: 1, y: 0 },
+                Descriptor { f: 0, x: 31, y: 1 },
+                Descriptor {
+                    f: 0,
+                    x: 63,
+                    y: 250,
+                },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+
+        let section = make_section(&[(8, 0), (16, 27315)]);
+        let events = read_all(&section, &spec);
+        assert_eq!(events.len(), 1);
+
+        let section = make_section(&[(8, 1), (16, 27315)]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let result = loop {
+            match reader.read_event() {
+                Ok(DataEvent::Eof) => break Ok(()),
+                Ok(_) => {}
+                Err(e) => break Err(e),
+            }
+        };
+        assert!(matches!(result, Err(Error::Table(_))));
+    }
+
+    #[test]
+    fn test_value_fmt() {
+        assert_eq!(format!("{:?}", Value::Missing), "Missing");
+        assert_eq!(format!("{:?}", Value::Decimal(1234, -2)), "12.34");
+        assert_eq!(format!("{:?}", Value::Decimal(1234, 2)), "123400");
+        assert_eq!(format!("{:?}", Value::Integer(42)), "42");
+        assert_eq!(format!("{:?}", Value::Decimal64(-1234, -2)), "-12.34");
+        assert_eq!(format!("{:?}", Value::Decimal64(1234, 2)), "123400");
+        assert_eq!(format!("{:?}", Value::Decimal64(12, 39)), "12e39");
+        assert_eq!(
+            format!("{:?}", Value::Decimal64(i64::MAX, 20)),
+            "9223372036854775807e20"
+        );
+        assert_eq!(
+            format!("{:?}", Value::Decimal64(1, -128)),
+            format!("0.{}1", "0".repeat(127))
+        );
+        assert_eq!(
+            format!("{:?}", Value::String("Hello".to_string())),
+            "\"Hello\""
+        );
+    }
+
+    #[test]
+    fn test_short_delayed_replication() {
+        let tables = Tables::default();
+        let mut dds = make_dds(
+            vec![
+                Descriptor { f: 1, x: 1, y: 0 },
+                Descriptor { f: 0, x: 31, y: 0 },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+                Descriptor { f: 0, x: 1, y: 1 },
+            ],
+            2,
+        );
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+
+        let read_events = |section: &[u8]| {
+            let mut reader = DataReader::new(section, &spec).unwrap();
+            let mut events = vec![];
+            loop {
+                match reader.read_event().unwrap() {
+                    DataEvent::Eof => return events,
+                    ev => events.push(format!("{ev:?}")),
+                }
+            }
+        };
+
+        // The 1-bit factor is 0: the replication is empty
+        let events = read_events(&make_section(&[(1, 0), (6, 0), (7, 47), (6, 0)]));
+        assert_eq!(
+            events[1],
+            "ReplicationStart { idx: 0, count: 0, factor_bits: 1, subset_counts: None }"
+        );
+        assert_eq!(events[2], "ReplicationEnd");
+        assert!(events[3].starts_with("CompressedData { idx: 1"));
+
+        // The 1-bit factor is 1
+        let events = read_events(&make_section(&[
+            (1, 1),
+            (6, 0),
+            (16, 27315),
+            (6, 0),
+            (7, 47),
+            (6, 0),
+        ]));
+        assert_eq!(
+            events[1],
+            "ReplicationStart { idx: 0, count: 1, factor_bits: 1, subset_counts: None }"
+        );
+        assert_eq!(events[2], "ReplicationItemStart");
+        assert!(events[3].starts_with("CompressedData { idx: 0"));
+        assert_eq!(events[4], "ReplicationItemEnd");
+        assert_eq!(events[5], "ReplicationEnd");
+        assert!(events[6].starts_with("CompressedData { idx: 1"));
+    }
+
+    #[test]
+    fn test_compressed_increments() {
+        let tables = Tables::default();
+        let mut dds = make_dds(vec![Descriptor { f: 0, x: 1, y: 1 }], 3);
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+
+        let read_values = |section: &[u8]| -> Result<String, Error> {
+            let mut reader = DataReader::new(section, &spec)?;
+            loop {
+                match reader.read_event()? {
+                    DataEvent::CompressedData { values, .. } => return Ok(format!("{values:?}")),
+                    DataEvent::Eof => unreachable!(),
+                    _ => {}
+                }
+            }
+        };
+
+        // All increment bits set means missing for that subset only
+        assert_eq!(
+            read_values(&make_section(&[(7, 120), (6, 4), (4, 1), (4, 15), (4, 7)])).unwrap(),
+            "[121, Missing, Missing]"
+        );
+
+        // NBINC wider than the element
+        assert!(matches!(
+            read_values(&make_section(&[(7, 120), (6, 8), (8, 0), (8, 0), (8, 0)])),
+            Err(Error::Invalid(msg)) if msg.contains("NBINC 8") && msg.contains("0 01 001")
+        ));
+
+        // Increment overflowing the width of the element
+        assert!(matches!(
+            read_values(&make_section(&[(7, 120), (6, 4), (4, 1), (4, 9), (4, 0)])),
+            Err(Error::Invalid(msg)) if msg.contains("Increment 9") && msg.contains("0 01 001")
+        ));
+    }
+
+    #[test]
+    fn test_negative_reference_values() {
+        let tables = Tables::default();
+        // 0 01 041 (31 bits, reference value -2^30) widened to 32 bits,
+        // and 0 02 063 (16 bits, reference value -18000)
+        let descriptors = vec![
+            Descriptor { f: 2, x: 1, y: 129 },
+            Descriptor { f: 0, x: 1, y: 41 },
+            Descriptor { f: 2, x: 1, y: 0 },
+            Descriptor { f: 0, x: 2, y: 63 },
+        ];
+        let read_values = |spec: &DataSpec, section: &[u8]| {
+            let mut reader = DataReader::new(section, spec).unwrap();
+            let mut values = vec![];
+            loop {
+                match reader.read_event().unwrap() {
+                    DataEvent::Data { value, .. } => values.push(format!("{value:?}")),
+                    DataEvent::CompressedData { values: v, .. } => values.push(format!("{v:?}")),
+                    DataEvent::Eof => return values,
+                    _ => {}
+                }
+            }
+        };
+
+        let dds = make_dds(descriptors.clone(), 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        assert_eq!(
+            read_values(&spec, &make_section(&[(32, 0xF000_0000), (16, 0)])),
+            ["29527.90016", "-180.00"]
+        );
+        assert_eq!(
+            read_values(&spec, &make_section(&[(32, 0x8000_0000), (16, 36000)])),
+            ["10737.41824", "180.00"]
+        );
+
+        let mut dds = make_dds(descriptors, 2);
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        assert_eq!(
+            read_values(
+                &spec,
+                &make_section(&[
+                    (32, 0x8000_0000),
+                    (6, 31),
+                    (31, 0),
+                    (31, 0x7000_0000),
+                    (16, 0),
+                    (6, 0),
+                ])
+            ),
+            ["[10737.41824, 29527.90016]", "[-180.00, -180.00]"]
+        );
+    }
+
+    #[test]
+    fn test_iter_elements() {
+        let tables = Tables::default();
+        let descriptors = vec![
+            Descriptor { f: 0, x: 1, y: 1 },
+            Descriptor { f: 2, x: 1, y: 130 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+            Descriptor { f: 2, x: 1, y: 0 },
+            Descriptor { f: 1, x: 1, y: 2 },
+            Descriptor { f: 0, x: 2, y: 63 },
+            Descriptor { f: 1, x: 1, y: 0 },
+            Descriptor { f: 0, x: 31, y: 1 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+            Descriptor { f: 2, x: 7, y: 1 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+            Descriptor { f: 2, x: 7, y: 0 },
+            Descriptor { f: 3, x: 1, y: 1 },
+        ];
+        let dds = make_dds(descriptors, 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let elements: Vec<_> = spec
+            .iter_elements()
+            .map(|e| {
+                (
+                    e.entry.xy,
+                    e.bit_width,
+                    e.reference_value,
+                    e.scale,
+                    e.in_delayed_replication,
+                )
+            })
+            .collect();
+        assert_eq!(
+            elements,
+            [
+                (XY { x: 1, y: 1 }, 7, 0, 0, false),
+                (XY { x: 12, y: 101 }, 18, 0, 2, false),
+                (XY { x: 2, y: 63 }, 16, -18000, 2, false),
+                (XY { x: 2, y: 63 }, 16, -18000, 2, false),
+                (XY { x: 12, y: 101 }, 16, 0, 2, true),
+                (XY { x: 12, y: 101 }, 20, 0, 3, false),
+                (XY { x: 1, y: 1 }, 7, 0, 0, false),
+                (XY { x: 1, y: 2 }, 10, 0, 0, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_value_policies() {
+        let tables = Tables::default();
+        let descriptors = vec![Descriptor { f: 0, x: 1, y: 1 }];
+        let read_values = |spec: &DataSpec, missing_values: &MissingValues, section: &[u8]| {
+            let mut reader = DataReader::builder(spec)
+                .missing_values(missing_values.clone())
+                .build(section)
+                .unwrap();
+            let mut values = vec![];
+            loop {
+                match reader.read_event().unwrap() {
+                    DataEvent::Data { value, .. } => values.push(format!("{value:?}")),
+                    DataEvent::CompressedData { values: v, .. } => values.push(format!("{v:?}")),
+                    DataEvent::Eof => return values,
+                    _ => {}
+                }
+            }
+        };
+
+        let dds = make_dds(descriptors.clone(), 2);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(7, 0), (7, 127)]);
+        let mut missing_values = MissingValues::default();
+        assert_eq!(
+            read_values(&spec, &missing_values, &section),
+            ["0", "Missing"]
+        );
+        missing_values.insert(XY { x: 1, y: 1 }, MissingValue::Sentinel(0));
+        assert_eq!(
+            read_values(&spec, &missing_values, &section),
+            ["Missing", "127"]
+        );
+        missing_values.insert(XY { x: 1, y: 1 }, MissingValue::None);
+        assert_eq!(read_values(&spec, &missing_values, &section), ["0", "127"]);
+
+        // The all-ones increment is not a missing value with other policies
+        let mut dds = make_dds(descriptors, 3);
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(7, 0), (6, 2), (2, 0), (2, 3), (2, 1)]);
+        let mut missing_values = MissingValues::default();
+        assert_eq!(
+            read_values(&spec, &missing_values, &section),
+            ["[0, Missing, 1]"]
+        );
+        missing_values.insert(XY { x: 1, y: 1 }, MissingValue::Sentinel(0));
+        assert_eq!(
+            read_values(&spec, &missing_values, &section),
+            ["[Missing, 3, 1]"]
+        );
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let tables = Tables::default();
+        // Block number and a delayed replication of station numbers
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 0, x: 1, y: 1 },
+                Descriptor { f: 1, x: 1, y: 0 },
+                Descriptor { f: 0, x: 31, y: 1 },
+                Descriptor { f: 0, x: 1, y: 2 },
+            ],
+            2,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[
+            (7, 47),
+            (8, 2),
+            (10, 662),
+            (10, 671),
+            (7, 48),
+            (8, 1),
+            (10, 1),
+        ]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        // Reads the given number of events, or all the remaining ones
+        let read_events = |reader: &mut DataReader<'_, &[u8]>, n: Option<usize>| {
+            let mut events = vec![];
+            while n.is_none_or(|n| events.len() < n) {
+                let event = reader.read_event().unwrap();
+                let eof = matches!(event, DataEvent::Eof);
+                events.push(format!("{event:?}"));
+                if eof {
+                    break;
+                }
+            }
+            events
+        };
+
+        // In the middle of an octet and of a replication
+        let start = read_events(&mut reader, Some(5));
+        assert!(start[4].contains("662"), "{start:?}");
+        let checkpoint = reader.checkpoint();
+        let rest = read_events(&mut reader, None);
+        assert!(rest.len() > 10);
+        for _ in 0..2 {
+            reader.restore(&checkpoint);
+            assert_eq!(read_events(&mut reader, None), rest);
+        }
+
+        // Decoding the second subset again after skipping to it
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        reader.skip_subset().unwrap();
+        let checkpoint = reader.checkpoint();
+        let second = reader.read_subset().unwrap().unwrap();
+        assert!(reader.read_subset().unwrap().is_none());
+        reader.restore(&checkpoint);
+        assert_eq!(reader.read_subset().unwrap().unwrap(), second);
+    }
+
+    #[test]
+    fn test_finish() {
+        let tables = Tables::default();
+        let dds = make_dds(vec![Descriptor { f: 0, x: 1, y: 1 }], 2);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(7, 47), (7, 48)]);
+        let mut message = section.clone();
+        message.extend(b"7777next");
+
+        // Before reading anything, in the middle of the data and after the end
+        for events in [0, 2, 100] {
+            let mut reader = DataReader::new(message.as_slice(), &spec).unwrap();
+            for _ in 0..events {
+                if matches!(reader.read_event().unwrap(), DataEvent::Eof) {
+                    break;
+                }
+            }
+            assert_eq!(reader.finish().unwrap(), b"7777next");
+        }
+
+        let reader = DataReader::new(message.as_slice(), &spec).unwrap();
+        assert_eq!(reader.finish_message(3).unwrap(), b"next");
+
+        // Missing end section
+        let reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        assert!(reader.finish_message(3).is_err());
+
+        // Data section longer than declared
+        let mut overrun = section.clone();
+        overrun[..3].copy_from_slice(&5u32.to_be_bytes()[1..]);
+        let reader = DataReader::builder(&spec)
+            .strictness(Strictness::Strict)
+            .build(overrun.as_slice())
+            .unwrap();
+        assert!(reader.finish().is_err());
+    }
+
+    #[test]
+    fn test_section_length() {
+        let tables = Tables::default();
+        let dds = make_dds(vec![Descriptor { f: 0, x: 1, y: 1 }], 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+
+        // Reads all events and returns the warnings and the number of bytes left
+        let read_section = |strictness: Strictness, section: &[u8]| {
+            let mut reader = DataReader::builder(&spec)
+                .strictness(strictness)
+                .build(section)?;
+            while !matches!(reader.read_event()?, DataEvent::Eof) {}
+            // Reading again after the end does not skip anything else
+            reader.read_event()?;
+            let warnings = reader.warnings().to_vec();
+            Ok::<_, Error>((warnings, reader.into_inner().len()))
+        };
+        let with_length = |section: &[u8], length: u32| {
+            let mut section = section.to_vec();
+            section[..3].copy_from_slice(&length.to_be_bytes()[1..]);
+            section
+        };
+
+        // Padded to an octet (5 bytes), and to an even length as in edition 3
+        let section = make_section(&[(7, 47)]);
+        assert_eq!(
+            read_section(Strictness::Lenient, &section).unwrap(),
+            (vec![], 0)
+        );
+        let mut padded = with_length(&section, 6);
+        padded.extend([0, 0x37]);
+        assert_eq!(
+            read_section(Strictness::Lenient, &padded).unwrap(),
+            (vec![], 1)
+        );
+
+        // Bytes left after the data are skipped with a warning
+        let mut padded = with_length(&section, 8);
+        padded.extend([0, 0, 0, 0x37]);
+        let (warnings, left) = read_section(Strictness::Lenient, &padded).unwrap();
+        assert_eq!(left, 1);
+        assert_eq!(
+            warnings,
+            ["Data section has 3 bytes left after the data (declared length 8 bytes)"]
+        );
+
+        // Data overrunning the declared length
+        let (warnings, _) = read_section(Strictness::Lenient, &with_length(&section, 4)).unwrap();
+        assert_eq!(
+            warnings,
+            ["Data section overruns its declared length of 4 bytes by 1 bytes"]
+        );
+
+        // Errors in strict mode
+        assert!(read_section(Strictness::Strict, &section).is_ok());
+        assert!(matches!(
+            read_section(Strictness::Strict, &padded),
+            Err(Error::Invalid(msg)) if msg.contains("3 bytes left")
+        ));
+    }
+
+    #[test]
+    fn test_compressed_delayed_factors_per_subset() {
+        let tables = Tables::default();
+        let mut dds = make_dds(
+            vec![
+                Descriptor { f: 1, x: 1, y: 0 },
+                Descriptor { f: 0, x: 31, y: 1 },
+                Descriptor { f: 0, x: 1, y: 1 },
+            ],
+            3,
+        );
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+
+        // Factors 1, 3 and 2, then 3 items with padding marked missing
+        let section = make_section(&[
+            (8, 1),
+            (6, 2),
+            (2, 0),
+            (2, 2),
+            (2, 1),
+            (7, 10),
+            (6, 0),
+            (7, 20),
+            (6, 3),
+            (3, 7),
+            (3, 0),
+            (3, 1),
+            (7, 30),
+            (6, 3),
+            (3, 7),
+            (3, 0),
+            (3, 7),
+        ]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let snapshot = crate::snapshot::snapshot_events(&mut reader).unwrap();
+        assert!(snapshot.contains("replication 3 (delayed, 8 bits, subsets [1, 3, 2])"));
+        assert!(snapshot.contains("0 01 001 = [Missing, 30, Missing]"));
+
+        // The same factor for all subsets
+        let section = make_section(&[(8, 1), (6, 1), (1, 0), (1, 0), (1, 0), (7, 10), (6, 0)]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let snapshot = crate::snapshot::snapshot_events(&mut reader).unwrap();
+        assert!(snapshot.contains("replication 1 (delayed, 8 bits)\n"));
+    }
+
+    #[test]
+    fn test_unknown_widths() {
+        let tables = Tables::default();
+        let descriptors = vec![
+            Descriptor {
+                f: 0,
+                x: 63,
+                y: 250,
+            },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+        ];
+        let dds = make_dds(descriptors, 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(12, 999), (16, 27315)]);
+        let read = |options: &DecodeOptions| {
+            let mut reader = DataReader::builder(&spec)
+                .decode_options(options)
+                .build(section.as_slice())?;
+            let mut events = vec![];
+            loop {
+                match reader.read_event()? {
+                    DataEvent::Eof => return Ok::<_, Error>((events, reader.warnings().to_vec())),
+                    DataEvent::Data { idx, xy, value, .. } => events.push(format!(
+                        "Data {{ idx: {idx}, xy: {xy:?}, value: {value:?} }}"
+                    )),
+                    ev => events.push(format!("{ev:?}")),
+                }
+            }
+        };
+        let mut options = DecodeOptions::lenient();
+        assert!(matches!(read(&options), Err(Error::Table(_))));
+
+        options.unknown_widths.insert(XY { x: 63, y: 250 }, 12);
+        let (events, warnings) = read(&options).unwrap();
+        assert_eq!(
+            events[1..3],
+            [
+                "UnknownLocalElement { idx: 0, xy: XY { x: 63, y: 250 }, width: 12, values: [999] }",
+                "Data { idx: 1, xy: XY { x: 12, y: 101 }, value: 273.15 }"
+            ]
+        );
+        assert_eq!(
+            warnings,
+            ["Unknown element 0 63 250 read as raw values (12 bits)"]
+        );
+
+        // Unknown elements are errors in strict mode
+        options.strictness = Strictness::Strict;
+        assert!(matches!(read(&options), Err(Error::Table(_))));
+    }
+
+    #[test]
+    fn test_character_decoding() {
+        let tables = Tables::default();
+        // Station or site name (20 characters) in Latin-1
+        let dds = make_dds(vec![Descriptor { f: 0, x: 1, y: 15 }], 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut name = b"K\xf6ln".to_vec();
+        name.resize(20, b' ');
+        let fields: Vec<_> = name.iter().map(|&c| (8, c as u32)).collect();
+        let section = make_section(&fields);
+        let read = |decoding: CharacterDecoding| {
+            let mut reader = DataReader::builder(&spec)
+                .character_decoding(decoding)
+                .build(section.as_slice())?;
+            loop {
+                if let DataEvent::Data { value, .. } = reader.read_event()? {
+                    return Ok::<_, Error>(value);
+                }
+            }
+        };
+        assert!(matches!(
+            read(CharacterDecoding::Utf8),
+            Err(Error::Invalid(_))
+        ));
+
+        let mut decoded = vec![];
+        for decoding in [
+            CharacterDecoding::Lossy,
+            CharacterDecoding::Latin1,
+            CharacterDecoding::Bytes,
+        ] {
+            decoded.push(format!("{:?}", read(decoding).unwrap()));
+        }
+        assert_eq!(
+            decoded,
+            [
+                "\"K\u{fffd}ln                \"",
+                "\"Köln                \"",
+                "b\"K\\xf6ln                \""
+            ]
+        );
+
+        // Valid UTF-8 is a string in every mode
+        assert_eq!(
+            CharacterDecoding::Bytes.decode("Köln".into()),
+            Some(Value::String("Köln".to_string()))
+        );
+        assert_eq!(
+            "latin1".parse::<CharacterDecoding>().unwrap(),
+            CharacterDecoding::Latin1
+        );
+    }
+
+    #[test]
+    fn test_data_reader_builder() {
+        let tables = Tables::default();
+        // Air temperatures replicated by a delayed factor, and pressure
+        let descriptors = vec![
+            Descriptor { f: 1, x: 1, y: 0 },
+            Descriptor { f: 0, x: 31, y: 1 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+            Descriptor { f: 0, x: 7, y: 4 },
+        ];
+        let dds = make_dds(descriptors, 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(8, 3), (16, 27315), (16, 27415), (16, 27515), (14, 5000)]);
+        let read = |builder: DataReaderBuilder<&[u8]>| {
+            let mut reader = builder.build(section.as_slice())?;
+            let mut values = vec![];
+            loop {
+                match reader.read_event()? {
+                    DataEvent::Data { xy, value, .. } => values.push((xy.x, format!("{value:?}"))),
+                    DataEvent::Eof => return Ok::<_, Error>(values),
+                    _ => {}
+                }
+            }
+        };
+        // The delayed replication factor is not a value
+        assert_eq!(read(DataReader::builder(&spec)).unwrap().len(), 4);
+
+        // Only the projected elements are emitted
+        let projected = read(DataReader::builder(&spec).projection([XY { x: 7, y: 4 }])).unwrap();
+        assert_eq!(projected, [(7, "50000".to_string())]);
+        let filtered = read(DataReader::builder(&spec).filter(|xy| xy.x == 7)).unwrap();
+        assert_eq!(filtered, projected);
+
+        // Limits
+        assert!(read(DataReader::builder(&spec).max_replication_count(3)).is_ok());
+        assert!(matches!(
+            read(DataReader::builder(&spec).max_replication_count(2)),
+            Err(Error::Invalid(_))
+        ));
+        assert!(read(DataReader::builder(&spec).max_values(4)).is_ok());
+        assert!(matches!(
+            read(DataReader::builder(&spec).max_values(3)),
+            Err(Error::Invalid(_))
+        ));
+        let limits = Limits {
+            max_values: Some(3),
+            time_limit: Some(std::time::Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert!(matches!(
+            read(DataReader::builder(&spec).limits(limits.clone())),
+            Err(Error::Invalid(_))
+        ));
+        // Unset limits leave those set before
+        let builder = DataReader::builder(&spec).max_replication_count(2);
+        assert!(matches!(
+            read(builder.limits(Limits::default())),
+            Err(Error::Invalid(_))
+        ));
+
+        // Hints reserve memory without changing the events
+        let hints = Hints {
+            elements_per_subset: Some(100),
+        };
+        assert_eq!(
+            read(DataReader::builder(&spec).hints(hints.clone())).unwrap(),
+            read(DataReader::builder(&spec)).unwrap()
+        );
+        let mut reader = DataReader::builder(&spec)
+            .hints(hints)
+            .build(section.as_slice())
+            .unwrap();
+        while !matches!(reader.read_event().unwrap(), DataEvent::Eof) {}
+        assert!(reader.bitmap.elements.capacity() >= 100);
+
+        // Any reader
+        let mut reader = DataReader::builder(&spec)
+            .projection([XY { x: 7, y: 4 }])
+            .build(std::io::Cursor::new(&section))
+            .unwrap();
+        assert!(matches!(reader.read_event(), Ok(DataEvent::SubsetStart(0))));
+
+        // Decode options, on their own or all at once
+        let mut truncated = section.clone();
+        truncated[2] += 1;
+        let read_truncated = |builder: DataReaderBuilder<&[u8]>| {
+            let mut reader = builder.build(truncated.as_slice())?;
+            while !matches!(reader.read_event()?, DataEvent::Eof) {}
+            Ok::<_, Error>(reader.warnings().len())
+        };
+        assert!(read_truncated(DataReader::builder(&spec).strictness(Strictness::Strict)).is_err());
+        assert_eq!(
+            read_truncated(DataReader::builder(&spec).decode_options(&DecodeOptions::lenient()))
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_deadline() {
+        let tables = Tables::default();
+        let dds = make_dds(vec![Descriptor { f: 0, x: 7, y: 4 }], 2);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(14, 5000), (14, 8500)]);
+        let mut reader = DataReader::builder(&spec)
+            .deadline(std::time::Instant::now())
+            .build(section.as_slice())
+            .unwrap();
+        let Err(Error::Timeout(message)) = reader.read_event() else {
+            panic!("The deadline should have passed");
+        };
+        assert_eq!(
+            message,
+            "Deadline passed after 0 events and 0 values (0 of 2 subsets started)"
+        );
+
+        let mut reader = DataReader::builder(&spec)
+            .time_limit(std::time::Duration::from_secs(3600))
+            .build(section.as_slice())
+            .unwrap();
+        while !matches!(reader.read_event().unwrap(), DataEvent::Eof) {}
+    }
+
+    #[test]
+    fn test_long_and_deeply_nested_descriptors() {
+        let tables = Tables::default();
+        let temperature = Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        };
+        // 300 temperatures followed by 40 nested replications of one item around a
+        // delayed replication of WMO block and station numbers (3 01 001)
+        let mut nested = vec![
+            Descriptor { f: 1, x: 1, y: 0 },
+            Descriptor { f: 0, x: 31, y: 1 },
+            Descriptor { f: 3, x: 1, y: 1 },
+        ];
+        for _ in 0..40 {
+            nested.insert(
+                0,
+                Descriptor {
+                    f: 1,
+                    x: nested.len() as u8,
+                    y: 1,
+                },
+            );
+        }
+        let mut descriptors = vec![temperature; 300];
+        descriptors.extend(nested);
+        let dds = make_dds(descriptors, 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+
+        let mut fields = vec![(16, 28015); 300];
+        fields.extend([(8, 2), (7, 47), (10, 662), (7, 47), (10, 671)]);
+        let section = make_section(&fields);
+        let reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        assert!(reader.stack.capacity() >= 43);
+        let events = read_all(&section, &spec);
+        assert_eq!(events.len(), 304);
+        assert!(matches!(
+            events[299],
+            DataEvent::Data {
+                idx: 299,
+                value: Value::Decimal(28015, -2),
+                ..
+            }
+        ));
+        assert!(matches!(
+            events[303],
+            DataEvent::Data {
+                value: Value::Integer(671),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_skip_subset() {
+        let tables = Tables::default();
+        let temperature = Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        };
+        let descriptors = vec![
+            // WMO block and station numbers
+            Descriptor { f: 3, x: 1, y: 1 },
+            // Station name
+            Descriptor { f: 0, x: 1, y: 15 },
+            Descriptor { f: 1, x: 1, y: 0 },
+            Descriptor { f: 0, x: 31, y: 1 },
+            temperature,
+            Descriptor { f: 1, x: 1, y: 2 },
+            temperature,
+        ];
+        let dds = make_dds(descriptors.clone(), 3);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut writer = crate::DataWriter::new(&spec);
+        for (station, count) in [(662, 1), (671, 3), (680, 0)] {
+            let mut values = vec![
+                Value::Integer(47),
+                Value::Integer(station),
+                Value::String(format!("{station:<20}")),
+                Value::Integer(count),
+            ];
+            values.extend((0..count).map(|i| Value::Decimal(27315 + i, -2)));
+            values.extend([Value::Decimal(28015, -2), Value::Missing]);
+            writer.write_subset(&values).unwrap();
+        }
+        let section = writer.finish().unwrap();
+
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        assert!(reader.skip_subset().unwrap());
+        let mut expected = DataReader::new(section.as_slice(), &spec).unwrap();
+        expected.read_subset().unwrap();
+        // The skipped elements are still counted for bit-maps
+        assert_eq!(reader.bitmap.elements.len(), 6);
+        assert_eq!(expected.bitmap.elements.len(), 6);
+        let second = reader.read_subset().unwrap().unwrap();
+        assert_eq!(second, expected.read_subset().unwrap().unwrap());
+
+        // The rest of the current subset
+        assert!(matches!(
+            reader.read_event().unwrap(),
+            DataEvent::SubsetStart(2)
+        ));
+        assert!(matches!(
+            reader.read_event().unwrap(),
+            DataEvent::SequenceStart { .. }
+        ));
+        assert!(reader.skip_subset().unwrap());
+        assert!(!reader.skip_subset().unwrap());
+        assert!(matches!(reader.read_event().unwrap(), DataEvent::Eof));
+
+        let mut dds = make_dds(descriptors, 3);
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        assert!(matches!(reader.skip_subset(), Err(Error::NotSupported(_))));
+    }
+
+    #[test]
+    fn test_data_entry() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            }],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(16, 27315)]);
+        let events = read_all(&section, &spec);
+        let DataEvent::Data { entry, .. } = events[0] else {
+            panic!("{:?}", events[0]);
+        };
+        assert!(std::ptr::eq(entry, tables.table_b[&XY { x: 12, y: 101 }]));
+        assert_eq!((entry.unit, entry.scale), ("K", 2));
+    }
+
+    #[test]
+    fn test_path() {
+        let tables = Tables::default();
+        // Block and station numbers, then those of two more stations
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 3, x: 1, y: 1 },
+                Descriptor { f: 1, x: 1, y: 2 },
+                Descriptor { f: 3, x: 1, y: 1 },
+            ],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(7, 47), (10, 662), (7, 47), (10, 671), (7, 47), (10, 401)]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let sequence = PathSegment::Sequence(XY { x: 1, y: 1 });
+        let item = |item| PathSegment::ReplicationItem { idx: 1, item };
+        let mut paths = vec![];
+        loop {
+            match reader.read_event().unwrap() {
+                DataEvent::Data {
+                    value: Value::Integer(station),
+                    xy: XY { x: 1, y: 2 },
+                    ..
+                } => paths.push((station, reader.path().to_vec())),
+                DataEvent::ReplicationStart { .. }
+                | DataEvent::ReplicationItemEnd
+                | DataEvent::SequenceEnd => {
+                    assert!(!reader.path().ends_with(&[sequence]));
+                }
+                DataEvent::SubsetEnd => break,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            paths,
+            [
+                (662, vec![sequence]),
+                (671, vec![item(0), sequence]),
+                (401, vec![item(1), sequence]),
+            ]
+        );
+        assert!(reader.path().is_empty());
+    }
+
+    #[test]
+    fn test_event_offset() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 0, x: 1, y: 1 },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+            ],
+            2,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(7, 47), (16, 27315), (7, 47), (16, 27415)]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let mut offsets = vec![];
+        loop {
+            let event = reader.read_event().unwrap();
+            offsets.push(reader.event_offset());
+            if let DataEvent::Eof = event {
+                break;
+            }
+        }
+        // The data start after the 4 octets of the section header
+        assert_eq!(offsets, [32, 32, 39, 55, 55, 55, 62, 78, 78]);
+
+        // The second subset is missing
+        let section = make_section(&[(7, 47), (16, 27315)]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let error = loop {
+            if let Err(e) = reader.read_event() {
+                break e;
+            }
+        };
+        assert!(matches!(error, Error::Io(_)));
+        assert_eq!(reader.event_offset(), 55);
+    }
+
+    #[test]
+    fn test_events() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 0, x: 1, y: 1 },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+            ],
+            2,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = make_section(&[(7, 47), (16, 27315), (7, 48), (16, 27415)]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let blocks: Vec<_> = reader
+            .events()
+            .filter_map(|event| match event.unwrap() {
+                DataEvent::Data {
+                    xy: XY { x: 1, y: 1 },
+                    value,
+                    ..
+                } => Some(value),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(blocks, [Value::Integer(47), Value::Integer(48)]);
+        assert!(reader.events().next().is_none());
+
+        // The first subset only, after which the events go on
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let first = reader
+            .events()
+            .take_while(|event| !matches!(event, Ok(DataEvent::SubsetEnd)))
+            .count();
+        assert_eq!(first, 3);
+        assert!(matches!(
+            reader.read_event().unwrap(),
+            DataEvent::SubsetStart(1)
+        ));
+
+        // Ends after an error
+        let section = make_section(&[(7, 47), (16, 27315)]);
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let events: Vec<_> = reader.events().collect();
+        assert_eq!(events.len(), 6);
+        assert!(matches!(events[5], Err(Error::Io(_))));
+    }
 }
@@ -0,0 +1,315 @@
+//! Decoding subsets into structs whose fields are mapped to descriptors
+//!
+//! With the `derive` feature, `#[derive(BufrRecord)]` implements [`BufrRecord`] for a
+//! struct whose fields are annotated with the descriptors they are read from:
+//!
+//! ```ignore
+//! use tinybufr::record::BufrRecord;
+//!
+//! #[derive(BufrRecord)]
+//! struct Synop {
+//!     #[bufr("001001")]
+//!     block: i32,
+//!     #[bufr("0 01 002")]
+//!     station: i32,
+//!     #[bufr("012101")]
+//!     temperature: Option<f64>,
+//!     #[bufr("020001", default)]
+//!     visibility: f64,
+//!     #[bufr("302043")]
+//!     wind: Option<Wind>,
+//!     #[bufr(replication)]
+//!     clouds: Vec<Cloud>,
+//! }
+//! ```
+//!
+//! Fields are looked up by descriptor among the values of the subset, wherever they
+//! are nested in sequences and replications:
+//!
+//! - A field of type `Option<T>` gets the first value found, or `None` if none is
+//!   found or the value is missing.
+//! - A field of type `Vec<T>` gets all the values found, in order of appearance. They
+//!   must not be missing, unless `T` is an `Option`.
+//! - A field of another type gets the first value found, which must not be missing
+//!   unless the field is marked as `default`.
+//! - `#[bufr(replication)]` fields get the items of the first replication, decoded as
+//!   records (`Vec<T>` or `Option<Vec<T>>`).
+//!
+//! Elements (0 XX YYY) are converted with [`FromSubsetValue`], and sequences (3 XX YYY)
+//! are decoded as records.
+
+use std::io::Read;
+
+use crate::{DataReader, Descriptor, Error, SubsetValue, Value};
+
+#[cfg(feature = "derive")]
+pub use tinybufr_derive::BufrRecord;
+
+/// A type decoded from the values of a subset (see the [module documentation](self)).
+pub trait BufrRecord: Sized {
+    /// Decodes a record from the values of a subset, a sequence or a replication item.
+    fn from_values(values: &[SubsetValue]) -> Result<Self, Error>;
+
+    /// Reads the next subset as a record, or returns `None` after the last one.
+    fn read<R: Read>(reader: &mut DataReader<'_, R>) -> Result<Option<Self>, Error> {
+        reader
+            .read_subset()?
+            .map(|subset| Self::from_values(&subset))
+            .transpose()
+    }
+}
+
+/// A type of the fields of a [`BufrRecord`].
+pub trait FromSubsetValue: Sized {
+    /// Converts a value, or returns `None` if it is missing.
+    fn from_value(value: &SubsetValue) -> Result<Option<Self>, Error>;
+}
+
+impl<T: FromSubsetValue> FromSubsetValue for Option<T> {
+    fn from_value(value: &SubsetValue) -> Result<Option<Self>, Error> {
+        Ok(Some(T::from_value(value)?))
+    }
+}
+
+impl FromSubsetValue for Value {
+    fn from_value(value: &SubsetValue) -> Result<Option<Self>, Error> {
+        match scalar(value)? {
+            Value::Missing => Ok(None),
+            value => Ok(Some(value.clone())),
+        }
+    }
+}
+
+impl FromSubsetValue for String {
+    fn from_value(value: &SubsetValue) -> Result<Option<Self>, Error> {
+        match scalar(value)? {
+            Value::Missing => Ok(None),
+            Value::String(s) => Ok(Some(s.clone())),
+            value => Err(unexpected_value(value)),
+        }
+    }
+}
+
+macro_rules! impl_from_subset_value_integer {
+    ($($ty:ty),*) => {
+        $(impl FromSubsetValue for $ty {
+            fn from_value(value: &SubsetValue) -> Result<Option<Self>, Error> {
+                let v = match *scalar(value)? {
+                    Value::Missing => return Ok(None),
+                    Value::Integer(v) => Some(v as i64),
+                    Value::Decimal(v, s) => scaled_integer(v as i64, s),
+                    Value::Decimal64(v, s) => scaled_integer(v, s),
+                    ref value => return Err(unexpected_value(value)),
+                };
+                v.and_then(|v| <$ty>::try_from(v).ok())
+                    .map(Some)
+                    .ok_or_else(|| {
+                        Error::Invalid(format!(
+                            "Value out of range for {}: {:?}",
+                            stringify!($ty),
+                            scalar(value).unwrap()
+                        ))
+                    })
+            }
+        })*
+    };
+}
+
+impl_from_subset_value_integer!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+macro_rules! impl_from_subset_value_float {
+    ($($ty:ty),*) => {
+        $(impl FromSubsetValue for $ty {
+            fn from_value(value: &SubsetValue) -> Result<Option<Self>, Error> {
+                Ok(match *scalar(value)? {
+                    Value::Missing => None,
+                    Value::Integer(v) => Some(v as $ty),
+                    ref v @ (Value::Decimal(..) | Value::Decimal64(..)) => {
+                        v.as_f64().map(|v| v as $ty)
+                    }
+                    ref value => return Err(unexpected_value(value)),
+                })
+            }
+        })*
+    };
+}
+
+impl_from_subset_value_float!(f32, f64);
+
+fn scalar(value: &SubsetValue) -> Result<&Value, Error> {
+    match value {
+        SubsetValue::Scalar { value, .. } => Ok(value),
+        _ => Err(Error::Invalid(
+            "Expected the value of an element, found a sequence or replication".to_string(),
+        )),
+    }
+}
+
+fn unexpected_value(value: &Value) -> Error {
+    Error::Invalid(format!("Unexpected value: {value:?}"))
+}
+
+fn scaled_integer(v: i64, scale: i8) -> Option<i64> {
+    if scale < 0 {
+        return None;
+    }
+    10i64
+        .checked_pow(scale as u32)
+        .and_then(|m| v.checked_mul(m))
+}
+
+/// Decodes a sequence as a record, for the [`FromSubsetValue`] implementation of
+/// derived records.
+pub fn from_sequence<T: BufrRecord>(value: &SubsetValue) -> Result<Option<T>, Error> {
+    match value {
+        SubsetValue::Sequence { values, .. } => T::from_values(values).map(Some),
+        _ => Err(Error::Invalid(
+            "Expected the values of a sequence".to_string(),
+        )),
+    }
+}
+
+/// Collects the values of an element or sequence, wherever they are nested in the
+/// given values, in order of appearance.
+pub fn find(values: &[SubsetValue], descriptor: Descriptor) -> Vec<&SubsetValue> {
+    fn find_into<'s>(
+        values: &'s [SubsetValue],
+        descriptor: Descriptor,
+        found: &mut Vec<&'s SubsetValue>,
+    ) {
+        for value in values {
+            match value {
+                SubsetValue::Scalar { xy, .. } if descriptor.f == 0 && descriptor.xy() == *xy => {
+                    found.push(value)
+                }
+                SubsetValue::Sequence { xy, .. } if descriptor.f == 3 && descriptor.xy() == *xy => {
+                    found.push(value)
+                }
+                SubsetValue::Sequence { values, .. } => find_into(values, descriptor, found),
+                SubsetValue::Replication { items } => {
+                    for item in items {
+                        find_into(item, descriptor, found);
+                    }
+                }
+                SubsetValue::Scalar { .. } => {}
+            }
+        }
+    }
+
+    let mut found = vec![];
+    find_into(values, descriptor, &mut found);
+    found
+}
+
+/// Converts the first value found, or returns `None` if none is found or it is missing.
+pub fn first<T: FromSubsetValue>(found: &[&SubsetValue]) -> Result<Option<T>, Error> {
+    match found.first() {
+        Some(value) => T::from_value(value),
+        None => Ok(None),
+    }
+}
+
+/// Converts all the values found, none of which may be missing.
+pub fn all<T: FromSubsetValue>(found: &[&SubsetValue], field: &str) -> Result<Vec<T>, Error> {
+    found
+        .iter()
+        .map(|value| T::from_value(value)?.ok_or_else(|| missing(field)))
+        .collect()
+}
+
+/// Decodes the items of the first replication in the given values, or returns `None`
+/// if there is none.
+pub fn items<T: BufrRecord>(values: &[SubsetValue]) -> Result<Option<Vec<T>>, Error> {
+    for value in values {
+        match value {
+            SubsetValue::Replication { items } => {
+                return items
+                    .iter()
+                    .map(|item| T::from_values(item))
+                    .collect::<Result<_, _>>()
+                    .map(Some);
+            }
+            SubsetValue::Sequence { values, .. } => {
+                if let Some(records) = items(values)? {
+                    return Ok(Some(records));
+                }
+            }
+            SubsetValue::Scalar { .. } => {}
+        }
+    }
+    Ok(None)
+}
+
+/// The error for a field whose value is missing or not found.
+pub fn missing(field: &str) -> Error {
+    Error::Invalid(format!("Missing value for field `{field}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::XY;
+    use crate::subset::tests::scalar;
+
+    #[test]
+    fn test_from_subset_value() {
+        let temperature = scalar(12, 101, Value::Decimal(28015, -2));
+        assert_eq!(f64::from_value(&temperature).unwrap(), Some(280.15));
+        assert!(i32::from_value(&temperature).is_err());
+        let pressure = scalar(7, 4, Value::Decimal(8500, 1));
+        assert_eq!(u32::from_value(&pressure).unwrap(), Some(85000));
+        assert!(u8::from_value(&pressure).is_err());
+        let missing = scalar(12, 101, Value::Missing);
+        assert_eq!(f64::from_value(&missing).unwrap(), None);
+        assert_eq!(Option::<f64>::from_value(&missing).unwrap(), Some(None));
+        let name = scalar(1, 15, Value::String("TOKYO".to_string()));
+        assert_eq!(String::from_value(&name).unwrap().unwrap(), "TOKYO");
+        assert!(f64::from_value(&name).is_err());
+        assert!(f64::from_value(&SubsetValue::Replication { items: vec![] }).is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        let values = vec![
+            SubsetValue::Sequence {
+                xy: XY { x: 1, y: 1 },
+                values: vec![scalar(1, 1, Value::Integer(47))],
+            },
+            SubsetValue::Replication {
+                items: vec![
+                    vec![scalar(12, 101, Value::Decimal(28015, -2))],
+                    vec![scalar(12, 101, Value::Missing)],
+                ],
+            },
+        ];
+        let block = find(&values, Descriptor { f: 0, x: 1, y: 1 });
+        assert_eq!(first::<i32>(&block).unwrap(), Some(47));
+        assert_eq!(find(&values, Descriptor { f: 3, x: 1, y: 1 }).len(), 1);
+        assert!(
+            find(
+                &values,
+                Descriptor {
+                    f: 3,
+                    x: 12,
+                    y: 101
+                }
+            )
+            .is_empty()
+        );
+
+        let temperatures = find(
+            &values,
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+        );
+        assert_eq!(
+            all::<Option<f64>>(&temperatures, "temperatures").unwrap(),
+            [Some(280.15), None]
+        );
+        assert!(all::<f64>(&temperatures, "temperatures").is_err());
+        assert_eq!(first::<f64>(&[]).unwrap(), None);
+    }
+}
@@ -0,0 +1,94 @@
+//! Policies for recovering from errors in inputs and messages
+//!
+//! A [`RecoveryPolicy`] is accepted by the pipeline entry points
+//! ([`MessageIterator`](crate::input::MessageIterator), the Arrow and Parquet converters
+//! and the command line tools), so a single switch trades completeness for robustness.
+//! Errors recovered from are reported alongside the results rather than discarded.
+
+use crate::Error;
+
+/// How errors in an input or a message are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecoveryPolicy {
+    /// The first error stops processing
+    #[default]
+    FailFast,
+    /// A message with an error is left out, and processing goes on with the next one
+    SkipMessage,
+    /// The subsets decoded completely before an error are kept, and processing goes on
+    /// with the next message
+    ///
+    /// Compressed data has no complete subset until all of it is decoded, so a
+    /// compressed message with an error is left out.
+    SkipSubset,
+    /// Everything decoded before an error is kept (including the columns of compressed
+    /// data decoded completely), and truncated messages are decoded as far as they go
+    BestEffort,
+}
+
+impl RecoveryPolicy {
+    /// Whether errors are recovered from at all.
+    pub fn recovers(self) -> bool {
+        self != RecoveryPolicy::FailFast
+    }
+
+    /// Whether the data decoded before an error in a message is kept.
+    pub fn keeps_partial(self, is_compressed: bool) -> bool {
+        match self {
+            RecoveryPolicy::FailFast | RecoveryPolicy::SkipMessage => false,
+            RecoveryPolicy::SkipSubset => !is_compressed,
+            RecoveryPolicy::BestEffort => true,
+        }
+    }
+}
+
+impl std::str::FromStr for RecoveryPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fail-fast" => Ok(RecoveryPolicy::FailFast),
+            "skip-message" => Ok(RecoveryPolicy::SkipMessage),
+            "skip-subset" => Ok(RecoveryPolicy::SkipSubset),
+            "best-effort" => Ok(RecoveryPolicy::BestEffort),
+            _ => Err(Error::Invalid(format!("Unknown recovery policy: {s:?}"))),
+        }
+    }
+}
+
+impl std::fmt::Display for RecoveryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RecoveryPolicy::FailFast => "fail-fast",
+            RecoveryPolicy::SkipMessage => "skip-message",
+            RecoveryPolicy::SkipSubset => "skip-subset",
+            RecoveryPolicy::BestEffort => "best-effort",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_policy() {
+        for policy in [
+            RecoveryPolicy::FailFast,
+            RecoveryPolicy::SkipMessage,
+            RecoveryPolicy::SkipSubset,
+            RecoveryPolicy::BestEffort,
+        ] {
+            assert_eq!(
+                policy.to_string().parse::<RecoveryPolicy>().unwrap(),
+                policy
+            );
+        }
+        assert!("skip".parse::<RecoveryPolicy>().is_err());
+        assert!(!RecoveryPolicy::FailFast.recovers());
+        assert!(RecoveryPolicy::SkipSubset.keeps_partial(false));
+        assert!(!RecoveryPolicy::SkipSubset.keeps_partial(true));
+        assert!(RecoveryPolicy::BestEffort.keeps_partial(true));
+        assert!(!RecoveryPolicy::SkipMessage.keeps_partial(false));
+    }
+}
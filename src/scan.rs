@@ -0,0 +1,216 @@
+//! Integrity scan of BUFR files
+//!
+//! [`scan`] checks the framing of every message in a file (total length, section
+//! lengths and the end section) without decoding values. With [`scan_with_tables`],
+//! the data section of each message is also read through to check that it holds
+//! enough bits for its descriptors. With [`scan_bytes_with_checksum`], the checksum
+//! that some centres embed in their messages is verified as well.
+
+use std::path::Path;
+
+use crate::{
+    DataEvent, DataReader, DataSpec, Error, HeaderSections, Tables,
+    checksum::{ChecksumStatus, ChecksumVerifier},
+    sections::DataSectionHeader,
+};
+
+/// Result of the scan of a message.
+#[derive(Debug)]
+pub struct MessageScanResult {
+    /// Offset of the message (`BUFR`) in the input
+    pub offset: usize,
+    /// Total length of the message declared in Section 0
+    pub total_length: usize,
+    /// Edition number declared in Section 0
+    pub edition_number: u8,
+    /// The first problem found, or `None` if the message passed
+    pub error: Option<Error>,
+    /// Result of the verification of the checksum, if a verifier was given and the
+    /// header sections could be read
+    pub checksum: Option<ChecksumStatus>,
+}
+
+impl MessageScanResult {
+    /// Whether no problem was found in the message, and its checksum (if verified) is
+    /// valid.
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && matches!(self.checksum, None | Some(ChecksumStatus::Valid))
+    }
+}
+
+/// Checks the framing of every message in a file.
+pub fn scan<P: AsRef<Path>>(path: P) -> Result<Vec<MessageScanResult>, Error> {
+    Ok(scan_bytes(&std::fs::read(path)?, None))
+}
+
+/// Checks the framing of every message in a file, and reads through their data sections.
+pub fn scan_with_tables<P: AsRef<Path>>(
+    path: P,
+    tables: &Tables,
+) -> Result<Vec<MessageScanResult>, Error> {
+    Ok(scan_bytes(&std::fs::read(path)?, Some(tables)))
+}
+
+/// Checks every message in the contents of a file (see [`scan`] and [`scan_with_tables`]).
+///
+/// Anything between messages (e.g. GTS headings) is ignored.
+pub fn scan_bytes(data: &[u8], tables: Option<&Tables>) -> Vec<MessageScanResult> {
+    scan_messages(data, tables, None)
+}
+
+/// Checks every message in the contents of a file as [`scan_bytes`] does, and verifies
+/// their checksums.
+pub fn scan_bytes_with_checksum(
+    data: &[u8],
+    tables: Option<&Tables>,
+    verifier: &dyn ChecksumVerifier,
+) -> Vec<MessageScanResult> {
+    scan_messages(data, tables, Some(verifier))
+}
+
+fn scan_messages(
+    data: &[u8],
+    tables: Option<&Tables>,
+    verifier: Option<&dyn ChecksumVerifier>,
+) -> Vec<MessageScanResult> {
+    let mut results = vec![];
+    let mut offset = 0;
+    while let Some(pos) = data[offset..].windows(4).position(|w| w == b"BUFR") {
+        offset += pos;
+        let rest = &data[offset..];
+        if rest.len() < 8 {
+            break;
+        }
+        let total_length = u32::from_be_bytes([0, rest[4], rest[5], rest[6]]) as usize;
+        let error = check_message(rest, total_length, tables).err();
+        let checksum = verifier.and_then(|verifier| {
+            let message = rest.get(..total_length)?;
+            let header = HeaderSections::from_bytes(message).ok()?;
+            Some(verifier.verify(message, &header))
+        });
+        // Look for the next message right after this one if its length can be trusted
+        let framed = !matches!(error, Some(Error::Io(_)))
+            && total_length >= 8
+            && total_length <= rest.len()
+            && rest[..total_length].ends_with(b"7777");
+        results.push(MessageScanResult {
+            offset,
+            total_length,
+            edition_number: rest[7],
+            error,
+            checksum,
+        });
+        offset += if framed { total_length } else { 4 };
+    }
+    results
+}
+
+fn check_message(data: &[u8], total_length: usize, tables: Option<&Tables>) -> Result<(), Error> {
+    let Some(message) = data.get(..total_length) else {
+        return Err(Error::Invalid(format!(
+            "Truncated message: {} of {total_length} bytes",
+            data.len()
+        )));
+    };
+    if !message.ends_with(b"7777") {
+        return Err(Error::Invalid("End section (7777) not found".to_string()));
+    }
+
+    let mut reader = &message[..total_length - 4];
+    let header = HeaderSections::read(&mut reader)?;
+    let data_section = reader;
+    let section_length = DataSectionHeader::read(&mut reader)?.section_length as usize;
+    if section_length != data_section.len() {
+        return Err(Error::Invalid(format!(
+            "Section lengths do not add up to the total length: data section has \
+             {section_length} bytes, {} bytes left",
+            data_section.len()
+        )));
+    }
+
+    if let Some(tables) = tables {
+        let data_spec = DataSpec::from_data_description(&header.data_description_section, tables)?;
+        let mut data_reader = DataReader::new(data_section, &data_spec)?;
+        while !matches!(data_reader.read_event()?, DataEvent::Eof) {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> Vec<u8> {
+        std::fs::read("./tests/testdata/dwd/synop_ISGD01_EDZW_2025_08_07_11_10.bufr").unwrap()
+    }
+
+    #[test]
+    fn test_scan_bytes() {
+        let message = message();
+        let mut data = b"ISMD01 EDZW 071110\r\r\n".to_vec();
+        data.extend_from_slice(&message);
+        data.extend_from_slice(&message);
+
+        let results = scan_bytes(&data, None);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.passed()));
+        assert_eq!(results[0].offset, 21);
+        assert_eq!(results[0].edition_number, 4);
+        assert_eq!(results[1].offset, 21 + message.len());
+        assert_eq!(results[1].total_length, message.len());
+
+        // The message uses local descriptors of DWD
+        let results = scan_bytes(&data, Some(&Tables::default()));
+        assert_eq!(results.len(), 2);
+        assert!(matches!(results[0].error, Some(Error::Table(_))));
+        assert_eq!(results[1].offset, 21 + message.len());
+    }
+
+    #[test]
+    fn test_scan_broken_messages() {
+        let message = message();
+
+        // Broken end section
+        let mut data = message.clone();
+        let len = data.len();
+        data[len - 1] = b'8';
+        // Data section shorter than declared
+        let mut truncated = message.clone();
+        let data_section_end = truncated.len() - 4;
+        truncated.remove(data_section_end - 1);
+        let total_length = (truncated.len() as u32).to_be_bytes();
+        truncated[4..7].copy_from_slice(&total_length[1..]);
+        data.extend_from_slice(&truncated);
+        // Truncated message
+        data.extend_from_slice(&message[..100]);
+
+        let results = scan_bytes(&data, None);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| !r.passed()));
+        assert_eq!(results[1].offset, message.len());
+        assert_eq!(results[2].offset, message.len() * 2 - 1);
+    }
+
+    #[test]
+    fn test_scan_checksum() {
+        let message = message();
+        let mut data = message.clone();
+        data.extend_from_slice(&message[..100]);
+        // Valid if the message has an optional section
+        let verifier = |_: &[u8], header: &HeaderSections| match header.optional_section {
+            Some(_) => ChecksumStatus::Valid,
+            None => ChecksumStatus::Invalid("no optional section".to_string()),
+        };
+        let results = scan_bytes_with_checksum(&data, None, &verifier);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].error.is_none());
+        assert!(matches!(
+            results[0].checksum,
+            Some(ChecksumStatus::Invalid(_))
+        ));
+        assert!(!results[0].passed());
+        // Not verified for the truncated message
+        assert_eq!(results[1].checksum, None);
+        assert!(scan_bytes(&data, None)[0].passed());
+    }
+}
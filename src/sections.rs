@@ -1,58 +1,206 @@
 //! The header sections of BUFR files
 
-use std::io::Read;
+use std::io::{Read, Write};
 
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::{Descriptor, Error, reader::three_bytes_to_u32};
+use crate::{
+    DecodeOptions, Descriptor, Error, checksum::ChecksumStatus, reader::three_bytes_to_u32,
+    validation::Strictness, writer::u32_to_three_bytes,
+};
 
 /// The header sections of a BUFR file.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct HeaderSections {
     pub indicator_section: IndicatorSection,
     pub identification_section: IdentificationSection,
     pub optional_section: Option<OptionalSection>,
     pub data_description_section: DataDescriptionSection,
+    /// The original bytes of the sections, if decoded with [`HeaderSections::from_bytes`]
+    ///
+    /// They are kept as read: changing the fields does not change them.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub raw: Option<RawSections>,
 }
 
 impl HeaderSections {
-    pub fn read<R: Read>(mut reader: R) -> Result<Self, Error> {
+    pub fn read<R: Read>(reader: R) -> Result<Self, Error> {
+        Self::read_marked(reader, |_| {})
+    }
+
+    /// Reads the header sections at the start of a buffer, keeping their original bytes
+    /// (see [`RawSections`]).
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        Self::from_bytes_with_length(buf).map(|(header, _)| header)
+    }
+
+    /// Reads the header sections as [`HeaderSections::from_bytes`] does, also returning
+    /// their length.
+    fn from_bytes_with_length(buf: &[u8]) -> Result<(Self, usize), Error> {
+        let mut ends = vec![];
+        let mut header = Self::read_marked(buf, |rest: &&[u8]| ends.push(buf.len() - rest.len()))?;
+        header.raw = Some(RawSections {
+            bytes: buf[..ends[3]].to_vec(),
+            ends: [ends[0], ends[1], ends[2], ends[3]],
+        });
+        Ok((header, ends[3]))
+    }
+
+    /// Reads the header sections, calling `mark` with the reader after each section
+    /// (including the absent optional section).
+    fn read_marked<R: Read>(mut reader: R, mut mark: impl FnMut(&R)) -> Result<Self, Error> {
         // Indicator section
         let indicator_section = IndicatorSection::read(&mut reader)?;
+        mark(&reader);
 
         // Identification section
-        let identification_section = match indicator_section.edition_number {
-            3 => IdentificationSectionV3::read(&mut reader)?.into(),
-            4 => IdentificationSection::read(&mut reader)?,
-            _ => {
-                return Err(Error::Invalid(format!(
-                    "Unsupported edition number {}",
-                    indicator_section.edition_number
-                )));
-            }
-        };
+        let layout = EditionLayout::of(indicator_section.edition_number)?;
+        let identification_section = IdentificationSection::read(&mut reader, layout)?;
+        mark(&reader);
 
         // Optional section
         let optional_section = match identification_section.flags.has_optional_section {
             true => Some(OptionalSection::read(&mut reader)?),
             false => None,
         };
+        mark(&reader);
 
         // Data description section
         let data_description_section = DataDescriptionSection::read(&mut reader)?;
+        mark(&reader);
 
         Ok(HeaderSections {
             indicator_section,
             identification_section,
             optional_section,
             data_description_section,
+            raw: None,
         })
     }
+
+    /// Number of subsets in the message (Section 3).
+    pub fn number_of_subsets(&self) -> u16 {
+        self.data_description_section.number_of_subsets
+    }
+
+    /// Whether the message contains observed data, as opposed to other data (Section 3).
+    pub fn is_observed_data(&self) -> bool {
+        self.data_description_section.flags.is_observed_data
+    }
+
+    /// Whether the data section is compressed (Section 3).
+    pub fn is_compressed(&self) -> bool {
+        self.data_description_section.flags.is_compressed
+    }
+
+    /// Writes the header sections (Sections 0 to 3).
+    ///
+    /// Section lengths are computed from the contents, so modified sections
+    /// (e.g. a replaced optional section) are written consistently.
+    /// `data_section_length` is the length of the data section (Section 4) that follows.
+    pub fn write<W: Write>(&self, mut writer: W, data_section_length: u32) -> Result<(), Error> {
+        let mut sections = Vec::new();
+        self.encode_into(&mut sections, data_section_length)?;
+        writer.write_all(&sections)?;
+        Ok(())
+    }
+
+    /// Appends the header sections (Sections 0 to 3) to a buffer, as written by
+    /// [`HeaderSections::write`].
+    ///
+    /// The buffer is left as it was if the sections cannot be encoded.
+    pub fn encode_into(&self, out: &mut Vec<u8>, data_section_length: u32) -> Result<(), Error> {
+        let start = out.len();
+        let result = self.encode_sections(out, start, data_section_length);
+        if result.is_err() {
+            out.truncate(start);
+        }
+        result
+    }
+
+    fn encode_sections(
+        &self,
+        out: &mut Vec<u8>,
+        start: usize,
+        data_section_length: u32,
+    ) -> Result<(), Error> {
+        let edition_number = self.indicator_section.edition_number;
+        let layout = EditionLayout::of(edition_number)?;
+
+        // Section 0, filled in once the length of the other sections is known
+        out.extend_from_slice(&[0; 8]);
+        self.identification_section
+            .write(out, layout, self.optional_section.is_some())?;
+        if let Some(optional_section) = &self.optional_section {
+            optional_section.write(out, layout)?;
+        }
+        self.data_description_section.write(out, layout)?;
+
+        let indicator_section = IndicatorSection {
+            total_length: (out.len() - start) as u32 + data_section_length + 4,
+            edition_number,
+        };
+        indicator_section.write(&mut &mut out[start..start + 8])?;
+        Ok(())
+    }
+}
+
+/// Writes the 3-byte length of a section, padding the section to an even
+/// length if the edition requires it. Returns the number of octets of padding.
+fn write_section_length<W: Write>(
+    writer: &mut W,
+    length: usize,
+    layout: &EditionLayout,
+) -> Result<usize, Error> {
+    let padding = layout.padding(length);
+    let length = length + padding;
+    if length > 0xffffff {
+        return Err(Error::Invalid(format!("Section too large: {length} bytes")));
+    }
+    writer.write_all(&u32_to_three_bytes(length as u32))?;
+    Ok(padding)
+}
+
+/// The original bytes of the header sections of a message (see
+/// [`HeaderSections::from_bytes`]), for tools that pass sections through unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawSections {
+    bytes: Vec<u8>,
+    /// End offsets of sections 0 to 3
+    ends: [usize; 4],
+}
+
+impl RawSections {
+    /// All the header sections (Sections 0 to 3).
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Indicator section (Section 0), with the total length as declared.
+    pub fn indicator_section(&self) -> &[u8] {
+        &self.bytes[..self.ends[0]]
+    }
+
+    /// Identification section (Section 1).
+    pub fn identification_section(&self) -> &[u8] {
+        &self.bytes[self.ends[0]..self.ends[1]]
+    }
+
+    /// Optional section (Section 2), if present.
+    pub fn optional_section(&self) -> Option<&[u8]> {
+        let section = &self.bytes[self.ends[1]..self.ends[2]];
+        (!section.is_empty()).then_some(section)
+    }
+
+    /// Data description section (Section 3).
+    pub fn data_description_section(&self) -> &[u8] {
+        &self.bytes[self.ends[2]..self.ends[3]]
+    }
 }
 
 /// Indicator section (Section 0).
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct IndicatorSection {
     pub total_length: u32,
@@ -78,10 +226,167 @@ impl IndicatorSection {
             edition_number,
         })
     }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        if self.total_length > 0xffffff {
+            return Err(Error::Invalid(format!(
+                "Message too large: {} bytes",
+                self.total_length
+            )));
+        }
+        writer.write_all(b"BUFR")?;
+        writer.write_all(&u32_to_three_bytes(self.total_length))?;
+        writer.write_u8(self.edition_number)?;
+        Ok(())
+    }
 }
 
-/// Identification section (Section 1) for BUFR edition 4.
-#[derive(Debug)]
+/// Layout of the sections of a BUFR edition.
+///
+/// What differs between editions is described by a layout in [`EDITIONS`], from which
+/// the sections are read and written, so that another edition can be supported by
+/// adding its layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditionLayout {
+    pub edition_number: u8,
+    /// Fields of the identification section (Section 1) after its length and up to the
+    /// local use, with their widths in octets (1 or 2)
+    ///
+    /// Fields of [`IdentificationSection`] missing from the layout are read as 0, and
+    /// must be 0 to be written if they identify the originator.
+    pub identification_fields: &'static [(IdentificationField, u8)],
+    /// Whether every section is padded to an even length
+    pub even_section_lengths: bool,
+    /// Whether the typical year is the year of the century
+    pub year_of_century: bool,
+}
+
+/// A field of the identification section (see [`EditionLayout`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentificationField {
+    MasterTableNumber,
+    Centre,
+    SubCentre,
+    UpdateSequenceNumber,
+    Flags,
+    DataCategory,
+    InternationalDataSubCategory,
+    LocalDataSubCategory,
+    MasterTableVersion,
+    LocalTablesVersion,
+    TypicalYear,
+    TypicalMonth,
+    TypicalDay,
+    TypicalHour,
+    TypicalMinute,
+    TypicalSecond,
+}
+
+/// The supported editions.
+pub const EDITIONS: [EditionLayout; 3] = {
+    use IdentificationField::*;
+    [
+        // The layout of edition 3, except that the originating centre takes two
+        // octets and there is no sub-centre
+        EditionLayout {
+            edition_number: 2,
+            identification_fields: &[
+                (MasterTableNumber, 1),
+                (Centre, 2),
+                (UpdateSequenceNumber, 1),
+                (Flags, 1),
+                (DataCategory, 1),
+                (InternationalDataSubCategory, 1),
+                (MasterTableVersion, 1),
+                (LocalTablesVersion, 1),
+                (TypicalYear, 1),
+                (TypicalMonth, 1),
+                (TypicalDay, 1),
+                (TypicalHour, 1),
+                (TypicalMinute, 1),
+            ],
+            even_section_lengths: true,
+            year_of_century: true,
+        },
+        EditionLayout {
+            edition_number: 3,
+            identification_fields: &[
+                (MasterTableNumber, 1),
+                (SubCentre, 1),
+                (Centre, 1),
+                (UpdateSequenceNumber, 1),
+                (Flags, 1),
+                (DataCategory, 1),
+                (InternationalDataSubCategory, 1),
+                (MasterTableVersion, 1),
+                (LocalTablesVersion, 1),
+                (TypicalYear, 1),
+                (TypicalMonth, 1),
+                (TypicalDay, 1),
+                (TypicalHour, 1),
+                (TypicalMinute, 1),
+            ],
+            even_section_lengths: true,
+            year_of_century: true,
+        },
+        EditionLayout {
+            edition_number: 4,
+            identification_fields: &[
+                (MasterTableNumber, 1),
+                (Centre, 2),
+                (SubCentre, 2),
+                (UpdateSequenceNumber, 1),
+                (Flags, 1),
+                (DataCategory, 1),
+                (InternationalDataSubCategory, 1),
+                (LocalDataSubCategory, 1),
+                (MasterTableVersion, 1),
+                (LocalTablesVersion, 1),
+                (TypicalYear, 2),
+                (TypicalMonth, 1),
+                (TypicalDay, 1),
+                (TypicalHour, 1),
+                (TypicalMinute, 1),
+                (TypicalSecond, 1),
+            ],
+            even_section_lengths: false,
+            year_of_century: false,
+        },
+    ]
+};
+
+impl EditionLayout {
+    /// Returns the layout of an edition.
+    pub fn of(edition_number: u8) -> Result<&'static Self, Error> {
+        EDITIONS
+            .iter()
+            .find(|layout| layout.edition_number == edition_number)
+            .ok_or_else(|| Error::Invalid(format!("Unsupported edition number {edition_number}")))
+    }
+
+    /// Length of the identification section without its local use.
+    pub fn identification_length(&self) -> usize {
+        3 + self
+            .identification_fields
+            .iter()
+            .map(|&(_, width)| width as usize)
+            .sum::<usize>()
+    }
+
+    /// Returns the number of octets padding a section of the given length.
+    fn padding(&self, length: usize) -> usize {
+        match self.even_section_lengths {
+            true => length % 2,
+            false => 0,
+        }
+    }
+}
+
+/// Identification section (Section 1).
+///
+/// The fields are those of edition 4, the others being read and written as set by
+/// their [`EditionLayout`].
+#[derive(Debug, Clone, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct IdentificationSection {
     pub section_length: u32,
@@ -105,175 +410,149 @@ pub struct IdentificationSection {
 }
 
 impl IdentificationSection {
-    fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
+    fn read<R: Read>(reader: &mut R, layout: &EditionLayout) -> Result<Self, Error> {
         let mut len_bytes = [0u8; 3];
         reader.read_exact(&mut len_bytes)?;
         let section_length = three_bytes_to_u32(len_bytes);
 
-        if section_length < 22 {
-            return Err(Error::Invalid(
-                "Identification section (BUFR4) length must be >= 22".to_string(),
-            ));
+        let min_length = layout.identification_length();
+        if (section_length as usize) < min_length {
+            return Err(Error::Invalid(format!(
+                "Identification section (BUFR{}) length must be >= {min_length}",
+                layout.edition_number
+            )));
         }
 
-        let master_table_number = reader.read_u8()?;
-        let centre = reader.read_u16::<BigEndian>()?;
-        let sub_centre = reader.read_u16::<BigEndian>()?;
-        let update_sequence_number = reader.read_u8()?;
-        let flags = IdentificationSectionFlags::read(reader)?;
-        let data_category = reader.read_u8()?;
-        let international_data_sub_category = reader.read_u8()?;
-        let local_data_sub_category = reader.read_u8()?;
-        let master_table_version = reader.read_u8()?;
-        let local_tables_version = reader.read_u8()?;
-        let typical_year = reader.read_u16::<BigEndian>()?;
-        let typical_month = reader.read_u8()?;
-        let typical_day = reader.read_u8()?;
-        let typical_hour = reader.read_u8()?;
-        let typical_minute = reader.read_u8()?;
-        let typical_second = reader.read_u8()?;
-
-        let mut local_use = vec![0u8; (section_length - 22) as usize];
-        reader.read_exact(&mut local_use)?;
-
-        Ok(Self {
+        let mut section = Self {
             section_length,
-            master_table_number,
-            centre,
-            sub_centre,
-            update_sequence_number,
-            flags,
-            data_category,
-            international_data_sub_category,
-            local_data_sub_category,
-            master_table_version,
-            local_tables_version,
-            typical_year,
-            typical_month,
-            typical_day,
-            typical_hour,
-            typical_minute,
-            typical_second,
-            local_use,
-        })
-    }
-}
-
-/// Identification section for BUFR edition 3.
-#[derive(Debug)]
-pub struct IdentificationSectionV3 {
-    pub section_length: u32,
-    pub master_table_number: u8,
-    pub sub_centre: u8,
-    pub centre: u8,
-    pub update_sequence_number: u8,
-    pub flags: IdentificationSectionFlags,
-    pub data_category: u8,
-    pub data_sub_category: u8,
-    pub master_table_version: u8,
-    pub local_tables_version: u8,
-    pub typical_year: u8,
-    pub typical_month: u8,
-    pub typical_day: u8,
-    pub typical_hour: u8,
-    pub typical_minute: u8,
-    pub local_use: Vec<u8>,
-}
+            ..Default::default()
+        };
+        for &(field, width) in layout.identification_fields {
+            let value = match width {
+                1 => reader.read_u8()? as u16,
+                _ => reader.read_u16::<BigEndian>()?,
+            };
+            section.set(field, value);
+        }
 
-impl IdentificationSectionV3 {
-    fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
-        let mut len_bytes = [0u8; 3];
-        reader.read_exact(&mut len_bytes)?;
-        let section_length = three_bytes_to_u32(len_bytes);
+        let mut local_use = vec![0u8; section_length as usize - min_length];
+        reader.read_exact(&mut local_use)?;
+        section.local_use = local_use;
+        Ok(section)
+    }
 
-        if section_length < 17 {
-            return Err(Error::Invalid(
-                "Identification section (BUFR3) length must be >= 17".to_string(),
-            ));
+    fn write<W: Write>(
+        &self,
+        writer: &mut W,
+        layout: &EditionLayout,
+        has_optional_section: bool,
+    ) -> Result<(), Error> {
+        let does_not_fit = |field: IdentificationField| {
+            Error::Invalid(format!(
+                "{field:?} {} does not fit in the BUFR{} identification section",
+                self.get(field),
+                layout.edition_number
+            ))
+        };
+        // The originator cannot be left out, unlike e.g. the second of editions 2 and 3
+        for field in [IdentificationField::Centre, IdentificationField::SubCentre] {
+            let in_layout = layout
+                .identification_fields
+                .iter()
+                .any(|&(f, _)| f == field);
+            if !in_layout && self.get(field) != 0 {
+                return Err(does_not_fit(field));
+            }
+        }
+        for &(field, width) in layout.identification_fields {
+            if width == 1 && self.get(field) > u8::MAX as u16 {
+                return Err(does_not_fit(field));
+            }
         }
 
-        let master_table_number = reader.read_u8()?;
-        let sub_centre = reader.read_u8()?;
-        let centre = reader.read_u8()?;
-        let update_sequence_number = reader.read_u8()?;
-        let flags = IdentificationSectionFlags::read(reader)?;
-        let data_category = reader.read_u8()?;
-        let data_sub_category = reader.read_u8()?;
-        let master_table_version = reader.read_u8()?;
-        let local_tables_version = reader.read_u8()?;
-        let typical_year = reader.read_u8()?;
-        let typical_month = reader.read_u8()?;
-        let typical_day = reader.read_u8()?;
-        let typical_hour = reader.read_u8()?;
-        let typical_minute = reader.read_u8()?;
-
-        let mut local_use = vec![0u8; (section_length - 17) as usize];
-        reader.read_exact(&mut local_use)?;
+        let padding = write_section_length(
+            writer,
+            layout.identification_length() + self.local_use.len(),
+            layout,
+        )?;
+        for &(field, width) in layout.identification_fields {
+            match (field, width) {
+                (IdentificationField::Flags, _) => {
+                    IdentificationSectionFlags::write(writer, has_optional_section)?
+                }
+                (_, 1) => writer.write_u8(self.get(field) as u8)?,
+                _ => writer.write_u16::<BigEndian>(self.get(field))?,
+            }
+        }
+        writer.write_all(&self.local_use)?;
+        writer.write_all(&vec![0; padding])?;
+        Ok(())
+    }
 
-        Ok(Self {
-            section_length,
-            master_table_number,
-            sub_centre,
-            centre,
-            update_sequence_number,
-            flags,
-            data_category,
-            data_sub_category,
-            master_table_version,
-            local_tables_version,
-            typical_year,
-            typical_month,
-            typical_day,
-            typical_hour,
-            typical_minute,
-            local_use,
-        })
+    /// Returns the value of a field (the flags as the octet written).
+    fn get(&self, field: IdentificationField) -> u16 {
+        use IdentificationField::*;
+        match field {
+            MasterTableNumber => self.master_table_number as u16,
+            Centre => self.centre,
+            SubCentre => self.sub_centre,
+            UpdateSequenceNumber => self.update_sequence_number as u16,
+            Flags => (self.flags.has_optional_section as u16) << 7,
+            DataCategory => self.data_category as u16,
+            InternationalDataSubCategory => self.international_data_sub_category as u16,
+            LocalDataSubCategory => self.local_data_sub_category as u16,
+            MasterTableVersion => self.master_table_version as u16,
+            LocalTablesVersion => self.local_tables_version as u16,
+            TypicalYear => self.typical_year,
+            TypicalMonth => self.typical_month as u16,
+            TypicalDay => self.typical_day as u16,
+            TypicalHour => self.typical_hour as u16,
+            TypicalMinute => self.typical_minute as u16,
+            TypicalSecond => self.typical_second as u16,
+        }
     }
-}
 
-impl From<IdentificationSectionV3> for IdentificationSection {
-    fn from(value: IdentificationSectionV3) -> Self {
-        Self {
-            section_length: value.section_length,
-            master_table_number: value.master_table_number,
-            centre: value.centre as u16,
-            sub_centre: value.sub_centre as u16,
-            update_sequence_number: value.update_sequence_number,
-            flags: value.flags,
-            data_category: value.data_category,
-            international_data_sub_category: value.data_sub_category,
-            local_data_sub_category: 0,
-            master_table_version: value.master_table_version,
-            local_tables_version: value.local_tables_version,
-            typical_year: value.typical_year as u16,
-            typical_month: value.typical_month,
-            typical_day: value.typical_day,
-            typical_hour: value.typical_hour,
-            typical_minute: value.typical_minute,
-            typical_second: 0,
-            local_use: value.local_use,
+    /// Sets a field from the value read (the flags from their octet).
+    fn set(&mut self, field: IdentificationField, value: u16) {
+        use IdentificationField::*;
+        let octet = value as u8;
+        match field {
+            MasterTableNumber => self.master_table_number = octet,
+            Centre => self.centre = value,
+            SubCentre => self.sub_centre = value,
+            UpdateSequenceNumber => self.update_sequence_number = octet,
+            Flags => self.flags.has_optional_section = value & 0b10000000 != 0,
+            DataCategory => self.data_category = octet,
+            InternationalDataSubCategory => self.international_data_sub_category = octet,
+            LocalDataSubCategory => self.local_data_sub_category = octet,
+            MasterTableVersion => self.master_table_version = octet,
+            LocalTablesVersion => self.local_tables_version = octet,
+            TypicalYear => self.typical_year = value,
+            TypicalMonth => self.typical_month = octet,
+            TypicalDay => self.typical_day = octet,
+            TypicalHour => self.typical_hour = octet,
+            TypicalMinute => self.typical_minute = octet,
+            TypicalSecond => self.typical_second = octet,
         }
     }
 }
 
 /// Flags in the identification section.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct IdentificationSectionFlags {
     pub has_optional_section: bool,
 }
 
 impl IdentificationSectionFlags {
-    fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
-        let flags = reader.read_u8()?;
-        Ok(Self {
-            has_optional_section: flags & 0b10000000 != 0,
-        })
+    fn write<W: Write>(writer: &mut W, has_optional_section: bool) -> Result<(), Error> {
+        writer.write_u8(if has_optional_section { 0b10000000 } else { 0 })?;
+        Ok(())
     }
 }
 
 /// Optional section (Section 2).
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct OptionalSection {
     pub section_length: u32,
@@ -281,6 +560,14 @@ pub struct OptionalSection {
 }
 
 impl OptionalSection {
+    /// Decodes the contents with the layout of the centre (see [`crate::optional_section`]).
+    pub fn fields(
+        &self,
+        schema: &crate::optional_section::Schema,
+    ) -> Result<crate::optional_section::Fields, Error> {
+        schema.decode(&self.optional)
+    }
+
     fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
         let mut len_bytes = [0u8; 3];
         reader.read_exact(&mut len_bytes)?;
@@ -303,10 +590,19 @@ impl OptionalSection {
             optional,
         })
     }
+
+    fn write<W: Write>(&self, writer: &mut W, layout: &EditionLayout) -> Result<(), Error> {
+        let padding = write_section_length(writer, 4 + self.optional.len(), layout)?;
+        // Reserved byte
+        writer.write_u8(0)?;
+        writer.write_all(&self.optional)?;
+        writer.write_all(&vec![0; padding])?;
+        Ok(())
+    }
 }
 
 /// Data description section (Section 3).
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DataDescriptionSection {
     pub section_length: u32,
@@ -353,10 +649,25 @@ impl DataDescriptionSection {
             _padding: padding,
         })
     }
+
+    fn write<W: Write>(&self, writer: &mut W, layout: &EditionLayout) -> Result<(), Error> {
+        let length = 7 + 2 * self.descriptors.len() + self._padding.len();
+        let padding = write_section_length(writer, length, layout)?;
+        // Reserved byte
+        writer.write_u8(0)?;
+        writer.write_u16::<BigEndian>(self.number_of_subsets)?;
+        self.flags.write(writer)?;
+        for descriptor in &self.descriptors {
+            descriptor.write(writer)?;
+        }
+        writer.write_all(&self._padding)?;
+        writer.write_all(&vec![0; padding])?;
+        Ok(())
+    }
 }
 
 /// Flags in the data description section.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DataDescriptionSectionFlags {
     pub is_observed_data: bool,
@@ -371,6 +682,18 @@ impl DataDescriptionSectionFlags {
             is_compressed: flags & 0b01000000 != 0,
         })
     }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let mut flags = 0;
+        if self.is_observed_data {
+            flags |= 0b10000000;
+        }
+        if self.is_compressed {
+            flags |= 0b01000000;
+        }
+        writer.write_u8(flags)?;
+        Ok(())
+    }
 }
 
 /// The header of the data section (Section 4).
@@ -396,29 +719,414 @@ impl DataSectionHeader {
 #[derive(Debug)]
 pub struct EndSection {}
 
+/// A message split into its sections by [`split_message`].
+#[derive(Debug)]
+pub struct MessageSections<'m> {
+    pub header: HeaderSections,
+    /// The data section (Section 4), including its header
+    pub data_section: &'m [u8],
+    /// Deviations tolerated in lenient mode
+    pub warnings: Vec<String>,
+}
+
+/// Splits a complete message into its header sections and data section, checking the
+/// total length and the end section (`7777`) as set by the options.
+///
+/// In strict mode, the total length declared in Section 0 must be the length of the
+/// message, and the data section must end right before the end section (apart from a
+/// padding octet of editions 2 and 3). In lenient mode, the message ends at its last
+/// `7777` (or is truncated if there is none), and the data section ends at its
+/// declared length or at the end of the message, whichever comes first, with warnings
+/// for the deviations. A complete data section followed by a missing or truncated end
+/// section is accepted with a warning as well.
+///
+/// If the options have a checksum verifier, an invalid or absent checksum is reported
+/// in the same way.
+pub fn split_message<'m>(
+    message: &'m [u8],
+    options: &DecodeOptions,
+) -> Result<MessageSections<'m>, Error> {
+    let mut warnings = vec![];
+    let mut report = |warning: String| match options.strictness {
+        Strictness::Strict => Err(Error::Invalid(warning)),
+        Strictness::Lenient => {
+            warnings.push(warning);
+            Ok(())
+        }
+    };
+
+    let (header, header_length) = HeaderSections::from_bytes_with_length(message)?;
+    if let Some(verifier) = &options.checksum {
+        match verifier.verify(message, &header) {
+            ChecksumStatus::Valid => {}
+            ChecksumStatus::Invalid(reason) => report(format!("Invalid checksum: {reason}"))?,
+            ChecksumStatus::Absent => report("Checksum not found".to_string())?,
+        }
+    }
+    let rest = &message[header_length..];
+    let total_length = header.indicator_section.total_length as usize;
+    if total_length != message.len() {
+        report(format!(
+            "Total length of {total_length} bytes declared for a message of {} bytes",
+            message.len()
+        ))?;
+    }
+    let end = match options.strictness {
+        Strictness::Strict => rest.ends_with(b"7777").then(|| rest.len() - 4),
+        Strictness::Lenient => match complete_data_section_end(&header, rest) {
+            Some(end) if is_unterminated(&rest[end..]) => {
+                report(format!(
+                    "End section (7777) missing or truncated ({} of 4 bytes)",
+                    rest.len() - end
+                ))?;
+                Some(end)
+            }
+            _ => rest.windows(4).rposition(|w| w == b"7777"),
+        },
+    };
+    let rest = match end {
+        Some(end) => &rest[..end],
+        None => {
+            report("End section (7777) not found".to_string())?;
+            rest
+        }
+    };
+
+    let declared = DataSectionHeader::read(&mut &rest[..])?.section_length as usize;
+    let is_padded = pads_sections(header.indicator_section.edition_number)
+        && declared + 1 == rest.len()
+        && rest[declared] == 0;
+    if declared < rest.len() && !is_padded {
+        report(format!(
+            "{} bytes before the end section after the data section of {declared} bytes",
+            rest.len() - declared
+        ))?;
+    } else if declared > rest.len() {
+        report(format!(
+            "Data section of {declared} bytes overruns the end section by {} bytes",
+            declared - rest.len()
+        ))?;
+    }
+    Ok(MessageSections {
+        data_section: &rest[..declared.min(rest.len())],
+        header,
+        warnings,
+    })
+}
+
+/// Returns the end of the data section at the start of `rest` (after the padding octet
+/// of editions 2 and 3, if any), or `None` if it runs past the end of `rest`.
+fn complete_data_section_end(header: &HeaderSections, rest: &[u8]) -> Option<usize> {
+    let declared = DataSectionHeader::read(&mut &rest[..]).ok()?.section_length as usize;
+    if declared > rest.len() {
+        return None;
+    }
+    match pads_sections(header.indicator_section.edition_number) {
+        true if rest.get(declared) == Some(&0) => Some(declared + 1),
+        _ => Some(declared),
+    }
+}
+
+/// Whether the sections of an edition are padded to an even length.
+pub(crate) fn pads_sections(edition_number: u8) -> bool {
+    EditionLayout::of(edition_number).is_ok_and(|layout| layout.even_section_lengths)
+}
+
+/// Whether the bytes after the data section are a missing or truncated end section.
+fn is_unterminated(tail: &[u8]) -> bool {
+    tail.len() < 4 && b"7777".starts_with(tail)
+}
+
+/// Returns the length of a message whose end section is missing or truncated right
+/// after a complete data section, taking all of `data` (which must not run into the
+/// next message), or `None` if the message is broken otherwise.
+pub(crate) fn unterminated_message_length(data: &[u8]) -> Option<usize> {
+    let mut rest = data;
+    let header = HeaderSections::read(&mut rest).ok()?;
+    let end = complete_data_section_end(&header, rest)?;
+    is_unterminated(&rest[end..]).then_some(data.len())
+}
+
 /// Check if the end section appears in the stream.
+///
+/// The stream must be positioned after the data section, as returned by
+/// [`DataReader::finish`](crate::DataReader::finish), or see
+/// [`DataReader::finish_message`](crate::DataReader::finish_message).
 pub fn ensure_end_section<R: std::io::Read>(edition: u8, reader: &mut R) -> Result<(), Error> {
-    if edition == 3 {
-        let mut buf: [u8; 1] = [0; 1];
-        reader.read_exact(&mut buf)?;
+    check_end_section(edition, reader, Strictness::Strict).map(|_| ())
+}
+
+/// Checks the end section in the stream as [`ensure_end_section`] does, except that in
+/// lenient mode the end of the stream is accepted in place of a missing or truncated
+/// end section, which is returned as a warning.
+pub fn check_end_section<R: std::io::Read>(
+    edition: u8,
+    reader: &mut R,
+    strictness: Strictness,
+) -> Result<Option<String>, Error> {
+    // The padding octet of editions 2 and 3 may precede the end section
+    let mut may_be_padded = pads_sections(edition);
+    let mut sevens = 0;
+    let mut buf: [u8; 1] = [0; 1];
+    while sevens < 4 {
+        if reader.read(&mut buf)? == 0 {
+            return match strictness {
+                Strictness::Strict => {
+                    Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into())
+                }
+                Strictness::Lenient => Ok(Some(format!(
+                    "End section (7777) missing or truncated ({sevens} of 4 bytes)"
+                ))),
+            };
+        }
         match buf[0] {
-            0x0 => {}
-            b'7' => {
-                let mut buf: [u8; 3] = [0; 3];
-                reader.read_exact(&mut buf)?;
-                if &buf != b"777" {
-                    return Err(Error::Invalid("Invalid end section".to_string()));
+            0x0 if may_be_padded => {}
+            b'7' => sevens += 1,
+            _ => return Err(Error::Invalid("Invalid end section".to_string())),
+        }
+        may_be_padded = false;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> Vec<u8> {
+        std::fs::read("./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin")
+            .unwrap()
+    }
+
+    fn set_total_length(message: &mut [u8]) {
+        let total_length = (message.len() as u32).to_be_bytes();
+        message[4..7].copy_from_slice(&total_length[1..]);
+    }
+
+    #[test]
+    fn test_split_message() {
+        let message = message();
+        let strict = DecodeOptions::strict();
+        let lenient = DecodeOptions::lenient();
+        let sections = split_message(&message, &strict).unwrap();
+        assert!(sections.warnings.is_empty());
+        let data_section_length = sections.data_section.len();
+        assert!(message[..message.len() - 4].ends_with(sections.data_section));
+
+        // Garbage before the end section
+        let mut garbage = message.clone();
+        garbage.splice(message.len() - 4..message.len() - 4, *b"\r\r\n");
+        set_total_length(&mut garbage);
+        assert!(split_message(&garbage, &strict).is_err());
+        let sections = split_message(&garbage, &lenient).unwrap();
+        assert_eq!(sections.data_section.len(), data_section_length);
+        assert_eq!(
+            sections.warnings,
+            [format!(
+                "3 bytes before the end section after the data section of {data_section_length} bytes"
+            )]
+        );
+
+        // Wrong total length
+        let mut wrong_length = message.clone();
+        wrong_length[6] ^= 1;
+        assert!(split_message(&wrong_length, &strict).is_err());
+        let sections = split_message(&wrong_length, &lenient).unwrap();
+        assert_eq!(sections.data_section.len(), data_section_length);
+        assert_eq!(sections.warnings.len(), 1);
+
+        // Truncated message
+        let truncated = &message[..message.len() - 10];
+        assert!(split_message(truncated, &strict).is_err());
+        let sections = split_message(truncated, &lenient).unwrap();
+        assert_eq!(sections.data_section.len(), data_section_length - 6);
+        assert_eq!(
+            sections.warnings[1..],
+            [
+                "End section (7777) not found".to_string(),
+                format!(
+                    "Data section of {data_section_length} bytes overruns the end section by 6 bytes"
+                ),
+            ]
+        );
+
+        // Missing or truncated end section after a complete data section
+        for cut in [4, 2] {
+            let unterminated = &message[..message.len() - cut];
+            assert!(split_message(unterminated, &strict).is_err());
+            let sections = split_message(unterminated, &lenient).unwrap();
+            assert_eq!(sections.data_section.len(), data_section_length);
+            assert_eq!(
+                sections.warnings[1..],
+                [format!(
+                    "End section (7777) missing or truncated ({} of 4 bytes)",
+                    4 - cut
+                )]
+            );
+            assert_eq!(
+                unterminated_message_length(unterminated),
+                Some(unterminated.len())
+            );
+        }
+        assert_eq!(
+            unterminated_message_length(&message[..message.len() - 5]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_check_end_section() {
+        let check = |edition, bytes: &[u8], strictness| {
+            check_end_section(edition, &mut &bytes[..], strictness)
+        };
+        assert_eq!(check(4, b"7777", Strictness::Strict).unwrap(), None);
+        assert_eq!(check(3, b"\x007777", Strictness::Strict).unwrap(), None);
+        assert!(check(4, b"\x007777", Strictness::Strict).is_err());
+        assert!(check(4, b"777", Strictness::Strict).is_err());
+        assert_eq!(
+            check(4, b"77", Strictness::Lenient).unwrap().unwrap(),
+            "End section (7777) missing or truncated (2 of 4 bytes)"
+        );
+        assert!(check(3, b"\x00", Strictness::Lenient).unwrap().is_some());
+        assert!(check(4, b"77BUFR", Strictness::Lenient).is_err());
+    }
+
+    #[test]
+    fn test_raw_sections() {
+        let message = message();
+        let header = HeaderSections::from_bytes(&message).unwrap();
+        let raw = header.raw.as_ref().unwrap();
+        assert_eq!(raw.indicator_section(), &message[..8]);
+        let identification = raw.identification_section();
+        assert_eq!(
+            identification.len(),
+            header.identification_section.section_length as usize
+        );
+        assert_eq!(raw.optional_section(), None);
+        assert_eq!(
+            raw.data_description_section().len(),
+            header.data_description_section.section_length as usize
+        );
+        assert_eq!(
+            raw.as_bytes(),
+            [
+                raw.indicator_section(),
+                identification,
+                raw.data_description_section()
+            ]
+            .concat()
+        );
+        let sections = split_message(&message, &DecodeOptions::strict()).unwrap();
+        assert_eq!(sections.header.raw.as_ref(), Some(raw));
+        assert_eq!(
+            raw.as_bytes().len() + sections.data_section.len() + 4,
+            message.len()
+        );
+        assert!(
+            HeaderSections::read(message.as_slice())
+                .unwrap()
+                .raw
+                .is_none()
+        );
+
+        // With an optional section
+        let mut header = header;
+        header.identification_section.flags.has_optional_section = true;
+        header.optional_section = Some(OptionalSection {
+            section_length: 0,
+            optional: vec![1, 2, 3, 4],
+        });
+        let mut bytes = vec![];
+        header.encode_into(&mut bytes, 0).unwrap();
+        let raw = HeaderSections::from_bytes(&bytes).unwrap().raw.unwrap();
+        assert_eq!(raw.optional_section(), Some(&[0, 0, 8, 0, 1, 2, 3, 4][..]));
+        assert_eq!(raw.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_edition_layouts() {
+        let lengths: Vec<_> = EDITIONS
+            .iter()
+            .map(|layout| (layout.edition_number, layout.identification_length()))
+            .collect();
+        assert_eq!(lengths, [(2, 17), (3, 17), (4, 22)]);
+        assert!(EditionLayout::of(5).is_err());
+
+        // Header sections of edition 3 are written back as read
+        let bufr3 = std::fs::read(
+            "./tests/testdata/jma/Z__C_RJTD_20181201000000_WPR_SEQ_RS-all_Pww_bufr3.bin",
+        )
+        .unwrap();
+        let sections = split_message(&bufr3, &DecodeOptions::strict()).unwrap();
+        let header = &sections.header;
+        assert_eq!(header.indicator_section.edition_number, 3);
+        assert_eq!(header.identification_section.typical_year, 18);
+        let mut bytes = vec![];
+        header
+            .encode_into(&mut bytes, sections.data_section.len() as u32)
+            .unwrap();
+        assert_eq!(bytes, header.raw.as_ref().unwrap().as_bytes());
+
+        // Editions 2 and 3 have no local data sub-category nor second
+        let mut header = HeaderSections::from_bytes(&message()).unwrap();
+        header.identification_section.local_data_sub_category = 1;
+        header.identification_section.typical_second = 30;
+        header.identification_section.typical_year = 20;
+        for edition_number in [2, 3, 4] {
+            header.indicator_section.edition_number = edition_number;
+            let mut bytes = vec![];
+            header.encode_into(&mut bytes, 0).unwrap();
+            let read = HeaderSections::from_bytes(&bytes).unwrap();
+            let (expected, actual) = (&header.identification_section, &read.identification_section);
+            assert_eq!(read.indicator_section.edition_number, edition_number);
+            assert_eq!(actual.centre, expected.centre);
+            assert_eq!(actual.data_category, expected.data_category);
+            assert_eq!(actual.typical_minute, expected.typical_minute);
+            assert_eq!(
+                (actual.local_data_sub_category, actual.typical_second),
+                match edition_number {
+                    4 => (1, 30),
+                    _ => (0, 0),
                 }
-            }
-            _ => {
-                return Err(Error::Invalid("Invalid end section".to_string()));
+            );
+            if edition_number != 4 {
+                assert_eq!(actual.section_length % 2, 0);
+                assert_eq!(read.data_description_section.section_length % 2, 0);
             }
         }
+
+        // Values that do not fit in the layout
+        header.indicator_section.edition_number = 2;
+        header.identification_section.sub_centre = 1;
+        assert!(header.encode_into(&mut vec![], 0).is_err());
+        header.indicator_section.edition_number = 3;
+        header.identification_section.centre = 256;
+        assert!(header.encode_into(&mut vec![], 0).is_err());
     }
-    let mut buf: [u8; 4] = [0; 4];
-    reader.read_exact(&mut buf)?;
-    if &buf != b"7777" {
-        return Err(Error::Invalid("Invalid end section".to_string()));
+
+    #[test]
+    fn test_split_message_checksum() {
+        let message = message();
+        let absent = |_: &[u8], _: &HeaderSections| ChecksumStatus::Absent;
+        let invalid = |_: &[u8], _: &HeaderSections| ChecksumStatus::Invalid("bad".to_string());
+        let mut strict = DecodeOptions::strict();
+        let mut lenient = DecodeOptions::lenient();
+        strict.checksum = Some(std::sync::Arc::new(invalid));
+        lenient.checksum = Some(std::sync::Arc::new(invalid));
+        assert!(split_message(&message, &strict).is_err());
+        let sections = split_message(&message, &lenient).unwrap();
+        assert_eq!(sections.warnings, ["Invalid checksum: bad"]);
+        lenient.checksum = Some(std::sync::Arc::new(absent));
+        let sections = split_message(&message, &lenient).unwrap();
+        assert_eq!(sections.warnings, ["Checksum not found"]);
+        strict.checksum = Some(std::sync::Arc::new(|_: &[u8], _: &HeaderSections| {
+            ChecksumStatus::Valid
+        }));
+        assert!(
+            split_message(&message, &strict)
+                .unwrap()
+                .warnings
+                .is_empty()
+        );
     }
-    Ok(())
 }
@@ -0,0 +1,344 @@
+//! Assembling the time series of an element at a station across messages
+//!
+//! [`SeriesBuilder`] collects the values of an element observed at a station from a
+//! stream of messages (e.g. the messages of an archive, or of [`crate::merge`]) into a
+//! series ordered by time:
+//!
+//! ```no_run
+//! use tinybufr::{Tables, XY, input::open_any, series::SeriesBuilder};
+//!
+//! let tables = Tables::default();
+//! let mut builder = SeriesBuilder::new("47662", XY { x: 12, y: 101 }, &tables);
+//! builder.add_messages(open_any("archive.bufr")?)?;
+//! for point in builder.finish() {
+//!     println!("{} {:?} {:?}", point.time, point.value, point.qc);
+//! }
+//! # Ok::<(), tinybufr::Error>(())
+//! ```
+//!
+//! The station and time of a value are given as in [`crate::grep`]. In each subset,
+//! only the first value of the element observed at the station is taken, and values
+//! without a complete date are ignored.
+//!
+//! When several values are observed at the same time, e.g. in retransmitted or
+//! corrected messages, the one in the message with the greatest update sequence number
+//! (Section 1) is kept. Among messages with the same number, a later message replaces
+//! an earlier one, unless its value is missing and the earlier one is not.
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+
+use crate::{
+    DataEvent, DataReader, DataSpec, DecodeOptions, Error, Tables, Value, XY,
+    grep::{Context, DateTime},
+    sections::split_message,
+};
+
+/// Collects the values of an element at a station into a time series.
+pub struct SeriesBuilder<'t> {
+    station: String,
+    xy: XY,
+    tables: &'t Tables,
+    options: DecodeOptions,
+    /// Number of messages added
+    messages: usize,
+    points: BTreeMap<DateTime, SeriesPoint>,
+}
+
+/// A value of a time series.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeriesPoint {
+    pub time: DateTime,
+    pub value: Value,
+    /// Quality information of the value: its associated field (2 04 YYY), or else the
+    /// first quality information (class 33) that a data present bit-map relates to it
+    pub qc: Option<Value>,
+    /// Update sequence number of the message (0 for an original message)
+    pub update_sequence_number: u8,
+    /// Index of the message, in the order the messages were added
+    pub message: usize,
+    pub subset: u16,
+    /// Number of other values observed at the same time, which were discarded
+    pub duplicates: u32,
+}
+
+/// The first value of the element at the station in a subset.
+struct Found {
+    position: u32,
+    time: Option<DateTime>,
+    value: Value,
+    qc: Option<Value>,
+}
+
+impl<'t> SeriesBuilder<'t> {
+    /// Creates a builder of the series of the element `xy` at a station identified as in
+    /// [`crate::grep`] (e.g. `47662`), decoding messages strictly.
+    pub fn new(station: &str, xy: XY, tables: &'t Tables) -> Self {
+        Self {
+            station: station.to_string(),
+            xy,
+            tables,
+            options: DecodeOptions::default(),
+            messages: 0,
+            points: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Adds the values of a message.
+    ///
+    /// A message that cannot be read still counts in the indices of the messages.
+    pub fn add_message(&mut self, message: &[u8]) -> Result<(), Error> {
+        let index = self.messages;
+        self.messages += 1;
+
+        let sections = split_message(message, &self.options)?;
+        let header = &sections.header;
+        let data_spec =
+            DataSpec::from_data_description(&header.data_description_section, self.tables)?;
+        if !data_spec.iter_elements().any(|e| e.entry.xy == self.xy) {
+            return Ok(());
+        }
+        let update_sequence_number = header.identification_section.update_sequence_number;
+        let found = self.read_values(
+            &data_spec,
+            sections.data_section,
+            header.number_of_subsets(),
+        )?;
+        for (subset, found) in found.into_iter().enumerate() {
+            let Some(Found {
+                time: Some(time),
+                value,
+                qc,
+                ..
+            }) = found
+            else {
+                continue;
+            };
+            self.insert(SeriesPoint {
+                time,
+                value,
+                qc,
+                update_sequence_number,
+                message: index,
+                subset: subset as u16,
+                duplicates: 0,
+            });
+        }
+        Ok(())
+    }
+
+    /// Adds the values of messages, stopping at the first one that cannot be read.
+    pub fn add_messages<I, M>(&mut self, messages: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = M>,
+        M: AsRef<[u8]>,
+    {
+        messages
+            .into_iter()
+            .try_for_each(|message| self.add_message(message.as_ref()))
+    }
+
+    /// Returns the series, ordered by time.
+    pub fn finish(self) -> Vec<SeriesPoint> {
+        self.points.into_values().collect()
+    }
+
+    /// Reads the first value of the element at the station in each subset.
+    fn read_values(
+        &self,
+        data_spec: &DataSpec,
+        data_section: &[u8],
+        number_of_subsets: u16,
+    ) -> Result<Vec<Option<Found>>, Error> {
+        let mut data_reader: DataReader<'_, &[u8]> = DataReader::builder(data_spec)
+            .decode_options(&self.options)
+            .build(data_section)?;
+
+        let number_of_subsets = number_of_subsets as usize;
+        let mut contexts = vec![Context::default(); number_of_subsets];
+        let mut found: Vec<Option<Found>> = (0..number_of_subsets).map(|_| None).collect();
+        let mut subset = 0;
+        let mut position = 0;
+        let mut associated: Option<Vec<Value>> = None;
+        let mut bitmap_target = None;
+
+        let mut handle =
+            |subset: usize, position, xy, value: &Value, associated, target: Option<u32>| {
+                if subset >= number_of_subsets {
+                    return;
+                }
+                if let Some(found) = &mut found[subset] {
+                    if target == Some(found.position) && found.qc.is_none() {
+                        found.qc = Some(value.clone());
+                    }
+                } else if xy == self.xy
+                    && contexts[subset].station().as_deref() == Some(self.station.as_str())
+                {
+                    found[subset] = Some(Found {
+                        position,
+                        time: contexts[subset].time(),
+                        value: value.clone(),
+                        qc: associated,
+                    });
+                }
+                contexts[subset].update(xy, value);
+            };
+        loop {
+            match data_reader.read_event()? {
+                DataEvent::SubsetStart(idx) => {
+                    subset = idx as usize;
+                    position = 0;
+                }
+                DataEvent::AssociatedField { value, .. } => associated = Some(vec![value]),
+                DataEvent::CompressedAssociatedField { values, .. } => associated = Some(values),
+                DataEvent::BitmapTarget { position, .. } => bitmap_target = Some(position),
+                DataEvent::Data { xy, value, .. } => {
+                    let associated = associated.take().and_then(|v| v.into_iter().next());
+                    handle(
+                        subset,
+                        position,
+                        xy,
+                        &value,
+                        associated,
+                        bitmap_target.take(),
+                    );
+                    position += 1;
+                }
+                DataEvent::CompressedData { xy, values, .. } => {
+                    let mut associated = associated.take().map(Vec::into_iter);
+                    let target = bitmap_target.take();
+                    for (subset, value) in values.iter().enumerate() {
+                        let associated = associated.as_mut().and_then(Iterator::next);
+                        handle(subset, position, xy, value, associated, target);
+                    }
+                    position += 1;
+                }
+                DataEvent::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(found)
+    }
+
+    fn insert(&mut self, mut point: SeriesPoint) {
+        match self.points.entry(point.time) {
+            Entry::Vacant(entry) => {
+                entry.insert(point);
+            }
+            Entry::Occupied(mut entry) => {
+                let current = entry.get_mut();
+                let replace = match point
+                    .update_sequence_number
+                    .cmp(&current.update_sequence_number)
+                {
+                    std::cmp::Ordering::Greater => true,
+                    std::cmp::Ordering::Less => false,
+                    std::cmp::Ordering::Equal => {
+                        point.value != Value::Missing || current.value == Value::Missing
+                    }
+                };
+                point.duplicates = current.duplicates + 1;
+                if replace {
+                    *current = point;
+                } else {
+                    current.duplicates = point.duplicates;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WPR: &str = "./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin";
+    /// Eastward wind speed
+    const U: XY = XY { x: 11, y: 3 };
+
+    fn station(message: &[u8], tables: &Tables) -> String {
+        crate::grep::Grep::new("011003!=missing".parse().unwrap(), tables)
+            .search_message(message)
+            .unwrap()[0]
+            .station
+            .clone()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_series() {
+        let tables = Tables::default();
+        let message = std::fs::read(WPR).unwrap();
+        let station = station(&message, &tables);
+
+        let mut builder = SeriesBuilder::new(&station, U, &tables);
+        builder.add_message(&message).unwrap();
+        let series = builder.finish();
+        assert!(!series.is_empty());
+        assert!(series.windows(2).all(|w| w[0].time < w[1].time));
+        assert!(series.iter().all(|p| p.message == 0 && p.duplicates == 0));
+        let time = series[0].time;
+        assert_eq!((time.year, time.month, time.day), (2020, 7, 28));
+
+        // A retransmission and a correction (update sequence number in octet 9 of Section 1)
+        let mut correction = message.clone();
+        correction[16] = 1;
+        let mut builder = SeriesBuilder::new(&station, U, &tables);
+        builder
+            .add_messages([&correction, &message, &message])
+            .unwrap();
+        let corrected = builder.finish();
+        assert_eq!(corrected.len(), series.len());
+        for (point, original) in corrected.iter().zip(&series) {
+            assert_eq!(point.value, original.value);
+            assert_eq!((point.message, point.update_sequence_number), (0, 1));
+            assert_eq!(point.duplicates, 2);
+        }
+
+        // Other stations and elements
+        let mut builder = SeriesBuilder::new("00000", U, &tables);
+        builder.add_message(&message).unwrap();
+        assert!(builder.finish().is_empty());
+        let mut builder = SeriesBuilder::new(&station, XY { x: 12, y: 101 }, &tables);
+        builder.add_message(&message).unwrap();
+        assert!(builder.finish().is_empty());
+    }
+
+    #[test]
+    fn test_insert() {
+        let tables = Tables::default();
+        let mut builder = SeriesBuilder::new("47662", U, &tables);
+        let time = DateTime {
+            year: 2025,
+            month: 8,
+            day: 7,
+            hour: 11,
+            minute: 0,
+            second: 0,
+        };
+        let point = |value, update_sequence_number, message| SeriesPoint {
+            time,
+            value,
+            qc: None,
+            update_sequence_number,
+            message,
+            subset: 0,
+            duplicates: 0,
+        };
+        builder.insert(point(Value::Integer(1), 0, 0));
+        builder.insert(point(Value::Missing, 0, 1));
+        builder.insert(point(Value::Integer(2), 0, 2));
+        builder.insert(point(Value::Integer(3), 1, 3));
+        builder.insert(point(Value::Integer(4), 0, 4));
+        let series = builder.finish();
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].value, Value::Integer(3));
+        assert_eq!(series[0].message, 3);
+        assert_eq!(series[0].duplicates, 4);
+    }
+}
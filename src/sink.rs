@@ -0,0 +1,518 @@
+//! Destinations of decoded messages shared by the exporters
+//!
+//! [`OutputSink`] is implemented by the exporters of this crate
+//! ([`SqliteScriptWriter`](crate::sqlite::SqliteScriptWriter),
+//! [`PgCopyWriter`](crate::postgres::PgCopyWriter),
+//! [`SampleWriter`](crate::tsdb::SampleWriter), with the `serde` feature
+//! [`JsonWriter`](crate::json::JsonWriter), and with the `arrow` feature
+//! [`RecordBatchSink`](crate::arrow::RecordBatchSink),
+//! [`CsvSink`](crate::arrow::CsvSink) and [`ParquetSink`](crate::arrow::ParquetSink)),
+//! and [`export`] decodes messages into any of them. Other destinations (e.g. databases or message queues) can be added by
+//! implementing the trait:
+//!
+//! ```
+//! use tinybufr::Error;
+//! use tinybufr::sink::{MessageInput, OutputSink, export};
+//!
+//! /// Counts the values of each message.
+//! struct CountSink(Vec<usize>);
+//!
+//! impl OutputSink for CountSink {
+//!     type Output = Vec<usize>;
+//!
+//!     fn write_message(&mut self, input: &mut MessageInput<'_, '_>) -> Result<usize, Error> {
+//!         let mut values = 0;
+//!         while let Some(subset) = input.data_reader.read_subset()? {
+//!             values += subset.len();
+//!         }
+//!         self.0.push(values);
+//!         Ok(1)
+//!     }
+//!
+//!     fn finish(self) -> Result<Vec<usize>, Error> {
+//!         Ok(self.0)
+//!     }
+//! }
+//!
+//! let messages = tinybufr::input::open_any(
+//!     "./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin",
+//! )?;
+//! let tables = tinybufr::Tables::default();
+//! let (counts, rows) = export(CountSink(vec![]), messages, &tables)?;
+//! assert_eq!((counts.len(), rows), (1, 1));
+//! # Ok::<(), tinybufr::Error>(())
+//! ```
+//!
+//! [`export_with_options`] takes a [`RecoveryPolicy`] for the messages that cannot be
+//! decoded, and reports what happened to each message. Sinks decoding with
+//! [`MessageInput::for_each_element`] follow the policy as they are; others report
+//! the errors they recover from with [`MessageInput::recover`].
+
+use crate::{
+    DataEvent, DataReader, DataSpec, DecodeOptions, Error, HeaderSections, Limits, Tables, Value,
+    XY, hooks::ValueHooks, missing::MissingValues, recovery::RecoveryPolicy,
+    sections::split_message, validation::Strictness,
+};
+
+/// A destination of decoded messages.
+pub trait OutputSink {
+    /// What the sink returns when finished, e.g. the underlying writer
+    type Output;
+
+    /// Called once before the first message, e.g. to create tables. Sinks that set up
+    /// their output when created, or from the first message (as the schema of
+    /// [`ParquetSink`](crate::arrow::ParquetSink)), do nothing.
+    fn begin(&mut self, tables: &Tables) -> Result<(), Error> {
+        let _ = tables;
+        Ok(())
+    }
+
+    /// Writes the data of a message and returns the number of rows (or samples)
+    /// written. The data left unread is skipped.
+    ///
+    /// An error returned stops the export whatever the policy, so errors in the data
+    /// to recover from go through [`MessageInput::recover`].
+    fn write_message(&mut self, input: &mut MessageInput<'_, '_>) -> Result<usize, Error>;
+
+    /// Finishes the output after the last message.
+    fn finish(self) -> Result<Self::Output, Error>
+    where
+        Self: Sized;
+}
+
+/// A message to be written by [`OutputSink::write_message`].
+pub struct MessageInput<'m, 'd> {
+    /// Index of the message in the input
+    pub index: u32,
+    pub header: &'m HeaderSections,
+    pub tables: &'d Tables,
+    pub data_spec: &'d DataSpec<'d>,
+    /// The data section (Section 4), including its header
+    pub data_section: &'m [u8],
+    /// Reader of the data section, positioned at its start
+    pub data_reader: DataReader<'d, &'m [u8]>,
+    /// The options `data_reader` decodes with, lenient when recovering from a deviation
+    /// from the declared length of the message
+    pub decode_options: &'m DecodeOptions,
+    /// The missing value policies applied by `data_reader`
+    pub missing_values: &'d MissingValues,
+    /// The value hooks applied by `data_reader`
+    pub value_hooks: &'d ValueHooks,
+    /// How errors in the data are handled
+    pub recovery: RecoveryPolicy,
+    recovered: Option<Error>,
+    data_decoded: bool,
+}
+
+impl MessageInput<'_, '_> {
+    /// Records an error in the data of the message if the policy recovers from it,
+    /// otherwise returns it to stop the export. The first error recorded is reported.
+    pub fn recover(&mut self, error: Error) -> Result<(), Error> {
+        if !self.recovery.recovers() {
+            return Err(error);
+        }
+        self.recovered.get_or_insert(error);
+        Ok(())
+    }
+
+    /// Tells the export that the data section was decoded without
+    /// [`MessageInput::data_reader`] (e.g. by a columnar decoder), so that it is not
+    /// read again.
+    pub fn mark_data_decoded(&mut self) {
+        self.data_decoded = true;
+    }
+
+    /// Calls `f` with the subset index, the position in the subset, the element and
+    /// the value of each element of the data, following the policy.
+    ///
+    /// When recovering, the elements are decoded before `f` is called, so that those
+    /// the policy drops after an error are never seen. Errors returned by `f` stop the
+    /// export.
+    pub fn for_each_element(
+        &mut self,
+        mut f: impl FnMut(u16, u32, XY, &Value) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        if !self.recovery.recovers() {
+            return self.data_reader.for_each_element(f);
+        }
+        let mut elements = vec![];
+        // Elements of the subsets decoded completely
+        let mut complete = 0;
+        let mut subset_index = 0;
+        let mut position = 0;
+        let result = loop {
+            match self.data_reader.read_event() {
+                Ok(DataEvent::SubsetStart(idx)) => {
+                    subset_index = idx;
+                    position = 0;
+                }
+                Ok(DataEvent::SubsetEnd) => complete = elements.len(),
+                Ok(DataEvent::Data { xy, value, .. }) => {
+                    elements.push((subset_index, position, xy, value));
+                    position += 1;
+                }
+                Ok(DataEvent::CompressedData { xy, values, .. }) => {
+                    for (subset_index, value) in values.into_iter().enumerate() {
+                        elements.push((subset_index as u16, position, xy, value));
+                    }
+                    position += 1;
+                }
+                Ok(DataEvent::Eof) => break Ok(()),
+                Ok(_) => {}
+                Err(error) => break Err(error),
+            }
+        };
+        if let Err(error) = result {
+            let is_compressed = self.data_spec.is_compressed;
+            elements.truncate(match self.recovery {
+                _ if !self.recovery.keeps_partial(is_compressed) => 0,
+                RecoveryPolicy::SkipSubset => complete,
+                _ => elements.len(),
+            });
+            self.recover(error)?;
+        }
+        for (subset_index, position, xy, value) in &elements {
+            f(*subset_index, *position, *xy, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// A callback taking the subset index, the position in the subset, the element and the
+/// value of each element of the data.
+pub(crate) type ElementVisitor<'f> = dyn FnMut(u16, u32, XY, &Value) -> Result<(), Error> + 'f;
+
+/// Options of [`export_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    pub decode_options: DecodeOptions,
+    /// Missing value policies applied to each message
+    pub missing_values: MissingValues,
+    /// Value hooks applied to each message
+    pub value_hooks: ValueHooks,
+    pub limits: Limits,
+    pub recovery: RecoveryPolicy,
+}
+
+/// What [`export_with_options`] did with a message.
+#[derive(Debug)]
+pub struct MessageReport {
+    /// Index of the message in the input
+    pub index: u32,
+    /// The header sections, unless the message could not be split into its sections
+    pub header: Option<HeaderSections>,
+    /// Number of rows written
+    pub rows: usize,
+    /// Deviations tolerated while decoding
+    pub warnings: Vec<String>,
+    /// The error recovered from, if any
+    pub error: Option<Error>,
+}
+
+/// The result of [`export_with_options`].
+#[derive(Debug)]
+pub struct ExportSummary<O> {
+    /// What the sink returned when finished
+    pub output: O,
+    /// Number of rows written
+    pub rows: usize,
+    /// A report for each message of the input
+    pub messages: Vec<MessageReport>,
+}
+
+/// Decodes messages strictly into a sink, then finishes it. Returns the output of the
+/// sink and the number of rows written.
+pub fn export<S, I, M>(sink: S, messages: I, tables: &Tables) -> Result<(S::Output, usize), Error>
+where
+    S: OutputSink,
+    I: IntoIterator<Item = M>,
+    M: AsRef<[u8]>,
+{
+    export_with_options(sink, messages, tables, &ExportOptions::default())
+        .map(|summary| (summary.output, summary.rows))
+}
+
+/// Decodes messages with the given options into a sink, then finishes it (see
+/// [`export`]).
+///
+/// A message that cannot be decoded stops the export, or is handled as set by
+/// `options.recovery` and reported. With a policy recovering from errors, a message
+/// deviating from its declared length is decoded leniently as far as it goes. Errors
+/// in writing the output always stop the export.
+pub fn export_with_options<S, I, M>(
+    mut sink: S,
+    messages: I,
+    tables: &Tables,
+    options: &ExportOptions,
+) -> Result<ExportSummary<S::Output>, Error>
+where
+    S: OutputSink,
+    I: IntoIterator<Item = M>,
+    M: AsRef<[u8]>,
+{
+    sink.begin(tables)?;
+    let mut rows = 0;
+    let mut reports = vec![];
+    for (index, message) in messages.into_iter().enumerate() {
+        let report = export_message(&mut sink, index as u32, message.as_ref(), tables, options)?;
+        rows += report.rows;
+        reports.push(report);
+    }
+    Ok(ExportSummary {
+        output: sink.finish()?,
+        rows,
+        messages: reports,
+    })
+}
+
+fn export_message<S: OutputSink>(
+    sink: &mut S,
+    index: u32,
+    message: &[u8],
+    tables: &Tables,
+    options: &ExportOptions,
+) -> Result<MessageReport, Error> {
+    let mut report = MessageReport {
+        index,
+        header: None,
+        rows: 0,
+        warnings: vec![],
+        error: None,
+    };
+    let lenient = DecodeOptions {
+        strictness: Strictness::Lenient,
+        ..options.decode_options.clone()
+    };
+    let mut decode_options = &options.decode_options;
+    let sections = match split_message(message, decode_options) {
+        Ok(sections) => sections,
+        Err(error) => {
+            report.recover(options.recovery, error)?;
+            // A message deviating from its declared length is decoded as far as it goes
+            match split_message(message, &lenient) {
+                Ok(sections) => {
+                    decode_options = &lenient;
+                    sections
+                }
+                Err(_) => return Ok(report),
+            }
+        }
+    };
+    report.header = Some(sections.header.clone());
+    report.warnings.extend(sections.warnings.iter().cloned());
+
+    let data_spec =
+        match DataSpec::from_data_description(&sections.header.data_description_section, tables) {
+            Ok(data_spec) => data_spec,
+            Err(error) => {
+                report.recover(options.recovery, error)?;
+                return Ok(report);
+            }
+        };
+    let data_reader = match DataReader::builder(&data_spec)
+        .decode_options(decode_options)
+        .missing_values(options.missing_values.clone())
+        .value_hooks(options.value_hooks.clone())
+        .limits(options.limits.clone())
+        .build(sections.data_section)
+    {
+        Ok(data_reader) => data_reader,
+        Err(error) => {
+            report.recover(options.recovery, error)?;
+            return Ok(report);
+        }
+    };
+    let mut input = MessageInput {
+        index,
+        header: &sections.header,
+        tables,
+        data_spec: &data_spec,
+        data_section: sections.data_section,
+        data_reader,
+        decode_options,
+        missing_values: &options.missing_values,
+        value_hooks: &options.value_hooks,
+        recovery: options.recovery,
+        recovered: None,
+        data_decoded: false,
+    };
+    report.rows = sink.write_message(&mut input)?;
+    if input.recovered.is_none() && !input.data_decoded {
+        // The data left unread is checked as the rest
+        loop {
+            match input.data_reader.read_event() {
+                Ok(DataEvent::Eof) => break,
+                Ok(_) => {}
+                Err(error) => {
+                    input.recover(error)?;
+                    break;
+                }
+            }
+        }
+    }
+    report
+        .warnings
+        .extend(input.data_reader.warnings().iter().cloned());
+    if let Some(error) = input.recovered {
+        report.error.get_or_insert(error);
+    }
+    Ok(report)
+}
+
+impl MessageReport {
+    /// Records an error if the policy recovers from it, otherwise returns it.
+    fn recover(&mut self, recovery: RecoveryPolicy, error: Error) -> Result<(), Error> {
+        if !recovery.recovers() {
+            return Err(error);
+        }
+        self.error.get_or_insert(error);
+        Ok(())
+    }
+
+    /// The warnings and the error recovered from as lines to show, e.g.
+    /// "Message 3 partially converted (12 rows): ...".
+    pub fn notes(&self) -> Vec<String> {
+        let index = self.index;
+        let mut notes: Vec<_> = self
+            .warnings
+            .iter()
+            .map(|warning| format!("Message {index}: {warning}"))
+            .collect();
+        if let Some(error) = &self.error {
+            let status = match self.rows {
+                0 => "skipped".to_string(),
+                rows => format!("partially converted ({rows} rows)"),
+            };
+            notes.push(format!("Message {index} {status}: {error}"));
+        }
+        notes
+    }
+}
+
+impl<O> ExportSummary<O> {
+    /// The notes of all messages (see [`MessageReport::notes`]).
+    pub fn notes(&self) -> impl Iterator<Item = String> + '_ {
+        self.messages.iter().flat_map(MessageReport::notes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite::SqliteScriptWriter;
+
+    const WPR: &str = "./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin";
+
+    /// Records the calls, reading only the first subset of each message.
+    #[derive(Default)]
+    struct TraceSink(Vec<String>);
+
+    impl OutputSink for TraceSink {
+        type Output = Vec<String>;
+
+        fn begin(&mut self, _tables: &Tables) -> Result<(), Error> {
+            self.0.push("begin".to_string());
+            Ok(())
+        }
+
+        fn write_message(&mut self, input: &mut MessageInput<'_, '_>) -> Result<usize, Error> {
+            let subset = input.data_reader.read_subset()?.unwrap();
+            self.0.push(format!(
+                "message {} subsets={} values={}",
+                input.index,
+                input.header.number_of_subsets(),
+                subset.len()
+            ));
+            Ok(1)
+        }
+
+        fn finish(mut self) -> Result<Vec<String>, Error> {
+            self.0.push("finish".to_string());
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn test_export() {
+        let message = std::fs::read(WPR).unwrap();
+        let tables = Tables::default();
+        let (trace, rows) = export(TraceSink::default(), [&message, &message], &tables).unwrap();
+        assert_eq!(rows, 2);
+        assert_eq!(trace.len(), 4);
+        assert_eq!(trace[0], "begin");
+        assert!(trace[2].starts_with("message 1 subsets=32 values="));
+        assert_eq!(trace[3], "finish");
+
+        // The first error stops the export
+        let truncated = &message[..message.len() - 10];
+        assert!(export(TraceSink::default(), [&message[..], truncated], &tables).is_err());
+    }
+
+    #[test]
+    fn test_export_with_recovery() {
+        let message = std::fs::read(WPR).unwrap();
+        let tables = Tables::default();
+        let sections = split_message(&message, &DecodeOptions::default()).unwrap();
+        // The data stops in the middle of the subsets
+        let mut data_section = sections.data_section[..sections.data_section.len() / 2].to_vec();
+        let length = (data_section.len() as u32).to_be_bytes();
+        data_section[..3].copy_from_slice(&length[1..]);
+        let mut broken = vec![];
+        crate::write_message(&mut broken, &sections.header, &data_section).unwrap();
+        let messages = [&message, &broken, &message];
+
+        let writer = SqliteScriptWriter::new(vec![], "bufr").unwrap();
+        assert!(export(writer, messages, &tables).is_err());
+        let mut rows = vec![];
+        for recovery in [
+            RecoveryPolicy::SkipMessage,
+            RecoveryPolicy::SkipSubset,
+            RecoveryPolicy::BestEffort,
+        ] {
+            let options = ExportOptions {
+                recovery,
+                ..Default::default()
+            };
+            let writer = SqliteScriptWriter::new(vec![], "bufr").unwrap();
+            let summary = export_with_options(writer, messages, &tables, &options).unwrap();
+            let script = String::from_utf8(summary.output).unwrap();
+            assert_eq!(
+                script.lines().filter(|l| l.starts_with("INSERT")).count(),
+                summary.rows
+            );
+            let reports = &summary.messages;
+            assert_eq!(reports.len(), 3);
+            assert!(reports[0].error.is_none() && reports[2].error.is_none());
+            assert_eq!(reports[0].rows, reports[2].rows);
+            assert!(reports[1].error.is_some());
+            assert!(reports[1].rows < reports[0].rows);
+            rows.push(reports[1].rows);
+        }
+        assert_eq!(rows[0], 0);
+        assert!(rows[0] < rows[1] && rows[1] <= rows[2]);
+
+        let options = ExportOptions {
+            recovery: RecoveryPolicy::SkipMessage,
+            ..Default::default()
+        };
+        let writer = SqliteScriptWriter::new(vec![], "bufr").unwrap();
+        let summary = export_with_options(writer, messages, &tables, &options).unwrap();
+        let notes: Vec<_> = summary.notes().collect();
+        assert_eq!(notes.len(), 1);
+        assert!(notes[0].starts_with("Message 1 skipped: "), "{}", notes[0]);
+    }
+
+    #[test]
+    fn test_export_sqlite() {
+        let message = std::fs::read(WPR).unwrap();
+        let tables = Tables::default();
+        let writer = SqliteScriptWriter::new(vec![], "bufr").unwrap();
+        let (script, rows) = export(writer, [&message], &tables).unwrap();
+        let script = String::from_utf8(script).unwrap();
+        assert_eq!(
+            script.lines().filter(|l| l.starts_with("INSERT")).count(),
+            rows
+        );
+        assert!(script.ends_with("COMMIT;\n"));
+    }
+}
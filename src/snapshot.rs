@@ -0,0 +1,211 @@
+//! Canonical text snapshots of decoded data
+//!
+//! A snapshot lists every decoded event on its own line, indented by nesting,
+//! with values in their exact decimal form. Snapshots of a corpus taken before
+//! and after upgrading tinybufr or its tables differ wherever a decoded value changed,
+//! so they can be checked in as golden files for regression tests.
+
+use std::fmt::Write;
+use std::io::Read;
+use std::path::Path;
+
+use crate::{DataEvent, DataReader, DataSpec, Error, HeaderSections, Tables, XY, input::open_any};
+
+/// Returns the snapshot of the events remaining in a data reader.
+pub fn snapshot_events<R: Read>(data_reader: &mut DataReader<'_, R>) -> Result<String, Error> {
+    let mut out = String::new();
+    let mut depth = 0;
+    loop {
+        let event = data_reader.read_event()?;
+        let opens = matches!(
+            event,
+            DataEvent::SubsetStart(_)
+                | DataEvent::ReplicationItemStart
+                | DataEvent::ReplicationStart { .. }
+                | DataEvent::SequenceStart { .. }
+        );
+        let line = match event {
+            DataEvent::Eof => return Ok(out),
+            DataEvent::SubsetEnd
+            | DataEvent::ReplicationItemEnd
+            | DataEvent::ReplicationEnd
+            | DataEvent::SequenceEnd => {
+                depth -= 1;
+                continue;
+            }
+            DataEvent::SubsetStart(i) => format!("subset {i}"),
+            DataEvent::CompressedStart => "compressed".to_string(),
+            DataEvent::ReplicationStart {
+                count,
+                factor_bits,
+                subset_counts,
+                ..
+            } => match (factor_bits, subset_counts) {
+                (0, _) => format!("replication {count}"),
+                (bits, None) => format!("replication {count} (delayed, {bits} bits)"),
+                (bits, Some(counts)) => {
+                    format!("replication {count} (delayed, {bits} bits, subsets {counts:?})")
+                }
+            },
+            DataEvent::ReplicationItemStart => "item".to_string(),
+            DataEvent::SequenceStart { xy, .. } => fxy(3, xy),
+            DataEvent::OperatorHandled { x, value, .. } => format!("operator 2 {x:02} {value}"),
+            DataEvent::Data { xy, value, .. } => format!("{} = {value:?}", fxy(0, xy)),
+            DataEvent::CompressedData { xy, values, .. } => {
+                format!("{} = {values:?}", fxy(0, xy))
+            }
+            DataEvent::AssociatedField { value, .. } => format!("associated = {value:?}"),
+            DataEvent::CompressedAssociatedField { values, .. } => {
+                format!("associated = {values:?}")
+            }
+            DataEvent::SignifiedCharacters { value, .. } => format!("signified = {value:?}"),
+            DataEvent::CompressedSignifiedCharacters { values, .. } => {
+                format!("signified = {values:?}")
+            }
+            DataEvent::ReferenceValueChanged {
+                xy,
+                reference_value,
+                ..
+            } => format!("{} reference = {reference_value}", fxy(0, xy)),
+            DataEvent::BitmapTarget { position, .. } => format!("bitmap target #{position}"),
+            DataEvent::Quality {
+                x,
+                position,
+                xy,
+                significance,
+                value,
+                ..
+            } => format!(
+                "{} = {value:?}",
+                quality_label(x, position, xy, significance)
+            ),
+            DataEvent::CompressedQuality {
+                x,
+                position,
+                xy,
+                significance,
+                values,
+                ..
+            } => format!(
+                "{} = {values:?}",
+                quality_label(x, position, xy, significance)
+            ),
+            DataEvent::UnknownLocalElement {
+                xy, width, values, ..
+            } => match values.as_slice() {
+                [value] => format!("{} unknown ({width} bits) = {value:?}", fxy(0, xy)),
+                values => format!("{} unknown ({width} bits) = {values:?}", fxy(0, xy)),
+            },
+        };
+        let _ = writeln!(out, "{:indent$}{line}", "", indent = depth * 2);
+        if opens {
+            depth += 1;
+        }
+    }
+}
+
+/// Returns the snapshot of a message, headed by its identification.
+pub fn snapshot_message(message: &[u8], tables: &Tables) -> Result<String, Error> {
+    let mut reader = message;
+    let header = HeaderSections::read(&mut reader)?;
+    let ids = &header.identification_section;
+    let mut out = format!(
+        "message edition={} centre={} sub_centre={} category={} master_table={} version={} \
+         local_version={} subsets={} observed={} compressed={}\n",
+        header.indicator_section.edition_number,
+        ids.centre,
+        ids.sub_centre,
+        ids.data_category,
+        ids.master_table_number,
+        ids.master_table_version,
+        ids.local_tables_version,
+        header.number_of_subsets(),
+        header.is_observed_data(),
+        header.is_compressed(),
+    );
+    let data_spec = DataSpec::from_data_description(&header.data_description_section, tables)?;
+    let mut data_reader = DataReader::new(&mut reader, &data_spec)?;
+    out += &snapshot_events(&mut data_reader)?;
+    data_reader.finish_message(header.indicator_section.edition_number)?;
+    Ok(out)
+}
+
+/// Returns the snapshot of all messages in a file of any format supported by [`open_any`].
+///
+/// Messages that fail to decode are recorded with their errors, which are part of
+/// the snapshot too.
+pub fn snapshot_file<P: AsRef<Path>>(path: P, tables: &Tables) -> Result<String, Error> {
+    let mut out = String::new();
+    for (i, message) in open_any(path)?.enumerate() {
+        let _ = writeln!(out, "# message {i}");
+        match snapshot_message(&message, tables) {
+            Ok(snapshot) => out += &snapshot,
+            Err(e) => {
+                let _ = writeln!(out, "error: {e}");
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn fxy(f: u8, xy: XY) -> String {
+    format!("{f} {:02} {:03}", xy.x, xy.y)
+}
+
+fn quality_label(x: u8, position: u32, xy: XY, significance: Option<i32>) -> String {
+    match significance {
+        Some(s) => format!("2 {x:02} 255 of #{position} {} ({s})", fxy(0, xy)),
+        None => format!("2 {x:02} 255 of #{position} {}", fxy(0, xy)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::tests::make_dds;
+    use crate::{DataWriter, Descriptor, Value};
+
+    #[test]
+    fn test_snapshot_events() {
+        let tables = Tables::default();
+        let descriptors = vec![
+            Descriptor { f: 3, x: 1, y: 1 },
+            Descriptor { f: 1, x: 1, y: 0 },
+            Descriptor { f: 0, x: 31, y: 1 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+        ];
+        let dds = make_dds(descriptors, 1);
+        let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut writer = DataWriter::new(&data_spec);
+        writer
+            .write_subset(&[
+                Value::Integer(47),
+                Value::Integer(662),
+                Value::Integer(2),
+                Value::Decimal(27315, -2),
+                Value::Missing,
+            ])
+            .unwrap();
+        let section = writer.finish().unwrap();
+
+        let mut data_reader = DataReader::new(section.as_slice(), &data_spec).unwrap();
+        assert_eq!(
+            snapshot_events(&mut data_reader).unwrap(),
+            "\
+subset 0
+  3 01 001
+    0 01 001 = 47
+    0 01 002 = 662
+  replication 2 (delayed, 8 bits)
+    item
+      0 12 101 = 273.15
+    item
+      0 12 101 = Missing
+"
+        );
+    }
+}
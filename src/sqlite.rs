@@ -0,0 +1,223 @@
+//! Export of decoded data as SQL scripts for SQLite
+//!
+//! Subsets are written into a single long-format table with one row per element.
+//! The script can be loaded without any extra dependency, e.g. `sqlite3 out.db < out.sql`.
+
+use std::io::{Read, Write};
+
+use crate::{
+    DataReader, Error, Tables, Value, XY,
+    sink::{MessageInput, OutputSink},
+};
+
+/// Writes decoded messages as a SQLite script.
+///
+/// The table has the following columns:
+///
+/// | column | type | description |
+/// |---|---|---|
+/// | `message_index` | INTEGER | Index of the message in the input |
+/// | `subset_index` | INTEGER | Index of the subset in the message |
+/// | `position` | INTEGER | Index of the element in the expanded subset |
+/// | `descriptor` | TEXT | Element descriptor (e.g. `012101`) |
+/// | `name` | TEXT | Element name from Table B |
+/// | `unit` | TEXT | Unit from Table B |
+/// | `value` | (any) | Numeric or text value, `NULL` if missing |
+pub struct SqliteScriptWriter<W: Write> {
+    writer: W,
+    table_name: String,
+}
+
+impl<W: Write> SqliteScriptWriter<W> {
+    /// Starts a script that inserts into `table_name`, creating the table if needed.
+    pub fn new(mut writer: W, table_name: &str) -> Result<Self, Error> {
+        let table_name = quote_identifier(table_name);
+        writeln!(writer, "BEGIN TRANSACTION;")?;
+        writeln!(
+            writer,
+            "CREATE TABLE IF NOT EXISTS {table_name} (message_index INTEGER NOT NULL, \
+             subset_index INTEGER NOT NULL, position INTEGER NOT NULL, descriptor TEXT NOT NULL, \
+             name TEXT NOT NULL, unit TEXT NOT NULL, value);"
+        )?;
+        Ok(Self { writer, table_name })
+    }
+
+    /// Writes all subsets of a message and returns the number of rows written.
+    pub fn write_message<R: Read>(
+        &mut self,
+        data_reader: &mut DataReader<'_, R>,
+        tables: &Tables,
+        message_index: u32,
+    ) -> Result<usize, Error> {
+        let mut rows = 0;
+        data_reader.for_each_element(|subset_index, position, xy, value| {
+            rows += 1;
+            self.write_row(tables, message_index, subset_index, position, xy, value)
+        })?;
+        Ok(rows)
+    }
+
+    /// Commits the transaction and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        writeln!(self.writer, "COMMIT;")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_row(
+        &mut self,
+        tables: &Tables,
+        message_index: u32,
+        subset_index: u16,
+        position: u32,
+        xy: XY,
+        value: &Value,
+    ) -> Result<(), Error> {
+        let Some(b) = tables.table_b.get(&xy) else {
+            return Err(Error::Table(format!(
+                "Table B entry not found for xy: {xy:?}"
+            )));
+        };
+        writeln!(
+            self.writer,
+            "INSERT INTO {} VALUES ({message_index}, {subset_index}, {position}, '0{:02}{:03}', {}, {}, {});",
+            self.table_name,
+            xy.x,
+            xy.y,
+            quote_string(b.element_name),
+            quote_string(b.unit),
+            sql_value(value),
+        )?;
+        Ok(())
+    }
+}
+
+impl<W: Write> OutputSink for SqliteScriptWriter<W> {
+    type Output = W;
+
+    fn write_message(&mut self, input: &mut MessageInput<'_, '_>) -> Result<usize, Error> {
+        let (tables, message_index) = (input.tables, input.index);
+        let mut rows = 0;
+        input.for_each_element(|subset_index, position, xy, value| {
+            rows += 1;
+            self.write_row(tables, message_index, subset_index, position, xy, value)
+        })?;
+        Ok(rows)
+    }
+
+    fn finish(self) -> Result<W, Error> {
+        SqliteScriptWriter::finish(self)
+    }
+}
+
+fn sql_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => quote_string(s),
+        Value::Bytes(b) => format!("X'{}'", crate::manifest::hex(b)),
+        v => v.to_decimal_string().unwrap_or_else(|| "NULL".to_string()),
+    }
+}
+
+fn quote_identifier(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+fn quote_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::tests::make_dds;
+    use crate::{DataSpec, DataWriter, Descriptor};
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+        assert_eq!(quote_string("it's"), "'it''s'");
+        assert_eq!(sql_value(&Value::Decimal(-105, -1)), "-10.5");
+        assert_eq!(sql_value(&Value::Decimal(12, 2)), "1200");
+        // Wide values are exact
+        assert_eq!(
+            sql_value(&Value::Decimal64(-1_234_567_890_123_456_789, -3)),
+            "-1234567890123456.789"
+        );
+        assert_eq!(sql_value(&Value::Decimal64(-5, -3)), "-0.005");
+        assert_eq!(sql_value(&Value::Decimal64(12, 2)), "1200");
+        assert_eq!(sql_value(&Value::Missing), "NULL");
+    }
+
+    #[test]
+    fn test_write_message() {
+        let tables = Tables::default();
+        let descriptors = vec![
+            Descriptor { f: 0, x: 1, y: 15 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+        ];
+        let dds = make_dds(descriptors, 2);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut writer = DataWriter::new(&spec);
+        writer
+            .write_subset(&[
+                Value::String("O'HARE".to_string()),
+                Value::Decimal(27315, -2),
+            ])
+            .unwrap();
+        writer
+            .write_subset(&[Value::Missing, Value::Missing])
+            .unwrap();
+        let section = writer.finish().unwrap();
+
+        let mut script = SqliteScriptWriter::new(Vec::new(), "obs").unwrap();
+        let mut data_reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let rows = script.write_message(&mut data_reader, &tables, 7).unwrap();
+        assert_eq!(rows, 4);
+        let script = String::from_utf8(script.finish().unwrap()).unwrap();
+        let lines: Vec<_> = script.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], "BEGIN TRANSACTION;");
+        assert!(lines[1].starts_with("CREATE TABLE IF NOT EXISTS \"obs\""));
+        assert_eq!(
+            lines[2],
+            "INSERT INTO \"obs\" VALUES (7, 0, 0, '001015', 'Station or site name', 'CCITT IA5', 'O''HARE              ');"
+        );
+        assert_eq!(
+            lines[3],
+            "INSERT INTO \"obs\" VALUES (7, 0, 1, '012101', 'Temperature/air temperature', 'K', 273.15);"
+        );
+        assert!(lines[5].ends_with("NULL);"));
+        assert_eq!(lines[6], "COMMIT;");
+    }
+
+    #[test]
+    fn test_signified_characters() {
+        let tables = Tables::default();
+        // 2 05 002 is not the element 0 05 002 (latitude)
+        let descriptors = vec![
+            Descriptor { f: 2, x: 5, y: 2 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+        ];
+        let dds = make_dds(descriptors, 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let section = [0, 0, 8, 0, b'O', b'K', 0x6a, 0xb3];
+
+        let mut script = SqliteScriptWriter::new(Vec::new(), "obs").unwrap();
+        let mut data_reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        assert_eq!(
+            script.write_message(&mut data_reader, &tables, 0).unwrap(),
+            1
+        );
+        let script = String::from_utf8(script.finish().unwrap()).unwrap();
+        assert!(script.contains("'012101', 'Temperature/air temperature', 'K', 273.15);"));
+        assert!(!script.contains("'005002'"));
+    }
+}
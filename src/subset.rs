@@ -0,0 +1,578 @@
+//! Reading subsets as trees of values
+
+use std::io::Read;
+
+use crate::{DataEvent, DataReader, Descriptor, Error, Value, XY, aliases::Aliases, record};
+
+/// What to do with a subset, decided by the filter of [`DataReader::read_subset_where`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterDecision {
+    /// Not decided yet: the filter is called again with the next element
+    Undecided,
+    Keep,
+    /// Skip the rest of the subset without decoding it
+    Skip,
+}
+
+/// Filter of the current subset, called with each element read until it decides.
+type Filter<'f> = &'f mut dyn FnMut(XY, &Value) -> FilterDecision;
+
+/// A value in a subset read by [`DataReader::read_subset`], nested as in the template.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubsetValue {
+    /// A value of an element
+    Scalar { xy: XY, value: Value },
+    /// The values of a Table D sequence
+    Sequence { xy: XY, values: Vec<SubsetValue> },
+    /// The values of each item of a replication
+    Replication { items: Vec<Vec<SubsetValue>> },
+}
+
+/// Lookup of the values of elements in a subset read by [`DataReader::read_subset`]
+/// (or in the values of a sequence or replication item), wherever they are nested.
+///
+/// Occurrences are counted in the order of the template, items of replications
+/// included, as in [`SubsetLookup::elements`].
+pub trait SubsetLookup {
+    /// Iterates over the elements and their values, depth first.
+    fn elements(&self) -> Elements<'_>;
+
+    /// Returns the value of the first occurrence of an element.
+    fn get_value(&self, xy: XY) -> Option<&Value> {
+        self.get_nth(xy, 0)
+    }
+
+    /// Returns the value of the `n`-th occurrence (from 0) of an element.
+    fn get_nth(&self, xy: XY, n: usize) -> Option<&Value> {
+        self.elements()
+            .filter(|&(e, _)| e == xy)
+            .nth(n)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the values of all the occurrences of an element.
+    fn get_all(&self, xy: XY) -> Vec<&Value> {
+        self.elements()
+            .filter(|&(e, _)| e == xy)
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    /// Returns the values of the elements selected by a path of descriptors (see
+    /// [`SubsetPath`]), e.g. `307080/302031/010004` or `#2#012101`.
+    fn select(&self, path: &str) -> Result<Vec<&Value>, Error> {
+        Ok(self.select_path(&path.parse()?))
+    }
+
+    /// Returns the values of the elements selected by a parsed path.
+    fn select_path(&self, path: &SubsetPath) -> Vec<&Value>;
+}
+
+impl SubsetLookup for [SubsetValue] {
+    fn elements(&self) -> Elements<'_> {
+        Elements {
+            stack: vec![self.iter()],
+        }
+    }
+
+    fn select_path(&self, path: &SubsetPath) -> Vec<&Value> {
+        let mut selected = vec![];
+        select_steps(self, &path.steps, &mut selected);
+        selected
+    }
+}
+
+fn select_steps<'s>(values: &'s [SubsetValue], steps: &[PathStep], selected: &mut Vec<&'s Value>) {
+    let Some((step, rest)) = steps.split_first() else {
+        return;
+    };
+    let found = record::find(values, step.descriptor);
+    let found = match step.occurrence {
+        Some(n) => found.get(n - 1).map_or(&[][..], std::slice::from_ref),
+        None => &found[..],
+    };
+    for value in found {
+        match value {
+            SubsetValue::Scalar { value, .. } if rest.is_empty() => selected.push(value),
+            SubsetValue::Sequence { values, .. } => select_steps(values, rest, selected),
+            _ => {}
+        }
+    }
+}
+
+/// A path selecting elements in a subset, for [`SubsetLookup::select`].
+///
+/// A path is a list of sequence descriptors (`3XXYYY`) ending with an element
+/// descriptor (`0XXYYY`), separated by `/`. Each descriptor matches sequences or
+/// elements nested at any depth in the ones matched before it, items of replications
+/// included, so `302031/010004` selects the pressures of the sequences 3 02 031. As in
+/// ecCodes keys, a descriptor preceded by `#n#` matches its `n`-th occurrence (from 1)
+/// only, e.g. `#2#012101` for the second air temperature of the subset.
+///
+/// With [`SubsetPath::parse_with_aliases`], elements can be given by key instead
+/// (e.g. `302031/pressure`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubsetPath {
+    steps: Vec<PathStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PathStep {
+    descriptor: Descriptor,
+    /// Occurrence to match, from 1
+    occurrence: Option<usize>,
+}
+
+impl SubsetPath {
+    /// Parses a path whose elements may be given by the keys of `aliases`.
+    pub fn parse_with_aliases(s: &str, aliases: &Aliases) -> Result<Self, Error> {
+        Self::parse(s, Some(aliases))
+    }
+
+    fn parse(s: &str, aliases: Option<&Aliases>) -> Result<Self, Error> {
+        let invalid = |reason: &str| Error::Invalid(format!("Invalid path {s:?}: {reason}"));
+        let segments: Vec<&str> = s.trim().split('/').map(str::trim).collect();
+        let last = segments.len() - 1;
+        let mut steps = vec![];
+        for (i, segment) in segments.into_iter().enumerate() {
+            let (occurrence, name) = match segment.strip_prefix('#') {
+                Some(rest) => {
+                    let (n, name) = rest
+                        .split_once('#')
+                        .ok_or_else(|| invalid("expected #n# before a descriptor"))?;
+                    match n.parse() {
+                        Ok(n) if n > 0 => (Some(n), name),
+                        _ => return Err(invalid("occurrences are counted from 1")),
+                    }
+                }
+                None => (None, segment),
+            };
+            let descriptor = match (name.len(), name.as_bytes().first()) {
+                // Sliced at byte offsets, so only ASCII digits are accepted
+                (6, Some(b'3')) if i < last && name.bytes().all(|b| b.is_ascii_digit()) => {
+                    Descriptor {
+                        f: 3,
+                        x: name[1..3].parse().map_err(|_| invalid(name))?,
+                        y: name[3..].parse().map_err(|_| invalid(name))?,
+                    }
+                }
+                _ if i < last => return Err(invalid("expected a sequence descriptor")),
+                _ => {
+                    let xy = match aliases {
+                        Some(aliases) => aliases.element(name)?,
+                        None => crate::overrides::parse_element_descriptor(name)?,
+                    };
+                    Descriptor {
+                        f: 0,
+                        x: xy.x,
+                        y: xy.y,
+                    }
+                }
+            };
+            steps.push(PathStep {
+                descriptor,
+                occurrence,
+            });
+        }
+        Ok(Self { steps })
+    }
+}
+
+impl std::str::FromStr for SubsetPath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, None)
+    }
+}
+
+/// Iterator over the elements of a subset (see [`SubsetLookup::elements`]).
+pub struct Elements<'s> {
+    stack: Vec<std::slice::Iter<'s, SubsetValue>>,
+}
+
+impl<'s> Iterator for Elements<'s> {
+    type Item = (XY, &'s Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(value) = self.stack.last_mut()?.next() else {
+                self.stack.pop();
+                continue;
+            };
+            match value {
+                SubsetValue::Scalar { xy, value } => return Some((*xy, value)),
+                SubsetValue::Sequence { values, .. } => self.stack.push(values.iter()),
+                // Items are pushed in reverse to be visited in order
+                SubsetValue::Replication { items } => self
+                    .stack
+                    .extend(items.iter().rev().map(|item| item.iter())),
+            }
+        }
+    }
+}
+
+impl<R: Read> DataReader<'_, R> {
+    /// Reads the next subset as a tree of values, or returns `None` after the last one.
+    ///
+    /// Only the values of elements are kept: operators, associated fields, bit-map
+    /// targets and quality information are skipped. Compressed data is not supported,
+    /// as its values are not stored subset by subset. Values of elements can be looked
+    /// up by descriptor with [`SubsetLookup`].
+    pub fn read_subset(&mut self) -> Result<Option<Vec<SubsetValue>>, Error> {
+        self.read_subset_where(|_, _, _| FilterDecision::Keep)
+    }
+
+    /// Reads the next subset kept by a filter as a tree of values, or returns `None`
+    /// after the last one.
+    ///
+    /// The filter is called with the index of the subset and each of its elements in
+    /// turn (e.g. station identifiers and coordinates), until it decides to keep the
+    /// subset or to skip the rest of it, which is done as by [`DataReader::skip_subset`].
+    /// Subsets the filter has not decided on by their end are kept.
+    pub fn read_subset_where(
+        &mut self,
+        mut filter: impl FnMut(u16, XY, &Value) -> FilterDecision,
+    ) -> Result<Option<Vec<SubsetValue>>, Error> {
+        loop {
+            let subset = match self.read_event()? {
+                DataEvent::SubsetStart(i) => {
+                    let mut filter = |xy, value: &Value| filter(i, xy, value);
+                    self.read_subset_values(&mut Some(&mut filter))?
+                }
+                DataEvent::Eof => return Ok(None),
+                DataEvent::CompressedStart => {
+                    return Err(Error::NotSupported(
+                        "Reading subsets of compressed data".to_string(),
+                    ));
+                }
+                ev => return Err(Error::Fatal(format!("Unexpected event: {ev:?}"))),
+            };
+            if let Some(subset) = subset {
+                return Ok(Some(subset));
+            }
+        }
+    }
+
+    /// Reads values up to the end of the current subset, sequence or replication item,
+    /// or returns `None` if the filter skipped the rest of the subset.
+    fn read_subset_values(
+        &mut self,
+        filter: &mut Option<Filter>,
+    ) -> Result<Option<Vec<SubsetValue>>, Error> {
+        let mut values = vec![];
+        loop {
+            match self.read_event()? {
+                DataEvent::SubsetEnd | DataEvent::SequenceEnd | DataEvent::ReplicationItemEnd => {
+                    return Ok(Some(values));
+                }
+                DataEvent::Data { xy, value, .. } => {
+                    if let Some(f) = filter {
+                        match f(xy, &value) {
+                            FilterDecision::Undecided => {}
+                            FilterDecision::Keep => *filter = None,
+                            FilterDecision::Skip => {
+                                self.skip_subset()?;
+                                return Ok(None);
+                            }
+                        }
+                    }
+                    values.push(SubsetValue::Scalar { xy, value })
+                }
+                DataEvent::SequenceStart { xy, .. } => {
+                    let Some(nested) = self.read_subset_values(filter)? else {
+                        return Ok(None);
+                    };
+                    values.push(SubsetValue::Sequence { xy, values: nested });
+                }
+                DataEvent::ReplicationStart { .. } => {
+                    let mut items = vec![];
+                    loop {
+                        match self.read_event()? {
+                            DataEvent::ReplicationItemStart => {
+                                let Some(item) = self.read_subset_values(filter)? else {
+                                    return Ok(None);
+                                };
+                                items.push(item)
+                            }
+                            DataEvent::ReplicationEnd => break,
+                            ev => {
+                                return Err(Error::Fatal(format!(
+                                    "Unexpected event in replication: {ev:?}"
+                                )));
+                            }
+                        }
+                    }
+                    values.push(SubsetValue::Replication { items });
+                }
+                DataEvent::OperatorHandled { .. }
+                | DataEvent::ReferenceValueChanged { .. }
+                | DataEvent::AssociatedField { .. }
+                | DataEvent::BitmapTarget { .. }
+                | DataEvent::Quality { .. }
+                | DataEvent::SignifiedCharacters { .. }
+                | DataEvent::UnknownLocalElement { .. } => {}
+                ev => return Err(Error::Fatal(format!("Unexpected event in subset: {ev:?}"))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::reader::tests::make_dds;
+    use crate::sections::{DataDescriptionSection, DataDescriptionSectionFlags};
+    use crate::{DataSpec, DataWriter, Descriptor, Tables};
+
+    pub(crate) fn scalar(x: u8, y: u8, value: Value) -> SubsetValue {
+        SubsetValue::Scalar {
+            xy: XY { x, y },
+            value,
+        }
+    }
+
+    /// A station with its name and two levels, the second without a temperature.
+    pub(crate) fn sounding() -> Vec<SubsetValue> {
+        vec![
+            SubsetValue::Sequence {
+                xy: XY { x: 1, y: 1 },
+                values: vec![
+                    scalar(1, 1, Value::Integer(47)),
+                    scalar(1, 2, Value::Integer(662)),
+                ],
+            },
+            scalar(1, 15, Value::String("TOKYO".to_string())),
+            SubsetValue::Replication {
+                items: vec![
+                    vec![
+                        scalar(7, 4, Value::Decimal(10000, 1)),
+                        scalar(12, 101, Value::Decimal(28015, -2)),
+                    ],
+                    vec![
+                        scalar(7, 4, Value::Decimal(8500, 1)),
+                        scalar(12, 101, Value::Missing),
+                    ],
+                ],
+            },
+        ]
+    }
+
+    fn data_description(is_compressed: bool) -> DataDescriptionSection {
+        let descriptors = vec![
+            // WMO block and station numbers
+            Descriptor { f: 3, x: 1, y: 1 },
+            // Air temperature, replicated twice
+            Descriptor { f: 1, x: 1, y: 2 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+        ];
+        let mut dds = make_dds(descriptors, 2);
+        dds.flags = DataDescriptionSectionFlags {
+            is_observed_data: true,
+            is_compressed,
+        };
+        dds
+    }
+
+    #[test]
+    fn test_read_subset() {
+        let tables = Tables::default();
+        let dds = data_description(false);
+        let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut writer = DataWriter::new(&data_spec);
+        for station in [662, 671] {
+            writer
+                .write_subset(&[
+                    Value::Integer(47),
+                    Value::Integer(station),
+                    Value::Decimal(28015, -2),
+                    Value::Missing,
+                ])
+                .unwrap();
+        }
+        let data_section = writer.finish().unwrap();
+
+        let mut reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+        let first = reader.read_subset().unwrap().unwrap();
+        assert_eq!(
+            first,
+            [
+                SubsetValue::Sequence {
+                    xy: XY { x: 1, y: 1 },
+                    values: vec![
+                        scalar(1, 1, Value::Integer(47)),
+                        scalar(1, 2, Value::Integer(662))
+                    ],
+                },
+                SubsetValue::Replication {
+                    items: vec![
+                        vec![scalar(12, 101, Value::Decimal(28015, -2))],
+                        vec![scalar(12, 101, Value::Missing)],
+                    ],
+                },
+            ]
+        );
+        let second = reader.read_subset().unwrap().unwrap();
+        assert_eq!(second.len(), 2);
+        assert!(reader.read_subset().unwrap().is_none());
+
+        // Only the subsets of the station 671
+        let mut reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+        let mut calls = vec![];
+        let mut filter = |subset, xy, value: &Value| {
+            calls.push(subset);
+            match xy {
+                XY { x: 1, y: 2 } if *value == Value::Integer(671) => FilterDecision::Keep,
+                XY { x: 1, y: 2 } => FilterDecision::Skip,
+                _ => FilterDecision::Undecided,
+            }
+        };
+        assert_eq!(reader.read_subset_where(&mut filter).unwrap(), Some(second));
+        assert!(reader.read_subset_where(&mut filter).unwrap().is_none());
+        // Called up to the station numbers
+        assert_eq!(calls, [0, 0, 1, 1]);
+
+        let dds = data_description(true);
+        let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut writer = DataWriter::new(&data_spec);
+        for _ in 0..2 {
+            writer
+                .write_subset(&[
+                    Value::Integer(47),
+                    Value::Integer(662),
+                    Value::Missing,
+                    Value::Missing,
+                ])
+                .unwrap();
+        }
+        let data_section = writer.finish().unwrap();
+        let mut reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+        assert!(matches!(reader.read_subset(), Err(Error::NotSupported(_))));
+    }
+
+    #[test]
+    fn test_subset_lookup() {
+        let temperature = XY { x: 12, y: 101 };
+        let subset = [
+            SubsetValue::Sequence {
+                xy: XY { x: 1, y: 1 },
+                values: vec![
+                    scalar(1, 1, Value::Integer(47)),
+                    scalar(1, 2, Value::Integer(662)),
+                ],
+            },
+            SubsetValue::Replication {
+                items: vec![
+                    vec![
+                        scalar(12, 101, Value::Decimal(28015, -2)),
+                        SubsetValue::Replication {
+                            items: vec![vec![scalar(12, 101, Value::Missing)]],
+                        },
+                    ],
+                    vec![scalar(12, 101, Value::Decimal(27315, -2))],
+                ],
+            },
+            scalar(1, 15, Value::String("TOKYO".to_string())),
+        ];
+        assert_eq!(
+            subset.get_value(XY { x: 1, y: 2 }),
+            Some(&Value::Integer(662))
+        );
+        assert_eq!(
+            subset.get_value(temperature),
+            Some(&Value::Decimal(28015, -2))
+        );
+        assert_eq!(subset.get_nth(temperature, 1), Some(&Value::Missing));
+        assert_eq!(
+            subset.get_nth(temperature, 2),
+            Some(&Value::Decimal(27315, -2))
+        );
+        assert_eq!(subset.get_nth(temperature, 3), None);
+        assert_eq!(subset.get_all(temperature).len(), 3);
+        assert_eq!(subset.get_value(XY { x: 20, y: 1 }), None);
+        let elements: Vec<_> = subset.elements().map(|(xy, _)| (xy.x, xy.y)).collect();
+        assert_eq!(
+            elements,
+            [(1, 1), (1, 2), (12, 101), (12, 101), (12, 101), (1, 15)]
+        );
+
+        // Within a sequence
+        let SubsetValue::Sequence { values, .. } = &subset[0] else {
+            unreachable!()
+        };
+        assert_eq!(
+            values.get_value(XY { x: 1, y: 1 }),
+            Some(&Value::Integer(47))
+        );
+        assert_eq!(values.get_value(temperature), None);
+    }
+
+    #[test]
+    fn test_select() {
+        let level = |pressure, temperature| SubsetValue::Sequence {
+            xy: XY { x: 3, y: 14 },
+            values: vec![
+                scalar(7, 4, Value::Integer(pressure)),
+                scalar(12, 101, Value::Decimal(temperature, -2)),
+            ],
+        };
+        let subset = [
+            scalar(12, 101, Value::Decimal(29315, -2)),
+            SubsetValue::Sequence {
+                xy: XY { x: 9, y: 52 },
+                values: vec![SubsetValue::Replication {
+                    items: vec![vec![level(100000, 28015)], vec![level(85000, 27315)]],
+                }],
+            },
+        ];
+        let integers = |values: Vec<&Value>| -> Vec<i32> {
+            values
+                .into_iter()
+                .map(|v| match *v {
+                    Value::Integer(v) | Value::Decimal(v, _) => v,
+                    _ => unreachable!(),
+                })
+                .collect()
+        };
+        assert_eq!(
+            integers(subset.select("012101").unwrap()),
+            [29315, 28015, 27315]
+        );
+        assert_eq!(
+            integers(subset.select("309052/012101").unwrap()),
+            [28015, 27315]
+        );
+        assert_eq!(
+            integers(subset.select("309052/303014/007004").unwrap()),
+            [100000, 85000]
+        );
+        assert_eq!(
+            integers(subset.select("309052/#2#303014/012101").unwrap()),
+            [27315]
+        );
+        assert_eq!(integers(subset.select("#3#012101").unwrap()), [27315]);
+        assert!(subset.select("#4#012101").unwrap().is_empty());
+        assert!(subset.select("303014/309052/012101").unwrap().is_empty());
+
+        for invalid in [
+            "012101/012101",
+            "309052",
+            "#0#012101",
+            "#1012101",
+            "pressure",
+            "3aé12/012101",
+            "309052/0aé12",
+        ] {
+            assert!(subset.select(invalid).is_err(), "{invalid}");
+        }
+
+        let mut aliases = Aliases::new();
+        aliases.insert(XY { x: 7, y: 4 }, "pressure").unwrap();
+        let path = SubsetPath::parse_with_aliases("309052/pressure", &aliases).unwrap();
+        assert_eq!(integers(subset.select_path(&path)), [100000, 85000]);
+    }
+}
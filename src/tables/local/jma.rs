@@ -5,10 +5,15 @@ use crate::{Descriptor, Tables};
 use super::super::{TableBEntry, TableDEntry, XY};
 
 /// Install JMA local descriptors into the provided tables instance.
+///
+/// This includes the local delayed replication factor 0 31 003 (8 bits).
 pub fn install_jma_descriptors(tables: &mut Tables) {
     for desc in &JMA_DATA_DESCRIPTORS {
         tables.table_b.insert(desc.xy, desc);
     }
+    tables
+        .delayed_replication_factors
+        .insert(XY { x: 31, y: 3 }, 8);
     for seq in &JMA_SEQUENCE_DESCRIPTORS {
         tables.table_d.insert(seq.xy, seq);
     }
@@ -1,9 +1,9 @@
 //! The BUFR edition 4 tables
 
 pub mod local;
-mod table_b;
-mod table_c;
-mod table_d;
+pub(crate) mod table_b;
+pub(crate) mod table_c;
+pub(crate) mod table_d;
 
 pub use table_b::*;
 pub use table_c::*;
@@ -17,6 +17,44 @@ pub struct Tables {
     pub table_b: HashMap<XY, &'static TableBEntry>,
     pub table_c: HashMap<(u8, Option<u8>), &'static TableCEntry>,
     pub table_d: HashMap<XY, &'static TableDEntry>,
+    /// Element descriptors allowed as delayed replication factors, with their widths in bits
+    ///
+    /// Centres using local factors (e.g. JMA's 0 31 003) add them here.
+    pub delayed_replication_factors: HashMap<XY, u8>,
+    /// Maximum nesting depth of Table D sequences alone, guarding against overly deep
+    /// (or malicious) local tables
+    ///
+    /// This is a cap within [`Tables::max_nesting_depth`], which counts the sequences
+    /// too: whichever limit is reached first fails, so a value above
+    /// `max_nesting_depth` has no effect.
+    pub max_sequence_depth: usize,
+    /// Maximum nesting depth of Table D sequences and replications together, bounding
+    /// the stack of [`DataReader`](crate::DataReader)
+    pub max_nesting_depth: usize,
+}
+
+/// Default of [`Tables::max_sequence_depth`], well above the nesting of WMO sequences.
+pub const DEFAULT_MAX_SEQUENCE_DEPTH: usize = 32;
+
+/// Default of [`Tables::max_nesting_depth`], leaving room for replications around
+/// sequences nested up to [`DEFAULT_MAX_SEQUENCE_DEPTH`].
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 64;
+
+impl Tables {
+    /// Returns the Table C entry of an operator descriptor 2 XX YYY.
+    ///
+    /// Entries defined for a specific YYY take precedence over those for any YYY.
+    pub fn operator(&self, x: u8, y: u8) -> Option<&'static TableCEntry> {
+        self.table_c
+            .get(&(x, Some(y)))
+            .or_else(|| self.table_c.get(&(x, None)))
+            .copied()
+    }
+
+    /// Returns the name of an operator descriptor 2 XX YYY (e.g. "Change data width").
+    pub fn operator_name(&self, x: u8, y: u8) -> Option<&'static str> {
+        self.operator(x, y).map(|c| c.operator_name)
+    }
 }
 
 impl Default for Tables {
@@ -25,6 +63,9 @@ impl Default for Tables {
             table_b: make_table_b(),
             table_c: make_table_c(),
             table_d: make_table_d(),
+            delayed_replication_factors: make_delayed_replication_factors(),
+            max_sequence_depth: DEFAULT_MAX_SEQUENCE_DEPTH,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
         }
     }
 }
@@ -41,6 +82,58 @@ pub struct TableBEntry {
     pub bits: u16,
 }
 
+impl TableBEntry {
+    /// Returns whether the element locates/qualifies other values or is a measurement.
+    pub fn kind(&self) -> ElementKind {
+        match self.xy.x {
+            1..=9 => ElementKind::Coordinate,
+            31 | 33 => ElementKind::Qualifier,
+            _ => ElementKind::Data,
+        }
+    }
+}
+
+/// Classification of Table B elements by their class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ElementKind {
+    /// Identification, time, location and other coordinates (classes 01 to 09)
+    Coordinate,
+    /// Replication factors, data present indicators and quality information (classes 31 and 33)
+    Qualifier,
+    /// Measurements and other data (all other classes)
+    Data,
+}
+
+impl ElementKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ElementKind::Coordinate => "coordinate",
+            ElementKind::Qualifier => "qualifier",
+            ElementKind::Data => "data",
+        }
+    }
+
+    /// Whether values of this kind describe other values rather than being measurements.
+    pub fn is_metadata(&self) -> bool {
+        !matches!(self, ElementKind::Data)
+    }
+}
+
+impl std::str::FromStr for ElementKind {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "coordinate" => Ok(ElementKind::Coordinate),
+            "qualifier" => Ok(ElementKind::Qualifier),
+            "data" => Ok(ElementKind::Data),
+            _ => Err(crate::Error::Invalid(format!(
+                "Unknown element kind: {s:?}"
+            ))),
+        }
+    }
+}
+
 /// Entry in Table C (operator descriptors).
 #[derive(Debug)]
 pub struct TableCEntry {
@@ -59,6 +152,15 @@ pub struct TableDEntry {
     pub elements: &'static [Descriptor],
 }
 
+/// Delayed replication factors defined in Table B (0 31 000 to 0 31 002).
+fn make_delayed_replication_factors() -> HashMap<XY, u8> {
+    HashMap::from([
+        (XY { x: 31, y: 0 }, 1),
+        (XY { x: 31, y: 1 }, 8),
+        (XY { x: 31, y: 2 }, 16),
+    ])
+}
+
 /// Table B (f = 0).
 fn make_table_b() -> HashMap<XY, &'static TableBEntry> {
     let mut map = HashMap::new();
@@ -85,3 +187,138 @@ fn make_table_d() -> HashMap<XY, &'static TableDEntry> {
     }
     map
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operator_name() {
+        let tables = Tables::default();
+        assert_eq!(tables.operator_name(1, 129), Some("Change data width"));
+        assert_eq!(
+            tables.operator_name(22, 0),
+            Some("Quality information follows")
+        );
+        assert_eq!(tables.operator_name(63, 0), None);
+    }
+
+    #[test]
+    fn test_delayed_replication_factors() {
+        use crate::{Error, ResolvedDescriptor, descriptor::resolve_descriptors};
+
+        let descriptors = [
+            Descriptor { f: 1, x: 1, y: 0 },
+            Descriptor { f: 0, x: 31, y: 3 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+        ];
+        let mut tables = Tables::default();
+        assert!(matches!(
+            resolve_descriptors(&tables, &descriptors),
+            Err(Error::NotSupported(_))
+        ));
+
+        // A local replication factor
+        tables
+            .delayed_replication_factors
+            .insert(XY { x: 31, y: 3 }, 8);
+        let resolved = resolve_descriptors(&tables, &descriptors).unwrap();
+        assert!(matches!(
+            resolved[..],
+            [ResolvedDescriptor::Replication {
+                y: 0,
+                delayed_bits: 8,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_sequence_cycles_and_depth() {
+        use crate::{Error, descriptor::resolve_descriptors};
+
+        // Local sequences 3 60 00N, each made of 0 01 001 and the next one
+        let mut tables = Tables::default();
+        let mut add_sequence = |y: u8, next: Option<u8>| {
+            let mut elements = vec![Descriptor { f: 0, x: 1, y: 1 }];
+            elements.extend(next.map(|y| Descriptor { f: 3, x: 60, y }));
+            let entry = Box::leak(Box::new(TableDEntry {
+                xy: XY { x: 60, y },
+                category: "",
+                title: "Local sequence",
+                sub_title: "",
+                elements: Vec::leak(elements),
+            }));
+            tables.table_d.insert(entry.xy, entry);
+        };
+        add_sequence(1, Some(2));
+        add_sequence(2, Some(3));
+        add_sequence(3, Some(1));
+        add_sequence(4, Some(5));
+        add_sequence(5, Some(6));
+        add_sequence(6, None);
+
+        let sequence = |y| [Descriptor { f: 3, x: 60, y }];
+        assert!(matches!(
+            resolve_descriptors(&tables, &sequence(1)),
+            Err(Error::Invalid(msg)) if msg.contains("3 60 001 refers to itself")
+        ));
+        // The cycle is found inside replications too
+        let replicated = [Descriptor { f: 1, x: 1, y: 2 }, sequence(2)[0]];
+        assert!(matches!(
+            resolve_descriptors(&tables, &replicated),
+            Err(Error::Invalid(msg)) if msg.contains("3 60 002 refers to itself")
+        ));
+
+        // The same sequence may appear more than once without a cycle
+        let repeated = [sequence(6)[0], sequence(6)[0]];
+        assert_eq!(resolve_descriptors(&tables, &repeated).unwrap().len(), 2);
+        assert!(resolve_descriptors(&tables, &sequence(4)).is_ok());
+        tables.max_sequence_depth = 2;
+        assert!(matches!(
+            resolve_descriptors(&tables, &sequence(4)),
+            Err(Error::Invalid(msg)) if msg.contains("3 60 006 nested deeper than 2")
+        ));
+
+        // Replications count towards the nesting depth only
+        tables.max_sequence_depth = 3;
+        tables.max_nesting_depth = 3;
+        let replicated = [Descriptor { f: 1, x: 1, y: 1 }, sequence(4)[0]];
+        assert!(resolve_descriptors(&tables, &sequence(4)).is_ok());
+        assert!(matches!(
+            resolve_descriptors(&tables, &replicated),
+            Err(Error::Invalid(msg)) if msg.contains("replications nested deeper than 3")
+        ));
+        // The lower limit wins
+        tables.max_sequence_depth = 32;
+        tables.max_nesting_depth = 2;
+        assert!(matches!(
+            resolve_descriptors(&tables, &sequence(4)),
+            Err(Error::Invalid(msg)) if msg.contains("replications nested deeper than 2")
+        ));
+    }
+
+    #[test]
+    fn test_element_kind() {
+        let tables = Tables::default();
+        let kind = |x, y| tables.table_b[&XY { x, y }].kind();
+        assert_eq!(kind(1, 1), ElementKind::Coordinate);
+        assert_eq!(kind(4, 1), ElementKind::Coordinate);
+        assert_eq!(kind(7, 30), ElementKind::Coordinate);
+        assert_eq!(kind(31, 1), ElementKind::Qualifier);
+        assert_eq!(kind(33, 7), ElementKind::Qualifier);
+        assert_eq!(kind(12, 101), ElementKind::Data);
+        assert!(!ElementKind::Data.is_metadata());
+        for k in [
+            ElementKind::Coordinate,
+            ElementKind::Qualifier,
+            ElementKind::Data,
+        ] {
+            assert_eq!(k.as_str().parse::<ElementKind>().unwrap(), k);
+        }
+    }
+}
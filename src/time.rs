@@ -0,0 +1,37 @@
+//! Date and time arithmetic shared by the exporters
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar,
+/// or `None` if the date is out of range
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> Option<i64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+/// Microseconds since the Unix epoch (UTC) of a date and the seconds within the day
+pub(crate) fn unix_micros(year: f64, month: f64, day: f64, seconds: f64) -> Option<i64> {
+    let date = days_from_civil(year as i64, month as i64, day as i64)?;
+    Some(date * 86_400_000_000 + (seconds * 1e6).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unix_micros() {
+        assert_eq!(days_from_civil(1970, 1, 1), Some(0));
+        assert_eq!(days_from_civil(2000, 3, 1), Some(11_017));
+        assert_eq!(days_from_civil(2020, 13, 1), None);
+        assert_eq!(
+            unix_micros(2020.0, 7.0, 28.0, 4.0 * 3600.0 + 0.5),
+            Some(1_595_908_800_500_000)
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Export of decoded observations as time-series samples
+//!
+//! Each measurement (a numeric value of an element outside classes 01 to 09, 31 and 33)
+//! becomes a sample in the InfluxDB line protocol or the Prometheus text exposition
+//! format, so live feeds can be pushed into time-series databases directly:
+//!
+//! - The measurement (metric) name is the element name in snake case, e.g.
+//!   `temperature_air_temperature` for 0 12 101.
+//! - The tags (labels) are the unit and the latest identification (class 01) and
+//!   vertical coordinate (class 07) values before the measurement in its subset.
+//! - The timestamp is given by the latest date and time elements (0 04 001 to 0 04 007)
+//!   before the measurement in its subset. Samples without a complete date have no
+//!   timestamp, which the databases replace with the time of ingestion.
+
+use std::io::{Read, Write};
+
+use crate::{
+    DataReader, Error, Tables, Value, XY,
+    sink::{ElementVisitor, MessageInput, OutputSink},
+    tables::ElementKind,
+    time::unix_micros,
+};
+
+/// Text format of the samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// InfluxDB line protocol (`measurement,tag=value value=1.5 <nanoseconds>`)
+    Influx,
+    /// Prometheus text exposition format (`metric{label="value"} 1.5 <milliseconds>`)
+    Prometheus,
+}
+
+impl std::str::FromStr for SampleFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "influx" => Ok(SampleFormat::Influx),
+            "prometheus" => Ok(SampleFormat::Prometheus),
+            _ => Err(Error::Invalid(format!("Unknown sample format: {s:?}"))),
+        }
+    }
+}
+
+/// Writes decoded messages as time-series samples.
+pub struct SampleWriter<W: Write> {
+    writer: W,
+    format: SampleFormat,
+}
+
+/// Tags and time of the values in a subset, updated element by element.
+#[derive(Debug, Default, Clone)]
+struct Context {
+    tags: Vec<(String, String)>,
+    /// Year, month, day, hour, minute and second (`Some(None)` if missing)
+    time: [Option<Option<f64>>; 6],
+}
+
+impl Context {
+    fn update(&mut self, key: String, xy: XY, value: &Value) {
+        match (xy.x, xy.y) {
+            (4, y @ 1..=6) => self.time[y as usize - 1] = Some(value.as_f64()),
+            (4, 7) => self.time[5] = Some(value.as_f64()),
+            (1 | 7, _) => {
+                self.tags.retain(|(k, _)| *k != key);
+                let value = match value {
+                    Value::Missing => return,
+                    Value::String(s) => s.trim().to_string(),
+                    v => format!("{v:?}"),
+                };
+                if !value.is_empty() {
+                    self.tags.push((key, value));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Microseconds since the Unix epoch, with hour, minute and second defaulting to 0
+    fn timestamp_micros(&self) -> Option<i64> {
+        let [year, month, day, hour, minute, second] = self.time;
+        let seconds = hour.unwrap_or(Some(0.0))? * 3600.0
+            + minute.unwrap_or(Some(0.0))? * 60.0
+            + second.unwrap_or(Some(0.0))?;
+        unix_micros(year??, month??, day??, seconds)
+    }
+}
+
+impl<W: Write> SampleWriter<W> {
+    pub fn new(writer: W, format: SampleFormat) -> Self {
+        Self { writer, format }
+    }
+
+    /// Writes the measurements of all subsets of a message and returns the number of
+    /// samples written. Missing and character values are left out.
+    pub fn write_message<R: Read>(
+        &mut self,
+        data_reader: &mut DataReader<'_, R>,
+        tables: &Tables,
+    ) -> Result<usize, Error> {
+        self.write_elements(tables, |f| data_reader.for_each_element(f))
+    }
+
+    /// Writes the samples of the elements visited by `for_each_element`.
+    fn write_elements(
+        &mut self,
+        tables: &Tables,
+        for_each_element: impl FnOnce(&mut ElementVisitor<'_>) -> Result<(), Error>,
+    ) -> Result<usize, Error> {
+        let mut samples = 0;
+        let mut contexts: Vec<Context> = vec![];
+        for_each_element(&mut |subset_index, _, xy, value| {
+            let Some(b) = tables.table_b.get(&xy) else {
+                return Err(Error::Table(format!(
+                    "Table B entry not found for xy: {xy:?}"
+                )));
+            };
+            if contexts.len() <= subset_index as usize {
+                contexts.resize_with(subset_index as usize + 1, Context::default);
+            }
+            let context = &mut contexts[subset_index as usize];
+            if b.kind() != ElementKind::Data {
+                context.update(snake_case(b.element_name), xy, value);
+                return Ok(());
+            }
+            let Some(value) = value.to_decimal_string() else {
+                return Ok(());
+            };
+            samples += 1;
+            self.write_sample(&snake_case(b.element_name), b.unit, context, &value)
+        })?;
+        Ok(samples)
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_sample(
+        &mut self,
+        name: &str,
+        unit: &str,
+        context: &Context,
+        value: &str,
+    ) -> Result<(), Error> {
+        let tags = std::iter::once(("unit", unit))
+            .chain(context.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        let timestamp = context.timestamp_micros();
+        match self.format {
+            SampleFormat::Influx => {
+                write!(self.writer, "{name}")?;
+                for (key, value) in tags {
+                    write!(self.writer, ",{key}={}", escape_influx(value))?;
+                }
+                write!(self.writer, " value={value}")?;
+                if let Some(micros) = timestamp {
+                    write!(self.writer, " {}", micros * 1000)?;
+                }
+            }
+            SampleFormat::Prometheus => {
+                let labels = tags
+                    .map(|(key, value)| format!("{key}=\"{}\"", escape_prometheus(value)))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                write!(self.writer, "{name}{{{labels}}} {value}")?;
+                if let Some(micros) = timestamp {
+                    write!(self.writer, " {}", micros.div_euclid(1000))?;
+                }
+            }
+        }
+        writeln!(self.writer)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> OutputSink for SampleWriter<W> {
+    type Output = W;
+
+    fn write_message(&mut self, input: &mut MessageInput<'_, '_>) -> Result<usize, Error> {
+        let tables = input.tables;
+        self.write_elements(tables, |f| input.for_each_element(f))
+    }
+
+    fn finish(self) -> Result<W, Error> {
+        SampleWriter::finish(self)
+    }
+}
+
+/// Converts an element name into a measurement, tag or label name (e.g.
+/// `Temperature/air temperature` into `temperature_air_temperature`).
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len());
+    for word in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        if word.is_empty() {
+            continue;
+        }
+        if !snake.is_empty() {
+            snake.push('_');
+        }
+        snake.push_str(&word.to_ascii_lowercase());
+    }
+    if snake.starts_with(|c: char| c.is_ascii_digit()) {
+        snake.insert(0, '_');
+    }
+    snake
+}
+
+fn escape_influx(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            ',' | '=' | ' ' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_prometheus(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::tests::make_dds;
+    use crate::{DataSpec, DataWriter, Descriptor};
+
+    fn write_samples(format: SampleFormat) -> String {
+        let tables = Tables::default();
+        let descriptors = [(1, 1), (1, 2), (4, 1), (4, 2), (4, 3), (4, 4), (4, 5)]
+            .into_iter()
+            .chain([(7, 9), (12, 101), (7, 9), (12, 101), (13, 3)])
+            .map(|(x, y)| Descriptor { f: 0, x, y })
+            .collect::<Vec<_>>();
+        let dds = make_dds(descriptors, 2);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut writer = DataWriter::new(&spec);
+        for day in [Value::Integer(28), Value::Missing] {
+            writer
+                .write_subset(&[
+                    Value::Integer(47),
+                    Value::Integer(662),
+                    Value::Integer(2020),
+                    Value::Integer(7),
+                    day,
+                    Value::Integer(4),
+                    Value::Integer(30),
+                    Value::Integer(2),
+                    Value::Decimal(29815, -2),
+                    Value::Integer(10),
+                    Value::Decimal(29715, -2),
+                    Value::Missing,
+                ])
+                .unwrap();
+        }
+        let section = writer.finish().unwrap();
+
+        let mut data_reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let mut samples = SampleWriter::new(Vec::new(), format);
+        assert_eq!(samples.write_message(&mut data_reader, &tables).unwrap(), 4);
+        String::from_utf8(samples.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_influx() {
+        let output = write_samples(SampleFormat::Influx);
+        let lines: Vec<_> = output.lines().collect();
+        let tags = "unit=K,wmo_block_number=47,wmo_station_number=662";
+        let name = "temperature_air_temperature";
+        assert_eq!(
+            lines,
+            [
+                format!("{name},{tags},geopotential_height=2 value=298.15 1595910600000000000"),
+                format!("{name},{tags},geopotential_height=10 value=297.15 1595910600000000000"),
+                format!("{name},{tags},geopotential_height=2 value=298.15"),
+                format!("{name},{tags},geopotential_height=10 value=297.15"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prometheus() {
+        let output = write_samples(SampleFormat::Prometheus);
+        assert_eq!(
+            output.lines().next().unwrap(),
+            "temperature_air_temperature{unit=\"K\",wmo_block_number=\"47\",\
+             wmo_station_number=\"662\",geopotential_height=\"2\"} \
+             298.15 1595910600000"
+        );
+    }
+
+    #[test]
+    fn test_names_and_escapes() {
+        assert_eq!(snake_case("WMO block number"), "wmo_block_number");
+        assert_eq!(snake_case("10-minute mean"), "_10_minute_mean");
+        assert_eq!(escape_influx("deg C,a=b"), "deg\\ C\\,a\\=b");
+        assert_eq!(escape_prometheus("a\"b\\"), "a\\\"b\\\\");
+        assert_eq!(
+            "prometheus".parse::<SampleFormat>().unwrap(),
+            SampleFormat::Prometheus
+        );
+        assert!("csv".parse::<SampleFormat>().is_err());
+    }
+}
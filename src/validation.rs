@@ -0,0 +1,264 @@
+//! Checks of the descriptor ordering rules in Section 3
+//!
+//! Descriptors that can still be decoded may break the ordering rules of the
+//! regulations (e.g. a replication factor not following its delayed replication,
+//! or an operator set inside a replication and cancelled outside of it). Such
+//! descriptors usually come from producer bugs, and are reported as warnings
+//! or errors depending on the [`Strictness`].
+
+use crate::{Descriptor, Error, Tables};
+
+/// How violations of the ordering rules are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// Violations are returned as warnings
+    #[default]
+    Lenient,
+    /// The first violation is returned as an error
+    Strict,
+}
+
+impl std::str::FromStr for Strictness {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lenient" => Ok(Strictness::Lenient),
+            "strict" => Ok(Strictness::Strict),
+            _ => Err(Error::Invalid(format!("Unknown strictness: {s:?}"))),
+        }
+    }
+}
+
+/// Checks the ordering rules of the descriptors of Section 3.
+///
+/// Returns the violations as warnings, or an [`Error::Invalid`] for the first
+/// violation in strict mode. The descriptors are checked as they appear in
+/// Section 3, without expanding sequences.
+pub fn check_descriptor_order(
+    descriptors: &[Descriptor],
+    tables: &Tables,
+    strictness: Strictness,
+) -> Result<Vec<String>, Error> {
+    let mut checker = Checker {
+        tables,
+        violations: vec![],
+    };
+    checker.check_range(descriptors, 0, false);
+    let mut violations = checker.violations;
+    match strictness {
+        Strictness::Strict if !violations.is_empty() => {
+            Err(Error::Invalid(violations.swap_remove(0)))
+        }
+        _ => Ok(violations),
+    }
+}
+
+struct Checker<'a> {
+    tables: &'a Tables,
+    violations: Vec<String>,
+}
+
+/// Operators that stay in effect until cancelled by YYY = 0
+fn is_cancellable_operator(desc: &Descriptor) -> bool {
+    desc.f == 2 && matches!(desc.x, 1 | 2 | 3 | 4 | 7 | 8)
+}
+
+fn describe(desc: &Descriptor) -> String {
+    format!("{} {:02} {:03}", desc.f, desc.x, desc.y)
+}
+
+impl Checker<'_> {
+    /// Checks a range of descriptors starting at `offset` in Section 3, and returns
+    /// the operators left in effect at its end.
+    fn check_range(
+        &mut self,
+        descriptors: &[Descriptor],
+        offset: usize,
+        in_replication: bool,
+    ) -> Vec<Descriptor> {
+        let mut active: Vec<Descriptor> = vec![];
+        let mut pos = 0;
+        while pos < descriptors.len() {
+            let desc = &descriptors[pos];
+            let at = offset + pos;
+            match desc.f {
+                1 => {
+                    if desc.x == 0 {
+                        self.violation(at, desc, "replicates no descriptors");
+                    }
+                    let mut start = pos + 1;
+                    if desc.y == 0 {
+                        match descriptors.get(start) {
+                            Some(factor) if self.is_replication_factor(factor) => start += 1,
+                            _ => self.violation(
+                                at,
+                                desc,
+                                "is not immediately followed by a delayed replication factor",
+                            ),
+                        }
+                    }
+                    let end = start + desc.x as usize;
+                    if end > descriptors.len() {
+                        self.violation(
+                            at,
+                            desc,
+                            "replicates more descriptors than there are in its range",
+                        );
+                        return active;
+                    }
+                    let left = self.check_range(&descriptors[start..end], offset + start, true);
+                    for op in left {
+                        self.violation(
+                            at,
+                            desc,
+                            &format!(
+                                "has the operator {} set inside and not cancelled within it",
+                                describe(&op)
+                            ),
+                        );
+                    }
+                    pos = end;
+                    continue;
+                }
+                0 if self.is_replication_factor(desc) => {
+                    self.violation(at, desc, "does not follow a delayed replication");
+                }
+                2 if is_cancellable_operator(desc) => {
+                    let set = active.iter().position(|op| op.x == desc.x);
+                    match (desc.y, set) {
+                        (0, Some(i)) => {
+                            active.remove(i);
+                        }
+                        (0, None) if in_replication => self.violation(
+                            at,
+                            desc,
+                            "cancels an operator set outside of the replication",
+                        ),
+                        (0, None) => {}
+                        // 2 03 255 terminates the definition of new reference values
+                        (255, _) if desc.x == 3 => {}
+                        (_, Some(i)) => active[i] = *desc,
+                        (_, None) => active.push(*desc),
+                    }
+                    if desc.x == 4
+                        && desc.y > 0
+                        && descriptors.get(pos + 1) != Some(&Descriptor { f: 0, x: 31, y: 21 })
+                    {
+                        self.violation(
+                            at,
+                            desc,
+                            "is not followed by the associated field significance (0 31 021)",
+                        );
+                    }
+                }
+                2 if desc.x == 6 && descriptors.get(pos + 1).is_none_or(|next| next.f != 0) => {
+                    self.violation(at, desc, "is not followed by an element descriptor");
+                }
+                _ => {}
+            }
+            pos += 1;
+        }
+        active
+    }
+
+    fn is_replication_factor(&self, desc: &Descriptor) -> bool {
+        desc.f == 0
+            && self
+                .tables
+                .delayed_replication_factors
+                .contains_key(&desc.xy())
+    }
+
+    fn violation(&mut self, at: usize, desc: &Descriptor, message: &str) {
+        self.violations
+            .push(format!("Descriptor #{at} ({}) {message}", describe(desc)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(f: u8, x: u8, y: u8) -> Descriptor {
+        Descriptor { f, x, y }
+    }
+
+    fn check(descriptors: &[Descriptor]) -> Vec<String> {
+        check_descriptor_order(descriptors, &Tables::default(), Strictness::Lenient).unwrap()
+    }
+
+    #[test]
+    fn test_valid_descriptors() {
+        let descriptors = [
+            d(3, 1, 1),
+            d(2, 1, 129),
+            d(0, 12, 101),
+            d(2, 1, 0),
+            d(1, 3, 0),
+            d(0, 31, 1),
+            d(2, 2, 129),
+            d(0, 12, 101),
+            d(2, 2, 0),
+            d(2, 4, 2),
+            d(0, 31, 21),
+            d(0, 12, 101),
+            d(2, 4, 0),
+            d(2, 6, 8),
+            d(0, 63, 255),
+        ];
+        assert!(check(&descriptors).is_empty());
+        assert!(
+            check_descriptor_order(&descriptors, &Tables::default(), Strictness::Strict).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_violations() {
+        // Replication factor outside of a delayed replication
+        assert_eq!(
+            check(&[d(0, 31, 1), d(0, 12, 101)]),
+            ["Descriptor #0 (0 31 001) does not follow a delayed replication"]
+        );
+
+        // Operator set inside a replication and cancelled outside of it
+        let descriptors = [d(1, 2, 3), d(2, 1, 129), d(0, 12, 101), d(2, 1, 0)];
+        assert_eq!(
+            check(&descriptors),
+            [
+                "Descriptor #0 (1 02 003) has the operator 2 01 129 set inside and not cancelled within it"
+            ]
+        );
+
+        // Operator set outside a replication and cancelled inside of it
+        let descriptors = [d(2, 2, 129), d(1, 2, 3), d(0, 12, 101), d(2, 2, 0)];
+        assert_eq!(
+            check(&descriptors),
+            ["Descriptor #3 (2 02 000) cancels an operator set outside of the replication"]
+        );
+
+        // Operators missing their following descriptors
+        let descriptors = [
+            d(2, 4, 2),
+            d(0, 12, 101),
+            d(2, 4, 0),
+            d(2, 6, 8),
+            d(3, 1, 1),
+        ];
+        assert_eq!(check(&descriptors).len(), 2);
+
+        // Empty and oversized replications
+        let descriptors = [d(1, 0, 2), d(1, 3, 2), d(0, 12, 101)];
+        assert_eq!(check(&descriptors).len(), 2);
+
+        // Strict mode reports the first violation as an error
+        assert!(matches!(
+            check_descriptor_order(
+                &[d(0, 31, 1), d(2, 6, 8)],
+                &Tables::default(),
+                Strictness::Strict
+            ),
+            Err(Error::Invalid(msg)) if msg.contains("#0")
+        ));
+    }
+}
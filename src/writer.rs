@@ -0,0 +1,1434 @@
+//! Writer for the data section of BUFR files
+
+use std::collections::VecDeque;
+use std::io::{self, IoSlice, Write};
+
+use bitstream_io::{BigEndian, BitWrite, BitWriter};
+
+use crate::{
+    Error, HeaderSections, Operator, ResolvedDescriptor, Value, XY,
+    codec::{PackError, effective_width_ref_scale, missing_value, pack},
+    describe_operator,
+    reader::DataSpec,
+    tables::{TableBEntry, TableCEntry},
+    unresolved_error,
+};
+
+/// A writer for encoding BUFR data sections.
+///
+/// Values of each subset are given in the order in which the descriptors are expanded.
+/// The factor of a delayed replication is given as a [`Value::Integer`] placed right
+/// before the values of the replicated descriptors.
+///
+/// For compressed data, subsets are buffered and encoded column by column in
+/// [`DataWriter::finish`], choosing the smallest increment width (NBINC) for each element.
+///
+/// Quality information blocks (2 22 000 followed by a data present bit-map) can be
+/// generated from quality information attached to the elements with
+/// [`DataWriter::write_subset_with_quality`].
+///
+/// The section is encoded in place in a single buffer, which can be reused for the next
+/// section with [`DataWriter::with_buffer`].
+pub struct DataWriter<'a> {
+    data_spec: &'a DataSpec<'a>,
+    current_subset_index: u16,
+    writer: BitWriter<Vec<u8>, BigEndian>,
+    /// Subsets waiting to be encoded in compressed form
+    pending_subsets: Vec<Vec<Value>>,
+    /// Current offset set by the "Change data width" operator
+    width_offset: i8,
+    /// Current offset set by the "Change scale" operator
+    scale_offset: i8,
+    /// Bit-maps generated for the first compressed subset, which all subsets must share
+    bitmaps: Option<Vec<Vec<u32>>>,
+}
+
+impl<'a> DataWriter<'a> {
+    pub fn new(spec: &'a DataSpec<'a>) -> DataWriter<'a> {
+        Self::with_buffer(spec, Vec::new())
+    }
+
+    /// Creates a writer encoding the section into a buffer, e.g. one returned by
+    /// [`DataWriter::finish`] for a previous message, to reuse its allocation.
+    ///
+    /// The contents of the buffer are discarded.
+    pub fn with_buffer(spec: &'a DataSpec<'a>, mut buffer: Vec<u8>) -> DataWriter<'a> {
+        buffer.clear();
+        // Section length and reserved octet, filled in by `finish`
+        buffer.extend_from_slice(&[0; 4]);
+        DataWriter {
+            data_spec: spec,
+            current_subset_index: 0,
+            writer: BitWriter::endian(buffer, BigEndian),
+            pending_subsets: Vec::new(),
+            width_offset: 0,
+            scale_offset: 0,
+            bitmaps: None,
+        }
+    }
+
+    /// Writes the values of the next subset.
+    pub fn write_subset(&mut self, values: &[Value]) -> Result<(), Error> {
+        if self.current_subset_index == self.data_spec.number_of_subsets {
+            return Err(Error::Invalid(format!(
+                "Too many subsets: expected {}",
+                self.data_spec.number_of_subsets
+            )));
+        }
+
+        if self.data_spec.is_compressed {
+            self.pending_subsets.push(values.to_vec());
+        } else {
+            let mut values = values.iter();
+            self.write_descriptors(&self.data_spec.root_descriptors, &mut values)?;
+            if values.next().is_some() {
+                return Err(Error::Invalid(format!(
+                    "Too many values for subset {}",
+                    self.current_subset_index
+                )));
+            }
+        }
+        self.current_subset_index += 1;
+        Ok(())
+    }
+
+    /// Writes the values of the next subset, generating its quality information blocks
+    /// from the quality information attached to its elements.
+    ///
+    /// `quality[i]` is the quality information (class 33) of the `i`-th element of the
+    /// subset, counted as data present bit-maps count them (delayed replication factors
+    /// are not elements). `values` are given as for [`DataWriter::write_subset`], without
+    /// the values generated for the blocks following 2 22 000:
+    ///
+    /// - the data present indicators (0 31 031) of a bit-map mark the elements with
+    ///   quality information as present,
+    /// - the class 33 elements following the bit-map take the quality information of
+    ///   the marked elements in order, and
+    /// - the factors of delayed replications of data present indicators, or of class 33
+    ///   elements, are the number of elements the bit-map refers back to, or the number
+    ///   of marked elements.
+    ///
+    /// A bit-map defined by 2 36 000 is used again by 2 37 000. In compressed data, all
+    /// subsets must have the same bit-maps.
+    pub fn write_subset_with_quality(
+        &mut self,
+        values: &[Value],
+        quality: &[Option<Value>],
+    ) -> Result<(), Error> {
+        let mut blocks = QualityBlocks::new(quality);
+        let mut given = values.iter();
+        blocks.expand(&self.data_spec.root_descriptors, &mut given)?;
+        blocks.finish_definition();
+        blocks.out.extend(given.cloned());
+
+        if self.data_spec.is_compressed {
+            match &self.bitmaps {
+                None => self.bitmaps = Some(blocks.bitmaps),
+                Some(bitmaps) if *bitmaps != blocks.bitmaps => {
+                    return Err(Error::Invalid(format!(
+                        "Data present bit-maps of compressed subset {} differ from the first subset",
+                        self.current_subset_index
+                    )));
+                }
+                Some(_) => {}
+            }
+        }
+        self.write_subset(&blocks.out)
+    }
+
+    /// Finishes writing and returns the encoded data section (Section 4).
+    pub fn finish(mut self) -> Result<Vec<u8>, Error> {
+        if self.current_subset_index != self.data_spec.number_of_subsets {
+            return Err(Error::Invalid(format!(
+                "Expected {} subsets, but {} were written",
+                self.data_spec.number_of_subsets, self.current_subset_index
+            )));
+        }
+        if self.data_spec.is_compressed {
+            let subsets = std::mem::take(&mut self.pending_subsets);
+            let mut columns: Vec<_> = subsets.iter().map(|values| values.iter()).collect();
+            self.write_compressed_descriptors(&self.data_spec.root_descriptors, &mut columns)?;
+            if columns.iter_mut().any(|values| values.next().is_some()) {
+                return Err(Error::Invalid(
+                    "Too many values for compressed subsets".to_string(),
+                ));
+            }
+        }
+        self.writer.byte_align()?;
+        let mut section = self.writer.into_writer();
+
+        let section_length = section.len();
+        if section_length > 0xffffff {
+            return Err(Error::Invalid(format!(
+                "Data section too large: {section_length} bytes"
+            )));
+        }
+        section[..3].copy_from_slice(&u32_to_three_bytes(section_length as u32));
+        Ok(section)
+    }
+
+    fn write_descriptors(
+        &mut self,
+        descriptors: &[ResolvedDescriptor<'_>],
+        values: &mut std::slice::Iter<'_, Value>,
+    ) -> Result<(), Error> {
+        for desc in descriptors {
+            match desc {
+                ResolvedDescriptor::Data(b) => {
+                    let Some(value) = values.next() else {
+                        return Err(Error::Invalid(format!(
+                            "Not enough values: missing value for {:?}",
+                            b.xy
+                        )));
+                    };
+                    self.write_data(b, value)?;
+                }
+                ResolvedDescriptor::Replication {
+                    y,
+                    descriptors,
+                    delayed_bits,
+                } => {
+                    let count = match y {
+                        0 => {
+                            let count = delayed_factor(values.next(), *delayed_bits)?;
+                            self.writer.write_var(*delayed_bits as u32, count)?;
+                            count
+                        }
+                        _ => *y as u32,
+                    };
+                    for _ in 0..count {
+                        self.write_descriptors(descriptors, values)?;
+                    }
+                }
+                ResolvedDescriptor::Operator(xy, c) => self.handle_operator_descriptor(*xy, *c)?,
+                ResolvedDescriptor::Unresolved(desc) => return Err(unresolved_error(desc)),
+                ResolvedDescriptor::Sequence(_, elements) => {
+                    self.write_descriptors(elements, values)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_compressed_descriptors(
+        &mut self,
+        descriptors: &[ResolvedDescriptor<'_>],
+        columns: &mut [std::slice::Iter<'_, Value>],
+    ) -> Result<(), Error> {
+        for desc in descriptors {
+            match desc {
+                ResolvedDescriptor::Data(b) => {
+                    let column = columns
+                        .iter_mut()
+                        .map(|values| values.next())
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| {
+                            Error::Invalid(format!(
+                                "Not enough values: missing value for {:?}",
+                                b.xy
+                            ))
+                        })?;
+                    self.write_compressed_data(b, &column)?;
+                }
+                ResolvedDescriptor::Replication {
+                    y,
+                    descriptors,
+                    delayed_bits,
+                } => {
+                    let count = match y {
+                        0 => {
+                            let counts = columns
+                                .iter_mut()
+                                .map(|values| delayed_factor(values.next(), *delayed_bits))
+                                .collect::<Result<Vec<_>, _>>()?;
+                            let count = counts.first().copied().unwrap_or(0);
+                            if counts.iter().any(|&c| c != count) {
+                                return Err(Error::NotSupported(
+                                    "Delayed replication factors varying between subsets not supported yet".to_string(),
+                                ));
+                            }
+                            // The factor itself is compressed (with no increments)
+                            self.writer.write_var(*delayed_bits as u32, count)?;
+                            self.writer.write::<6, u8>(0)?;
+                            count
+                        }
+                        _ => *y as u32,
+                    };
+                    for _ in 0..count {
+                        self.write_compressed_descriptors(descriptors, columns)?;
+                    }
+                }
+                ResolvedDescriptor::Operator(xy, c) => self.handle_operator_descriptor(*xy, *c)?,
+                ResolvedDescriptor::Unresolved(desc) => return Err(unresolved_error(desc)),
+                ResolvedDescriptor::Sequence(_, elements) => {
+                    self.write_compressed_descriptors(elements, columns)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // f = 0
+    fn write_data(&mut self, b: &TableBEntry, value: &Value) -> Result<(), Error> {
+        let (bit_width, ref_value, scale) =
+            effective_width_ref_scale(b, self.width_offset, self.scale_offset, 0, 0);
+        if b.unit == "CCITT IA5" {
+            if bit_width % 8 != 0 {
+                return Err(Error::Invalid(format!("Unsupported bit width {bit_width}")));
+            }
+            let bytes = encode_characters(b.xy, (bit_width / 8) as usize, value)?;
+            self.writer.write_bytes(&bytes)?;
+            return Ok(());
+        }
+        if bit_width > 64 {
+            return Err(Error::Invalid(format!("Unsupported bit width {bit_width}")));
+        }
+        let raw = encode_numeric(b.xy, bit_width, ref_value, scale, value)?;
+        self.writer
+            .write_var(bit_width, raw.unwrap_or(missing_value(bit_width)))?;
+        Ok(())
+    }
+
+    // f = 0 (compressed)
+    fn write_compressed_data(&mut self, b: &TableBEntry, column: &[&Value]) -> Result<(), Error> {
+        let (bit_width, ref_value, scale) =
+            effective_width_ref_scale(b, self.width_offset, self.scale_offset, 0, 0);
+        if b.unit == "CCITT IA5" {
+            if bit_width % 8 != 0 {
+                return Err(Error::Invalid(format!("Unsupported bit width {bit_width}")));
+            }
+            let len = (bit_width / 8) as usize;
+            let strings = column
+                .iter()
+                .map(|value| encode_characters(b.xy, len, value))
+                .collect::<Result<Vec<_>, _>>()?;
+            // The common string is the local reference with NBINC 0. Otherwise the
+            // local reference is all zeros and NBINC is the number of bytes.
+            if let Some(first) = strings.first()
+                && strings.iter().all(|s| s == first)
+            {
+                self.writer.write_bytes(first)?;
+                self.writer.write::<6, u8>(0)?;
+                return Ok(());
+            }
+            if len >= 64 {
+                return Err(Error::Invalid(format!(
+                    "Cannot compress differing strings of {len} bytes for {:?} (NBINC is at most 63)",
+                    b.xy
+                )));
+            }
+            self.writer.write_bytes(&vec![0; len])?;
+            self.writer.write::<6, u8>(len as u8)?;
+            for bytes in strings {
+                self.writer.write_bytes(&bytes)?;
+            }
+            return Ok(());
+        }
+        if bit_width > 64 {
+            return Err(Error::Invalid(format!("Unsupported bit width {bit_width}")));
+        }
+        let raws = column
+            .iter()
+            .map(|value| encode_numeric(b.xy, bit_width, ref_value, scale, value))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (local_ref_value, nbinc) = compress_column(bit_width, &raws);
+        if nbinc > 63 {
+            // Only possible with 64-bit elements, whose increments may need all the bits
+            return Err(Error::Invalid(format!(
+                "Increments of {:?} need {nbinc} bits, more than NBINC can describe",
+                b.xy
+            )));
+        }
+        self.writer.write_var(bit_width, local_ref_value)?;
+        self.writer.write::<6, u8>(nbinc)?;
+        if nbinc > 0 {
+            for raw in raws {
+                let inc = match raw {
+                    Some(raw) => raw - local_ref_value,
+                    None => missing_value(nbinc as u32),
+                };
+                self.writer.write_var(nbinc as u32, inc)?;
+            }
+        }
+        Ok(())
+    }
+
+    // f = 2
+    fn handle_operator_descriptor(
+        &mut self,
+        xy: XY,
+        entry: Option<&TableCEntry>,
+    ) -> Result<(), Error> {
+        match Operator::from_xy(xy) {
+            Operator::ChangeWidth(offset) => self.width_offset = offset,
+            Operator::ChangeScale(offset) => self.scale_offset = offset,
+            // Applies to the immediately following local descriptor
+            Operator::SignifyWidth(_) => {}
+            // Quality information blocks are written as ordinary elements
+            Operator::BitmapFollows(22)
+            | Operator::CancelBackwardReference
+            | Operator::DefineBitmap
+            | Operator::UseDefinedBitmap
+            | Operator::CancelDefinedBitmap => {}
+            // Not supported
+            _ => {
+                return Err(Error::NotSupported(format!(
+                    "Operator descriptor {} not supported yet.",
+                    describe_operator(xy, entry)
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validates the factor of a delayed replication.
+fn delayed_factor(value: Option<&Value>, delayed_bits: u8) -> Result<u32, Error> {
+    let count = match value {
+        Some(&Value::Integer(count)) if count >= 0 => count as u32,
+        Some(value) => {
+            return Err(Error::Invalid(format!(
+                "Invalid delayed replication factor: {value:?}"
+            )));
+        }
+        None => {
+            return Err(Error::Invalid(
+                "Not enough values: missing delayed replication factor".to_string(),
+            ));
+        }
+    };
+    if count as u64 >= (1u64 << delayed_bits) {
+        return Err(Error::Invalid(format!(
+            "Delayed replication factor {count} does not fit in {delayed_bits} bits"
+        )));
+    }
+    Ok(count)
+}
+
+/// Chooses the local reference value and the smallest increment width (NBINC)
+/// for a column of raw values, where `None` is a missing value.
+///
+/// When some values are missing, the all-ones increment is reserved for them.
+fn compress_column(bit_width: u32, raws: &[Option<u64>]) -> (u64, u8) {
+    let present = raws.iter().flatten();
+    let (Some(&min), Some(&max)) = (present.clone().min(), present.max()) else {
+        // All values are missing
+        return (missing_value(bit_width), 0);
+    };
+    let has_missing = raws.iter().any(Option::is_none);
+    if min == max && !has_missing {
+        // All values are the same
+        return (min, 0);
+    }
+    let range = (max - min) + has_missing as u64;
+    (min, (64 - range.leading_zeros()) as u8)
+}
+
+/// Values of a subset with the generated values of its quality information blocks (see
+/// [`DataWriter::write_subset_with_quality`]), following the bit-maps as
+/// [`DataReader`](crate::DataReader) reads them.
+struct QualityBlocks<'q> {
+    quality: &'q [Option<Value>],
+    /// Values of the subset, including the generated ones
+    out: Vec<Value>,
+    /// Number of elements written, which bit-maps refer back to
+    num_elements: u32,
+    /// Number of elements preceding the first 2 22 000, until 2 35 000
+    back_reference: Option<u32>,
+    /// Indices in `out` of the data present indicators of the bit-map being generated
+    defining: Option<Vec<usize>>,
+    /// Whether the bit-map being generated is kept for reuse (2 36 000)
+    define_for_reuse: bool,
+    /// Bit-map kept for reuse by 2 37 000
+    reusable: Option<Vec<u32>>,
+    /// Positions of the elements whose quality information remains to be written
+    targets: VecDeque<u32>,
+    /// Whether a bit-map has been generated or used for the current quality information
+    in_block: bool,
+    /// Positions of the elements marked by each bit-map generated
+    bitmaps: Vec<Vec<u32>>,
+}
+
+impl<'q> QualityBlocks<'q> {
+    fn new(quality: &'q [Option<Value>]) -> Self {
+        Self {
+            quality,
+            out: Vec::new(),
+            num_elements: 0,
+            back_reference: None,
+            defining: None,
+            define_for_reuse: false,
+            reusable: None,
+            targets: VecDeque::new(),
+            in_block: false,
+            bitmaps: Vec::new(),
+        }
+    }
+
+    fn expand(
+        &mut self,
+        descriptors: &[ResolvedDescriptor<'_>],
+        values: &mut std::slice::Iter<'_, Value>,
+    ) -> Result<(), Error> {
+        for desc in descriptors {
+            match desc {
+                ResolvedDescriptor::Data(b) => {
+                    self.num_elements += 1;
+                    if b.xy == DATA_PRESENT_INDICATOR
+                        && let Some(indices) = &mut self.defining
+                    {
+                        // Filled in once the length of the bit-map is known
+                        indices.push(self.out.len());
+                        self.out.push(Value::Missing);
+                        continue;
+                    }
+                    self.finish_definition();
+                    if b.xy.x == 33
+                        && let Some(position) = self.targets.pop_front()
+                    {
+                        let value = self.quality.get(position as usize).cloned().flatten();
+                        self.out.push(value.unwrap_or(Value::Missing));
+                        continue;
+                    }
+                    let Some(value) = values.next() else {
+                        return Err(Error::Invalid(format!(
+                            "Not enough values: missing value for {:?}",
+                            b.xy
+                        )));
+                    };
+                    self.out.push(value.clone());
+                }
+                ResolvedDescriptor::Replication { y, descriptors, .. } => {
+                    let count = match y {
+                        0 => {
+                            let count = match self.generated_factor(descriptors) {
+                                Some(count) => count,
+                                // Checked against the width of the factor when written
+                                None => delayed_factor(values.next(), 32)?,
+                            };
+                            self.out.push(Value::Integer(count as i32));
+                            count
+                        }
+                        _ => *y as u32,
+                    };
+                    for _ in 0..count {
+                        self.expand(descriptors, values)?;
+                    }
+                }
+                ResolvedDescriptor::Operator(xy, c) => match Operator::from_xy(*xy) {
+                    Operator::BitmapFollows(22) => {
+                        self.finish_definition();
+                        self.targets.clear();
+                        self.in_block = false;
+                        self.back_reference.get_or_insert(self.num_elements);
+                        self.defining = Some(vec![]);
+                    }
+                    Operator::CancelBackwardReference => {
+                        self.finish_definition();
+                        self.back_reference = None;
+                        self.targets.clear();
+                        self.in_block = false;
+                        self.reusable = None;
+                    }
+                    Operator::DefineBitmap => self.define_for_reuse = true,
+                    Operator::UseDefinedBitmap => {
+                        let Some(targets) = self.reusable.clone() else {
+                            return Err(Error::Invalid(
+                                "2 37 000 without a preceding 2 36 000".to_string(),
+                            ));
+                        };
+                        self.defining = None;
+                        self.targets = targets.into();
+                        self.in_block = true;
+                    }
+                    Operator::CancelDefinedBitmap => self.reusable = None,
+                    Operator::BitmapFollows(_) | Operator::MarkerOperator(_) => {
+                        return Err(Error::NotSupported(format!(
+                            "Operator descriptor {} not supported yet.",
+                            describe_operator(*xy, *c)
+                        )));
+                    }
+                    _ => {}
+                },
+                ResolvedDescriptor::Unresolved(desc) => return Err(unresolved_error(desc)),
+                ResolvedDescriptor::Sequence(_, elements) => self.expand(elements, values)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the factor of a delayed replication of data present indicators (the
+    /// number of elements the bit-map refers back to) or of class 33 elements following
+    /// a bit-map (the number of marked elements remaining), if it is generated.
+    fn generated_factor(&mut self, descriptors: &[ResolvedDescriptor<'_>]) -> Option<u32> {
+        let all = |predicate: fn(XY) -> bool| {
+            descriptors
+                .iter()
+                .all(|d| matches!(d, ResolvedDescriptor::Data(b) if predicate(b.xy)))
+        };
+        if self.defining.is_some() && all(|xy| xy == DATA_PRESENT_INDICATOR) {
+            return self.back_reference;
+        }
+        self.finish_definition();
+        (self.in_block && all(|xy| xy.x == 33)).then_some(self.targets.len() as u32)
+    }
+
+    /// Fills in the data present indicators of the bit-map being generated, marking the
+    /// elements with quality information.
+    fn finish_definition(&mut self) {
+        let Some(indices) = self.defining.take() else {
+            return;
+        };
+        let start = self
+            .back_reference
+            .unwrap_or(0)
+            .saturating_sub(indices.len() as u32);
+        let mut targets = vec![];
+        for (position, index) in (start..).zip(indices) {
+            let present = matches!(self.quality.get(position as usize), Some(Some(_)));
+            if present {
+                targets.push(position);
+                // 0 means the data is present, and all ones (missing) that it is not
+                self.out[index] = Value::Integer(0);
+            }
+        }
+        if self.define_for_reuse {
+            self.define_for_reuse = false;
+            self.reusable = Some(targets.clone());
+        }
+        self.bitmaps.push(targets.clone());
+        self.targets = targets.into();
+        self.in_block = true;
+    }
+}
+
+/// Data present indicator (0 31 031), the bits of data present bit-maps
+const DATA_PRESENT_INDICATOR: XY = XY { x: 31, y: 31 };
+
+/// Writes a complete BUFR message from its header sections and an encoded data section.
+///
+/// The header sections are written as they are, including the optional section
+/// (Section 2), so a message can be re-emitted after decoding it with [`HeaderSections::read`].
+///
+/// The data section is not copied: it is written together with the encoded header
+/// sections using vectored writes.
+pub fn write_message<W: Write>(
+    mut writer: W,
+    header: &HeaderSections,
+    data_section: &[u8],
+) -> Result<(), Error> {
+    write_message_with(&mut writer, header, data_section, &mut Vec::new())
+}
+
+/// Appends a complete BUFR message to a buffer, as written by [`write_message`].
+///
+/// Encoding messages one after another into the same buffer avoids allocating for
+/// each of them. The buffer is left as it was if the message cannot be encoded.
+pub fn encode_message_into(
+    out: &mut Vec<u8>,
+    header: &HeaderSections,
+    data_section: &[u8],
+) -> Result<(), Error> {
+    let padding = data_section_padding(header, data_section)?;
+    let data_section_length = (data_section.len() + padding) as u32;
+    header.encode_into(out, data_section_length)?;
+    out.extend_from_slice(&u32_to_three_bytes(data_section_length));
+    out.extend_from_slice(&data_section[3..]);
+    out.extend_from_slice(&[0][..padding]);
+    out.extend_from_slice(b"7777");
+    Ok(())
+}
+
+/// Writes a message, encoding its header sections into a reusable buffer.
+fn write_message_with<W: Write>(
+    writer: &mut W,
+    header: &HeaderSections,
+    data_section: &[u8],
+    buffer: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let padding = data_section_padding(header, data_section)?;
+    let data_section_length = (data_section.len() + padding) as u32;
+    buffer.clear();
+    header.encode_into(buffer, data_section_length)?;
+    let length = u32_to_three_bytes(data_section_length);
+    write_all_vectored(
+        writer,
+        &mut [
+            IoSlice::new(buffer),
+            IoSlice::new(&length),
+            IoSlice::new(&data_section[3..]),
+            IoSlice::new(&[0][..padding]),
+            IoSlice::new(b"7777"),
+        ],
+    )
+}
+
+/// Returns the number of octets padding the data section to an even length, if the
+/// edition requires it for every section.
+fn data_section_padding(header: &HeaderSections, data_section: &[u8]) -> Result<usize, Error> {
+    if data_section.len() < 4 {
+        return Err(Error::Invalid(
+            "Data section length must be >= 4".to_string(),
+        ));
+    }
+    match crate::sections::pads_sections(header.indicator_section.edition_number) {
+        true => Ok(data_section.len() % 2),
+        false => Ok(0),
+    }
+}
+
+/// Writes all the buffers, as `Write::write_all_vectored` (not stable yet) does.
+fn write_all_vectored<W: Write>(writer: &mut W, mut bufs: &mut [IoSlice<'_>]) -> Result<(), Error> {
+    // Skips empty buffers
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        match writer.write_vectored(bufs) {
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero).into()),
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Writer appending complete BUFR messages to a single stream.
+///
+/// This produces the common layout of BUFR files on the GTS, where messages are
+/// simply concatenated. Each message carries its own total length.
+///
+/// The header sections of each message are encoded into a buffer kept across messages,
+/// and written with the data section using vectored writes.
+pub struct MessageWriter<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+    messages_written: usize,
+}
+
+impl<W: Write> MessageWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffer: Vec::new(),
+            messages_written: 0,
+        }
+    }
+
+    /// Appends a message built from header sections and an encoded data section.
+    pub fn write_message(
+        &mut self,
+        header: &HeaderSections,
+        data_section: &[u8],
+    ) -> Result<(), Error> {
+        write_message_with(&mut self.writer, header, data_section, &mut self.buffer)?;
+        self.messages_written += 1;
+        Ok(())
+    }
+
+    /// Returns the number of messages written so far.
+    pub fn messages_written(&self) -> usize {
+        self.messages_written
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Encodes a CCITT IA5 value into `len` bytes.
+///
+/// Strings are padded with spaces or truncated to fit, and missing values are
+/// encoded as all ones. Bytes (see [`Value::Bytes`]) are written as they are.
+fn encode_characters(xy: XY, len: usize, value: &Value) -> Result<Vec<u8>, Error> {
+    match value {
+        Value::Missing => Ok(vec![0xff; len]),
+        Value::String(s) => {
+            if !s.is_ascii() {
+                return Err(Error::Invalid(format!(
+                    "Value of {xy:?} contains non-IA5 characters: {s:?}"
+                )));
+            }
+            let mut bytes = s.as_bytes().to_vec();
+            bytes.resize(len, b' ');
+            Ok(bytes)
+        }
+        Value::Bytes(bytes) => {
+            let mut bytes = bytes.clone();
+            bytes.resize(len, b' ');
+            Ok(bytes)
+        }
+        _ => Err(Error::Invalid(format!(
+            "Cannot encode a non-string value as the character element {xy:?}"
+        ))),
+    }
+}
+
+/// Encodes a numeric value into its raw bits, or `None` if it is missing.
+///
+/// Values needing more precision than the (effective) scale or wider than the
+/// (effective) bit width can be encoded by adding "Change scale" (2 02 YYY) or
+/// "Change data width" (2 01 YYY) operators to the data description.
+fn encode_numeric(
+    xy: XY,
+    bit_width: u32,
+    ref_value: i32,
+    scale: i8,
+    value: &Value,
+) -> Result<Option<u64>, Error> {
+    pack(value, bit_width, ref_value, scale).map_err(|e| {
+        Error::Invalid(match e {
+            PackError::NotNumeric => {
+                format!("Cannot encode a string as the numeric element {xy:?}")
+            }
+            PackError::Precision => {
+                format!("Value of {xy:?} needs more precision than scale {scale} (use 2 02 YYY)")
+            }
+            PackError::Overflow => {
+                format!("Value of {xy:?} does not fit in {bit_width} bits (use 2 01 YYY)")
+            }
+        })
+    })
+}
+
+pub(crate) fn u32_to_three_bytes(value: u32) -> [u8; 3] {
+    [(value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::tests::make_dds;
+    use crate::{DataEvent, DataReader, Descriptor, Tables};
+
+    fn read_values(section: &[u8], spec: &DataSpec) -> Vec<Value> {
+        let mut reader = DataReader::new(section, spec).unwrap();
+        let mut values = vec![];
+        loop {
+            match reader.read_event().unwrap() {
+                DataEvent::Data { value, .. } => values.push(value),
+                DataEvent::ReplicationStart { count, .. } => {
+                    values.push(Value::Integer(count as i32))
+                }
+                DataEvent::Eof => break,
+                _ => {}
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 0, x: 1, y: 1 },
+                Descriptor { f: 0, x: 7, y: 30 },
+                Descriptor { f: 1, x: 1, y: 0 },
+                Descriptor { f: 0, x: 31, y: 1 },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+            ],
+            2,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let subsets = [
+            vec![
+                Value::Integer(47),
+                Value::Decimal(-105, -1),
+                Value::Integer(2),
+                Value::Decimal(27315, -2),
+                Value::Missing,
+            ],
+            vec![Value::Missing, Value::Decimal(123, 0), Value::Integer(0)],
+        ];
+
+        let mut writer = DataWriter::new(&spec);
+        for subset in &subsets {
+            writer.write_subset(subset).unwrap();
+        }
+        let section = writer.finish().unwrap();
+        assert_eq!(three_bytes(&section), section.len() as u32);
+
+        // Reusing the buffer of the previous section
+        let buffer = section.clone();
+        let ptr = buffer.as_ptr();
+        let mut writer = DataWriter::with_buffer(&spec, buffer);
+        for subset in &subsets {
+            writer.write_subset(subset).unwrap();
+        }
+        let buffer = writer.finish().unwrap();
+        assert_eq!(buffer, section);
+        assert_eq!(buffer.as_ptr(), ptr);
+
+        let values = read_values(&section, &spec);
+        assert_eq!(
+            values,
+            [
+                Value::Integer(47),
+                Value::Decimal(-105, -1),
+                Value::Integer(2),
+                Value::Decimal(27315, -2),
+                Value::Missing,
+                Value::Missing,
+                Value::Decimal(1230, -1),
+                Value::Integer(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_width_and_scale_operators() {
+        let tables = Tables::default();
+        let descriptors = vec![
+            Descriptor { f: 2, x: 1, y: 131 },
+            Descriptor { f: 2, x: 2, y: 129 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+            Descriptor { f: 2, x: 2, y: 0 },
+            Descriptor { f: 2, x: 1, y: 0 },
+            Descriptor { f: 0, x: 1, y: 1 },
+        ];
+        let dds = make_dds(descriptors, 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+
+        let mut writer = DataWriter::new(&spec);
+        writer
+            .write_subset(&[Value::Decimal(273155, -3), Value::Integer(127)])
+            .unwrap_err();
+
+        let mut writer = DataWriter::new(&spec);
+        writer
+            .write_subset(&[Value::Decimal(273155, -3), Value::Integer(126)])
+            .unwrap();
+        let section = writer.finish().unwrap();
+        assert_eq!(
+            read_values(&section, &spec),
+            [Value::Decimal(273155, -3), Value::Integer(126),]
+        );
+    }
+
+    #[test]
+    fn test_overflow_is_error() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            }],
+            1,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+
+        // Too wide for 16 bits
+        let mut writer = DataWriter::new(&spec);
+        assert!(matches!(
+            writer.write_subset(&[Value::Decimal(70000, -2)]),
+            Err(Error::Invalid(_))
+        ));
+        // Too precise for scale 2
+        let mut writer = DataWriter::new(&spec);
+        assert!(matches!(
+            writer.write_subset(&[Value::Decimal(273155, -3)]),
+            Err(Error::Invalid(_))
+        ));
+        // Below the reference value
+        let mut writer = DataWriter::new(&spec);
+        assert!(matches!(
+            writer.write_subset(&[Value::Integer(-1)]),
+            Err(Error::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_characters() {
+        let tables = Tables::default();
+        let dds = make_dds(
+            vec![
+                Descriptor { f: 0, x: 1, y: 1 },
+                Descriptor { f: 0, x: 1, y: 15 },
+            ],
+            3,
+        );
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+
+        let mut writer = DataWriter::new(&spec);
+        writer
+            .write_subset(&[Value::Integer(47), Value::String("Tokyo".to_string())])
+            .unwrap();
+        writer
+            .write_subset(&[
+                Value::Integer(47),
+                Value::String("A very long station name".to_string()),
+            ])
+            .unwrap();
+        writer
+            .write_subset(&[Value::Integer(47), Value::Missing])
+            .unwrap();
+        let section = writer.finish().unwrap();
+        assert_eq!(
+            read_values(&section, &spec),
+            [
+                Value::Integer(47),
+                Value::String("Tokyo               ".to_string()),
+                Value::Integer(47),
+                Value::String("A very long station ".to_string()),
+                Value::Integer(47),
+                Value::Missing,
+            ]
+        );
+
+        let mut writer = DataWriter::new(&spec);
+        assert!(matches!(
+            writer.write_subset(&[Value::Integer(47), Value::String("東京".to_string())]),
+            Err(Error::Invalid(_))
+        ));
+        let mut writer = DataWriter::new(&spec);
+        assert!(matches!(
+            writer.write_subset(&[Value::Integer(47), Value::Integer(1)]),
+            Err(Error::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_compress_column() {
+        // All the same
+        assert_eq!(compress_column(16, &[Some(5), Some(5)]), (5, 0));
+        // All missing
+        assert_eq!(compress_column(16, &[None, None]), (0xffff, 0));
+        // Increments 0..=3 need 2 bits
+        assert_eq!(
+            compress_column(16, &[Some(100), Some(103), Some(101)]),
+            (100, 2)
+        );
+        // All-ones increment is reserved for missing values
+        assert_eq!(compress_column(16, &[Some(100), None, Some(103)]), (100, 3));
+        assert_eq!(compress_column(16, &[Some(100), None]), (100, 1));
+        // Increments of 64-bit elements may need 64 bits
+        assert_eq!(
+            compress_column(64, &[Some(0), Some(1 << 63), Some(1)]),
+            (0, 64)
+        );
+        assert_eq!(compress_column(64, &[Some(1 << 63), None]), (1 << 63, 1));
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let tables = Tables::default();
+        let mut dds = make_dds(
+            vec![
+                Descriptor { f: 0, x: 1, y: 1 },
+                Descriptor { f: 0, x: 7, y: 30 },
+                Descriptor { f: 1, x: 1, y: 0 },
+                Descriptor { f: 0, x: 31, y: 1 },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+            ],
+            3,
+        );
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let subsets = [
+            vec![
+                Value::Integer(47),
+                Value::Decimal(-105, -1),
+                Value::Integer(2),
+                Value::Decimal(27315, -2),
+                Value::Missing,
+            ],
+            vec![
+                Value::Integer(47),
+                Value::Missing,
+                Value::Integer(2),
+                Value::Decimal(27415, -2),
+                Value::Missing,
+            ],
+            vec![
+                Value::Integer(47),
+                Value::Decimal(30, 0),
+                Value::Integer(2),
+                Value::Decimal(27215, -2),
+                Value::Missing,
+            ],
+        ];
+
+        let mut writer = DataWriter::new(&spec);
+        for subset in &subsets {
+            writer.write_subset(subset).unwrap();
+        }
+        let section = writer.finish().unwrap();
+
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let mut columns = vec![];
+        loop {
+            match reader.read_event().unwrap() {
+                DataEvent::CompressedData { values, .. } => columns.push(values),
+                DataEvent::ReplicationStart { count, .. } => assert_eq!(count, 2),
+                DataEvent::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            columns,
+            [
+                vec![Value::Integer(47); 3],
+                vec![
+                    Value::Decimal(-105, -1),
+                    Value::Missing,
+                    Value::Decimal(300, -1),
+                ],
+                vec![
+                    Value::Decimal(27315, -2),
+                    Value::Decimal(27415, -2),
+                    Value::Decimal(27215, -2),
+                ],
+                vec![Value::Missing; 3],
+            ]
+        );
+
+        // Different delayed replication factors are rejected
+        let mut writer = DataWriter::new(&spec);
+        writer.write_subset(&subsets[0]).unwrap();
+        writer.write_subset(&subsets[1]).unwrap();
+        writer
+            .write_subset(&[
+                Value::Integer(47),
+                Value::Missing,
+                Value::Integer(1),
+                Value::Missing,
+            ])
+            .unwrap();
+        assert!(matches!(writer.finish(), Err(Error::NotSupported(_))));
+    }
+
+    #[test]
+    fn test_compressed_characters() {
+        let tables = Tables::default();
+        let mut dds = make_dds(
+            vec![
+                Descriptor { f: 0, x: 1, y: 15 },
+                Descriptor { f: 0, x: 1, y: 15 },
+                Descriptor { f: 0, x: 1, y: 15 },
+            ],
+            3,
+        );
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let subsets = [
+            ["Tokyo", "Sapporo", "Naha"],
+            ["Tokyo", "", "Naha"],
+            ["Tokyo", "Osaka", "Naha"],
+        ];
+
+        let mut writer = DataWriter::new(&spec);
+        for subset in subsets {
+            let values = subset.map(|s| match s {
+                "" => Value::Missing,
+                s => Value::String(s.to_string()),
+            });
+            writer.write_subset(&values).unwrap();
+        }
+        let section = writer.finish().unwrap();
+        // The same strings: a 20-byte local reference and NBINC 0
+        // The differing strings: a zero local reference, NBINC 20 and 3 x 20 bytes
+        assert_eq!(
+            section.len(),
+            4 + (2 * (160 + 6) + 160 + 6 + 3 * 160usize).div_ceil(8)
+        );
+
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        let mut columns = vec![];
+        loop {
+            match reader.read_event().unwrap() {
+                DataEvent::CompressedData { values, .. } => columns.push(values),
+                DataEvent::Eof => break,
+                _ => {}
+            }
+        }
+        let padded = |s: &str| Value::String(format!("{s:20}"));
+        assert_eq!(
+            columns,
+            [
+                vec![padded("Tokyo"); 3],
+                vec![padded("Sapporo"), Value::Missing, padded("Osaka")],
+                vec![padded("Naha"); 3],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wide_elements() {
+        let tables = Tables::default();
+        // 0 12 101 widened from 16 to 40 bits
+        let descriptors = vec![
+            Descriptor { f: 2, x: 1, y: 152 },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+            Descriptor { f: 2, x: 1, y: 0 },
+        ];
+        let values = [
+            Value::Decimal64(1_000_000_000_000, -2),
+            Value::Missing,
+            Value::Decimal(27315, -2),
+        ];
+        let expected = [
+            Value::Decimal64(1_000_000_000_000, -2),
+            Value::Missing,
+            Value::Decimal64(27315, -2),
+        ];
+
+        let dds = make_dds(descriptors.clone(), 3);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut writer = DataWriter::new(&spec);
+        for value in &values {
+            writer.write_subset(std::slice::from_ref(value)).unwrap();
+        }
+        let section = writer.finish().unwrap();
+        assert_eq!(section.len(), 4 + 5 * 3);
+        assert_eq!(read_values(&section, &spec), expected);
+
+        let mut dds = make_dds(descriptors, 3);
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut writer = DataWriter::new(&spec);
+        for value in &values {
+            writer.write_subset(std::slice::from_ref(value)).unwrap();
+        }
+        let section = writer.finish().unwrap();
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        loop {
+            match reader.read_event().unwrap() {
+                DataEvent::CompressedData { values, .. } => assert_eq!(values, expected),
+                DataEvent::Eof => break,
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_compressed_64_bit_elements() {
+        let tables = Tables::default();
+        // 0 12 101 widened from 16 to 64 bits
+        let mut dds = make_dds(
+            vec![
+                Descriptor { f: 2, x: 1, y: 176 },
+                Descriptor {
+                    f: 0,
+                    x: 12,
+                    y: 101,
+                },
+                Descriptor { f: 2, x: 1, y: 0 },
+            ],
+            3,
+        );
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let write = |values: &[Value]| {
+            let mut writer = DataWriter::new(&spec);
+            for value in values {
+                writer.write_subset(std::slice::from_ref(value))?;
+            }
+            writer.finish()
+        };
+
+        let values = [
+            Value::Decimal64(i64::MAX, -2),
+            Value::Missing,
+            Value::Decimal64(i64::MAX - 1, -2),
+        ];
+        let section = write(&values).unwrap();
+        let mut reader = DataReader::new(section.as_slice(), &spec).unwrap();
+        loop {
+            match reader.read_event().unwrap() {
+                DataEvent::CompressedData { values: read, .. } => assert_eq!(read, values),
+                DataEvent::Eof => break,
+                _ => {}
+            }
+        }
+
+        // Increments from 0 to 2^63 and the missing value need 64 bits
+        let values = [
+            Value::Decimal64(0, -2),
+            Value::Decimal64(i64::MAX, -2),
+            Value::Missing,
+        ];
+        assert!(matches!(write(&values), Err(Error::Invalid(_))));
+    }
+
+    /// A template with two quality information blocks, the second one using the bit-map
+    /// of the first.
+    fn quality_template() -> Vec<Descriptor> {
+        let quality = Descriptor { f: 0, x: 33, y: 7 };
+        vec![
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 101,
+            },
+            Descriptor {
+                f: 0,
+                x: 12,
+                y: 103,
+            },
+            Descriptor { f: 2, x: 22, y: 0 },
+            Descriptor { f: 2, x: 36, y: 0 },
+            Descriptor { f: 1, x: 1, y: 2 },
+            Descriptor { f: 0, x: 31, y: 31 },
+            Descriptor { f: 1, x: 1, y: 0 },
+            Descriptor { f: 0, x: 31, y: 1 },
+            quality,
+            Descriptor { f: 2, x: 22, y: 0 },
+            Descriptor { f: 2, x: 37, y: 0 },
+            quality,
+        ]
+    }
+
+    /// Reads the bit-map targets, and the values with their descriptors.
+    fn read_quality_events(section: &[u8], spec: &DataSpec) -> Vec<String> {
+        let mut reader = DataReader::new(section, spec).unwrap();
+        let mut events = vec![];
+        loop {
+            match reader.read_event().unwrap() {
+                DataEvent::Data { xy, value, .. } => events.push(format!("{xy:?} {value:?}")),
+                DataEvent::CompressedData { xy, values, .. } => {
+                    events.push(format!("{xy:?} {values:?}"))
+                }
+                DataEvent::BitmapTarget { position, .. } => {
+                    events.push(format!("target {position}"))
+                }
+                DataEvent::Eof => break,
+                _ => {}
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn test_quality_blocks() {
+        let tables = Tables::default();
+        let dds = make_dds(quality_template(), 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let values = [Value::Decimal(27315, -2), Value::Decimal(27015, -2)];
+        // Only the dew-point temperature has quality information
+        let quality = [None, Some(Value::Integer(70))];
+
+        let mut writer = DataWriter::new(&spec);
+        writer.write_subset_with_quality(&values, &quality).unwrap();
+        let section = writer.finish().unwrap();
+        let events = read_quality_events(&section, &spec);
+        assert_eq!(
+            events[2..],
+            [
+                "XY { x: 31, y: 31 } Missing",
+                "XY { x: 31, y: 31 } 0",
+                "target 1",
+                "XY { x: 33, y: 7 } 70",
+                "target 1",
+                "XY { x: 33, y: 7 } 70",
+            ]
+        );
+
+        // The same values given explicitly (with the factor of the delayed replication)
+        let mut writer = DataWriter::new(&spec);
+        writer
+            .write_subset(&[
+                values[0].clone(),
+                values[1].clone(),
+                Value::Missing,
+                Value::Integer(0),
+                Value::Integer(1),
+                Value::Integer(70),
+                Value::Integer(70),
+            ])
+            .unwrap();
+        assert_eq!(writer.finish().unwrap(), section);
+
+        // Without quality information, no element is marked, and the class 33 element
+        // after 2 37 000 is an ordinary element
+        let mut writer = DataWriter::new(&spec);
+        let mut unmarked = values.to_vec();
+        unmarked.push(Value::Missing);
+        writer.write_subset_with_quality(&unmarked, &[]).unwrap();
+        let events = read_quality_events(&writer.finish().unwrap(), &spec);
+        assert_eq!(
+            events[2..],
+            [
+                "XY { x: 31, y: 31 } Missing",
+                "XY { x: 31, y: 31 } Missing",
+                "XY { x: 33, y: 7 } Missing",
+            ]
+        );
+
+        // 2 37 000 needs a bit-map defined by 2 36 000
+        let mut descriptors = quality_template();
+        descriptors.remove(3);
+        let dds = make_dds(descriptors, 1);
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let mut writer = DataWriter::new(&spec);
+        assert!(matches!(
+            writer.write_subset_with_quality(&values, &quality),
+            Err(Error::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_compressed_quality_blocks() {
+        let tables = Tables::default();
+        let mut dds = make_dds(quality_template(), 2);
+        dds.flags.is_compressed = true;
+        let spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+
+        let mut writer = DataWriter::new(&spec);
+        let subsets = [(27315, Value::Integer(70)), (27215, Value::Integer(100))];
+        for (temperature, quality) in subsets {
+            writer
+                .write_subset_with_quality(
+                    &[Value::Decimal(temperature, -2), Value::Decimal(27015, -2)],
+                    &[Some(quality), None],
+                )
+                .unwrap();
+        }
+        let events = read_quality_events(&writer.finish().unwrap(), &spec);
+        assert_eq!(
+            events[2..],
+            [
+                "XY { x: 31, y: 31 } [0, 0]",
+                "XY { x: 31, y: 31 } [Missing, Missing]",
+                "target 0",
+                "XY { x: 33, y: 7 } [70, 100]",
+                "target 0",
+                "XY { x: 33, y: 7 } [70, 100]",
+            ]
+        );
+
+        // The bit-maps cannot differ between subsets
+        let mut writer = DataWriter::new(&spec);
+        let values = [Value::Decimal(27315, -2), Value::Decimal(27015, -2)];
+        writer
+            .write_subset_with_quality(&values, &[Some(Value::Integer(70))])
+            .unwrap();
+        assert!(matches!(
+            writer.write_subset_with_quality(&values, &[None, Some(Value::Integer(70))]),
+            Err(Error::Invalid(_))
+        ));
+    }
+
+    fn three_bytes(bytes: &[u8]) -> u32 {
+        crate::reader::three_bytes_to_u32([bytes[0], bytes[1], bytes[2]])
+    }
+}
@@ -0,0 +1,972 @@
+use std::fs;
+use std::io::{BufRead, BufReader};
+
+use arrow::array::{Array, Float64Array, StringArray, UInt32Array};
+use tinybufr::arrow::{
+    ConvertOptions, TIMESTAMP_COLUMN, add_timestamp_column, convert_to_arrow,
+    convert_to_arrow_columnar, convert_to_arrow_long, convert_to_arrow_partial,
+    convert_to_arrow_with_options, convert_to_arrow_with_recovery, coordinates_first, element_kind,
+    flatten_struct_columns,
+};
+use tinybufr::tables::ElementKind;
+use tinybufr::{DataReader, DataSpec, DataWriter, Descriptor, HeaderSections, Tables, Value, XY};
+
+#[test]
+fn test_provenance_columns() {
+    let filename = "./tests/testdata/jma/ISTC82.dat";
+    let mut tables = Tables::default();
+    tinybufr::tables::local::jma::install_jma_descriptors(&mut tables);
+    let mut reader = BufReader::new(fs::File::open(filename).unwrap());
+    skip_local_header(&mut reader);
+    let header = HeaderSections::read(&mut reader).unwrap();
+    let data_spec =
+        DataSpec::from_data_description(&header.data_description_section, &tables).unwrap();
+    let mut data_reader = DataReader::new(&mut reader, &data_spec).unwrap();
+
+    let options = ConvertOptions {
+        provenance_columns: true,
+        source_file: Some(filename.to_string()),
+        message_index: 3,
+        ..Default::default()
+    };
+    let record_batch =
+        convert_to_arrow_with_options(&mut data_reader, &tables, &data_spec, &options).unwrap();
+    let num_rows = data_spec.number_of_subsets as usize;
+    assert_eq!(record_batch.num_rows(), num_rows);
+
+    let schema = record_batch.schema();
+    let names: Vec<_> = schema.fields().iter().take(3).map(|f| f.name()).collect();
+    assert_eq!(names, ["_message_index", "_subset_index", "_source_file"]);
+
+    let message_index = record_batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<UInt32Array>()
+        .unwrap();
+    assert!(message_index.values().iter().all(|&v| v == 3));
+    let subset_index = record_batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<UInt32Array>()
+        .unwrap();
+    assert_eq!(
+        subset_index.values().to_vec(),
+        (0..num_rows as u32).collect::<Vec<_>>()
+    );
+    let source_file = record_batch
+        .column(2)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(source_file.value(0), filename);
+    assert_eq!(source_file.null_count(), 0);
+
+    // Struct columns flatten into dotted names and lists are rendered as text
+    let flattened = flatten_struct_columns(&record_batch).unwrap();
+    assert_eq!(flattened.num_rows(), num_rows);
+    let schema = flattened.schema();
+    assert!(schema.fields().iter().all(|f| !f.data_type().is_nested()));
+    assert!(schema.field_with_name("Year, month, day.Year [a]").is_ok());
+
+    // Element columns are classified as coordinates, qualifiers or data
+    let year = schema.field_with_name("Year, month, day.Year [a]").unwrap();
+    assert_eq!(element_kind(year), Some(ElementKind::Coordinate));
+    assert_eq!(element_kind(schema.field(0)), None);
+    let reordered = coordinates_first(&flattened).unwrap();
+    let kinds: Vec<_> = reordered
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| element_kind(f))
+        .collect();
+    assert_eq!(&kinds[..3], [None, None, None]);
+    let first_data = kinds
+        .iter()
+        .position(|k| *k == Some(ElementKind::Data))
+        .unwrap();
+    assert!(
+        kinds[first_data..]
+            .iter()
+            .all(|k| *k == Some(ElementKind::Data))
+    );
+}
+
+fn skip_local_header<R: BufRead>(reader: &mut R) {
+    let buf = reader.fill_buf().unwrap();
+    if !buf.starts_with(b"BUFR") {
+        let pos = buf.iter().position(|&b| b == b'\n').unwrap();
+        reader.consume(pos + 1);
+    }
+}
+
+#[test]
+fn test_columnar_matches_events() {
+    let mut tables = Tables::default();
+    tinybufr::tables::local::jma::install_jma_descriptors(&mut tables);
+
+    let mut columnar_count = 0;
+    for entry in fs::read_dir("./tests/testdata/jma").unwrap() {
+        let path = entry.unwrap().path();
+        if path.file_name().unwrap() == "LICENSE" {
+            continue;
+        }
+        let data = fs::read(&path).unwrap();
+        let mut reader = data.as_slice();
+        skip_local_header(&mut reader);
+        let header = HeaderSections::read(&mut reader).unwrap();
+        let data_spec =
+            DataSpec::from_data_description(&header.data_description_section, &tables).unwrap();
+
+        let Some(columnar) = convert_to_arrow_columnar(reader, &data_spec).unwrap() else {
+            continue;
+        };
+        let mut data_reader = DataReader::new(reader, &data_spec).unwrap();
+        let events = convert_to_arrow(&mut data_reader, &tables, &data_spec).unwrap();
+        assert_eq!(columnar, events, "{}", path.display());
+        columnar_count += 1;
+    }
+    assert!(columnar_count > 0);
+}
+
+#[test]
+fn test_signified_characters() {
+    use tinybufr::sections::DataDescriptionSection;
+
+    let tables = Tables::default();
+    // 2 05 002 is not the element 0 05 002 (latitude)
+    let descriptors = vec![
+        Descriptor { f: 2, x: 5, y: 2 },
+        Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        },
+    ];
+    let dds = DataDescriptionSection {
+        section_length: 7 + 2 * descriptors.len() as u32,
+        number_of_subsets: 1,
+        flags: Default::default(),
+        descriptors,
+        _padding: vec![],
+    };
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let data_section = [0, 0, 8, 0, b'O', b'K', 0x6a, 0xb3];
+    let mut data_reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+    let record_batch = convert_to_arrow(&mut data_reader, &tables, &data_spec).unwrap();
+    let schema = record_batch.schema();
+    let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(names, ["Temperature/air temperature [K]"]);
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_convert_file_fast() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use tinybufr::arrow::{FastConvertOptions, convert_file_fast};
+
+    let mut tables = Tables::default();
+    tinybufr::tables::local::jma::install_jma_descriptors(&mut tables);
+    let options = FastConvertOptions {
+        tables: Some(&tables),
+        provenance_columns: true,
+        ..Default::default()
+    };
+    let output = std::env::temp_dir().join("tinybufr_test_convert_file_fast.parquet");
+    let rows = convert_file_fast(
+        "./tests/testdata/jma/Z__C_RJTD_20230815070000_MET_SEQ_Ggis1km_Proi_Aper10min_RJsuikei830_ANAL_bufr4.bin",
+        &output,
+        &options,
+    )
+    .unwrap();
+    assert!(rows > 0);
+
+    let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+    let file_metadata = reader.metadata().file_metadata();
+    assert_eq!(file_metadata.num_rows() as usize, rows);
+    // The build used for decoding is recorded
+    let key_values = file_metadata.key_value_metadata().unwrap();
+    assert!(key_values.iter().any(|kv| kv.key == "tinybufr.version"
+        && kv.value.as_deref() == Some(tinybufr::build_info().version)));
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn test_record_batch_sink() {
+    use tinybufr::arrow::RecordBatchSink;
+
+    let message =
+        fs::read("./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin")
+            .unwrap();
+    let tables = Tables::default();
+    let options = ConvertOptions {
+        provenance_columns: true,
+        ..Default::default()
+    };
+    let (batches, rows) =
+        tinybufr::sink::export(RecordBatchSink::new(options), [&message, &message], &tables)
+            .unwrap();
+    assert_eq!(batches.len(), 2);
+    assert_eq!(rows, 64);
+    let message_index = batches[1]
+        .column(0)
+        .as_any()
+        .downcast_ref::<UInt32Array>()
+        .unwrap();
+    assert!(message_index.values().iter().all(|&v| v == 1));
+}
+
+#[test]
+fn test_csv_sink() {
+    use tinybufr::arrow::CsvSink;
+
+    let message =
+        fs::read("./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin")
+            .unwrap();
+    let tables = Tables::default();
+    let options = ConvertOptions {
+        provenance_columns: true,
+        ..Default::default()
+    };
+    let sink = CsvSink::new(Vec::new(), options).coordinates_first(true);
+    let (csv, rows) = tinybufr::sink::export(sink, [&message, &message], &tables).unwrap();
+    assert_eq!(rows, 64);
+    let csv = String::from_utf8(csv).unwrap();
+    let mut lines = csv.lines();
+    // A single header, with struct columns flattened
+    let header = lines.next().unwrap();
+    assert!(
+        header.starts_with("_message_index,_subset_index,"),
+        "{header}"
+    );
+    assert_eq!(lines.clone().count(), 64);
+    assert!(lines.last().unwrap().starts_with("1,31,"));
+
+    // No output without subsets
+    let sink = CsvSink::new(Vec::new(), ConvertOptions::default());
+    let (csv, rows) = tinybufr::sink::export(sink, Vec::<Vec<u8>>::new(), &tables).unwrap();
+    assert_eq!((csv.len(), rows), (0, 0));
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_parquet_sink() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use tinybufr::arrow::ParquetSink;
+
+    let message =
+        fs::read("./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin")
+            .unwrap();
+    let tables = Tables::default();
+    let output = std::env::temp_dir().join("tinybufr_test_parquet_sink.parquet");
+    let sink = ParquetSink::new(
+        fs::File::create(&output).unwrap(),
+        ConvertOptions::default(),
+        parquet::basic::Compression::SNAPPY,
+    );
+    let (_, rows) = tinybufr::sink::export(sink, [&message, &message], &tables).unwrap();
+    assert_eq!(rows, 64);
+    let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 64);
+    fs::remove_file(&output).unwrap();
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_parquet_sink_compressed_characters() {
+    use tinybufr::arrow::ParquetSink;
+    use tinybufr::sections::DataDescriptionSection;
+
+    let tables = Tables::default();
+    let descriptors = vec![
+        // Station or site name
+        Descriptor { f: 0, x: 1, y: 15 },
+        Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        },
+    ];
+    let dds = DataDescriptionSection {
+        section_length: 7 + 2 * descriptors.len() as u32,
+        number_of_subsets: 2,
+        flags: tinybufr::sections::DataDescriptionSectionFlags {
+            is_observed_data: true,
+            is_compressed: true,
+        },
+        descriptors,
+        _padding: vec![],
+    };
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let mut writer = DataWriter::new(&data_spec);
+    for name in ["TOKYO", "OSAKA"] {
+        writer
+            .write_subset(&[Value::String(name.to_string()), Value::Decimal(27315, -2)])
+            .unwrap();
+    }
+    let data_section = writer.finish().unwrap();
+    // Compressed characters are left to the event-based path
+    assert!(
+        convert_to_arrow_columnar(data_section.as_slice(), &data_spec)
+            .unwrap()
+            .is_none()
+    );
+
+    let original =
+        fs::read("./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin")
+            .unwrap();
+    let mut header = HeaderSections::read(original.as_slice()).unwrap();
+    header.data_description_section = dds;
+    let mut message = vec![];
+    tinybufr::write_message(&mut message, &header, &data_section).unwrap();
+    let sink = ParquetSink::new(
+        vec![],
+        ConvertOptions::default(),
+        parquet::basic::Compression::SNAPPY,
+    );
+    let (_, rows) = tinybufr::sink::export(sink, [&message], &tables).unwrap();
+    assert_eq!(rows, 2);
+}
+
+#[test]
+fn test_columnar_compressed_nulls() {
+    use tinybufr::sections::{DataDescriptionSection, DataDescriptionSectionFlags};
+
+    let tables = Tables::default();
+    let descriptors = vec![
+        // WMO block number: constant
+        Descriptor { f: 0, x: 1, y: 1 },
+        // Air temperature: partly missing
+        Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        },
+        // Pressure: all missing
+        Descriptor { f: 0, x: 10, y: 4 },
+    ];
+    let dds = DataDescriptionSection {
+        section_length: 7 + 2 * descriptors.len() as u32,
+        number_of_subsets: 3,
+        flags: DataDescriptionSectionFlags {
+            is_observed_data: true,
+            is_compressed: true,
+        },
+        descriptors,
+        _padding: vec![],
+    };
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let mut writer = DataWriter::new(&data_spec);
+    for temperature in [
+        Value::Decimal(27315, -2),
+        Value::Missing,
+        Value::Decimal(27015, -2),
+    ] {
+        writer
+            .write_subset(&[Value::Integer(47), temperature, Value::Missing])
+            .unwrap();
+    }
+    let data_section = writer.finish().unwrap();
+
+    let columnar = convert_to_arrow_columnar(data_section.as_slice(), &data_spec)
+        .unwrap()
+        .unwrap();
+    let mut data_reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+    let events = convert_to_arrow(&mut data_reader, &tables, &data_spec).unwrap();
+    assert_eq!(columnar, events);
+    let null_counts: Vec<_> = columnar.columns().iter().map(|c| c.null_count()).collect();
+    assert_eq!(null_counts, [0, 1, 3]);
+}
+
+/// Describes three subsets of a WMO block number and an air temperature.
+fn subsets_description(is_compressed: bool) -> tinybufr::sections::DataDescriptionSection {
+    use tinybufr::sections::{DataDescriptionSection, DataDescriptionSectionFlags};
+
+    let descriptors = vec![
+        Descriptor { f: 0, x: 1, y: 1 },
+        Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        },
+    ];
+    DataDescriptionSection {
+        section_length: 7 + 2 * descriptors.len() as u32,
+        number_of_subsets: 3,
+        flags: DataDescriptionSectionFlags {
+            is_observed_data: true,
+            is_compressed,
+        },
+        descriptors,
+        _padding: vec![],
+    }
+}
+
+fn encode_subsets(data_spec: &DataSpec) -> Vec<u8> {
+    let mut writer = DataWriter::new(data_spec);
+    for (block, temperature) in [(47, 27315), (48, 27015), (49, 26915)] {
+        writer
+            .write_subset(&[Value::Integer(block), Value::Decimal(temperature, -2)])
+            .unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+#[test]
+fn test_convert_partial() {
+    let tables = Tables::default();
+    let options = ConvertOptions::default();
+
+    // Complete data converts without an error
+    let dds = subsets_description(false);
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let data_section = encode_subsets(&data_spec);
+    let mut data_reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+    let partial = convert_to_arrow_partial(&mut data_reader, &tables, &data_spec, &options);
+    assert!(partial.error.is_none());
+    assert_eq!(partial.record_batch.unwrap().num_rows(), 3);
+
+    // Non-compressed: the subsets read completely are kept
+    let truncated = &data_section[..data_section.len() - 2];
+    let mut data_reader = DataReader::new(truncated, &data_spec).unwrap();
+    let partial = convert_to_arrow_partial(&mut data_reader, &tables, &data_spec, &options);
+    assert!(partial.error.is_some());
+    let record_batch = partial.record_batch.unwrap();
+    assert_eq!(record_batch.num_rows(), 2);
+    assert_eq!(record_batch.num_columns(), 2);
+
+    // Compressed: the columns read completely are kept
+    let dds = subsets_description(true);
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let data_section = encode_subsets(&data_spec);
+    let truncated = &data_section[..data_section.len() - 2];
+    let mut data_reader = DataReader::new(truncated, &data_spec).unwrap();
+    let partial = convert_to_arrow_partial(&mut data_reader, &tables, &data_spec, &options);
+    assert!(partial.error.is_some());
+    let record_batch = partial.record_batch.unwrap();
+    assert_eq!(record_batch.num_rows(), 3);
+    assert_eq!(record_batch.num_columns(), 1);
+
+    // Nothing to keep
+    let mut data_reader = DataReader::new(&data_section[..5], &data_spec).unwrap();
+    let partial = convert_to_arrow_partial(&mut data_reader, &tables, &data_spec, &options);
+    assert!(partial.error.is_some());
+    assert!(partial.record_batch.is_none());
+}
+
+#[test]
+fn test_convert_with_recovery() {
+    use tinybufr::recovery::RecoveryPolicy;
+
+    let tables = Tables::default();
+    for (is_compressed, policy, rows) in [
+        (false, RecoveryPolicy::SkipMessage, None),
+        (false, RecoveryPolicy::SkipSubset, Some(2)),
+        (false, RecoveryPolicy::BestEffort, Some(2)),
+        (true, RecoveryPolicy::SkipSubset, None),
+        (true, RecoveryPolicy::BestEffort, Some(3)),
+    ] {
+        let dds = subsets_description(is_compressed);
+        let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let data_section = encode_subsets(&data_spec);
+        let truncated = &data_section[..data_section.len() - 2];
+        let options = ConvertOptions {
+            recovery: policy,
+            ..Default::default()
+        };
+        let mut data_reader = DataReader::new(truncated, &data_spec).unwrap();
+        let partial =
+            convert_to_arrow_with_recovery(&mut data_reader, &tables, &data_spec, &options)
+                .unwrap();
+        assert!(partial.error.is_some());
+        assert_eq!(partial.record_batch.map(|b| b.num_rows()), rows);
+    }
+    let dds = subsets_description(false);
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let data_section = encode_subsets(&data_spec);
+    let mut data_reader = DataReader::new(&data_section[..5], &data_spec).unwrap();
+    let options = ConvertOptions::default();
+    assert!(
+        convert_to_arrow_with_recovery(&mut data_reader, &tables, &data_spec, &options).is_err()
+    );
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_convert_file_fast_with_recovery() {
+    use tinybufr::arrow::{FastConvertOptions, convert_file_fast};
+    use tinybufr::recovery::RecoveryPolicy;
+
+    let tables = Tables::default();
+    let dds = subsets_description(false);
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let data_section = encode_subsets(&data_spec);
+
+    // A truncated message between complete ones
+    let original =
+        fs::read("./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin")
+            .unwrap();
+    let mut header = HeaderSections::read(original.as_slice()).unwrap();
+    header.data_description_section = subsets_description(false);
+    // The data section ends in the third subset
+    let mut truncated = data_section[..12].to_vec();
+    let length = (truncated.len() as u32).to_be_bytes();
+    truncated[..3].copy_from_slice(&length[1..]);
+    let mut input = vec![];
+    for data_section in [&data_section, &truncated, &data_section] {
+        tinybufr::write_message(&mut input, &header, data_section).unwrap();
+    }
+    let input_path = std::env::temp_dir().join("tinybufr_test_convert_with_recovery.bufr");
+    let output_path = std::env::temp_dir().join("tinybufr_test_convert_with_recovery.parquet");
+    fs::write(&input_path, &input).unwrap();
+    assert!(convert_file_fast(&input_path, &output_path, &Default::default()).is_err());
+    for (policy, expected) in [
+        (RecoveryPolicy::SkipMessage, 6),
+        (RecoveryPolicy::SkipSubset, 8),
+        (RecoveryPolicy::BestEffort, 8),
+    ] {
+        let errors = std::sync::Mutex::new(vec![]);
+        let on_error = |index: usize, _: &tinybufr::Error| errors.lock().unwrap().push(index);
+        let options = FastConvertOptions {
+            recovery: policy,
+            on_recovered_error: Some(&on_error),
+            ..Default::default()
+        };
+        let rows = convert_file_fast(&input_path, &output_path, &options).unwrap();
+        assert_eq!(rows, expected);
+        assert_eq!(*errors.lock().unwrap(), [1]);
+    }
+
+    // The last message lacks its end section (7777)
+    let mut input = vec![];
+    for _ in 0..2 {
+        tinybufr::write_message(&mut input, &header, &data_section).unwrap();
+    }
+    input.truncate(input.len() - 4);
+    fs::write(&input_path, &input).unwrap();
+    assert!(convert_file_fast(&input_path, &output_path, &Default::default()).is_err());
+    let errors = std::sync::Mutex::new(vec![]);
+    let on_error =
+        |index: usize, e: &tinybufr::Error| errors.lock().unwrap().push(format!("{index}: {e}"));
+    let options = FastConvertOptions {
+        recovery: RecoveryPolicy::SkipMessage,
+        on_recovered_error: Some(&on_error),
+        ..Default::default()
+    };
+    assert_eq!(
+        convert_file_fast(&input_path, &output_path, &options).unwrap(),
+        6
+    );
+    assert_eq!(
+        *errors.lock().unwrap(),
+        ["1: Invalid data: Total length of 58 bytes declared for a message of 54 bytes"]
+    );
+    fs::remove_file(&input_path).unwrap();
+    fs::remove_file(&output_path).unwrap();
+}
+
+#[test]
+fn test_add_timestamp_column() {
+    use arrow::array::TimestampMicrosecondArray;
+    use tinybufr::sections::{DataDescriptionSection, DataDescriptionSectionFlags};
+
+    let tables = Tables::default();
+    // Year, month and day (in a sequence), hour, minute and seconds with microseconds
+    let descriptors = vec![
+        Descriptor { f: 3, x: 1, y: 11 },
+        Descriptor { f: 0, x: 4, y: 4 },
+        Descriptor { f: 0, x: 4, y: 5 },
+        Descriptor { f: 0, x: 4, y: 7 },
+    ];
+    let dds = DataDescriptionSection {
+        section_length: 7 + 2 * descriptors.len() as u32,
+        number_of_subsets: 2,
+        flags: DataDescriptionSectionFlags {
+            is_observed_data: true,
+            is_compressed: false,
+        },
+        descriptors,
+        _padding: vec![],
+    };
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let mut writer = DataWriter::new(&data_spec);
+    for second in [Value::Decimal(59123456, -6), Value::Missing] {
+        writer
+            .write_subset(&[
+                Value::Integer(2024),
+                Value::Integer(2),
+                Value::Integer(29),
+                Value::Integer(23),
+                Value::Integer(59),
+                second,
+            ])
+            .unwrap();
+    }
+    let data_section = writer.finish().unwrap();
+
+    let mut data_reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+    let record_batch = convert_to_arrow(&mut data_reader, &tables, &data_spec).unwrap();
+
+    // The date is nested in a sequence
+    let record_batch = add_timestamp_column(&record_batch).unwrap();
+    assert_eq!(record_batch.num_columns(), 5);
+    let timestamps = record_batch
+        .column_by_name(TIMESTAMP_COLUMN)
+        .unwrap()
+        .as_any()
+        .downcast_ref::<TimestampMicrosecondArray>()
+        .unwrap();
+    // 2024-02-29T23:59:59.123456Z
+    assert_eq!(timestamps.value(0), 1_709_251_199_123_456);
+    assert!(timestamps.is_null(1));
+}
+
+#[test]
+fn test_value_hooks() {
+    use arrow::array::Int32Array;
+
+    let tables = Tables::default();
+    let dds = subsets_description(true);
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let data_section = encode_subsets(&data_spec);
+    let mut value_hooks = tinybufr::hooks::ValueHooks::default();
+    value_hooks.insert_class(1, |v| match v {
+        Value::Integer(v) => Value::Integer(v * 1000),
+        v => v,
+    });
+
+    let mut data_reader = DataReader::builder(&data_spec)
+        .value_hooks(value_hooks)
+        .build(data_section.as_slice())
+        .unwrap();
+    let record_batch = convert_to_arrow(&mut data_reader, &tables, &data_spec).unwrap();
+    let blocks = record_batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int32Array>()
+        .unwrap();
+    assert_eq!(blocks.values(), &[47000, 48000, 49000]);
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_parquet_sink_value_hooks() {
+    use arrow::array::Int32Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use tinybufr::arrow::ParquetSink;
+    use tinybufr::sink::{ExportOptions, export_with_options};
+
+    let tables = Tables::default();
+    let dds = subsets_description(false);
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let data_section = encode_subsets(&data_spec);
+    let original =
+        fs::read("./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin")
+            .unwrap();
+    let mut header = HeaderSections::read(original.as_slice()).unwrap();
+    header.data_description_section = dds.clone();
+    let mut message = vec![];
+    tinybufr::write_message(&mut message, &header, &data_section).unwrap();
+
+    // The sink leaves the hooks to the event-based path
+    let mut options = ExportOptions::default();
+    options.value_hooks.insert_class(1, |v| match v {
+        Value::Integer(v) => Value::Integer(v * 1000),
+        v => v,
+    });
+    let output = std::env::temp_dir().join("tinybufr_test_parquet_sink_value_hooks.parquet");
+    let sink = ParquetSink::new(
+        fs::File::create(&output).unwrap(),
+        ConvertOptions::default(),
+        parquet::basic::Compression::SNAPPY,
+    );
+    export_with_options(sink, [&message], &tables, &options).unwrap();
+    let record_batch = ParquetRecordBatchReaderBuilder::try_new(fs::File::open(&output).unwrap())
+        .unwrap()
+        .build()
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    let blocks = record_batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int32Array>()
+        .unwrap();
+    assert_eq!(blocks.values(), &[47000, 48000, 49000]);
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn test_deterministic_column_order() {
+    use tinybufr::sections::{DataDescriptionSection, DataDescriptionSectionFlags};
+
+    let tables = Tables::default();
+    // Elements out of the order of their descriptors, around a sequence
+    let descriptors = vec![
+        Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        },
+        Descriptor { f: 0, x: 1, y: 1 },
+        Descriptor { f: 3, x: 1, y: 11 },
+        Descriptor { f: 0, x: 7, y: 30 },
+        Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        },
+    ];
+    let dds = DataDescriptionSection {
+        section_length: 7 + 2 * descriptors.len() as u32,
+        number_of_subsets: 1,
+        flags: DataDescriptionSectionFlags {
+            is_observed_data: true,
+            is_compressed: false,
+        },
+        descriptors,
+        _padding: vec![],
+    };
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let mut writer = DataWriter::new(&data_spec);
+    writer
+        .write_subset(&[
+            Value::Decimal(27315, -2),
+            Value::Integer(47),
+            Value::Integer(2020),
+            Value::Integer(7),
+            Value::Integer(28),
+            Value::Decimal(123, 0),
+            Value::Decimal(27415, -2),
+        ])
+        .unwrap();
+    let data_section = writer.finish().unwrap();
+
+    // Each thread hashes with its own seeds
+    let names: Vec<Vec<String>> = std::thread::scope(|scope| {
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut data_reader =
+                        DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+                    let record_batch =
+                        convert_to_arrow(&mut data_reader, &tables, &data_spec).unwrap();
+                    let flat = flatten_struct_columns(&record_batch).unwrap();
+                    flat.schema()
+                        .fields()
+                        .iter()
+                        .map(|f| f.name().to_string())
+                        .collect()
+                })
+            })
+            .collect();
+        threads.into_iter().map(|t| t.join().unwrap()).collect()
+    });
+    let sequence = tables.table_d[&XY { x: 1, y: 11 }].title;
+    assert_eq!(
+        names[0],
+        [
+            "Temperature/air temperature [K]".to_string(),
+            "WMO block number".to_string(),
+            format!("{sequence}.Year [a]"),
+            format!("{sequence}.Month [mon]"),
+            format!("{sequence}.Day [d]"),
+            "Height of station ground above mean sea level [m]".to_string(),
+            "Temperature/air temperature [K] (2)".to_string(),
+        ]
+    );
+    assert!(names.iter().all(|n| *n == names[0]));
+
+    let columnar = convert_to_arrow_columnar(data_section.as_slice(), &data_spec)
+        .unwrap()
+        .unwrap();
+    let columnar = flatten_struct_columns(&columnar).unwrap();
+    let columnar_names: Vec<_> = columnar
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().to_string())
+        .collect();
+    assert_eq!(columnar_names, names[0]);
+}
+
+#[test]
+fn test_zero_subsets() {
+    let tables = Tables::default();
+    for is_compressed in [false, true] {
+        let mut dds = subsets_description(is_compressed);
+        dds.number_of_subsets = 0;
+        let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        // Some producers leave a byte of data in the data section
+        let data_section = [0, 0, 6, 0, 0xff, 0xff];
+        let mut data_reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+        let record_batch = convert_to_arrow(&mut data_reader, &tables, &data_spec).unwrap();
+        assert_eq!(record_batch.num_rows(), 0);
+        assert!(data_reader.warnings().is_empty());
+        assert!(data_reader.into_inner().is_empty());
+    }
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_convert_file_fast_zero_subsets() {
+    // A message without subsets followed by one with subsets
+    let original =
+        fs::read("./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin")
+            .unwrap();
+    let mut header = HeaderSections::read(original.as_slice()).unwrap();
+    let subsets = header.number_of_subsets() as usize;
+    header.data_description_section.number_of_subsets = 0;
+    let mut input = vec![];
+    tinybufr::write_message(&mut input, &header, &[0, 0, 4, 0]).unwrap();
+    input.extend(&original);
+    let input_path = std::env::temp_dir().join("tinybufr_test_zero_subsets.bufr");
+    let output_path = std::env::temp_dir().join("tinybufr_test_zero_subsets.parquet");
+    fs::write(&input_path, &input).unwrap();
+    let rows =
+        tinybufr::arrow::convert_file_fast(&input_path, &output_path, &Default::default()).unwrap();
+    assert_eq!(rows, subsets);
+    fs::remove_file(&output_path).unwrap();
+
+    // No subset at all
+    input.truncate(input.len() - original.len());
+    fs::write(&input_path, &input).unwrap();
+    assert!(
+        tinybufr::arrow::convert_file_fast(&input_path, &output_path, &Default::default()).is_err()
+    );
+    assert!(!output_path.exists());
+    fs::remove_file(&input_path).unwrap();
+}
+
+#[test]
+fn test_convert_to_arrow_long() {
+    let tables = Tables::default();
+    for is_compressed in [false, true] {
+        let dds = subsets_description(is_compressed);
+        let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+        let data_section = encode_subsets(&data_spec);
+        let mut data_reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+        let record_batch =
+            convert_to_arrow_long(&mut data_reader, &tables, &ConvertOptions::default()).unwrap();
+        assert_eq!(record_batch.num_rows(), 6);
+
+        let column = |name: &str| record_batch.column_by_name(name).unwrap().clone();
+        let subset_index = column("_subset_index");
+        let subset_index = subset_index.as_any().downcast_ref::<UInt32Array>().unwrap();
+        assert_eq!(subset_index.values(), &[0, 0, 1, 1, 2, 2]);
+        let descriptor = column("descriptor");
+        let descriptor = descriptor.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(descriptor.value(2), "001001");
+        assert_eq!(descriptor.value(3), "012101");
+        let unit = column("unit");
+        let unit = unit.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(unit.value(3), "K");
+        let value = column("value");
+        let value = value.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(value.value(2), 48.0);
+        assert!((value.value(3) - 270.15).abs() < 1e-9);
+        assert_eq!(column("qc").null_count(), 6);
+    }
+
+    // Templates with replications and quality information share the same schema
+    let data =
+        fs::read("./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin")
+            .unwrap();
+    let mut reader = data.as_slice();
+    let header = HeaderSections::read(&mut reader).unwrap();
+    let data_spec =
+        DataSpec::from_data_description(&header.data_description_section, &tables).unwrap();
+    let mut data_reader = DataReader::new(reader, &data_spec).unwrap();
+    let record_batch =
+        convert_to_arrow_long(&mut data_reader, &tables, &ConvertOptions::default()).unwrap();
+    assert!(record_batch.num_rows() > header.number_of_subsets() as usize);
+    assert_eq!(record_batch.num_columns(), 9);
+}
+
+#[test]
+fn test_compressed_delayed_factors_per_subset() {
+    use arrow::array::{Int32Array, ListArray, StructArray};
+    use bitstream_io::{BigEndian, BitWrite, BitWriter};
+    use tinybufr::sections::{DataDescriptionSection, DataDescriptionSectionFlags};
+
+    let tables = Tables::default();
+    let descriptors = vec![
+        Descriptor { f: 1, x: 1, y: 0 },
+        Descriptor { f: 0, x: 31, y: 1 },
+        Descriptor { f: 0, x: 1, y: 1 },
+    ];
+    let dds = DataDescriptionSection {
+        section_length: 7 + 2 * descriptors.len() as u32,
+        number_of_subsets: 3,
+        flags: DataDescriptionSectionFlags {
+            is_observed_data: true,
+            is_compressed: true,
+        },
+        descriptors,
+        _padding: vec![],
+    };
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+
+    // Factors 1, 3 and 2, then 3 items of 0 01 001 (padding encoded as missing)
+    let mut writer = BitWriter::endian(Vec::new(), BigEndian);
+    for (bits, value) in [
+        (8, 1),
+        (6, 2),
+        (2, 0),
+        (2, 2),
+        (2, 1),
+        (7, 10),
+        (6, 0),
+        (7, 20),
+        (6, 3),
+        (3, 7),
+        (3, 0),
+        (3, 1),
+        (7, 30),
+        (6, 3),
+        (3, 7),
+        (3, 0),
+        (3, 7),
+    ] {
+        writer.write_var::<u32>(bits, value).unwrap();
+    }
+    writer.byte_align().unwrap();
+    let data = writer.into_writer();
+    let mut data_section = vec![0, 0, 4 + data.len() as u8, 0];
+    data_section.extend(data);
+
+    let mut data_reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+    let record_batch = convert_to_arrow(&mut data_reader, &tables, &data_spec).unwrap();
+    assert_eq!(record_batch.num_rows(), 3);
+    let list = record_batch
+        .column_by_name("replication:1")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<ListArray>()
+        .unwrap();
+    assert_eq!(list.value_offsets(), &[0, 1, 4, 6]);
+    let items = list
+        .values()
+        .as_any()
+        .downcast_ref::<StructArray>()
+        .unwrap();
+    let block = items
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int32Array>()
+        .unwrap();
+    assert_eq!(block.values(), &[10, 10, 20, 30, 10, 21]);
+    assert_eq!(block.null_count(), 0);
+
+    // The long format leaves out the padding too
+    let mut data_reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+    let record_batch =
+        convert_to_arrow_long(&mut data_reader, &tables, &ConvertOptions::default()).unwrap();
+    let subset_index = record_batch
+        .column_by_name("_subset_index")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<UInt32Array>()
+        .unwrap();
+    assert_eq!(subset_index.values(), &[0, 1, 1, 1, 2, 2]);
+}
@@ -0,0 +1,543 @@
+// This file is generated by tinybufr::codegen. Do not edit.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DwdSynop {
+    /// 0 01 125: WIGOS identifier series
+    pub wigos_identifier_series: Option<i32>,
+    /// 0 01 126: WIGOS issuer of identifier
+    pub wigos_issuer_of_identifier: Option<i32>,
+    /// 0 01 127: WIGOS issue number
+    pub wigos_issue_number: Option<i32>,
+    /// 0 01 128: WIGOS local identifier (character) [CCITT IA5]
+    pub wigos_local_identifier_character: Option<String>,
+    /// 0 01 018: Short station or site name [CCITT IA5]
+    pub short_station_or_site_name: Option<String>,
+    /// 0 01 023: Observation sequence number
+    pub observation_sequence_number: Option<i32>,
+    /// 0 01 001: WMO block number
+    pub wmo_block_number: Option<i32>,
+    /// 0 01 002: WMO station number
+    pub wmo_station_number: Option<i32>,
+    /// 0 01 015: Station or site name [CCITT IA5]
+    pub station_or_site_name: Option<String>,
+    /// 0 02 001: Type of station [Code table]
+    pub type_of_station: Option<i32>,
+    /// 0 04 001: Year [a]
+    pub year: Option<i32>,
+    /// 0 04 002: Month [mon]
+    pub month: Option<i32>,
+    /// 0 04 003: Day [d]
+    pub day: Option<i32>,
+    /// 0 04 004: Hour [h]
+    pub hour: Option<i32>,
+    /// 0 04 005: Minute [min]
+    pub minute: Option<i32>,
+    /// 0 05 001: Latitude (high accuracy) [deg]
+    pub latitude_high_accuracy: Option<f64>,
+    /// 0 06 001: Longitude (high accuracy) [deg]
+    pub longitude_high_accuracy: Option<f64>,
+    /// 0 07 030: Height of station ground above mean sea level [m]
+    pub height_of_station_ground_above_mean_sea_level: Option<f64>,
+    /// 0 07 031: Height of barometer above mean sea level [m]
+    pub height_of_barometer_above_mean_sea_level: Option<f64>,
+    /// 0 01 101: State identifier [Code table]
+    pub state_identifier: Option<i32>,
+    /// 0 01 102: National station number
+    pub national_station_number: Option<i32>,
+    /// Replicated items
+    pub replication_1: Vec<DwdSynopItem1>,
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform: Option<f64>,
+    /// 0 13 009: Relative humidity [%]
+    pub relative_humidity: Option<f64>,
+    /// 0 12 103: Dewpoint temperature [K]
+    pub dewpoint_temperature: Option<f64>,
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform_2: Option<f64>,
+    /// Replicated items
+    pub replication_2: Vec<DwdSynopItem2>,
+    /// 0 07 061: Depth below land surface [m]
+    pub depth_below_land_surface: Option<f64>,
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform_3: Option<f64>,
+    /// 0 04 025: Time period or displacement [min]
+    pub time_period_or_displacement: Option<i32>,
+    /// 0 13 011: Total precipitation/total water equivalent [kg m-2]
+    pub total_precipitation_total_water_equivalent: Option<f64>,
+    /// 0 26 020: Duration of precipitation [min]
+    pub duration_of_precipitation: Option<i32>,
+    /// 0 04 015: Time increment [min]
+    pub time_increment: Option<i32>,
+    /// 0 04 025: Time period or displacement [min]
+    pub time_period_or_displacement_2: Option<i32>,
+    /// 0 04 065: Short time increment [min]
+    pub short_time_increment: Option<i32>,
+    /// Replicated items
+    pub replication_3: Vec<DwdSynopItem3>,
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform_4: Option<f64>,
+    /// 0 04 025: Time period or displacement [min]
+    pub time_period_or_displacement_3: Option<i32>,
+    /// 0 10 004: Pressure [Pa]
+    pub pressure: Option<i32>,
+    /// 0 10 051: Pressure reduced to mean sea level [Pa]
+    pub pressure_reduced_to_mean_sea_level: Option<i32>,
+    /// 0 10 009: Geopotential height [gpm]
+    pub geopotential_height: Option<i32>,
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform_5: Option<f64>,
+    /// 0 20 237: METEOROLOGICAL OPTIONAL RANGE [M]
+    pub meteorological_optional_range: Option<i32>,
+    /// 0 20 238: MINIMUM METEOROLOGICAL OPTIONAL RANGE [M]
+    pub minimum_meteorological_optional_range: Option<i32>,
+    /// 0 20 239: MAXIMUM METEOROLOGICAL OPTIONAL RANGE [M]
+    pub maximum_meteorological_optional_range: Option<i32>,
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform_6: Option<f64>,
+    /// 0 04 025: Time period or displacement [min]
+    pub time_period_or_displacement_4: Option<i32>,
+    /// 0 20 003: Present weather [Code table]
+    pub present_weather: Option<i32>,
+    /// 0 04 015: Time increment [min]
+    pub time_increment_2: Option<i32>,
+    /// 0 04 025: Time period or displacement [min]
+    pub time_period_or_displacement_5: Option<i32>,
+    /// 0 04 065: Short time increment [min]
+    pub short_time_increment_2: Option<i32>,
+    /// Replicated items
+    pub replication_4: Vec<DwdSynopItem4>,
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform_7: Option<f64>,
+    /// 0 04 025: Time period or displacement [min]
+    pub time_period_or_displacement_6: Option<i32>,
+    /// 0 13 013: Total snow depth [m]
+    pub total_snow_depth: Option<f64>,
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform_8: Option<f64>,
+    /// 0 04 025: Time period or displacement [min]
+    pub time_period_or_displacement_7: Option<i32>,
+    /// 0 08 021: Time significance [Code table]
+    pub time_significance: Option<i32>,
+    /// 0 11 002: Wind speed [m/s]
+    pub wind_speed: Option<f64>,
+    /// 0 11 001: Wind direction [degree true]
+    pub wind_direction: Option<i32>,
+    /// 0 08 021: Time significance [Code table]
+    pub time_significance_2: Option<i32>,
+    /// 0 11 041: Maximum wind gust speed [m/s]
+    pub maximum_wind_gust_speed: Option<f64>,
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform_9: Option<f64>,
+    /// 0 14 034: Sunshine over period specified [min]
+    pub sunshine_over_period_specified: Option<i32>,
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform_10: Option<f64>,
+    /// 0 14 002: Long-wave radiation, integrated over period specified [J m-2]
+    pub long_wave_radiation_integrated_over_period_specified: Option<i32>,
+    /// 0 14 028: Global solar radiation (high accuracy), integrated over period specified [J m-2]
+    pub global_solar_radiation_high_accuracy_integrated_over_period_specified: Option<i32>,
+    /// 0 14 029: Diffuse solar radiation (high accuracy), integrated over period specified [J m-2]
+    pub diffuse_solar_radiation_high_accuracy_integrated_over_period_specified: Option<i32>,
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform_11: Option<f64>,
+    /// 0 04 025: Time period or displacement [min]
+    pub time_period_or_displacement_8: Option<i32>,
+    /// 0 08 002: Vertical significance (surface observations) [Code table]
+    pub vertical_significance_surface_observations: Option<i32>,
+    /// 0 20 013: Height of base of cloud [m]
+    pub height_of_base_of_cloud: Option<i32>,
+    /// 0 08 002: Vertical significance (surface observations) [Code table]
+    pub vertical_significance_surface_observations_2: Option<i32>,
+    /// 0 20 010: Cloud cover (total) [%]
+    pub cloud_cover_total: Option<i32>,
+    /// Replicated items
+    pub replication_5: Vec<DwdSynopItem5>,
+    /// 0 08 002: Vertical significance (surface observations) [Code table]
+    pub vertical_significance_surface_observations_3: Option<i32>,
+}
+
+impl DwdSynop {
+    fn read_fields<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+    ) -> Result<Self, tinybufr::Error> {
+        let wigos_identifier_series = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 1, y: 125 })?)?;
+        let wigos_issuer_of_identifier = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 1, y: 126 })?)?;
+        let wigos_issue_number = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 1, y: 127 })?)?;
+        let wigos_local_identifier_character = DwdSynop::as_string(DwdSynop::next_value(reader, tinybufr::XY { x: 1, y: 128 })?)?;
+        let short_station_or_site_name = DwdSynop::as_string(DwdSynop::next_value(reader, tinybufr::XY { x: 1, y: 18 })?)?;
+        let observation_sequence_number = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 1, y: 23 })?)?;
+        let wmo_block_number = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 1, y: 1 })?)?;
+        let wmo_station_number = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 1, y: 2 })?)?;
+        let station_or_site_name = DwdSynop::as_string(DwdSynop::next_value(reader, tinybufr::XY { x: 1, y: 15 })?)?;
+        let type_of_station = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 2, y: 1 })?)?;
+        let year = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 1 })?)?;
+        let month = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 2 })?)?;
+        let day = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 3 })?)?;
+        let hour = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 4 })?)?;
+        let minute = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 5 })?)?;
+        let latitude_high_accuracy = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 5, y: 1 })?)?;
+        let longitude_high_accuracy = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 6, y: 1 })?)?;
+        let height_of_station_ground_above_mean_sea_level = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 30 })?)?;
+        let height_of_barometer_above_mean_sea_level = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 31 })?)?;
+        let state_identifier = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 1, y: 101 })?)?;
+        let national_station_number = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 1, y: 102 })?)?;
+        let count = DwdSynop::next_replication(reader)?;
+        let mut replication_1 = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            replication_1.push(DwdSynopItem1::read_fields(reader)?);
+        }
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let relative_humidity = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 13, y: 9 })?)?;
+        let dewpoint_temperature = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 12, y: 103 })?)?;
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform_2 = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let count = DwdSynop::next_replication(reader)?;
+        let mut replication_2 = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            replication_2.push(DwdSynopItem2::read_fields(reader)?);
+        }
+        let depth_below_land_surface = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 61 })?)?;
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform_3 = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let time_period_or_displacement = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 25 })?)?;
+        let total_precipitation_total_water_equivalent = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 13, y: 11 })?)?;
+        let duration_of_precipitation = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 26, y: 20 })?)?;
+        let time_increment = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 15 })?)?;
+        let time_period_or_displacement_2 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 25 })?)?;
+        let short_time_increment = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 65 })?)?;
+        let count = DwdSynop::next_replication(reader)?;
+        let mut replication_3 = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            replication_3.push(DwdSynopItem3::read_fields(reader)?);
+        }
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform_4 = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let time_period_or_displacement_3 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 25 })?)?;
+        let pressure = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 10, y: 4 })?)?;
+        let pressure_reduced_to_mean_sea_level = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 10, y: 51 })?)?;
+        let geopotential_height = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 10, y: 9 })?)?;
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform_5 = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let meteorological_optional_range = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 20, y: 237 })?)?;
+        let minimum_meteorological_optional_range = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 20, y: 238 })?)?;
+        let maximum_meteorological_optional_range = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 20, y: 239 })?)?;
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform_6 = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let time_period_or_displacement_4 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 25 })?)?;
+        let present_weather = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 20, y: 3 })?)?;
+        let time_increment_2 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 15 })?)?;
+        let time_period_or_displacement_5 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 25 })?)?;
+        let short_time_increment_2 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 65 })?)?;
+        let count = DwdSynop::next_replication(reader)?;
+        let mut replication_4 = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            replication_4.push(DwdSynopItem4::read_fields(reader)?);
+        }
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform_7 = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let time_period_or_displacement_6 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 25 })?)?;
+        let total_snow_depth = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 13, y: 13 })?)?;
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform_8 = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let time_period_or_displacement_7 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 25 })?)?;
+        let time_significance = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 8, y: 21 })?)?;
+        let wind_speed = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 11, y: 2 })?)?;
+        let wind_direction = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 11, y: 1 })?)?;
+        let time_significance_2 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 8, y: 21 })?)?;
+        let maximum_wind_gust_speed = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 11, y: 41 })?)?;
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform_9 = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let sunshine_over_period_specified = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 14, y: 34 })?)?;
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform_10 = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let long_wave_radiation_integrated_over_period_specified = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 14, y: 2 })?)?;
+        let global_solar_radiation_high_accuracy_integrated_over_period_specified = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 14, y: 28 })?)?;
+        let diffuse_solar_radiation_high_accuracy_integrated_over_period_specified = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 14, y: 29 })?)?;
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform_11 = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let time_period_or_displacement_8 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 4, y: 25 })?)?;
+        let vertical_significance_surface_observations = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 8, y: 2 })?)?;
+        let height_of_base_of_cloud = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 20, y: 13 })?)?;
+        let vertical_significance_surface_observations_2 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 8, y: 2 })?)?;
+        let cloud_cover_total = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 20, y: 10 })?)?;
+        let count = DwdSynop::next_replication(reader)?;
+        let mut replication_5 = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            replication_5.push(DwdSynopItem5::read_fields(reader)?);
+        }
+        let vertical_significance_surface_observations_3 = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 8, y: 2 })?)?;
+        Ok(Self {
+            wigos_identifier_series,
+            wigos_issuer_of_identifier,
+            wigos_issue_number,
+            wigos_local_identifier_character,
+            short_station_or_site_name,
+            observation_sequence_number,
+            wmo_block_number,
+            wmo_station_number,
+            station_or_site_name,
+            type_of_station,
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            latitude_high_accuracy,
+            longitude_high_accuracy,
+            height_of_station_ground_above_mean_sea_level,
+            height_of_barometer_above_mean_sea_level,
+            state_identifier,
+            national_station_number,
+            replication_1,
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform,
+            relative_humidity,
+            dewpoint_temperature,
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform_2,
+            replication_2,
+            depth_below_land_surface,
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform_3,
+            time_period_or_displacement,
+            total_precipitation_total_water_equivalent,
+            duration_of_precipitation,
+            time_increment,
+            time_period_or_displacement_2,
+            short_time_increment,
+            replication_3,
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform_4,
+            time_period_or_displacement_3,
+            pressure,
+            pressure_reduced_to_mean_sea_level,
+            geopotential_height,
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform_5,
+            meteorological_optional_range,
+            minimum_meteorological_optional_range,
+            maximum_meteorological_optional_range,
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform_6,
+            time_period_or_displacement_4,
+            present_weather,
+            time_increment_2,
+            time_period_or_displacement_5,
+            short_time_increment_2,
+            replication_4,
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform_7,
+            time_period_or_displacement_6,
+            total_snow_depth,
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform_8,
+            time_period_or_displacement_7,
+            time_significance,
+            wind_speed,
+            wind_direction,
+            time_significance_2,
+            maximum_wind_gust_speed,
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform_9,
+            sunshine_over_period_specified,
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform_10,
+            long_wave_radiation_integrated_over_period_specified,
+            global_solar_radiation_high_accuracy_integrated_over_period_specified,
+            diffuse_solar_radiation_high_accuracy_integrated_over_period_specified,
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform_11,
+            time_period_or_displacement_8,
+            vertical_significance_surface_observations,
+            height_of_base_of_cloud,
+            vertical_significance_surface_observations_2,
+            cloud_cover_total,
+            replication_5,
+            vertical_significance_surface_observations_3,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DwdSynopItem1 {
+    /// 0 07 032: Height of sensor above local ground (or deck of marine platform) [m]
+    pub height_of_sensor_above_local_ground_or_deck_of_marine_platform: Option<f64>,
+    /// 0 12 101: Temperature/air temperature [K]
+    pub temperature_air_temperature: Option<f64>,
+}
+
+impl DwdSynopItem1 {
+    fn read_fields<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+    ) -> Result<Self, tinybufr::Error> {
+        let height_of_sensor_above_local_ground_or_deck_of_marine_platform = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 32 })?)?;
+        let temperature_air_temperature = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 12, y: 101 })?)?;
+        Ok(Self {
+            height_of_sensor_above_local_ground_or_deck_of_marine_platform,
+            temperature_air_temperature,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DwdSynopItem2 {
+    /// 0 07 061: Depth below land surface [m]
+    pub depth_below_land_surface: Option<f64>,
+    /// 0 12 130: Soil temperature [K]
+    pub soil_temperature: Option<f64>,
+}
+
+impl DwdSynopItem2 {
+    fn read_fields<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+    ) -> Result<Self, tinybufr::Error> {
+        let depth_below_land_surface = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 7, y: 61 })?)?;
+        let soil_temperature = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 12, y: 130 })?)?;
+        Ok(Self {
+            depth_below_land_surface,
+            soil_temperature,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DwdSynopItem3 {
+    /// 0 13 011: Total precipitation/total water equivalent [kg m-2]
+    pub total_precipitation_total_water_equivalent: Option<f64>,
+}
+
+impl DwdSynopItem3 {
+    fn read_fields<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+    ) -> Result<Self, tinybufr::Error> {
+        let total_precipitation_total_water_equivalent = DwdSynop::as_f64(DwdSynop::next_value(reader, tinybufr::XY { x: 13, y: 11 })?)?;
+        Ok(Self {
+            total_precipitation_total_water_equivalent,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DwdSynopItem4 {
+    /// 0 20 003: Present weather [Code table]
+    pub present_weather: Option<i32>,
+}
+
+impl DwdSynopItem4 {
+    fn read_fields<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+    ) -> Result<Self, tinybufr::Error> {
+        let present_weather = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 20, y: 3 })?)?;
+        Ok(Self {
+            present_weather,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DwdSynopItem5 {
+    /// 0 08 002: Vertical significance (surface observations) [Code table]
+    pub vertical_significance_surface_observations: Option<i32>,
+    /// 0 20 011: Cloud amount [Code table]
+    pub cloud_amount: Option<i32>,
+    /// 0 20 013: Height of base of cloud [m]
+    pub height_of_base_of_cloud: Option<i32>,
+}
+
+impl DwdSynopItem5 {
+    fn read_fields<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+    ) -> Result<Self, tinybufr::Error> {
+        let vertical_significance_surface_observations = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 8, y: 2 })?)?;
+        let cloud_amount = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 20, y: 11 })?)?;
+        let height_of_base_of_cloud = DwdSynop::as_i32(DwdSynop::next_value(reader, tinybufr::XY { x: 20, y: 13 })?)?;
+        Ok(Self {
+            vertical_significance_surface_observations,
+            cloud_amount,
+            height_of_base_of_cloud,
+        })
+    }
+}
+
+#[allow(dead_code)]
+impl DwdSynop {
+    /// Reads the next subset from the data reader.
+    pub fn read<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+    ) -> Result<Self, tinybufr::Error> {
+        match reader.read_event()? {
+            tinybufr::DataEvent::SubsetStart(_) => {}
+            ev => return Err(Self::unexpected(ev)),
+        }
+        let subset = Self::read_fields(reader)?;
+        loop {
+            match reader.read_event()? {
+                tinybufr::DataEvent::SubsetEnd => return Ok(subset),
+                tinybufr::DataEvent::SequenceEnd | tinybufr::DataEvent::OperatorHandled { .. } => {}
+                ev => return Err(Self::unexpected(ev)),
+            }
+        }
+    }
+
+    fn next_value<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+        expected: tinybufr::XY,
+    ) -> Result<tinybufr::Value, tinybufr::Error> {
+        loop {
+            match reader.read_event()? {
+                tinybufr::DataEvent::Data { xy, value, .. } if xy == expected => return Ok(value),
+                tinybufr::DataEvent::AssociatedField { .. }
+                | tinybufr::DataEvent::BitmapTarget { .. }
+                | tinybufr::DataEvent::Quality { .. }
+                | tinybufr::DataEvent::SequenceStart { .. }
+                | tinybufr::DataEvent::SequenceEnd
+                | tinybufr::DataEvent::ReplicationItemStart
+                | tinybufr::DataEvent::ReplicationItemEnd
+                | tinybufr::DataEvent::ReplicationEnd
+                | tinybufr::DataEvent::OperatorHandled { .. } => {}
+                ev => return Err(Self::unexpected(ev)),
+            }
+        }
+    }
+
+    fn next_replication<R: std::io::Read>(
+        reader: &mut tinybufr::DataReader<'_, R>,
+    ) -> Result<u16, tinybufr::Error> {
+        loop {
+            match reader.read_event()? {
+                tinybufr::DataEvent::ReplicationStart { count, .. } => return Ok(count),
+                tinybufr::DataEvent::SequenceStart { .. }
+                | tinybufr::DataEvent::SequenceEnd
+                | tinybufr::DataEvent::ReplicationItemStart
+                | tinybufr::DataEvent::ReplicationItemEnd
+                | tinybufr::DataEvent::ReplicationEnd
+                | tinybufr::DataEvent::OperatorHandled { .. } => {}
+                ev => return Err(Self::unexpected(ev)),
+            }
+        }
+    }
+
+    fn as_i32(value: tinybufr::Value) -> Result<Option<i32>, tinybufr::Error> {
+        match value {
+            tinybufr::Value::Missing => Ok(None),
+            tinybufr::Value::Integer(v) => Ok(Some(v)),
+            tinybufr::Value::Decimal(v, s) if s >= 0 => 10i32
+                .checked_pow(s as u32)
+                .and_then(|m| v.checked_mul(m))
+                .map(Some)
+                .ok_or_else(|| tinybufr::Error::Invalid("Integer value out of range".to_string())),
+            tinybufr::Value::Decimal64(v, s) if s >= 0 => 10i64
+                .checked_pow(s as u32)
+                .and_then(|m| v.checked_mul(m))
+                .and_then(|v| i32::try_from(v).ok())
+                .map(Some)
+                .ok_or_else(|| tinybufr::Error::Invalid("Integer value out of range".to_string())),
+            v => Err(Self::unexpected_value(v)),
+        }
+    }
+
+    fn as_f64(value: tinybufr::Value) -> Result<Option<f64>, tinybufr::Error> {
+        match value {
+            tinybufr::Value::Missing => Ok(None),
+            tinybufr::Value::Integer(v) => Ok(Some(v as f64)),
+            tinybufr::Value::Decimal(v, s) => Ok(Some(v as f64 * 10f64.powi(s as i32))),
+            tinybufr::Value::Decimal64(v, s) => Ok(Some(v as f64 * 10f64.powi(s as i32))),
+            v => Err(Self::unexpected_value(v)),
+        }
+    }
+
+    fn as_string(value: tinybufr::Value) -> Result<Option<String>, tinybufr::Error> {
+        match value {
+            tinybufr::Value::Missing => Ok(None),
+            tinybufr::Value::String(s) => Ok(Some(s)),
+            v => Err(Self::unexpected_value(v)),
+        }
+    }
+
+    fn unexpected(ev: tinybufr::DataEvent) -> tinybufr::Error {
+        tinybufr::Error::Invalid(format!("Unexpected data event for DwdSynop: {ev:?}"))
+    }
+
+    fn unexpected_value(value: tinybufr::Value) -> tinybufr::Error {
+        tinybufr::Error::Invalid(format!("Unexpected value for DwdSynop: {value:?}"))
+    }
+}
@@ -57,6 +57,7 @@ pub fn test_full_read(mut reader: impl Read, tables: &Tables) {
             Ok(DataEvent::CompressedData { .. }) => {}
             Ok(DataEvent::Eof) => {
                 assert_eq!(subset_counter, 0);
+                assert_eq!(data_reader.warnings(), [] as [String; 0]);
                 break;
             }
             Ok(_) => {}
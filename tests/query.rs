@@ -0,0 +1,349 @@
+use std::fs;
+
+use tinybufr::query::{Decoder, Message, MessageIter, Operand, ReplicationSummary, Selector};
+use tinybufr::sections::{DataDescriptionSection, DataDescriptionSectionFlags};
+use tinybufr::{
+    DataSpec, DataWriter, DecodeOptions, Descriptor, Error, HeaderSections, Tables, Value, XY,
+};
+
+const WIND_PROFILER: &str =
+    "./tests/testdata/jma/Z__C_RJTD_20200728040000_WPR_SEQ_RS-all_Pww_bufr4.bin";
+
+#[test]
+fn test_query_wind_profiler() {
+    let tables = Tables::default();
+    let message = Message::decode(fs::File::open(WIND_PROFILER).unwrap(), &tables).unwrap();
+    assert_eq!(
+        message.subsets.len(),
+        message.header.number_of_subsets() as usize
+    );
+
+    // u-component of wind at the height of 582 m at station 47406
+    let matches = message
+        .query("011003[001001=47,001002=406,007006=582]")
+        .unwrap();
+    assert_eq!(matches.len(), 1);
+    let found = &matches[0];
+    assert_eq!(found.subset, 0);
+    assert_eq!(format!("{:?}", found.element.value), "0.7");
+    let coordinates: Vec<_> = found
+        .coordinates
+        .iter()
+        .map(|(xy, value)| format!("0{:02}{:03}={value:?}", xy.x, xy.y))
+        .collect();
+    assert_eq!(
+        coordinates,
+        [
+            "005002=43.95",
+            "006002=141.63",
+            "007001=23",
+            "004001=2020",
+            "004002=7",
+            "004003=28",
+            "004004=4",
+            "004005=0",
+            "004025=-10",
+            "007006=582"
+        ]
+    );
+
+    // Every level of the first subset
+    let levels = message.query("011003[001002=406]").unwrap();
+    assert_eq!(levels.len(), 10);
+    let missing = message.query("011003[001002=406,011004=missing]").unwrap();
+    assert!(missing.iter().all(|m| m.subset == 0));
+    assert!(message.query("011003[001002!=406]").unwrap().len() > 10);
+    assert!(message.query("011003[007006<0]").unwrap().is_empty());
+}
+
+#[test]
+fn test_query_sequences() {
+    let tables = Tables::default();
+    let mut header = HeaderSections::read(fs::read(WIND_PROFILER).unwrap().as_slice()).unwrap();
+    let descriptors = vec![
+        // WMO block and station numbers
+        Descriptor { f: 3, x: 1, y: 1 },
+        // Pressure and air temperature, replicated twice
+        Descriptor { f: 1, x: 2, y: 2 },
+        Descriptor { f: 0, x: 7, y: 4 },
+        Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        },
+    ];
+    header.data_description_section = DataDescriptionSection {
+        section_length: 7 + 2 * descriptors.len() as u32,
+        number_of_subsets: 2,
+        flags: DataDescriptionSectionFlags {
+            is_observed_data: true,
+            is_compressed: false,
+        },
+        descriptors,
+        _padding: vec![],
+    };
+    let data_spec =
+        DataSpec::from_data_description(&header.data_description_section, &tables).unwrap();
+    let mut writer = DataWriter::new(&data_spec);
+    for station in [662, 671] {
+        writer
+            .write_subset(&[
+                Value::Integer(47),
+                Value::Integer(station),
+                Value::Integer(85000),
+                Value::Decimal(28015, -2),
+                Value::Integer(50000),
+                Value::Decimal(station - 662 + 26015, -2),
+            ])
+            .unwrap();
+    }
+    let data_section = writer.finish().unwrap();
+    let mut bytes = vec![];
+    tinybufr::write_message(&mut bytes, &header, &data_section).unwrap();
+    let message = Message::decode(bytes.as_slice(), &tables).unwrap();
+
+    let stations = message.query("301001/001002").unwrap();
+    assert_eq!(stations.len(), 2);
+    assert_eq!(stations[1].element.sequences.as_ref(), [XY { x: 1, y: 1 }]);
+    assert!(message.query("301011/001002").unwrap().is_empty());
+
+    // Scaled values compare as they are
+    let temperatures = message.query("012101[007004=50000]").unwrap();
+    let values: Vec<_> = temperatures
+        .iter()
+        .map(|m| (m.subset, format!("{:?}", m.element.value)))
+        .collect();
+    assert_eq!(
+        values,
+        [(0, "260.15".to_string()), (1, "260.24".to_string())]
+    );
+    let warm = message.query("012101[001002=671,007004>50000]").unwrap();
+    assert_eq!(warm.len(), 1);
+    assert_eq!(warm[0].element.value, Value::Decimal(28015, -2));
+
+    let selector: Selector = "301001/001002[001001 = \"47\"]".parse().unwrap();
+    assert_eq!(selector.sequences, [XY { x: 1, y: 1 }]);
+    assert_eq!(
+        selector.predicates[0].operand,
+        Operand::Text("47".to_string())
+    );
+    // A number in quotes is text, which integers are not equal to
+    assert!(message.select(&selector).is_empty());
+
+    for invalid in [
+        "",
+        "12101[007004]",
+        "012101[007004=1",
+        "001001/012101",
+        "abc",
+    ] {
+        assert!(invalid.parse::<Selector>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_column_cache() {
+    let tables = Tables::default();
+    let data = fs::read(WIND_PROFILER).unwrap();
+    let message = Message::decode(data.as_slice(), &tables).unwrap();
+    let heights = XY { x: 7, y: 6 };
+    let expected = message.column(heights);
+    assert_eq!(expected.len(), 32);
+    assert_eq!(expected[0].len(), 10);
+
+    let mut decoder = Decoder::new(&tables).with_column_cache(1 << 20);
+    let first = decoder.column(&data, heights).unwrap();
+    let second = decoder.column(&data, heights).unwrap();
+    assert_eq!(first, expected);
+    assert!(std::sync::Arc::ptr_eq(&first, &second));
+    let stats = decoder.cache().unwrap().stats();
+    assert_eq!((stats.hits, stats.misses), (1, 1));
+    // The columns of the other elements are cached by the same decoding
+    assert_eq!(stats.entries, message.columns().len());
+    let blocks = XY { x: 1, y: 1 };
+    assert_eq!(
+        decoder.column(&data, blocks).unwrap(),
+        message.column(blocks)
+    );
+    assert_eq!(decoder.cache().unwrap().stats().misses, 1);
+
+    // Without room for the column, every access decodes the message
+    let mut decoder = Decoder::new(&tables).with_column_cache(16);
+    assert_eq!(decoder.column(&data, heights).unwrap(), expected);
+    assert_eq!(decoder.cache().unwrap().stats().entries, 0);
+}
+
+#[test]
+fn test_decode_from_reader() {
+    let tables = Tables::default();
+    let data = fs::read(WIND_PROFILER).unwrap();
+
+    // Messages are read one after another
+    let mut input = data.repeat(2);
+    input.extend_from_slice(b"BUFR");
+    let mut reader = input.as_slice();
+    for _ in 0..2 {
+        let message = Message::decode(&mut reader, &tables).unwrap();
+        assert_eq!(message.subsets.len(), 32);
+    }
+    assert_eq!(reader, b"BUFR");
+    assert!(Message::decode(&mut reader, &tables).is_err());
+    assert!(Message::decode(&b"GRIB\0\0\0\x04"[..], &tables).is_err());
+
+    // A truncated message is decoded as far as it goes in lenient mode only
+    let truncated = &data[..data.len() - 2];
+    assert!(Message::decode(truncated, &tables).is_err());
+    let message =
+        Message::decode_with_options(truncated, &tables, &DecodeOptions::lenient()).unwrap();
+    assert_eq!(message.subsets.len(), 32);
+    assert_eq!(
+        message.warnings[1..],
+        ["End section (7777) missing or truncated (2 of 4 bytes)"]
+    );
+}
+
+#[test]
+fn test_message_iter() {
+    let tables = Tables::default();
+    let data = fs::read(WIND_PROFILER).unwrap();
+
+    let input = data.repeat(3);
+    let messages: Vec<_> = MessageIter::new(input.as_slice(), &tables)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(messages.len(), 3);
+    assert!(messages.iter().all(|message| message.subsets.len() == 32));
+    assert_eq!(MessageIter::new(&b""[..], &tables).count(), 0);
+
+    // Anything between the messages is skipped, as is a truncated message unless
+    // decoding leniently
+    let mut input = b"GARBAGE\n".to_vec();
+    input.extend_from_slice(&data);
+    input.extend_from_slice(b"GARBAGE\n");
+    input.extend_from_slice(&data);
+    input.extend_from_slice(&data[..100]);
+    let mut iter = MessageIter::new(input.as_slice(), &tables);
+    assert!(iter.next().unwrap().is_ok());
+    assert!(iter.next().unwrap().is_ok());
+    assert!(iter.next().is_none());
+    let iter = MessageIter::with_options(input.as_slice(), &tables, DecodeOptions::lenient());
+    assert_eq!(iter.count(), 3);
+
+    // The iteration ends after an error reading the input
+    let mut iter = MessageIter::new(&b"GARBAGE"[..], &tables);
+    assert!(iter.next().unwrap().is_err());
+    assert!(iter.next().is_none());
+
+    // but goes on after a message that cannot be decoded
+    let mut input = data.clone();
+    // The first descriptor in Section 3 (0 01 001) replaced with an unknown 0 63 250
+    input[37..39].copy_from_slice(&[0x3f, 250]);
+    input.extend_from_slice(&data);
+    let mut iter = MessageIter::new(input.as_slice(), &tables);
+    assert!(matches!(iter.next(), Some(Err(Error::Table(_)))));
+    assert!(iter.next().unwrap().is_ok());
+    assert!(iter.next().is_none());
+
+    // Messages can be read without decoding them
+    let mut iter = MessageIter::new(input.as_slice(), &tables);
+    assert_eq!(iter.next_raw().unwrap().unwrap().len(), data.len());
+    assert_eq!(iter.next_raw().unwrap().unwrap(), data);
+    assert!(iter.next_raw().is_none());
+}
+
+#[test]
+fn test_replication_summary() {
+    let tables = Tables::default();
+    let message = Message::decode(fs::File::open(WIND_PROFILER).unwrap(), &tables).unwrap();
+    let summary = message.replication_summary();
+    assert_eq!(summary.len(), 1);
+    assert_eq!(summary[0].factor_bits, 8);
+    assert_eq!(summary[0].occurrences, 32);
+    assert_eq!((summary[0].min, summary[0].max), (3, 40));
+    assert_eq!(
+        summary[0].total as usize,
+        message.query("007006").unwrap().len()
+    );
+
+    // Nested delayed replications, with different counts in each subset
+    let header = HeaderSections::read(fs::read(WIND_PROFILER).unwrap().as_slice()).unwrap();
+    let descriptors = vec![
+        // WMO block and station numbers
+        Descriptor { f: 3, x: 1, y: 1 },
+        // Levels of pressure with air temperatures
+        Descriptor { f: 1, x: 4, y: 0 },
+        Descriptor { f: 0, x: 31, y: 1 },
+        Descriptor { f: 0, x: 7, y: 4 },
+        Descriptor { f: 1, x: 1, y: 0 },
+        Descriptor { f: 0, x: 31, y: 1 },
+        Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        },
+    ];
+    let temperature = Value::Decimal(28015, -2);
+    let subsets = [
+        vec![
+            Value::Integer(47),
+            Value::Integer(662),
+            Value::Integer(2),
+            Value::Integer(85000),
+            Value::Integer(1),
+            temperature.clone(),
+            Value::Integer(50000),
+            Value::Integer(3),
+            temperature.clone(),
+            temperature.clone(),
+            temperature.clone(),
+        ],
+        vec![
+            Value::Integer(47),
+            Value::Integer(671),
+            Value::Integer(1),
+            Value::Integer(85000),
+            Value::Integer(0),
+        ],
+    ];
+    let decode = |is_compressed: bool, subsets: &[Vec<Value>]| {
+        let mut header = header.clone();
+        header.data_description_section = DataDescriptionSection {
+            section_length: 7 + 2 * descriptors.len() as u32,
+            number_of_subsets: subsets.len() as u16,
+            flags: DataDescriptionSectionFlags {
+                is_observed_data: true,
+                is_compressed,
+            },
+            descriptors: descriptors.clone(),
+            _padding: vec![],
+        };
+        let data_spec =
+            DataSpec::from_data_description(&header.data_description_section, &tables).unwrap();
+        let mut writer = DataWriter::new(&data_spec);
+        for values in subsets {
+            writer.write_subset(values).unwrap();
+        }
+        let data_section = writer.finish().unwrap();
+        let mut bytes = vec![];
+        tinybufr::write_message(&mut bytes, &header, &data_section).unwrap();
+        Message::decode(bytes.as_slice(), &tables).unwrap()
+    };
+    let counts = |summary: &ReplicationSummary| {
+        (summary.occurrences, summary.min, summary.max, summary.total)
+    };
+
+    let message = decode(false, &subsets);
+    let summary = message.replication_summary();
+    assert_eq!(summary.len(), 2);
+    assert_eq!(summary[0].path, [1]);
+    assert_eq!(counts(&summary[0]), (2, 1, 2, 3));
+    // Counted in each item of the enclosing replication
+    assert_eq!(summary[1].path, [1, 1]);
+    assert_eq!(counts(&summary[1]), (3, 0, 3, 4));
+
+    // Counted for each subset in compressed data
+    let message = decode(true, &[subsets[0].clone(), subsets[0].clone()]);
+    let summary = message.replication_summary();
+    assert_eq!(counts(&summary[0]), (2, 2, 2, 4));
+    assert_eq!(counts(&summary[1]), (4, 1, 3, 8));
+}
@@ -61,3 +61,81 @@ fn read_example(filename: &str, skip_first_line: bool) {
 
     common::test_full_read(reader, &tables);
 }
+
+#[cfg(feature = "codegen")]
+mod codegen {
+    use super::*;
+
+    include!("codegen/dwd_synop.rs");
+
+    #[test]
+    fn test_generated_struct() {
+        let mut tables = Tables::default();
+        for desc in DWD_TABLE_B {
+            tables.table_b.insert(desc.xy, desc);
+        }
+
+        let file =
+            fs::File::open("./tests/testdata/dwd/synop_ISGD01_EDZW_2025_08_07_11_10.bufr").unwrap();
+        let mut reader = BufReader::new(file);
+        let header = HeaderSections::read(&mut reader).unwrap();
+
+        // The checked-in source is up to date with the generator (set UPDATE_CODEGEN to regenerate)
+        let source = tinybufr::codegen::generate_struct(
+            "DwdSynop",
+            &header.data_description_section.descriptors,
+            &tables,
+        )
+        .unwrap();
+        if std::env::var_os("UPDATE_CODEGEN").is_some() {
+            fs::write("./tests/codegen/dwd_synop.rs", &source).unwrap();
+        }
+        assert_eq!(
+            source,
+            fs::read_to_string("./tests/codegen/dwd_synop.rs").unwrap()
+        );
+
+        // Decode the subset into the generated struct
+        let data_spec =
+            DataSpec::from_data_description(&header.data_description_section, &tables).unwrap();
+        let mut data_reader = DataReader::new(&mut reader, &data_spec).unwrap();
+        let synop = DwdSynop::read(&mut data_reader).unwrap();
+        assert!(matches!(data_reader.read_event().unwrap(), DataEvent::Eof));
+        assert_eq!(
+            (synop.wmo_block_number, synop.wmo_station_number),
+            (Some(10), Some(20))
+        );
+        assert_eq!(
+            synop.station_or_site_name.as_deref().map(str::trim_end),
+            Some("List auf Sylt")
+        );
+        assert_eq!(
+            (synop.year, synop.month, synop.day, synop.hour, synop.minute),
+            (Some(2025), Some(8), Some(7), Some(11), Some(10))
+        );
+        assert!((synop.latitude_high_accuracy.unwrap() - 55.01099).abs() < 1e-9);
+        assert_eq!(synop.national_station_number, None);
+        let temperatures: Vec<_> = synop
+            .replication_1
+            .iter()
+            .map(|item| item.temperature_air_temperature)
+            .collect();
+        assert_eq!(temperatures, [Some(292.05), Some(294.95)]);
+        assert!(synop.replication_2.is_empty());
+    }
+}
+
+#[test]
+fn test_header_flags() {
+    let file =
+        fs::File::open("./tests/testdata/dwd/synop_ISGD01_EDZW_2025_08_07_11_10.bufr").unwrap();
+    let header = HeaderSections::read(BufReader::new(file)).unwrap();
+    let flags = &header.data_description_section.flags;
+    assert_eq!(header.is_observed_data(), flags.is_observed_data);
+    assert_eq!(header.is_compressed(), flags.is_compressed);
+    assert_eq!(
+        header.number_of_subsets(),
+        header.data_description_section.number_of_subsets
+    );
+    assert!(header.is_observed_data());
+}
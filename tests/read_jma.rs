@@ -1,7 +1,7 @@
 use std::fs;
 use std::io::{BufRead, BufReader};
 
-use tinybufr::tables::local::jma::{JMA_DATA_DESCRIPTORS, JMA_SEQUENCE_DESCRIPTORS};
+use tinybufr::tables::local::jma::install_jma_descriptors;
 use tinybufr::*;
 
 mod common;
@@ -59,15 +59,30 @@ fn test_ixac41() {
     );
 }
 
+#[test]
+fn test_ixac41_bufr2() {
+    let mut tables = Tables::default();
+    install_jma_descriptors(&mut tables);
+
+    // Without a sub-centre, the edition 3 message is laid out as in edition 2
+    let bufr3 = fs::read("./tests/testdata/jma/201806180758.20230110141530_520.BUFR").unwrap();
+    let mut bufr2 = bufr3.clone();
+    bufr2[7] = 2;
+
+    let header = HeaderSections::read(&bufr2[..]).unwrap();
+    assert_eq!(header.indicator_section.edition_number, 2);
+    assert_eq!(header.identification_section.centre, 34);
+    assert_eq!(header.identification_section.sub_centre, 0);
+
+    let snapshot2 = tinybufr::snapshot::snapshot_message(&bufr2, &tables).unwrap();
+    let snapshot3 = tinybufr::snapshot::snapshot_message(&bufr3, &tables).unwrap();
+    assert_eq!(snapshot2.replacen("edition=2", "edition=3", 1), snapshot3);
+}
+
 fn read_example(filename: &str, skip_first_line: bool) {
     // Extend the default tables with JMA local descriptors
     let mut tables = Tables::default();
-    for desc in &JMA_DATA_DESCRIPTORS {
-        tables.table_b.insert(desc.xy, desc);
-    }
-    for seq in &JMA_SEQUENCE_DESCRIPTORS {
-        tables.table_d.insert(seq.xy, seq);
-    }
+    install_jma_descriptors(&mut tables);
 
     let file = fs::File::open(filename).unwrap();
     let mut reader = BufReader::new(file);
@@ -79,3 +94,21 @@ fn read_example(filename: &str, skip_first_line: bool) {
 
     common::test_full_read(reader, &tables);
 }
+
+#[test]
+fn test_snapshot_amedas_rain() {
+    let mut tables = Tables::default();
+    install_jma_descriptors(&mut tables);
+    let snapshot = tinybufr::snapshot::snapshot_file(
+        "./tests/testdata/jma/Z__C_RJTD_20210918110000_OBS_AMDSRR_Rjp_N1_bufr4.bin",
+        &tables,
+    )
+    .unwrap();
+
+    // The checked-in snapshot is up to date (set UPDATE_SNAPSHOTS to regenerate)
+    let path = "./tests/snapshots/amdsrr_bufr4.txt";
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(path, &snapshot).unwrap();
+    }
+    assert_eq!(snapshot, fs::read_to_string(path).unwrap());
+}
@@ -0,0 +1,143 @@
+use tinybufr::record::BufrRecord;
+use tinybufr::sections::{DataDescriptionSection, DataDescriptionSectionFlags};
+use tinybufr::{DataReader, DataSpec, DataWriter, Descriptor, Error, SubsetValue, Tables, Value};
+
+#[derive(Debug, PartialEq, BufrRecord)]
+struct Identification {
+    #[bufr("001001")]
+    block: u8,
+    #[bufr("001002")]
+    station: u16,
+}
+
+#[derive(Debug, PartialEq, BufrRecord)]
+struct Level {
+    #[bufr("007004")]
+    pressure: f64,
+    #[bufr("012101")]
+    temperature: Option<f64>,
+}
+
+#[derive(Debug, PartialEq, BufrRecord)]
+struct Sounding {
+    #[bufr("301001")]
+    identification: Identification,
+    #[bufr("0 01 002")]
+    station: i32,
+    #[bufr("001015")]
+    name: Option<String>,
+    #[bufr("012101")]
+    temperatures: Vec<Option<f64>>,
+    #[bufr("020001", default)]
+    visibility: f64,
+    #[bufr(replication)]
+    levels: Vec<Level>,
+}
+
+#[derive(Debug, BufrRecord)]
+#[allow(dead_code)]
+struct Visibility {
+    #[bufr("020001")]
+    visibility: f64,
+}
+
+fn data_description() -> DataDescriptionSection {
+    let descriptors = vec![
+        Descriptor { f: 3, x: 1, y: 1 },
+        Descriptor { f: 1, x: 2, y: 2 },
+        Descriptor { f: 0, x: 7, y: 4 },
+        Descriptor {
+            f: 0,
+            x: 12,
+            y: 101,
+        },
+    ];
+    DataDescriptionSection {
+        section_length: 7 + 2 * descriptors.len() as u32,
+        number_of_subsets: 2,
+        flags: DataDescriptionSectionFlags {
+            is_observed_data: true,
+            is_compressed: false,
+        },
+        descriptors,
+        _padding: vec![],
+    }
+}
+
+/// Two soundings of the stations 47662 and 47671.
+fn data_section(data_spec: &DataSpec) -> Vec<u8> {
+    let mut writer = DataWriter::new(data_spec);
+    for station in [662, 671] {
+        writer
+            .write_subset(&[
+                Value::Integer(47),
+                Value::Integer(station),
+                Value::Decimal(10000, 1),
+                Value::Decimal(28015, -2),
+                Value::Decimal(8500, 1),
+                Value::Missing,
+            ])
+            .unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+fn subset() -> Vec<SubsetValue> {
+    let tables = Tables::default();
+    let dds = data_description();
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let data_section = data_section(&data_spec);
+    let mut reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+    reader.read_subset().unwrap().unwrap()
+}
+
+#[test]
+fn test_derive() {
+    let sounding = Sounding::from_values(&subset()).unwrap();
+    assert_eq!(
+        sounding,
+        Sounding {
+            identification: Identification {
+                block: 47,
+                station: 662
+            },
+            station: 662,
+            name: None,
+            temperatures: vec![Some(280.15), None],
+            visibility: 0.0,
+            levels: vec![
+                Level {
+                    pressure: 100000.0,
+                    temperature: Some(280.15)
+                },
+                Level {
+                    pressure: 85000.0,
+                    temperature: None
+                },
+            ],
+        }
+    );
+
+    let error = Visibility::from_values(&subset()).unwrap_err();
+    assert!(
+        matches!(&error, Error::Invalid(msg) if msg.contains("`visibility`")),
+        "{error:?}"
+    );
+}
+
+#[test]
+fn test_read() {
+    let tables = Tables::default();
+    let dds = data_description();
+    let data_spec = DataSpec::from_data_description(&dds, &tables).unwrap();
+    let data_section = data_section(&data_spec);
+
+    let mut reader = DataReader::new(data_section.as_slice(), &data_spec).unwrap();
+    let first = Sounding::read(&mut reader).unwrap().unwrap();
+    assert_eq!(first.identification.station, 662);
+    assert_eq!(first.levels.len(), 2);
+    assert_eq!(first.temperatures, [Some(280.15), None]);
+    let second = Sounding::read(&mut reader).unwrap().unwrap();
+    assert_eq!(second.station, 671);
+    assert!(Sounding::read(&mut reader).unwrap().is_none());
+}
@@ -0,0 +1,162 @@
+use std::fs;
+use std::io::Read;
+
+use tinybufr::sections::{DataSectionHeader, OptionalSection};
+use tinybufr::*;
+
+#[test]
+fn test_rewrite_bufr4() {
+    rewrite_example("./tests/testdata/dwd/synop_ISGD01_EDZW_2025_08_07_11_10.bufr");
+}
+
+#[test]
+fn test_rewrite_bufr3() {
+    rewrite_example("./tests/testdata/jma/201806180758.20230110141530_520.BUFR");
+}
+
+#[test]
+fn test_rewrite_bufr2() {
+    let mut original =
+        fs::read("./tests/testdata/jma/201806180758.20230110141530_520.BUFR").unwrap();
+    original[7] = 2;
+    let (header, data_section) = split_message(&original);
+
+    let mut rewritten = Vec::new();
+    write_message(&mut rewritten, &header, &data_section).unwrap();
+    assert_eq!(original, rewritten);
+}
+
+#[test]
+fn test_rewrite_optional_section() {
+    let original =
+        fs::read("./tests/testdata/dwd/synop_ISGD01_EDZW_2025_08_07_11_10.bufr").unwrap();
+    let (mut header, data_section) = split_message(&original);
+
+    // Add a local optional section and re-emit the message
+    header.optional_section = Some(OptionalSection {
+        section_length: 0,
+        optional: b"local RDB key".to_vec(),
+    });
+    let mut rewritten = Vec::new();
+    write_message(&mut rewritten, &header, &data_section).unwrap();
+
+    // The optional section survives another round trip unchanged
+    let (header, data_section_2) = split_message(&rewritten);
+    assert_eq!(
+        header.indicator_section.total_length as usize,
+        rewritten.len()
+    );
+    assert!(header.identification_section.flags.has_optional_section);
+    let optional_section = header.optional_section.as_ref().unwrap();
+    assert_eq!(optional_section.optional, b"local RDB key");
+    assert_eq!(data_section, data_section_2);
+
+    let mut rewritten_2 = Vec::new();
+    write_message(&mut rewritten_2, &header, &data_section_2).unwrap();
+    assert_eq!(rewritten, rewritten_2);
+}
+
+#[test]
+fn test_write_multiple_messages() {
+    let bufr4 = fs::read("./tests/testdata/dwd/synop_ISGD01_EDZW_2025_08_07_11_10.bufr").unwrap();
+    let bufr3 = fs::read("./tests/testdata/jma/201806180758.20230110141530_520.BUFR").unwrap();
+    let (header4, data_section4) = split_message(&bufr4);
+    let (header3, data_section3) = split_message(&bufr3);
+
+    let mut writer = MessageWriter::new(Vec::new());
+    writer.write_message(&header4, &data_section4).unwrap();
+    writer.write_message(&header3, &data_section3).unwrap();
+    writer.write_message(&header4, &data_section4).unwrap();
+    assert_eq!(writer.messages_written(), 3);
+    let output = writer.finish().unwrap();
+
+    // The messages can be read back one after another
+    let mut rest = output.as_slice();
+    for (header, data_section) in [
+        (&header4, &data_section4),
+        (&header3, &data_section3),
+        (&header4, &data_section4),
+    ] {
+        let total_length = u32::from_be_bytes([0, rest[4], rest[5], rest[6]]) as usize;
+        let (message, tail) = rest.split_at(total_length);
+        let (header_2, data_section_2) = split_message(message);
+        assert_eq!(
+            header_2.indicator_section.edition_number,
+            header.indicator_section.edition_number
+        );
+        assert_eq!(&data_section_2, data_section);
+        rest = tail;
+    }
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn test_encode_into_buffer() {
+    let bufr4 = fs::read("./tests/testdata/dwd/synop_ISGD01_EDZW_2025_08_07_11_10.bufr").unwrap();
+    let mut bufr2 = fs::read("./tests/testdata/jma/201806180758.20230110141530_520.BUFR").unwrap();
+    bufr2[7] = 2;
+    let (header4, data_section4) = split_message(&bufr4);
+    let (header2, data_section2) = split_message(&bufr2);
+
+    let mut expected = Vec::new();
+    write_message(&mut expected, &header4, &data_section4).unwrap();
+    write_message(&mut expected, &header2, &data_section2).unwrap();
+
+    let mut buffer = Vec::new();
+    encode_message_into(&mut buffer, &header4, &data_section4).unwrap();
+    encode_message_into(&mut buffer, &header2, &data_section2).unwrap();
+    assert_eq!(buffer, expected);
+
+    // The buffer is left as it was on errors
+    let len = buffer.len();
+    assert!(encode_message_into(&mut buffer, &header4, &[0; 3]).is_err());
+    assert_eq!(buffer.len(), len);
+
+    // Writers accepting a few octets at a time
+    let mut output = Trickle(Vec::new());
+    let mut writer = MessageWriter::new(&mut output);
+    writer.write_message(&header4, &data_section4).unwrap();
+    writer.write_message(&header2, &data_section2).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(output.0, expected);
+}
+
+/// A writer accepting up to 3 octets per call.
+struct Trickle(Vec<u8>);
+
+impl std::io::Write for Trickle {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(3);
+        self.0.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn rewrite_example(filename: &str) {
+    let original = fs::read(filename).unwrap();
+    let (header, data_section) = split_message(&original);
+
+    let mut rewritten = Vec::new();
+    write_message(&mut rewritten, &header, &data_section).unwrap();
+    assert_eq!(
+        &original[..header.indicator_section.total_length as usize],
+        rewritten
+    );
+}
+
+/// Splits a message into its header sections and the raw data section.
+fn split_message(message: &[u8]) -> (HeaderSections, Vec<u8>) {
+    let mut reader = message;
+    let header = HeaderSections::read(&mut reader).unwrap();
+    let section_length = DataSectionHeader::read(&mut &reader[..])
+        .unwrap()
+        .section_length;
+    let mut data_section = vec![0; section_length as usize];
+    reader.read_exact(&mut data_section).unwrap();
+    ensure_end_section(header.indicator_section.edition_number, &mut reader).unwrap();
+    (header, data_section)
+}